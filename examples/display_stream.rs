@@ -1,3 +1,4 @@
+#[cfg(target_os = "macos")]
 use core_foundation::{
     base::{CFType, TCFType},
     boolean::CFBoolean,
@@ -5,15 +6,20 @@ use core_foundation::{
     number::CFNumber,
     string::CFString,
 };
+#[cfg(target_os = "macos")]
 use core_graphics2::{
     color_space::{CGColorSpace, CGColorSpaceNames},
     display::CGDisplay,
     display_stream::*,
 };
+#[cfg(target_os = "macos")]
 use core_video::pixel_buffer::{self, CVPixelBuffer};
+#[cfg(target_os = "macos")]
 use dispatch2::{Queue, QueueAttribute};
+#[cfg(target_os = "macos")]
 use io_surface::IOSurface;
 
+#[cfg(target_os = "macos")]
 fn main() {
     let display = CGDisplay::main();
     let output_width = display.pixels_wide();
@@ -63,3 +69,6 @@ fn main() {
         display_stream.stop();
     }
 }
+
+#[cfg(not(target_os = "macos"))]
+fn main() {}
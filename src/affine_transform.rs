@@ -1,3 +1,5 @@
+use std::ops::{Mul, MulAssign};
+
 use crate::{
     base::CGFloat,
     geometry::{CGPoint, CGRect, CGSize, CGVector},
@@ -50,52 +52,305 @@ extern "C" {
     pub fn CGAffineTransformMakeWithComponents(components: CGAffineTransformComponents) -> CGAffineTransform;
 }
 
-impl CGAffineTransform {
-    pub fn new(a: CGFloat, b: CGFloat, c: CGFloat, d: CGFloat, tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
-        unsafe { CGAffineTransformMake(a, b, c, d, tx, ty) }
+cfg_if! {
+    if #[cfg(any(feature = "pure-geometry", not(target_os = "macos")))] {
+        impl CGAffineTransform {
+            pub fn new(a: CGFloat, b: CGFloat, c: CGFloat, d: CGFloat, tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
+                CGAffineTransform {
+                    a,
+                    b,
+                    c,
+                    d,
+                    tx,
+                    ty,
+                }
+            }
+
+            pub fn new_translate(tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
+                CGAffineTransform::new(1.0, 0.0, 0.0, 1.0, tx, ty)
+            }
+
+            pub fn new_scale(sx: CGFloat, sy: CGFloat) -> CGAffineTransform {
+                CGAffineTransform::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+            }
+
+            pub fn new_rotate(angle: CGFloat) -> CGAffineTransform {
+                let (sin, cos) = angle.sin_cos();
+                CGAffineTransform::new(cos, sin, -sin, cos, 0.0, 0.0)
+            }
+
+            // scale * shear * rotation * translation, using the row vector convention of CGAffineTransform
+            pub fn from_components(components: CGAffineTransformComponents) -> CGAffineTransform {
+                let CGAffineTransformComponents {
+                    scale,
+                    horizontal_shear,
+                    rotation,
+                    translation,
+                } = components;
+                let (sin, cos) = rotation.sin_cos();
+                CGAffineTransform::new(
+                    scale.width * cos,
+                    scale.width * sin,
+                    scale.height * (horizontal_shear * cos - sin),
+                    scale.height * (horizontal_shear * sin + cos),
+                    translation.dx,
+                    translation.dy,
+                )
+            }
+
+            pub fn translate(&self, tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
+                CGAffineTransform::new_translate(tx, ty).concat(self)
+            }
+
+            pub fn scale(&self, sx: CGFloat, sy: CGFloat) -> CGAffineTransform {
+                CGAffineTransform::new_scale(sx, sy).concat(self)
+            }
+
+            pub fn rotate(&self, angle: CGFloat) -> CGAffineTransform {
+                CGAffineTransform::new_rotate(angle).concat(self)
+            }
+
+            pub fn is_identity(&self) -> bool {
+                *self == CGAffineTransformIdentity
+            }
+
+            pub fn invert(&self) -> CGAffineTransform {
+                let determinant = self.a * self.d - self.b * self.c;
+                if determinant == 0.0 {
+                    return *self;
+                }
+                CGAffineTransform::new(
+                    self.d / determinant,
+                    -self.b / determinant,
+                    -self.c / determinant,
+                    self.a / determinant,
+                    (self.c * self.ty - self.d * self.tx) / determinant,
+                    (self.b * self.tx - self.a * self.ty) / determinant,
+                )
+            }
+
+            pub fn concat(&self, other: &CGAffineTransform) -> CGAffineTransform {
+                CGAffineTransform::new(
+                    self.a * other.a + self.b * other.c,
+                    self.a * other.b + self.b * other.d,
+                    self.c * other.a + self.d * other.c,
+                    self.c * other.b + self.d * other.d,
+                    self.tx * other.a + self.ty * other.c + other.tx,
+                    self.tx * other.b + self.ty * other.d + other.ty,
+                )
+            }
+
+            // the x scale is kept non-negative, so a reflection shows up as a negative y scale
+            pub fn decompose(&self) -> CGAffineTransformComponents {
+                let scale_x = (self.a * self.a + self.b * self.b).sqrt();
+                let rotation = self.b.atan2(self.a);
+                let (sin, cos) = if scale_x == 0.0 {
+                    (0.0, 1.0)
+                } else {
+                    (self.b / scale_x, self.a / scale_x)
+                };
+                let scale_y = self.d * cos - self.c * sin;
+                let horizontal_shear = if scale_y == 0.0 {
+                    0.0
+                } else {
+                    (self.c * cos + self.d * sin) / scale_y
+                };
+                CGAffineTransformComponents {
+                    scale: CGSize::new(scale_x, scale_y),
+                    horizontal_shear,
+                    rotation,
+                    translation: CGVector {
+                        dx: self.tx,
+                        dy: self.ty,
+                    },
+                }
+            }
+        }
+    } else {
+        impl CGAffineTransform {
+            pub fn new(a: CGFloat, b: CGFloat, c: CGFloat, d: CGFloat, tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
+                unsafe { CGAffineTransformMake(a, b, c, d, tx, ty) }
+            }
+
+            pub fn new_translate(tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
+                unsafe { CGAffineTransformMakeTranslation(tx, ty) }
+            }
+
+            pub fn new_scale(sx: CGFloat, sy: CGFloat) -> CGAffineTransform {
+                unsafe { CGAffineTransformMakeScale(sx, sy) }
+            }
+
+            pub fn new_rotate(angle: CGFloat) -> CGAffineTransform {
+                unsafe { CGAffineTransformMakeRotation(angle) }
+            }
+
+            pub fn from_components(components: CGAffineTransformComponents) -> CGAffineTransform {
+                unsafe { CGAffineTransformMakeWithComponents(components) }
+            }
+
+            pub fn translate(&self, tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
+                unsafe { CGAffineTransformTranslate(*self, tx, ty) }
+            }
+
+            pub fn scale(&self, sx: CGFloat, sy: CGFloat) -> CGAffineTransform {
+                unsafe { CGAffineTransformScale(*self, sx, sy) }
+            }
+
+            pub fn rotate(&self, angle: CGFloat) -> CGAffineTransform {
+                unsafe { CGAffineTransformRotate(*self, angle) }
+            }
+
+            pub fn is_identity(&self) -> bool {
+                unsafe { CGAffineTransformIsIdentity(*self) }
+            }
+
+            pub fn invert(&self) -> CGAffineTransform {
+                unsafe { CGAffineTransformInvert(*self) }
+            }
+
+            pub fn concat(&self, other: &CGAffineTransform) -> CGAffineTransform {
+                unsafe { CGAffineTransformConcat(*self, *other) }
+            }
+
+            pub fn decompose(&self) -> CGAffineTransformComponents {
+                unsafe { CGAffineTransformDecompose(*self) }
+            }
+        }
+    }
+}
+
+impl Mul for CGAffineTransform {
+    type Output = CGAffineTransform;
+
+    fn mul(self, rhs: CGAffineTransform) -> CGAffineTransform {
+        self.concat(&rhs)
     }
+}
 
-    pub fn new_translate(tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
-        unsafe { CGAffineTransformMakeTranslation(tx, ty) }
+impl MulAssign for CGAffineTransform {
+    fn mul_assign(&mut self, rhs: CGAffineTransform) {
+        *self = self.concat(&rhs);
     }
+}
+
+impl Mul<CGPoint> for CGAffineTransform {
+    type Output = CGPoint;
 
-    pub fn new_scale(sx: CGFloat, sy: CGFloat) -> CGAffineTransform {
-        unsafe { CGAffineTransformMakeScale(sx, sy) }
+    fn mul(self, rhs: CGPoint) -> CGPoint {
+        rhs.apply_transform(&self)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn new_rotate(angle: CGFloat) -> CGAffineTransform {
-        unsafe { CGAffineTransformMakeRotation(angle) }
+    fn assert_transform_eq(lhs: CGAffineTransform, rhs: CGAffineTransform) {
+        let lhs = [lhs.a, lhs.b, lhs.c, lhs.d, lhs.tx, lhs.ty];
+        let rhs = [rhs.a, rhs.b, rhs.c, rhs.d, rhs.tx, rhs.ty];
+        for (lhs, rhs) in lhs.iter().zip(rhs.iter()) {
+            assert!((lhs - rhs).abs() < 1e-9, "{:?} != {:?}", lhs, rhs);
+        }
     }
 
-    pub fn from_components(components: CGAffineTransformComponents) -> CGAffineTransform {
-        unsafe { CGAffineTransformMakeWithComponents(components) }
+    fn assert_components_eq(lhs: CGAffineTransformComponents, rhs: CGAffineTransformComponents) {
+        let lhs = [
+            lhs.scale.width,
+            lhs.scale.height,
+            lhs.horizontal_shear,
+            lhs.rotation,
+            lhs.translation.dx,
+            lhs.translation.dy,
+        ];
+        let rhs = [
+            rhs.scale.width,
+            rhs.scale.height,
+            rhs.horizontal_shear,
+            rhs.rotation,
+            rhs.translation.dx,
+            rhs.translation.dy,
+        ];
+        for (lhs, rhs) in lhs.iter().zip(rhs.iter()) {
+            assert!((lhs - rhs).abs() < 1e-9, "{:?} != {:?}", lhs, rhs);
+        }
     }
 
-    pub fn translate(&self, tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
-        unsafe { CGAffineTransformTranslate(*self, tx, ty) }
+    fn components(sx: CGFloat, sy: CGFloat, horizontal_shear: CGFloat, rotation: CGFloat) -> CGAffineTransformComponents {
+        CGAffineTransformComponents {
+            scale: CGSize::new(sx, sy),
+            horizontal_shear,
+            rotation,
+            translation: CGVector {
+                dx: 7.0,
+                dy: -3.0,
+            },
+        }
     }
 
-    pub fn scale(&self, sx: CGFloat, sy: CGFloat) -> CGAffineTransform {
-        unsafe { CGAffineTransformScale(*self, sx, sy) }
+    #[test]
+    fn concat_order() {
+        let translate = CGAffineTransform::new_translate(10.0, 0.0);
+        let scale = CGAffineTransform::new_scale(2.0, 3.0);
+        // the receiver is applied first
+        let point = CGPoint::new(1.0, 1.0);
+        assert_eq!(point.apply_transform(&translate.concat(&scale)), CGPoint::new(22.0, 3.0));
+        assert_eq!(point.apply_transform(&scale.concat(&translate)), CGPoint::new(12.0, 3.0));
+        assert_eq!(translate * scale, translate.concat(&scale));
+        let mut transform = translate;
+        transform *= scale;
+        assert_eq!(transform, translate.concat(&scale));
+        // translate, scale and rotate prepend to the receiver
+        assert_eq!(scale.translate(10.0, 0.0), translate.concat(&scale));
+        assert_eq!(translate.scale(2.0, 3.0), scale.concat(&translate));
+        assert_transform_eq(translate.rotate(0.5), CGAffineTransform::new_rotate(0.5).concat(&translate));
     }
 
-    pub fn rotate(&self, angle: CGFloat) -> CGAffineTransform {
-        unsafe { CGAffineTransformRotate(*self, angle) }
+    #[test]
+    fn invert() {
+        let transform = CGAffineTransform::new(2.0, 1.0, -1.0, 3.0, 5.0, -7.0);
+        assert_transform_eq(transform.concat(&transform.invert()), CGAffineTransformIdentity);
+        assert_transform_eq(transform.invert().concat(&transform), CGAffineTransformIdentity);
+        assert!(CGAffineTransformIdentity.invert().is_identity());
     }
 
-    pub fn is_identity(&self) -> bool {
-        unsafe { CGAffineTransformIsIdentity(*self) }
+    #[test]
+    fn invert_singular_returns_input() {
+        let singular = CGAffineTransform::new(1.0, 2.0, 2.0, 4.0, 5.0, 6.0);
+        assert_eq!(singular.invert(), singular);
+        let zero = CGAffineTransform::new_scale(0.0, 1.0).translate(3.0, 4.0);
+        assert_eq!(zero.invert(), zero);
     }
 
-    pub fn invert(&self) -> CGAffineTransform {
-        unsafe { CGAffineTransformInvert(*self) }
+    #[test]
+    fn components_round_trip() {
+        for components in [
+            components(2.0, 3.0, 0.0, 0.0),
+            components(2.0, 3.0, 0.5, 0.3),
+            components(0.5, -3.0, -0.25, 2.0),
+            components(1.0, -1.0, 0.0, -1.0),
+        ] {
+            let transform = CGAffineTransform::from_components(components);
+            assert_components_eq(transform.decompose(), components);
+            assert_transform_eq(CGAffineTransform::from_components(transform.decompose()), transform);
+        }
     }
 
-    pub fn concat(&self, other: &CGAffineTransform) -> CGAffineTransform {
-        unsafe { CGAffineTransformConcat(*self, *other) }
+    #[test]
+    fn decompose_negative_x_scale() {
+        // a negative x scale decomposes into a rotation by pi and a negative y scale
+        let transform = CGAffineTransform::from_components(components(-2.0, 3.0, 0.5, 0.3));
+        let decomposed = transform.decompose();
+        assert!((decomposed.scale.width - 2.0).abs() < 1e-9);
+        assert!((decomposed.scale.height + 3.0).abs() < 1e-9);
+        assert_transform_eq(CGAffineTransform::from_components(decomposed), transform);
     }
 
-    pub fn decompose(&self) -> CGAffineTransformComponents {
-        unsafe { CGAffineTransformDecompose(*self) }
+    #[test]
+    fn mul_point() {
+        let transform = CGAffineTransform::new(2.0, 1.0, -1.0, 3.0, 5.0, -7.0);
+        let point = CGPoint::new(2.0, 3.0);
+        assert_eq!(transform * point, CGPoint::new(6.0, 4.0));
+        assert_eq!(transform * point, point.apply_transform(&transform));
+        assert_eq!(CGAffineTransformIdentity * point, point);
     }
 }
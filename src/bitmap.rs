@@ -0,0 +1,677 @@
+use std::{
+    error::Error,
+    fmt::{self, Debug, Display, Formatter},
+    mem, ptr, slice,
+};
+
+use crate::{
+    affine_transform::CGAffineTransform,
+    bitmap_context::BitmapData,
+    color_space::CGColorSpaceModel,
+    image::{CGBitmapInfo, CGImageAlphaInfo},
+    pixel_conversion::{PixelConversionError, PixelConverter, PixelLayout},
+    pixel_format::PixelFormat,
+    resample::{self, ResampleError, ResampleFilter},
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BitmapError {
+    InvalidLayout(PixelConversionError),
+    ColorModelMismatch,
+    BytesPerRowTooSmall,
+    DataTooSmall,
+    SizeOverflow,
+    UnalignedRect,
+}
+
+impl Display for BitmapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BitmapError::InvalidLayout(error) => write!(f, "invalid pixel layout: {}", error),
+            BitmapError::ColorModelMismatch => write!(f, "color model does not match the number of color components"),
+            BitmapError::BytesPerRowTooSmall => write!(f, "bytes per row is smaller than a row of pixels"),
+            BitmapError::DataTooSmall => write!(f, "data is smaller than the bitmap"),
+            BitmapError::SizeOverflow => write!(f, "bitmap size overflows"),
+            BitmapError::UnalignedRect => write!(f, "rect does not start on a byte boundary"),
+        }
+    }
+}
+
+impl Error for BitmapError {}
+
+impl From<PixelConversionError> for BitmapError {
+    fn from(error: PixelConversionError) -> BitmapError {
+        BitmapError::InvalidLayout(error)
+    }
+}
+
+/// # Safety
+/// Implementors must be plain data without padding for which every bit pattern is a valid value,
+/// so that pixel bytes can be viewed as them.
+pub unsafe trait Pixel: Copy + 'static {}
+
+unsafe impl Pixel for u8 {}
+unsafe impl Pixel for u16 {}
+unsafe impl Pixel for u32 {}
+unsafe impl Pixel for u64 {}
+unsafe impl Pixel for f32 {}
+unsafe impl<T: Pixel, const N: usize> Pixel for [T; N] {}
+
+// storage is 16 byte aligned so rows can be viewed as any pixel type when bytes_per_row allows
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+struct Block([u8; 16]);
+
+#[derive(Clone)]
+pub struct Bitmap {
+    blocks: Vec<Block>,
+    len: usize,
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    layout: PixelLayout,
+    color_model: Option<CGColorSpaceModel>,
+}
+
+impl Debug for Bitmap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bitmap")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("bytes_per_row", &self.bytes_per_row)
+            .field("layout", &self.layout)
+            .field("color_model", &self.color_model)
+            .finish()
+    }
+}
+
+impl Bitmap {
+    // rows are padded to 16 bytes
+    pub fn new(width: usize, height: usize, format: PixelFormat) -> Option<Bitmap> {
+        let bytes_per_row = format.aligned_bytes_per_row(width)?;
+        Bitmap::with_layout(width, height, bytes_per_row, format.color_space_model(), format.into()).ok()
+    }
+
+    // a color model of None is an alpha only bitmap
+    pub fn with_layout(
+        width: usize,
+        height: usize,
+        bytes_per_row: usize,
+        color_model: Option<CGColorSpaceModel>,
+        layout: PixelLayout,
+    ) -> Result<Bitmap, BitmapError> {
+        let len = bitmap_len(width, height, bytes_per_row, color_model, &layout)?;
+        Ok(Bitmap {
+            blocks: vec![Block([0; 16]); len.div_ceil(16)],
+            len,
+            width,
+            height,
+            bytes_per_row,
+            layout,
+            color_model,
+        })
+    }
+
+    pub fn from_bytes(
+        data: &[u8],
+        width: usize,
+        height: usize,
+        bytes_per_row: usize,
+        color_model: Option<CGColorSpaceModel>,
+        layout: PixelLayout,
+    ) -> Result<Bitmap, BitmapError> {
+        let mut bitmap = Bitmap::with_layout(width, height, bytes_per_row, color_model, layout)?;
+        if data.len() < bitmap.len {
+            return Err(BitmapError::DataTooSmall);
+        }
+        let len = bitmap.len;
+        bitmap.data_mut().copy_from_slice(&data[..len]);
+        Ok(bitmap)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+
+    pub fn bits_per_component(&self) -> usize {
+        self.layout.bits_per_component()
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.layout.bits_per_pixel()
+    }
+
+    pub fn bitmap_info(&self) -> CGBitmapInfo {
+        self.layout.bitmap_info()
+    }
+
+    pub fn alpha_info(&self) -> CGImageAlphaInfo {
+        self.layout.alpha_info()
+    }
+
+    pub fn layout(&self) -> &PixelLayout {
+        &self.layout
+    }
+
+    pub fn color_model(&self) -> Option<CGColorSpaceModel> {
+        self.color_model
+    }
+
+    pub fn data(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.blocks.as_ptr() as *const u8, self.len) }
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.blocks.as_mut_ptr() as *mut u8, self.len) }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.data().to_vec()
+    }
+
+    pub fn as_view(&self) -> BitmapView<'_> {
+        BitmapView {
+            data: self.data(),
+            width: self.width,
+            height: self.height,
+            bytes_per_row: self.bytes_per_row,
+            layout: self.layout,
+            color_model: self.color_model,
+        }
+    }
+
+    pub fn as_view_mut(&mut self) -> BitmapViewMut<'_> {
+        let (width, height, bytes_per_row, layout, color_model) = (self.width, self.height, self.bytes_per_row, self.layout, self.color_model);
+        BitmapViewMut {
+            data: self.data_mut(),
+            width,
+            height,
+            bytes_per_row,
+            layout,
+            color_model,
+        }
+    }
+
+    pub fn row(&self, y: usize) -> Option<&[u8]> {
+        self.as_view().row(y)
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut [u8]> {
+        self.as_view_mut().into_row_mut(y)
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.as_view().rows()
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        self.as_view_mut().into_rows_mut()
+    }
+
+    pub fn rows_as<P: Pixel>(&self) -> Option<impl Iterator<Item = &[P]>> {
+        self.as_view().rows_as()
+    }
+
+    pub fn rows_as_mut<P: Pixel>(&mut self) -> Option<impl Iterator<Item = &mut [P]>> {
+        self.as_view_mut().into_rows_as_mut()
+    }
+
+    pub fn pixel<P: Pixel>(&self, x: usize, y: usize) -> Option<P> {
+        self.as_view().pixel(x, y)
+    }
+
+    pub fn set_pixel<P: Pixel>(&mut self, x: usize, y: usize, pixel: P) -> Option<()> {
+        self.as_view_mut().set_pixel(x, y, pixel)
+    }
+
+    pub fn color(&self, x: usize, y: usize) -> Option<([f32; 4], f32)> {
+        self.as_view().color(x, y)
+    }
+
+    pub fn set_color(&mut self, x: usize, y: usize, color: [f32; 4], alpha: f32) -> Option<()> {
+        self.as_view_mut().set_color(x, y, color, alpha)
+    }
+
+    pub fn view(&self, x: usize, y: usize, width: usize, height: usize) -> Option<BitmapView<'_>> {
+        self.as_view().view(x, y, width, height)
+    }
+
+    pub fn view_mut(&mut self, x: usize, y: usize, width: usize, height: usize) -> Option<BitmapViewMut<'_>> {
+        self.as_view_mut().into_view_mut(x, y, width, height)
+    }
+
+    pub fn copy_from(&mut self, source: &BitmapView<'_>, x: usize, y: usize) -> Result<(), BitmapError> {
+        self.as_view_mut().copy_from(source, x, y)
+    }
+
+    pub fn to_format(&self, format: PixelFormat) -> Result<Bitmap, BitmapError> {
+        self.as_view().to_bitmap(format)
+    }
+
+    pub fn resize(&self, width: usize, height: usize, filter: ResampleFilter) -> Result<Bitmap, ResampleError> {
+        resample::resize(&self.as_view(), width, height, filter)
+    }
+
+    pub fn transform(&self, transform: &CGAffineTransform, width: usize, height: usize, filter: ResampleFilter) -> Result<Bitmap, ResampleError> {
+        resample::transform(&self.as_view(), transform, width, height, filter)
+    }
+}
+
+// the bitmap is owned by the context and released along with it
+impl BitmapData for Bitmap {
+    unsafe fn ptr(&self) -> *const u8 {
+        self.blocks.as_ptr() as *const u8
+    }
+
+    unsafe fn mut_ptr(&self) -> *mut u8 {
+        self.blocks.as_ptr() as *mut u8
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+}
+
+// a rectangle of a bitmap, rows are bytes_per_row apart and the last row is not padded
+#[derive(Clone, Copy, Debug)]
+pub struct BitmapView<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    layout: PixelLayout,
+    color_model: Option<CGColorSpaceModel>,
+}
+
+impl<'a> BitmapView<'a> {
+    pub fn new(
+        data: &'a [u8],
+        width: usize,
+        height: usize,
+        bytes_per_row: usize,
+        color_model: Option<CGColorSpaceModel>,
+        layout: PixelLayout,
+    ) -> Result<BitmapView<'a>, BitmapError> {
+        let len = bitmap_len(width, height, bytes_per_row, color_model, &layout)?;
+        if data.len() < len {
+            return Err(BitmapError::DataTooSmall);
+        }
+        Ok(BitmapView {
+            data: &data[..len],
+            width,
+            height,
+            bytes_per_row,
+            layout,
+            color_model,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+
+    pub fn layout(&self) -> &PixelLayout {
+        &self.layout
+    }
+
+    pub fn color_model(&self) -> Option<CGColorSpaceModel> {
+        self.color_model
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn row(&self, y: usize) -> Option<&'a [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y * self.bytes_per_row;
+        Some(&self.data[start..start + self.row_len()])
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> {
+        let row_len = self.row_len();
+        self.data.chunks(self.bytes_per_row.max(1)).take(self.height).map(move |row| &row[..row_len])
+    }
+
+    // None when the pixel type does not have the size of a pixel or rows are not aligned for it
+    pub fn rows_as<P: Pixel>(&self) -> Option<impl Iterator<Item = &'a [P]>> {
+        check_pixel_type::<P>(self.data.as_ptr(), self.bytes_per_row, &self.layout)?;
+        let width = self.width;
+        Some(self.rows().map(move |row| unsafe { slice::from_raw_parts(row.as_ptr() as *const P, width) }))
+    }
+
+    pub fn pixel<P: Pixel>(&self, x: usize, y: usize) -> Option<P> {
+        let offset = pixel_offset::<P>(x, y, self.width, self.height, self.bytes_per_row, &self.layout)?;
+        Some(unsafe { ptr::read_unaligned(self.data[offset..offset + mem::size_of::<P>()].as_ptr() as *const P) })
+    }
+
+    // straight color components and alpha, components are in the range of the layout
+    pub fn color(&self, x: usize, y: usize) -> Option<([f32; 4], f32)> {
+        if x >= self.width {
+            return None;
+        }
+        Some(self.layout.read_pixel(self.row(y)?, x))
+    }
+
+    // copies the pixels into a new bitmap in the given format, component values are converted
+    // but not color matched so the color models must agree unless either side is alpha only
+    pub fn to_bitmap(&self, format: PixelFormat) -> Result<Bitmap, BitmapError> {
+        if let (Some(model), Some(format_model)) = (self.color_model, format.color_space_model()) {
+            if model != format_model {
+                return Err(BitmapError::ColorModelMismatch);
+            }
+        }
+        let bytes_per_row = format.aligned_bytes_per_row(self.width).ok_or(BitmapError::SizeOverflow)?;
+        let mut bitmap = Bitmap::with_layout(self.width, self.height, bytes_per_row, format.color_space_model(), format.into())?;
+        bitmap.copy_from(self, 0, 0)?;
+        Ok(bitmap)
+    }
+
+    pub fn view(&self, x: usize, y: usize, width: usize, height: usize) -> Option<BitmapView<'a>> {
+        let (start, len) = sub_rect((x, y, width, height), self.width, self.height, self.bytes_per_row, &self.layout)?;
+        Some(BitmapView {
+            data: &self.data[start..start + len],
+            width,
+            height,
+            ..*self
+        })
+    }
+
+    fn row_len(&self) -> usize {
+        row_len(self.width, &self.layout)
+    }
+}
+
+pub struct BitmapViewMut<'a> {
+    data: &'a mut [u8],
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    layout: PixelLayout,
+    color_model: Option<CGColorSpaceModel>,
+}
+
+impl<'a> BitmapViewMut<'a> {
+    pub fn new(
+        data: &'a mut [u8],
+        width: usize,
+        height: usize,
+        bytes_per_row: usize,
+        color_model: Option<CGColorSpaceModel>,
+        layout: PixelLayout,
+    ) -> Result<BitmapViewMut<'a>, BitmapError> {
+        let len = bitmap_len(width, height, bytes_per_row, color_model, &layout)?;
+        if data.len() < len {
+            return Err(BitmapError::DataTooSmall);
+        }
+        Ok(BitmapViewMut {
+            data: &mut data[..len],
+            width,
+            height,
+            bytes_per_row,
+            layout,
+            color_model,
+        })
+    }
+
+    pub fn as_view(&self) -> BitmapView<'_> {
+        BitmapView {
+            data: self.data,
+            width: self.width,
+            height: self.height,
+            bytes_per_row: self.bytes_per_row,
+            layout: self.layout,
+            color_model: self.color_model,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+
+    pub fn layout(&self) -> &PixelLayout {
+        &self.layout
+    }
+
+    pub fn color_model(&self) -> Option<CGColorSpaceModel> {
+        self.color_model
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let (start, row_len) = (y * self.bytes_per_row, row_len(self.width, &self.layout));
+        Some(&mut self.data[start..start + row_len])
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        let row_len = row_len(self.width, &self.layout);
+        self.data.chunks_mut(self.bytes_per_row.max(1)).take(self.height).map(move |row| &mut row[..row_len])
+    }
+
+    pub fn rows_as_mut<P: Pixel>(&mut self) -> Option<impl Iterator<Item = &mut [P]>> {
+        check_pixel_type::<P>(self.data.as_ptr(), self.bytes_per_row, &self.layout)?;
+        let width = self.width;
+        Some(self.rows_mut().map(move |row| unsafe { slice::from_raw_parts_mut(row.as_mut_ptr() as *mut P, width) }))
+    }
+
+    pub fn set_pixel<P: Pixel>(&mut self, x: usize, y: usize, pixel: P) -> Option<()> {
+        let offset = pixel_offset::<P>(x, y, self.width, self.height, self.bytes_per_row, &self.layout)?;
+        unsafe { ptr::write_unaligned(self.data[offset..offset + mem::size_of::<P>()].as_mut_ptr() as *mut P, pixel) };
+        Some(())
+    }
+
+    pub fn set_color(&mut self, x: usize, y: usize, color: [f32; 4], alpha: f32) -> Option<()> {
+        if x >= self.width {
+            return None;
+        }
+        let layout = self.layout;
+        layout.write_pixel(self.row_mut(y)?, x, color, alpha);
+        Some(())
+    }
+
+    pub fn view_mut(&mut self, x: usize, y: usize, width: usize, height: usize) -> Option<BitmapViewMut<'_>> {
+        let (start, len) = sub_rect((x, y, width, height), self.width, self.height, self.bytes_per_row, &self.layout)?;
+        Some(BitmapViewMut {
+            data: &mut self.data[start..start + len],
+            width,
+            height,
+            bytes_per_row: self.bytes_per_row,
+            layout: self.layout,
+            color_model: self.color_model,
+        })
+    }
+
+    // copies the source with its top left corner at x, y, converting between layouts,
+    // the part of the source outside of this view is skipped
+    pub fn copy_from(&mut self, source: &BitmapView<'_>, x: usize, y: usize) -> Result<(), BitmapError> {
+        let width = source.width.min(self.width.saturating_sub(x));
+        let height = source.height.min(self.height.saturating_sub(y));
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let converter = PixelConverter::new(source.layout, self.layout)?;
+        let source = source.view(0, 0, width, height).ok_or(BitmapError::UnalignedRect)?;
+        let mut destination = self.view_mut(x, y, width, height).ok_or(BitmapError::UnalignedRect)?;
+        let destination_bytes_per_row = destination.bytes_per_row;
+        converter.convert(source.data, source.bytes_per_row, destination.data_mut(), destination_bytes_per_row, width, height)?;
+        Ok(())
+    }
+
+    fn into_row_mut(self, y: usize) -> Option<&'a mut [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let (start, row_len) = (y * self.bytes_per_row, row_len(self.width, &self.layout));
+        let data = self.data;
+        Some(&mut data[start..start + row_len])
+    }
+
+    fn into_rows_mut(self) -> impl Iterator<Item = &'a mut [u8]> {
+        let row_len = row_len(self.width, &self.layout);
+        let data = self.data;
+        data.chunks_mut(self.bytes_per_row.max(1)).take(self.height).map(move |row| &mut row[..row_len])
+    }
+
+    fn into_rows_as_mut<P: Pixel>(self) -> Option<impl Iterator<Item = &'a mut [P]>> {
+        check_pixel_type::<P>(self.data.as_ptr(), self.bytes_per_row, &self.layout)?;
+        let width = self.width;
+        Some(self.into_rows_mut().map(move |row| unsafe { slice::from_raw_parts_mut(row.as_mut_ptr() as *mut P, width) }))
+    }
+
+    fn into_view_mut(self, x: usize, y: usize, width: usize, height: usize) -> Option<BitmapViewMut<'a>> {
+        let (start, len) = sub_rect((x, y, width, height), self.width, self.height, self.bytes_per_row, &self.layout)?;
+        let data = self.data;
+        Some(BitmapViewMut {
+            data: &mut data[start..start + len],
+            width,
+            height,
+            bytes_per_row: self.bytes_per_row,
+            layout: self.layout,
+            color_model: self.color_model,
+        })
+    }
+}
+
+fn row_len(width: usize, layout: &PixelLayout) -> usize {
+    (width * layout.bits_per_pixel()).div_ceil(8)
+}
+
+fn bitmap_len(
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    color_model: Option<CGColorSpaceModel>,
+    layout: &PixelLayout,
+) -> Result<usize, BitmapError> {
+    let color_components = match color_model {
+        None => Some(0),
+        Some(CGColorSpaceModel::Monochrome) | Some(CGColorSpaceModel::Indexed) => Some(1),
+        Some(CGColorSpaceModel::RGB) | Some(CGColorSpaceModel::Lab) | Some(CGColorSpaceModel::XYZ) => Some(3),
+        Some(CGColorSpaceModel::CMYK) => Some(4),
+        _ => None,
+    };
+    if color_components.is_some_and(|count| count != layout.color_components()) {
+        return Err(BitmapError::ColorModelMismatch);
+    }
+    let row_len = layout.min_bytes_per_row(width).ok_or(BitmapError::SizeOverflow)?;
+    if bytes_per_row < row_len {
+        return Err(BitmapError::BytesPerRowTooSmall);
+    }
+    match height {
+        0 => Ok(0),
+        _ => (height - 1).checked_mul(bytes_per_row).and_then(|len| len.checked_add(row_len)).ok_or(BitmapError::SizeOverflow),
+    }
+}
+
+fn check_pixel_type<P: Pixel>(data: *const u8, bytes_per_row: usize, layout: &PixelLayout) -> Option<()> {
+    let align = mem::align_of::<P>();
+    if mem::size_of::<P>() * 8 != layout.bits_per_pixel() || (data as usize) % align != 0 || bytes_per_row % align != 0 {
+        return None;
+    }
+    Some(())
+}
+
+fn pixel_offset<P: Pixel>(x: usize, y: usize, width: usize, height: usize, bytes_per_row: usize, layout: &PixelLayout) -> Option<usize> {
+    if x >= width || y >= height || mem::size_of::<P>() * 8 != layout.bits_per_pixel() {
+        return None;
+    }
+    Some(y * bytes_per_row + x * mem::size_of::<P>())
+}
+
+// the byte offset and length of an x, y, width, height rectangle, which must start on a byte boundary
+fn sub_rect(
+    rect: (usize, usize, usize, usize),
+    bitmap_width: usize,
+    bitmap_height: usize,
+    bytes_per_row: usize,
+    layout: &PixelLayout,
+) -> Option<(usize, usize)> {
+    let (x, y, width, height) = rect;
+    if x.checked_add(width)? > bitmap_width || y.checked_add(height)? > bitmap_height || (x * layout.bits_per_pixel()) % 8 != 0 {
+        return None;
+    }
+    if height == 0 {
+        return Some((0, 0));
+    }
+    let start = y * bytes_per_row + x * layout.bits_per_pixel() / 8;
+    Some((start, (height - 1) * bytes_per_row + row_len(width, layout)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn straight_rgba() -> PixelLayout {
+        PixelLayout::new(3, 8, 32, CGBitmapInfo::from_bits_retain(CGImageAlphaInfo::AlphaLast as u32)).unwrap()
+    }
+
+    #[test]
+    fn view_to_bitmap_premultiplies() {
+        // two rows of one pixel, 8 bytes apart
+        let data = [255, 128, 0, 128, 9, 9, 9, 9, 0, 0, 255, 255];
+        let view = BitmapView::new(&data, 1, 2, 8, Some(CGColorSpaceModel::RGB), straight_rgba()).unwrap();
+        let bitmap = view.to_bitmap(PixelFormat::RGBA8888).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height(), bitmap.bytes_per_row()), (1, 2, 16));
+        assert_eq!(bitmap.row(0), Some(&[128, 64, 0, 128][..]));
+        assert_eq!(bitmap.row(1), Some(&[0, 0, 255, 255][..]));
+        assert_eq!(bitmap.alpha_info(), CGImageAlphaInfo::AlphaPremultipliedLast);
+
+        let alpha = view.to_bitmap(PixelFormat::Alpha8).unwrap();
+        assert_eq!(alpha.color_model(), None);
+        assert_eq!(alpha.rows().collect::<Vec<_>>(), [&[128][..], &[255][..]]);
+    }
+
+    #[test]
+    fn view_to_bitmap_converts_components() {
+        let data = [0x12, 0x34, 0xff, 0xff];
+        let view = BitmapView::new(&data, 2, 1, 4, Some(CGColorSpaceModel::Monochrome), PixelFormat::Gray16.into()).unwrap();
+        assert_eq!(view.to_bitmap(PixelFormat::Gray8).unwrap().row(0), Some(&[0x12, 0xff][..]));
+        let floats = view.to_bitmap(PixelFormat::Gray32F).unwrap();
+        assert_eq!(floats.color(1, 0), Some(([1.0, 0.0, 0.0, 0.0], 1.0)));
+        assert_eq!(view.to_bitmap(PixelFormat::RGBA8888).err(), Some(BitmapError::ColorModelMismatch));
+        assert_eq!(
+            BitmapView::new(&data, 2, 1, 8, Some(CGColorSpaceModel::Monochrome), PixelFormat::Gray32F.into()).err(),
+            Some(BitmapError::DataTooSmall)
+        );
+    }
+}
@@ -4,9 +4,11 @@ use core_foundation::base::TCFType;
 use libc::{c_void, size_t};
 
 use crate::{
+    bitmap::Bitmap,
     color_space::{CGColorSpace, CGColorSpaceRef},
     context::{CGContext, CGContextRef},
     image::{CGBitmapInfo, CGImage, CGImageAlphaInfo, CGImageRef},
+    pixel_format::PixelFormat,
 };
 
 pub type CGBitmapContextReleaseDataCallback = extern "C" fn(*mut c_void, *mut c_void);
@@ -79,6 +81,22 @@ impl CGContext {
         }
     }
 
+    // returns None when the format does not fit the color space or row length, see PixelFormat::validate_color_space
+    // and PixelFormat::validate_bytes_per_row for the reason, a bytes_per_row of 0 lets CoreGraphics choose
+    pub fn new_bitmap_context_with_pixel_format(
+        width: size_t,
+        height: size_t,
+        bytes_per_row: size_t,
+        space: Option<&CGColorSpace>,
+        format: PixelFormat,
+    ) -> Option<Self> {
+        format.validate_color_space(space).ok()?;
+        if bytes_per_row != 0 {
+            format.validate_bytes_per_row(width, bytes_per_row).ok()?;
+        }
+        CGContext::new_bitmap_context(width, height, format.bits_per_component(), bytes_per_row, space, format.bitmap_info().bits())
+    }
+
     pub unsafe fn new_bitmap_context_with_data(
         data: &mut [u8],
         width: size_t,
@@ -143,6 +161,12 @@ impl CGContext {
         }
     }
 
+    // the context takes ownership of the bitmap and draws into its pixels
+    pub fn new_bitmap_context_with_bitmap(bitmap: Bitmap, space: Option<&CGColorSpace>) -> Option<Self> {
+        let (bits_per_component, bitmap_info) = (bitmap.bits_per_component(), bitmap.bitmap_info());
+        CGContext::new_bitmap_context_with_bitmap_data(Box::new(Box::new(bitmap)), bits_per_component, space, bitmap_info.bits())
+    }
+
     pub fn new_image(&self) -> Option<CGImage> {
         unsafe {
             let image = CGBitmapContextCreateImage(self.as_concrete_TypeRef());
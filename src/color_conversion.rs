@@ -0,0 +1,277 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    base::CGFloat,
+    bitmap::{BitmapError, BitmapView, BitmapViewMut},
+    color_model::{apply, invert, multiply, Matrix3, D50},
+    color_space::{CGColorRenderingIntent, CGColorSpaceModel},
+    icc::{Curve, IccProfile},
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorConversionError {
+    Bitmap(BitmapError),
+    UnsupportedProfile,
+    ComponentCountMismatch,
+    SizeMismatch,
+}
+
+impl Display for ColorConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorConversionError::Bitmap(error) => write!(f, "{}", error),
+            ColorConversionError::UnsupportedProfile => write!(f, "profile is not a gray or RGB matrix/TRC profile"),
+            ColorConversionError::ComponentCountMismatch => write!(f, "pixels do not have the color components of the profile"),
+            ColorConversionError::SizeMismatch => write!(f, "source and destination have a different size"),
+        }
+    }
+}
+
+impl Error for ColorConversionError {}
+
+impl From<BitmapError> for ColorConversionError {
+    fn from(error: BitmapError) -> ColorConversionError {
+        ColorConversionError::Bitmap(error)
+    }
+}
+
+// the options of CGColorConversionInfo::new_with_options and the rendering intent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorConversionOptions {
+    // the default intent is the one in the header of the source profile
+    pub intent: CGColorRenderingIntent,
+    // kCGColorConversionBlackPointCompensation, ignored for absolute colorimetric
+    pub black_point_compensation: bool,
+    // kCGColorConversionTRCSize, the curves are sampled into tables of this many entries instead of evaluated exactly
+    pub trc_size: Option<usize>,
+}
+
+impl Default for ColorConversionOptions {
+    fn default() -> ColorConversionOptions {
+        ColorConversionOptions {
+            intent: CGColorRenderingIntent::Default,
+            black_point_compensation: false,
+            trc_size: None,
+        }
+    }
+}
+
+// a curve in one direction, with its lookup table when the conversion has a TRC size
+#[derive(Clone, Debug)]
+struct Shaper {
+    curve: Curve,
+    inverse: bool,
+    table: Vec<CGFloat>,
+}
+
+impl Shaper {
+    fn new(curve: &Curve, inverse: bool, size: Option<usize>) -> Shaper {
+        let mut shaper = Shaper {
+            curve: curve.clone(),
+            inverse,
+            table: Vec::new(),
+        };
+        if let Some(size) = size.filter(|size| *size >= 2) {
+            shaper.table = (0..size).map(|index| shaper.evaluate(index as CGFloat / (size - 1) as CGFloat)).collect();
+        }
+        shaper
+    }
+
+    fn evaluate(&self, value: CGFloat) -> CGFloat {
+        match self.inverse {
+            true => self.curve.from_linear(value),
+            false => self.curve.to_linear(value),
+        }
+    }
+
+    fn apply(&self, value: CGFloat) -> CGFloat {
+        if self.table.is_empty() {
+            return self.evaluate(value);
+        }
+        let last = self.table.len() - 1;
+        let position = value.clamp(0.0, 1.0) * last as CGFloat;
+        let index = (position as usize).min(last - 1);
+        let fraction = position - index as CGFloat;
+        self.table[index] + (self.table[index + 1] - self.table[index]) * fraction
+    }
+}
+
+// one side of the conversion: the curves of the device components and the matrix from their linear values to the
+// D50 connection space, gray is the first column scaled by D50, and the media white used by absolute colorimetric
+struct Device {
+    curves: Vec<Curve>,
+    matrix: Matrix3,
+    white_point: [CGFloat; 3],
+}
+
+impl Device {
+    fn new(profile: &IccProfile) -> Result<Device, ColorConversionError> {
+        let unsupported = ColorConversionError::UnsupportedProfile;
+        if &profile.header().pcs != b"XYZ " {
+            return Err(unsupported);
+        }
+        let (curves, matrix) = match profile.header().color_space_model() {
+            CGColorSpaceModel::Monochrome => {
+                (vec![profile.gray_trc().ok_or(unsupported)?.clone()], [[D50[0], 0.0, 0.0], [D50[1], 0.0, 0.0], [D50[2], 0.0, 0.0]])
+            }
+            CGColorSpaceModel::RGB => {
+                let curves = profile.rgb_trc().ok_or(unsupported)?;
+                (curves.iter().map(|curve| (*curve).clone()).collect(), profile.colorant_matrix().ok_or(unsupported)?)
+            }
+            _ => return Err(unsupported),
+        };
+        Ok(Device {
+            curves,
+            matrix,
+            // profiles without a media white point are taken to reproduce their adopted white exactly
+            white_point: profile.media_white_point().unwrap_or_else(|| profile.adopted_white_point()),
+        })
+    }
+
+    // the Y of device black in the connection space, taken as neutral
+    fn black_point(&self) -> CGFloat {
+        let linear = [0, 1, 2].map(|index| self.curves.get(index).map_or(0.0, |curve| curve.to_linear(0.0)));
+        apply(&self.matrix, &linear)[1]
+    }
+}
+
+// a matrix/TRC pipeline between two gray or RGB profiles: source curves, one affine map of linear values and
+// destination curves, results are clipped to 0 to 1
+#[derive(Clone, Debug)]
+pub struct ColorConversion {
+    source: Vec<Shaper>,
+    matrix: Matrix3,
+    offset: [CGFloat; 3],
+    destination: Vec<Shaper>,
+}
+
+impl ColorConversion {
+    pub fn new(source: &IccProfile, destination: &IccProfile, options: ColorConversionOptions) -> Result<ColorConversion, ColorConversionError> {
+        let intent = match options.intent {
+            CGColorRenderingIntent::Default => source.header().rendering_intent,
+            intent => intent,
+        };
+        let source_device = Device::new(source)?;
+        let destination_device = Device::new(destination)?;
+        let to_destination = match destination_device.curves.len() {
+            1 => [[0.0, 1.0, 0.0], [0.0; 3], [0.0; 3]],
+            _ => invert(&destination_device.matrix).ok_or(ColorConversionError::UnsupportedProfile)?,
+        };
+
+        // in the connection space, absolute colorimetric scales by the ratio of the media white points and the other intents
+        // map black to black when compensating
+        let (scale, offset) = if intent == CGColorRenderingIntent::AbsoluteColorimetric {
+            let (source_white, destination_white) = (source_device.white_point, destination_device.white_point);
+            ([0, 1, 2].map(|index| source_white[index] / destination_white[index]), [0.0; 3])
+        } else if options.black_point_compensation {
+            let source_black = source_device.black_point();
+            let destination_black = destination_device.black_point();
+            let scale = (1.0 - destination_black) / (1.0 - source_black);
+            ([scale; 3], D50.map(|white| white * (destination_black - source_black * scale)))
+        } else {
+            ([1.0; 3], [0.0; 3])
+        };
+        let scale = [[scale[0], 0.0, 0.0], [0.0, scale[1], 0.0], [0.0, 0.0, scale[2]]];
+
+        Ok(ColorConversion {
+            source: source_device.curves.iter().map(|curve| Shaper::new(curve, false, options.trc_size)).collect(),
+            matrix: multiply(&to_destination, &multiply(&scale, &source_device.matrix)),
+            offset: apply(&to_destination, &offset),
+            destination: destination_device.curves.iter().map(|curve| Shaper::new(curve, true, options.trc_size)).collect(),
+        })
+    }
+
+    pub fn source_components(&self) -> usize {
+        self.source.len()
+    }
+
+    pub fn destination_components(&self) -> usize {
+        self.destination.len()
+    }
+
+    // missing components read as zero
+    pub fn convert_color(&self, components: &[CGFloat]) -> Vec<CGFloat> {
+        let mut linear = [0.0; 3];
+        for ((value, shaper), component) in linear.iter_mut().zip(&self.source).zip(components) {
+            *value = shaper.apply(*component);
+        }
+        let linear = apply(&self.matrix, &linear);
+        self.destination
+            .iter()
+            .zip(&linear)
+            .zip(&self.offset)
+            .map(|((shaper, value), offset)| shaper.apply((value + offset).clamp(0.0, 1.0)).clamp(0.0, 1.0))
+            .collect()
+    }
+
+    // alpha is carried over, the layouts must have the components of the profiles
+    pub fn convert(&self, source: &BitmapView<'_>, destination: &mut BitmapViewMut<'_>) -> Result<(), ColorConversionError> {
+        if source.layout().color_components() != self.source.len() || destination.layout().color_components() != self.destination.len() {
+            return Err(ColorConversionError::ComponentCountMismatch);
+        }
+        if source.width() != destination.width() || source.height() != destination.height() {
+            return Err(ColorConversionError::SizeMismatch);
+        }
+        let (source_layout, destination_layout) = (*source.layout(), *destination.layout());
+        let mut components = [0.0; 3];
+        for y in 0..source.height() {
+            let source_row = source.row(y).unwrap();
+            let destination_row = destination.row_mut(y).unwrap();
+            for x in 0..source.width() {
+                let (color, alpha) = source_layout.read_pixel(source_row, x);
+                for (component, value) in components.iter_mut().zip(&color) {
+                    *component = *value as CGFloat;
+                }
+                let converted = self.convert_color(&components[..self.source.len()]);
+                let mut color = [0.0; 4];
+                for (value, component) in color.iter_mut().zip(&converted) {
+                    *value = *component as f32;
+                }
+                destination_layout.write_pixel(destination_row, x, color, alpha);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color_model::ColorModel,
+        icc::{IccTagData, CHROMATIC_ADAPTATION_TAG, MEDIA_WHITE_POINT_TAG},
+    };
+
+    fn absolute(source: &IccProfile, destination: &IccProfile, components: &[CGFloat]) -> Vec<CGFloat> {
+        let options = ColorConversionOptions {
+            intent: CGColorRenderingIntent::AbsoluteColorimetric,
+            ..Default::default()
+        };
+        ColorConversion::new(source, destination, options).unwrap().convert_color(components)
+    }
+
+    #[test]
+    fn absolute_colorimetric_uses_the_media_white_point() {
+        // v4 display profiles have a D50 media white whatever white their chad adapts from
+        let srgb = IccProfile::from_color_model(&ColorModel::srgb(), "sRGB").unwrap();
+        assert!(srgb.tag(CHROMATIC_ADAPTATION_TAG).is_some());
+        let white = absolute(&srgb, &srgb, &[1.0, 1.0, 1.0]);
+        assert!(white.iter().all(|component| (component - 1.0).abs() < 1e-6), "{:?}", white);
+
+        // a darker paper white darkens everything in proportion
+        let mut paper = IccProfile::new_gray("paper", &D50, Curve::Table(Vec::new()));
+        paper.set_tag(MEDIA_WHITE_POINT_TAG, IccTagData::XYZ(vec![D50.map(|value| value * 0.8)]));
+        let display = IccProfile::new_gray("display", &D50, Curve::Table(Vec::new()));
+        let gray = absolute(&paper, &display, &[1.0]);
+        assert!((gray[0] - 0.8).abs() < 1e-6, "{:?}", gray);
+
+        // without one the adopted white of chad is used, so the D65 white of sRGB turns blue against D50
+        let mut adopted = srgb.clone();
+        adopted.remove_tag(MEDIA_WHITE_POINT_TAG);
+        let white = absolute(&adopted, &srgb, &[1.0, 1.0, 1.0]);
+        assert!(white[0] < 0.99 && white[2] > white[0], "{:?}", white);
+    }
+}
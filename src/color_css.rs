@@ -0,0 +1,667 @@
+use std::{
+    error::Error,
+    f64::consts::PI,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use core_foundation::string::CFString;
+
+use crate::{
+    base::CGFloat,
+    color::CGColor,
+    color_space::{CGColorSpace, CGColorSpaceNames},
+};
+
+// the predefined spaces of color() with the color spaces they map to, and the extended range variant used for
+// components outside of 0 to 1
+const COLOR_SPACES: [(&str, CGColorSpaceNames, Option<CGColorSpaceNames>); 7] = [
+    ("srgb", CGColorSpaceNames::SRGB, Some(CGColorSpaceNames::ExtendedSRGB)),
+    ("srgb-linear", CGColorSpaceNames::LinearSRGB, Some(CGColorSpaceNames::ExtendedLinearSRGB)),
+    ("display-p3", CGColorSpaceNames::DisplayP3, Some(CGColorSpaceNames::ExtendedDisplayP3)),
+    ("a98-rgb", CGColorSpaceNames::AdobeRGB1998, None),
+    ("prophoto-rgb", CGColorSpaceNames::ROMMRGB, None),
+    ("rec2020", CGColorSpaceNames::ITUR_2020, Some(CGColorSpaceNames::ExtendedITUR_2020)),
+    ("xyz-d50", CGColorSpaceNames::GenericXYZ, None),
+];
+
+// the CSS named colors as 0xRRGGBB, sorted by name
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CssColorError {
+    UnexpectedCharacter(usize),
+    UnexpectedEnd,
+    UnknownName(usize),
+}
+
+impl Display for CssColorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CssColorError::UnexpectedCharacter(position) => write!(f, "unexpected character at position {}", position),
+            CssColorError::UnexpectedEnd => write!(f, "unexpected end of color"),
+            CssColorError::UnknownName(position) => write!(f, "unknown color, function or color space name at position {}", position),
+        }
+    }
+}
+
+impl Error for CssColorError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Unit {
+    Number,
+    Percent,
+    Degrees,
+    Radians,
+    Gradians,
+    Turns,
+}
+
+// a function argument, none is a missing value and reads as zero
+#[derive(Clone, Copy, Debug)]
+struct Argument {
+    value: Option<CGFloat>,
+    unit: Unit,
+    position: usize,
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a str) -> Parser<'a> {
+        Parser {
+            data: data.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r' | b'\x0C') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), CssColorError> {
+        self.skip_whitespace();
+        if self.peek() != Some(byte) {
+            return Err(self.error_at(self.position));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    // lowercased, CSS names are ASCII case insensitive
+    fn identifier(&mut self) -> String {
+        let start = self.position;
+        while let Some(b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-') = self.peek() {
+            self.position += 1;
+        }
+        String::from_utf8_lossy(&self.data[start..self.position]).to_ascii_lowercase()
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.position;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    fn argument(&mut self) -> Result<Argument, CssColorError> {
+        self.skip_whitespace();
+        let start = self.position;
+        if let Some(b'a'..=b'z' | b'A'..=b'Z') = self.peek() {
+            return match self.identifier().as_str() {
+                "none" => Ok(Argument {
+                    value: None,
+                    unit: Unit::Number,
+                    position: start,
+                }),
+                _ => Err(CssColorError::UnexpectedCharacter(start)),
+            };
+        }
+        if let Some(b'-' | b'+') = self.peek() {
+            self.position += 1;
+        }
+        let mut digits = self.digits();
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            digits += self.digits();
+        }
+        if digits == 0 {
+            return Err(self.error_at(start));
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            let mantissa_end = self.position;
+            self.position += 1;
+            if let Some(b'-' | b'+') = self.peek() {
+                self.position += 1;
+            }
+            if self.digits() == 0 {
+                self.position = mantissa_end;
+            }
+        }
+        // the scanned range only contains ASCII digits, signs, dots and exponents
+        let number = std::str::from_utf8(&self.data[start..self.position]).map_err(|_| self.error_at(start))?;
+        let value = number.parse::<CGFloat>().map_err(|_| self.error_at(start))?;
+        let unit = match self.peek() {
+            Some(b'%') => {
+                self.position += 1;
+                Unit::Percent
+            }
+            Some(b'a'..=b'z' | b'A'..=b'Z') => match self.identifier().as_str() {
+                "deg" => Unit::Degrees,
+                "rad" => Unit::Radians,
+                "grad" => Unit::Gradians,
+                "turn" => Unit::Turns,
+                _ => return Err(CssColorError::UnexpectedCharacter(start)),
+            },
+            _ => Unit::Number,
+        };
+        Ok(Argument {
+            value: Some(value),
+            unit,
+            position: start,
+        })
+    }
+
+    // three arguments and an optional alpha, separated by commas in the legacy syntax or by spaces with a slash
+    // before alpha in the modern one
+    fn arguments(&mut self) -> Result<([Argument; 3], Option<Argument>), CssColorError> {
+        let mut arguments = vec![self.argument()?];
+        let mut alpha = None;
+        let mut legacy = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b')') => break,
+                Some(b',') if legacy != Some(false) => {
+                    legacy = Some(true);
+                    self.position += 1;
+                    arguments.push(self.argument()?);
+                }
+                Some(b'/') if legacy != Some(true) && alpha.is_none() => {
+                    self.position += 1;
+                    alpha = Some(self.argument()?);
+                }
+                Some(_) if legacy != Some(true) && alpha.is_none() => {
+                    legacy = Some(false);
+                    arguments.push(self.argument()?);
+                }
+                _ => return Err(self.error_at(self.position)),
+            }
+        }
+        if legacy == Some(true) && arguments.len() == 4 {
+            alpha = arguments.pop();
+        }
+        if arguments.len() != 3 {
+            return Err(CssColorError::UnexpectedCharacter(self.position));
+        }
+        self.expect(b')')?;
+        Ok(([arguments[0], arguments[1], arguments[2]], alpha))
+    }
+
+    fn error_at(&self, position: usize) -> CssColorError {
+        if position >= self.data.len() {
+            CssColorError::UnexpectedEnd
+        } else {
+            CssColorError::UnexpectedCharacter(position)
+        }
+    }
+}
+
+// the value of an argument with the units allowed for it, percentages scaled so 100% is the given value
+fn value(argument: &Argument, hundred_percent: CGFloat, units: &[Unit]) -> Result<CGFloat, CssColorError> {
+    if !units.contains(&argument.unit) {
+        return Err(CssColorError::UnexpectedCharacter(argument.position));
+    }
+    let value = argument.value.unwrap_or(0.0);
+    Ok(match argument.unit {
+        Unit::Percent => value / 100.0 * hundred_percent,
+        Unit::Radians => value * 180.0 / PI,
+        Unit::Gradians => value * 0.9,
+        Unit::Turns => value * 360.0,
+        Unit::Number | Unit::Degrees => value,
+    })
+}
+
+fn alpha_value(alpha: Option<Argument>) -> Result<CGFloat, CssColorError> {
+    match alpha {
+        Some(alpha) => Ok(value(&alpha, 1.0, &[Unit::Number, Unit::Percent])?.clamp(0.0, 1.0)),
+        None => Ok(1.0),
+    }
+}
+
+fn hsl_to_rgb(hue: CGFloat, saturation: CGFloat, lightness: CGFloat) -> [CGFloat; 3] {
+    let hue = hue.rem_euclid(360.0);
+    let amount = saturation * lightness.min(1.0 - lightness);
+    [0.0, 8.0, 4.0].map(|offset: CGFloat| {
+        let k = (offset + hue / 30.0) % 12.0;
+        lightness - amount * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    })
+}
+
+// components in one of the color spaces CSS can name, alpha is kept apart from them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CssColor {
+    space: CGColorSpaceNames,
+    components: [CGFloat; 3],
+    alpha: CGFloat,
+}
+
+impl CssColor {
+    // the spaces of color(), extended range names are taken as their base space
+    pub fn new(space: CGColorSpaceNames, components: [CGFloat; 3], alpha: CGFloat) -> Option<CssColor> {
+        let (_, space, _) = COLOR_SPACES.iter().find(|(_, base, extended)| *base == space || *extended == Some(space))?;
+        Some(CssColor {
+            space: *space,
+            components,
+            alpha,
+        })
+    }
+
+    pub fn new_srgb(red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) -> CssColor {
+        CssColor {
+            space: CGColorSpaceNames::SRGB,
+            components: [red, green, blue],
+            alpha,
+        }
+    }
+
+    pub fn parse(data: &str) -> Result<CssColor, CssColorError> {
+        parse_css_color(data)
+    }
+
+    // colors in one of the spaces of color() with three components and alpha
+    pub fn from_color(color: &CGColor) -> Option<CssColor> {
+        let name = color.color_space()?.name()?;
+        let space = COLOR_SPACES.iter().flat_map(|(_, base, extended)| Some(*base).into_iter().chain(*extended)).find(|space| {
+            let space_name: CFString = (*space).into();
+            space_name == name
+        })?;
+        match *color.components() {
+            [red, green, blue, alpha] => CssColor::new(space, [red, green, blue], alpha),
+            _ => None,
+        }
+    }
+
+    // sRGB colors are created with CGColor::new_srgb, components outside of 0 to 1 use the extended range variant
+    // of the space when there is one
+    pub fn to_color(&self) -> Option<CGColor> {
+        let [red, green, blue] = self.components;
+        let in_range = self.components.iter().all(|component| (0.0..=1.0).contains(component));
+        if self.space == CGColorSpaceNames::SRGB && in_range {
+            return Some(CGColor::new_srgb(red, green, blue, self.alpha));
+        }
+        let (_, base, extended) = COLOR_SPACES.iter().find(|(_, base, _)| *base == self.space)?;
+        let name = match in_range {
+            true => *base,
+            false => extended.unwrap_or(*base),
+        };
+        CGColor::new(&CGColorSpace::from_name(&name.into())?, &[red, green, blue, self.alpha])
+    }
+
+    pub fn color_space_name(&self) -> CGColorSpaceNames {
+        self.space
+    }
+
+    pub fn components(&self) -> [CGFloat; 3] {
+        self.components
+    }
+
+    pub fn alpha(&self) -> CGFloat {
+        self.alpha
+    }
+}
+
+// hex colors when the components and alpha are exact 8 bit values in sRGB, color() otherwise
+impl Display for CssColor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let byte = |value: CGFloat| match (0.0..=1.0).contains(&value) && (value * 255.0).round() / 255.0 == value {
+            true => Some((value * 255.0).round() as u8),
+            false => None,
+        };
+        let bytes = [
+            byte(self.components[0]),
+            byte(self.components[1]),
+            byte(self.components[2]),
+            byte(self.alpha),
+        ];
+        if let (CGColorSpaceNames::SRGB, [Some(red), Some(green), Some(blue), Some(alpha)]) = (self.space, bytes) {
+            write!(f, "#{:02x}{:02x}{:02x}", red, green, blue)?;
+            if alpha != 255 {
+                write!(f, "{:02x}", alpha)?;
+            }
+            return Ok(());
+        }
+        let (name, _, _) = COLOR_SPACES.iter().find(|(_, space, _)| *space == self.space).ok_or(fmt::Error)?;
+        // adding zero turns negative zero into zero
+        let [first, second, third] = self.components.map(|component| component + 0.0);
+        write!(f, "color({} {} {} {}", name, first, second, third)?;
+        if self.alpha != 1.0 {
+            write!(f, " / {}", self.alpha + 0.0)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl FromStr for CssColor {
+    type Err = CssColorError;
+
+    fn from_str(data: &str) -> Result<CssColor, CssColorError> {
+        parse_css_color(data)
+    }
+}
+
+// hex colors, rgb(), rgba(), hsl(), hsla(), color() and the named colors, rgb() and hsl() components and all alpha
+// values are clamped to 0 to 1 while color() components may be out of range
+pub fn parse_css_color(data: &str) -> Result<CssColor, CssColorError> {
+    let mut parser = Parser::new(data);
+    parser.skip_whitespace();
+    let start = parser.position;
+    let color = match parser.peek() {
+        Some(b'#') => {
+            parser.position += 1;
+            let digits_start = parser.position;
+            while let Some(b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F') = parser.peek() {
+                parser.position += 1;
+            }
+            let digits = std::str::from_utf8(&parser.data[digits_start..parser.position]).unwrap();
+            let value = |index: usize, width: usize| {
+                let value = u8::from_str_radix(&digits[index * width..(index + 1) * width], 16).unwrap();
+                // a single digit is repeated, f is ff
+                let value = if width == 1 {
+                    value * 17
+                } else {
+                    value
+                };
+                value as CGFloat / 255.0
+            };
+            let width = match digits.len() {
+                3 | 4 => 1,
+                6 | 8 => 2,
+                _ if parser.position < parser.data.len() => return Err(CssColorError::UnexpectedCharacter(parser.position)),
+                _ => return Err(CssColorError::UnexpectedCharacter(start)),
+            };
+            let alpha = match digits.len() / width {
+                4 => value(3, width),
+                _ => 1.0,
+            };
+            CssColor::new_srgb(value(0, width), value(1, width), value(2, width), alpha)
+        }
+        Some(b'a'..=b'z' | b'A'..=b'Z') => {
+            let name = parser.identifier();
+            if parser.peek() == Some(b'(') {
+                parser.position += 1;
+                match name.as_str() {
+                    "rgb" | "rgba" => {
+                        let (arguments, alpha) = parser.arguments()?;
+                        let mut components = [0.0; 3];
+                        for (component, argument) in components.iter_mut().zip(&arguments) {
+                            *component = value(argument, 255.0, &[Unit::Number, Unit::Percent])?.clamp(0.0, 255.0) / 255.0;
+                        }
+                        CssColor::new_srgb(components[0], components[1], components[2], alpha_value(alpha)?)
+                    }
+                    "hsl" | "hsla" => {
+                        let ([hue, saturation, lightness], alpha) = parser.arguments()?;
+                        let hue = value(&hue, 1.0, &[Unit::Number, Unit::Degrees, Unit::Radians, Unit::Gradians, Unit::Turns])?;
+                        let saturation = value(&saturation, 100.0, &[Unit::Number, Unit::Percent])?.clamp(0.0, 100.0) / 100.0;
+                        let lightness = value(&lightness, 100.0, &[Unit::Number, Unit::Percent])?.clamp(0.0, 100.0) / 100.0;
+                        let [red, green, blue] = hsl_to_rgb(hue, saturation, lightness);
+                        CssColor::new_srgb(red, green, blue, alpha_value(alpha)?)
+                    }
+                    "color" => {
+                        parser.skip_whitespace();
+                        let space_start = parser.position;
+                        let space = parser.identifier();
+                        let (_, space, _) = COLOR_SPACES.iter().find(|(name, _, _)| *name == space).ok_or(CssColorError::UnknownName(space_start))?;
+                        let (arguments, alpha) = parser.arguments()?;
+                        let mut components = [0.0; 3];
+                        for (component, argument) in components.iter_mut().zip(&arguments) {
+                            *component = value(argument, 1.0, &[Unit::Number, Unit::Percent])?;
+                        }
+                        CssColor {
+                            space: *space,
+                            components,
+                            alpha: alpha_value(alpha)?,
+                        }
+                    }
+                    _ => return Err(CssColorError::UnknownName(start)),
+                }
+            } else if name == "transparent" {
+                CssColor::new_srgb(0.0, 0.0, 0.0, 0.0)
+            } else {
+                let index = NAMED_COLORS.binary_search_by(|(color, _)| (*color).cmp(name.as_str())).map_err(|_| CssColorError::UnknownName(start))?;
+                let rgb = NAMED_COLORS[index].1;
+                let channel = |shift: u32| ((rgb >> shift) & 0xff) as CGFloat / 255.0;
+                CssColor::new_srgb(channel(16), channel(8), channel(0), 1.0)
+            }
+        }
+        _ => return Err(parser.error_at(start)),
+    };
+    parser.skip_whitespace();
+    if parser.position < parser.data.len() {
+        return Err(CssColorError::UnexpectedCharacter(parser.position));
+    }
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let cases = [
+            ("#FF8000", "#ff8000"),
+            ("#f80", "#ff8800"),
+            ("#f808", "#ff880088"),
+            ("#ff800080", "#ff800080"),
+            ("rgb(255, 128, 0)", "#ff8000"),
+            ("rgba(255 0 0 / 50%)", "color(srgb 1 0 0 / 0.5)"),
+            ("rgb(300 -20 none)", "#ff0000"),
+            ("hsl(120, 100%, 50%)", "#00ff00"),
+            ("hsl(0.5turn 100% 50%)", "#00ffff"),
+            ("rebeccapurple", "#663399"),
+            ("  Transparent ", "#00000000"),
+            ("color(display-p3 1 0 0)", "color(display-p3 1 0 0)"),
+            ("color(srgb 1.5 -0.25 -0 / 0.25)", "color(srgb 1.5 -0.25 0 / 0.25)"),
+            ("color(srgb-linear 50% 0.1 1e-1)", "color(srgb-linear 0.5 0.1 0.1)"),
+            ("color(xyz-d50 0.9642 1 0.8249)", "color(xyz-d50 0.9642 1 0.8249)"),
+        ];
+        for (input, formatted) in cases {
+            let color = CssColor::parse(input).unwrap();
+            assert_eq!(color.to_string(), formatted, "{}", input);
+            assert_eq!(formatted.parse::<CssColor>(), Ok(color), "{}", input);
+        }
+        let color = CssColor::parse("hsl(210 40% 30% / 0.7)").unwrap();
+        assert!(color.to_string().starts_with("color(srgb "));
+        assert_eq!(CssColor::parse(&color.to_string()), Ok(color));
+        let color = CssColor::new(CGColorSpaceNames::ExtendedDisplayP3, [1.25, 0.5, -0.5], 1.0).unwrap();
+        assert_eq!(color.color_space_name(), CGColorSpaceNames::DisplayP3);
+        assert_eq!(color.to_string(), "color(display-p3 1.25 0.5 -0.5)");
+        assert_eq!(CssColor::parse(&color.to_string()), Ok(color));
+    }
+
+    #[test]
+    fn error_positions() {
+        let cases = [
+            ("", CssColorError::UnexpectedEnd),
+            ("   ", CssColorError::UnexpectedEnd),
+            ("#12", CssColorError::UnexpectedCharacter(0)),
+            ("#12345g", CssColorError::UnexpectedCharacter(6)),
+            ("#123 x", CssColorError::UnexpectedCharacter(5)),
+            ("notacolor", CssColorError::UnknownName(0)),
+            ("  foo(1 2 3)", CssColorError::UnknownName(2)),
+            ("color( cmyk 1 2 3)", CssColorError::UnknownName(7)),
+            ("rgb(1, 2 3)", CssColorError::UnexpectedCharacter(9)),
+            ("rgb(1 2, 3)", CssColorError::UnexpectedCharacter(7)),
+            ("rgb(1 2)", CssColorError::UnexpectedCharacter(7)),
+            ("rgb(1 2 3", CssColorError::UnexpectedEnd),
+            ("rgb(1 2deg 3)", CssColorError::UnexpectedCharacter(6)),
+            ("hsl(1 2 foo)", CssColorError::UnexpectedCharacter(8)),
+            ("rgb(1 2 3 / 4 / 5)", CssColorError::UnexpectedCharacter(14)),
+            ("red blue", CssColorError::UnexpectedCharacter(4)),
+            ("(1 2 3)", CssColorError::UnexpectedCharacter(0)),
+        ];
+        for (input, error) in cases {
+            assert_eq!(CssColor::parse(input), Err(error), "{}", input);
+        }
+    }
+}
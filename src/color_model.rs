@@ -0,0 +1,566 @@
+use crate::{
+    base::CGFloat,
+    color_space::{CGColorRenderingIntent, CGColorSpaceModel},
+};
+
+pub type Matrix3 = [[CGFloat; 3]; 3];
+
+// XYZ of the standard illuminants, normalized to Y = 1
+pub const D50: [CGFloat; 3] = [0.9642, 1.0, 0.8249];
+pub const D65: [CGFloat; 3] = [0.95047, 1.0, 1.08883];
+
+const BRADFORD: Matrix3 = [[0.8951, 0.2664, -0.1614], [-0.7502, 1.7135, 0.0367], [0.0389, -0.0685, 1.0296]];
+const LAB_RANGE: [CGFloat; 4] = [-100.0, 100.0, -100.0, 100.0];
+
+pub fn multiply(first: &Matrix3, second: &Matrix3) -> Matrix3 {
+    let mut result = [[0.0; 3]; 3];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = (0..3).map(|index| first[row][index] * second[index][column]).sum();
+        }
+    }
+    result
+}
+
+pub fn apply(matrix: &Matrix3, vector: &[CGFloat; 3]) -> [CGFloat; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+pub fn invert(matrix: &Matrix3) -> Option<Matrix3> {
+    let [[a, b, c], [d, e, f], [g, h, i]] = *matrix;
+    let determinant = a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g);
+    if determinant == 0.0 || !determinant.is_finite() {
+        return None;
+    }
+    let cofactors = [
+        [e * i - f * h, c * h - b * i, b * f - c * e],
+        [f * g - d * i, a * i - c * g, c * d - a * f],
+        [d * h - e * g, b * g - a * h, a * e - b * d],
+    ];
+    Some(cofactors.map(|row| row.map(|value| value / determinant)))
+}
+
+// XYZ of a chromaticity with Y = 1
+pub fn xy_to_xyz(xy: &[CGFloat; 2]) -> [CGFloat; 3] {
+    [xy[0] / xy[1], 1.0, (1.0 - xy[0] - xy[1]) / xy[1]]
+}
+
+// Bradford adaptation of XYZ colors seen under the source white to the destination white
+pub fn bradford_adaptation(source_white: &[CGFloat; 3], destination_white: &[CGFloat; 3]) -> Matrix3 {
+    let source = apply(&BRADFORD, source_white);
+    let destination = apply(&BRADFORD, destination_white);
+    let scale = [
+        [destination[0] / source[0], 0.0, 0.0],
+        [0.0, destination[1] / source[1], 0.0],
+        [0.0, 0.0, destination[2] / source[2]],
+    ];
+    multiply(&invert(&BRADFORD).unwrap(), &multiply(&scale, &BRADFORD))
+}
+
+// the matrix from linear RGB to XYZ for the chromaticities of the red, green and blue primaries and of white
+pub fn rgb_to_xyz_matrix(primaries: &[[CGFloat; 2]; 3], white: &[CGFloat; 2]) -> Option<Matrix3> {
+    let [red, green, blue] = primaries.map(|xy| xy_to_xyz(&xy));
+    let primaries = [[red[0], green[0], blue[0]], [red[1], green[1], blue[1]], [red[2], green[2], blue[2]]];
+    let scale = apply(&invert(&primaries)?, &xy_to_xyz(white));
+    Some(primaries.map(|row| [row[0] * scale[0], row[1] * scale[1], row[2] * scale[2]]))
+}
+
+pub fn xyz_to_lab(xyz: &[CGFloat; 3], white: &[CGFloat; 3]) -> [CGFloat; 3] {
+    let f = |t: CGFloat| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let [x, y, z] = [f(xyz[0] / white[0]), f(xyz[1] / white[1]), f(xyz[2] / white[2])];
+    [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+pub fn lab_to_xyz(lab: &[CGFloat; 3], white: &[CGFloat; 3]) -> [CGFloat; 3] {
+    let inverse = |t: CGFloat| {
+        if t > 6.0 / 29.0 {
+            t * t * t
+        } else {
+            (116.0 * t - 16.0) * 27.0 / 24389.0
+        }
+    };
+    let y = (lab[0] + 16.0) / 116.0;
+    [
+        white[0] * inverse(y + lab[1] / 500.0),
+        white[1] * inverse(y),
+        white[2] * inverse(y - lab[2] / 200.0),
+    ]
+}
+
+// maps encoded component values to linear light, negative values are mirrored as in extended range spaces
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferFunction {
+    Linear,
+    Gamma(CGFloat),
+    // ICC parametric curve: (a * x + b) ^ g + e from d up, c * x + f below d
+    Parametric {
+        g: CGFloat,
+        a: CGFloat,
+        b: CGFloat,
+        c: CGFloat,
+        d: CGFloat,
+        e: CGFloat,
+        f: CGFloat,
+    },
+    // SMPTE ST 2084, linear 1.0 is 100 cd/m² so the full signal of 10000 cd/m² is 100.0
+    Pq,
+    // ARIB STD-B67 inverse OETF, linear 1.0 is a signal of 0.5 and the full signal is 12.0
+    Hlg,
+}
+
+const PQ_M1: CGFloat = 2610.0 / 16384.0;
+const PQ_M2: CGFloat = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: CGFloat = 3424.0 / 4096.0;
+const PQ_C2: CGFloat = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: CGFloat = 2392.0 / 4096.0 * 32.0;
+const HLG_A: CGFloat = 0.17883277;
+const HLG_B: CGFloat = 0.28466892;
+const HLG_C: CGFloat = 0.55991073;
+
+impl TransferFunction {
+    pub const SRGB: TransferFunction = TransferFunction::Parametric {
+        g: 2.4,
+        a: 1.0 / 1.055,
+        b: 0.055 / 1.055,
+        c: 1.0 / 12.92,
+        d: 0.04045,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    pub fn to_linear(&self, value: CGFloat) -> CGFloat {
+        let magnitude = value.abs();
+        let linear = match *self {
+            TransferFunction::Linear => magnitude,
+            TransferFunction::Gamma(gamma) => magnitude.powf(gamma),
+            TransferFunction::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if magnitude >= d {
+                    (a * magnitude + b).max(0.0).powf(g) + e
+                } else {
+                    c * magnitude + f
+                }
+            }
+            TransferFunction::Pq => {
+                let power = magnitude.powf(1.0 / PQ_M2);
+                ((power - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * power)).powf(1.0 / PQ_M1) * 100.0
+            }
+            TransferFunction::Hlg => {
+                if magnitude <= 0.5 {
+                    4.0 * magnitude * magnitude
+                } else {
+                    ((magnitude - HLG_C) / HLG_A).exp() + HLG_B
+                }
+            }
+        };
+        linear.copysign(value)
+    }
+
+    pub fn from_linear(&self, value: CGFloat) -> CGFloat {
+        let magnitude = value.abs();
+        let encoded = match *self {
+            TransferFunction::Linear => magnitude,
+            TransferFunction::Gamma(gamma) => magnitude.powf(1.0 / gamma),
+            TransferFunction::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if magnitude >= (a * d + b).max(0.0).powf(g) + e {
+                    ((magnitude - e).max(0.0).powf(1.0 / g) - b) / a
+                } else if c != 0.0 {
+                    (magnitude - f) / c
+                } else {
+                    0.0
+                }
+            }
+            TransferFunction::Pq => {
+                let power = (magnitude / 100.0).powf(PQ_M1);
+                ((PQ_C1 + PQ_C2 * power) / (1.0 + PQ_C3 * power)).powf(PQ_M2)
+            }
+            TransferFunction::Hlg => {
+                if magnitude <= 1.0 {
+                    magnitude.sqrt() / 2.0
+                } else {
+                    HLG_A * (magnitude - HLG_B).ln() + HLG_C
+                }
+            }
+        };
+        encoded.copysign(value)
+    }
+}
+
+// a colorimetric description of a color space for each CGColorSpaceModel the crate converts between,
+// colors are carried between models as absolute XYZ
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorModel {
+    Monochrome {
+        white_point: [CGFloat; 3],
+        transfer: TransferFunction,
+        // components are not clipped to 0 to 1
+        extended_range: bool,
+    },
+    RGB {
+        white_point: [CGFloat; 3],
+        // linear RGB to XYZ relative to the white point
+        matrix: Matrix3,
+        transfer: [TransferFunction; 3],
+        extended_range: bool,
+    },
+    // uncalibrated device CMYK, converted through sRGB by its naive complement
+    CMYK,
+    Lab {
+        white_point: [CGFloat; 3],
+        // minimum and maximum of a and b
+        range: [CGFloat; 4],
+    },
+    // XYZ relative to D50
+    XYZ,
+    Indexed {
+        base: Box<ColorModel>,
+        // the components of each entry in the base model, scaled to 0 to 255
+        table: Vec<u8>,
+    },
+}
+
+impl ColorModel {
+    pub fn srgb() -> ColorModel {
+        ColorModel::new_rgb(&[[0.64, 0.33], [0.30, 0.60], [0.15, 0.06]], &[0.3127, 0.3290], TransferFunction::SRGB).unwrap()
+    }
+
+    pub fn generic_gray_gamma_2_2() -> ColorModel {
+        ColorModel::Monochrome {
+            white_point: D50,
+            transfer: TransferFunction::Gamma(2.2),
+            extended_range: false,
+        }
+    }
+
+    pub fn new_rgb(primaries: &[[CGFloat; 2]; 3], white: &[CGFloat; 2], transfer: TransferFunction) -> Option<ColorModel> {
+        Some(ColorModel::RGB {
+            white_point: xy_to_xyz(white),
+            matrix: rgb_to_xyz_matrix(primaries, white)?,
+            transfer: [transfer; 3],
+            extended_range: false,
+        })
+    }
+
+    // the same parameters as CGColorSpace::new_calibrated_gray, the black point is not modelled
+    pub fn new_calibrated_gray(white_point: &[CGFloat; 3], gamma: CGFloat) -> ColorModel {
+        ColorModel::Monochrome {
+            white_point: *white_point,
+            transfer: TransferFunction::Gamma(gamma),
+            extended_range: false,
+        }
+    }
+
+    // the same parameters as CGColorSpace::new_calibrated_rgb, matrix holds the XYZ of red, green and blue in turn
+    pub fn new_calibrated_rgb(white_point: &[CGFloat; 3], gamma: Option<&[CGFloat; 3]>, matrix: Option<&[CGFloat; 9]>) -> ColorModel {
+        let gamma = gamma.copied().unwrap_or([1.0; 3]);
+        let matrix = matrix.copied().unwrap_or([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+        ColorModel::RGB {
+            white_point: *white_point,
+            matrix: [
+                [matrix[0], matrix[3], matrix[6]],
+                [matrix[1], matrix[4], matrix[7]],
+                [matrix[2], matrix[5], matrix[8]],
+            ],
+            transfer: gamma.map(TransferFunction::Gamma),
+            extended_range: false,
+        }
+    }
+
+    pub fn new_lab(white_point: &[CGFloat; 3], range: Option<&[CGFloat; 4]>) -> ColorModel {
+        ColorModel::Lab {
+            white_point: *white_point,
+            range: range.copied().unwrap_or(LAB_RANGE),
+        }
+    }
+
+    // entries past the end of the table read as zero, as do components of a base that is itself indexed
+    pub fn new_indexed(base: ColorModel, table: Vec<u8>) -> ColorModel {
+        ColorModel::Indexed {
+            base: Box::new(base),
+            table,
+        }
+    }
+
+    pub fn model(&self) -> CGColorSpaceModel {
+        match self {
+            ColorModel::Monochrome {
+                ..
+            } => CGColorSpaceModel::Monochrome,
+            ColorModel::RGB {
+                ..
+            } => CGColorSpaceModel::RGB,
+            ColorModel::CMYK => CGColorSpaceModel::CMYK,
+            ColorModel::Lab {
+                ..
+            } => CGColorSpaceModel::Lab,
+            ColorModel::XYZ => CGColorSpaceModel::XYZ,
+            ColorModel::Indexed {
+                ..
+            } => CGColorSpaceModel::Indexed,
+        }
+    }
+
+    pub fn number_of_components(&self) -> usize {
+        match self {
+            ColorModel::Monochrome {
+                ..
+            }
+            | ColorModel::Indexed {
+                ..
+            } => 1,
+            ColorModel::CMYK => 4,
+            _ => 3,
+        }
+    }
+
+    pub fn white_point(&self) -> [CGFloat; 3] {
+        match self {
+            ColorModel::Monochrome {
+                white_point,
+                ..
+            }
+            | ColorModel::RGB {
+                white_point,
+                ..
+            }
+            | ColorModel::Lab {
+                white_point,
+                ..
+            } => *white_point,
+            ColorModel::CMYK => D65,
+            ColorModel::XYZ => D50,
+            ColorModel::Indexed {
+                base,
+                ..
+            } => base.white_point(),
+        }
+    }
+
+    // missing components read as zero
+    pub fn to_xyz(&self, components: &[CGFloat]) -> [CGFloat; 3] {
+        let component = |index: usize| components.get(index).copied().unwrap_or(0.0);
+        match self {
+            ColorModel::Monochrome {
+                white_point,
+                transfer,
+                ..
+            } => {
+                let luminance = transfer.to_linear(component(0));
+                white_point.map(|value| value * luminance)
+            }
+            ColorModel::RGB {
+                matrix,
+                transfer,
+                ..
+            } => apply(
+                matrix,
+                &[
+                    transfer[0].to_linear(component(0)),
+                    transfer[1].to_linear(component(1)),
+                    transfer[2].to_linear(component(2)),
+                ],
+            ),
+            ColorModel::CMYK => {
+                let black = 1.0 - component(3);
+                let rgb = [(1.0 - component(0)) * black, (1.0 - component(1)) * black, (1.0 - component(2)) * black];
+                ColorModel::srgb().to_xyz(&rgb)
+            }
+            ColorModel::Lab {
+                white_point,
+                ..
+            } => lab_to_xyz(&[component(0), component(1), component(2)], white_point),
+            ColorModel::XYZ => [component(0), component(1), component(2)],
+            ColorModel::Indexed {
+                base,
+                table,
+            } => base.to_xyz(&indexed_entry(base, table, component(0).round().max(0.0) as usize)),
+        }
+    }
+
+    // colors outside of the model's gamut are clipped to it, except in XYZ and extended range spaces
+    pub fn from_xyz(&self, xyz: &[CGFloat; 3]) -> Vec<CGFloat> {
+        let clip = |value: CGFloat, extended_range: bool| {
+            if extended_range {
+                value
+            } else {
+                value.clamp(0.0, 1.0)
+            }
+        };
+        match self {
+            ColorModel::Monochrome {
+                white_point,
+                transfer,
+                extended_range,
+            } => vec![clip(transfer.from_linear(xyz[1] / white_point[1]), *extended_range)],
+            ColorModel::RGB {
+                matrix,
+                transfer,
+                extended_range,
+                ..
+            } => {
+                let linear = invert(matrix).map(|inverse| apply(&inverse, xyz)).unwrap_or([0.0; 3]);
+                (0..3).map(|index| clip(transfer[index].from_linear(linear[index]), *extended_range)).collect()
+            }
+            ColorModel::CMYK => {
+                let rgb = ColorModel::srgb().from_xyz(xyz);
+                let black = 1.0 - rgb.iter().cloned().fold(0.0, CGFloat::max);
+                let cmy = rgb.iter().map(|value| {
+                    if black < 1.0 {
+                        (1.0 - value - black) / (1.0 - black)
+                    } else {
+                        0.0
+                    }
+                });
+                cmy.chain([black]).collect()
+            }
+            ColorModel::Lab {
+                white_point,
+                range,
+            } => {
+                let lab = xyz_to_lab(xyz, white_point);
+                vec![
+                    lab[0].clamp(0.0, 100.0),
+                    lab[1].clamp(range[0], range[1]),
+                    lab[2].clamp(range[2], range[3]),
+                ]
+            }
+            ColorModel::XYZ => xyz.to_vec(),
+            // the entry nearest in CIELAB
+            ColorModel::Indexed {
+                base,
+                table,
+            } => {
+                let target = xyz_to_lab(xyz, &D50);
+                let count = table.len() / base.number_of_components().max(1);
+                let distance = |index: usize| {
+                    let lab = xyz_to_lab(&base.to_xyz(&indexed_entry(base, table, index)), &D50);
+                    (0..3).map(|channel| (lab[channel] - target[channel]).powi(2)).sum::<CGFloat>()
+                };
+                let nearest = (0..count).min_by(|first, second| distance(*first).total_cmp(&distance(*second))).unwrap_or(0);
+                vec![nearest as CGFloat]
+            }
+        }
+    }
+
+    // converts the color components of a color to the destination, honoring the white point adaptation of the intent
+    pub fn convert(&self, components: &[CGFloat], destination: &ColorModel, intent: CGColorRenderingIntent) -> Vec<CGFloat> {
+        let xyz = self.to_xyz(components);
+        destination.from_xyz(&adapt(&xyz, &self.white_point(), &destination.white_point(), intent))
+    }
+}
+
+// relative intents map the source white onto the destination white, absolute colorimetric keeps the XYZ values;
+// perceptual and saturation behave as relative colorimetric, as they do for matrix based ICC profiles
+pub fn adapt(xyz: &[CGFloat; 3], source_white: &[CGFloat; 3], destination_white: &[CGFloat; 3], intent: CGColorRenderingIntent) -> [CGFloat; 3] {
+    if intent == CGColorRenderingIntent::AbsoluteColorimetric || source_white == destination_white {
+        return *xyz;
+    }
+    apply(&bradford_adaptation(source_white, destination_white), xyz)
+}
+
+fn indexed_entry(base: &ColorModel, table: &[u8], index: usize) -> Vec<CGFloat> {
+    let count = base.number_of_components();
+    let range = |component: usize| match base {
+        ColorModel::Lab {
+            range,
+            ..
+        } if component > 0 => (range[component * 2 - 2], range[component * 2 - 1]),
+        ColorModel::Lab {
+            ..
+        } => (0.0, 100.0),
+        _ => (0.0, 1.0),
+    };
+    (0..count)
+        .map(|component| match table.get(index * count + component) {
+            Some(value) if !matches!(base, ColorModel::Indexed { .. }) => {
+                let (minimum, maximum) = range(component);
+                minimum + *value as CGFloat / 255.0 * (maximum - minimum)
+            }
+            _ => 0.0,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELATIVE: CGColorRenderingIntent = CGColorRenderingIntent::RelativeColorimetric;
+
+    fn assert_close(actual: &[CGFloat], expected: &[CGFloat], tolerance: CGFloat) {
+        assert_eq!(actual.len(), expected.len(), "{:?} != {:?}", actual, expected);
+        for (actual_value, expected_value) in actual.iter().zip(expected) {
+            assert!((actual_value - expected_value).abs() <= tolerance, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn bradford_round_trip() {
+        assert_close(&apply(&bradford_adaptation(&D65, &D50), &D65), &D50, 1e-4);
+        let xyz = [0.4, 0.3, 0.6];
+        let adapted = adapt(&xyz, &D65, &D50, RELATIVE);
+        assert_close(&adapt(&adapted, &D50, &D65, RELATIVE), &xyz, 1e-12);
+        assert_eq!(adapt(&xyz, &D65, &D50, CGColorRenderingIntent::AbsoluteColorimetric), xyz);
+    }
+
+    #[test]
+    fn srgb_white_to_lab() {
+        let lab = ColorModel::new_lab(&D50, None);
+        assert_close(&ColorModel::srgb().convert(&[1.0, 1.0, 1.0], &lab, RELATIVE), &[100.0, 0.0, 0.0], 1e-6);
+        assert_close(&ColorModel::srgb().convert(&[0.0, 0.0, 0.0], &lab, RELATIVE), &[0.0, 0.0, 0.0], 1e-9);
+        assert_close(&lab.convert(&[100.0, 0.0, 0.0], &ColorModel::srgb(), RELATIVE), &[1.0, 1.0, 1.0], 1e-6);
+    }
+
+    #[test]
+    fn cmyk_conversions() {
+        // CMYK is converted with the D65 constant as its white, a little off the white of srgb()
+        let srgb = ColorModel::srgb();
+        assert_close(&srgb.convert(&[1.0, 0.0, 0.0], &ColorModel::CMYK, RELATIVE), &[0.0, 1.0, 1.0, 0.0], 1e-3);
+        assert_close(&srgb.convert(&[0.5, 0.5, 0.5], &ColorModel::CMYK, RELATIVE), &[0.0, 0.0, 0.0, 0.5], 1e-3);
+        assert_close(&ColorModel::CMYK.convert(&[0.0, 0.0, 0.0, 1.0], &srgb, RELATIVE), &[0.0, 0.0, 0.0], 1e-3);
+        assert_close(&ColorModel::CMYK.convert(&[0.0, 1.0, 0.0, 0.0], &srgb, RELATIVE), &[1.0, 0.0, 1.0], 1e-3);
+    }
+
+    #[test]
+    fn gray_conversions() {
+        let gray = ColorModel::generic_gray_gamma_2_2();
+        let rgb = gray.convert(&[0.5], &ColorModel::srgb(), RELATIVE);
+        assert_close(&rgb, &[rgb[0]; 3], 1e-6);
+        assert!(rgb[0] > 0.5 && rgb[0] < 0.51);
+        assert_close(&ColorModel::srgb().convert(&rgb, &gray, RELATIVE), &[0.5], 1e-6);
+        assert_close(&ColorModel::srgb().convert(&[1.0, 1.0, 1.0], &gray, RELATIVE), &[1.0], 1e-6);
+    }
+
+    #[test]
+    fn indexed_conversions() {
+        let palette = ColorModel::new_indexed(ColorModel::srgb(), vec![255, 0, 0, 0, 0, 255, 255, 255, 255]);
+        assert_eq!(palette.model(), CGColorSpaceModel::Indexed);
+        assert_eq!(palette.number_of_components(), 1);
+        assert_close(&palette.convert(&[1.0], &ColorModel::srgb(), RELATIVE), &[0.0, 0.0, 1.0], 1e-9);
+        assert_close(&ColorModel::srgb().convert(&[0.1, 0.2, 0.8], &palette, RELATIVE), &[1.0], 0.0);
+        assert_close(&ColorModel::srgb().convert(&[0.9, 0.9, 0.8], &palette, RELATIVE), &[2.0], 0.0);
+        // past the end of the table
+        assert_close(&palette.convert(&[7.0], &ColorModel::srgb(), RELATIVE), &[0.0, 0.0, 0.0], 1e-9);
+    }
+}
@@ -472,6 +472,7 @@ impl CGColorSpace {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum CGColorSpaceNames {
     GenericGray,
     GenericRGB,
@@ -0,0 +1,749 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{
+    affine_transform::CGAffineTransform,
+    base::CGFloat,
+    color_space::CGColorRenderingIntent,
+    context::{CGBlendMode, CGInterpolationQuality, CGPathDrawingMode, CGTextDrawingMode},
+    font::CGGlyph,
+    geometry::{CGPoint, CGRect, CGSize},
+    graphics_context::GraphicsContext,
+    path::{CGLineCap, CGLineJoin},
+    path_builder::Path,
+};
+#[cfg(target_os = "macos")]
+use crate::{
+    color::CGColor,
+    color_space::CGColorSpace,
+    font::CGFont,
+    gradient::{CGGradient, CGGradientDrawingOptions},
+    image::CGImage,
+    pattern::CGPattern,
+    shading::CGShading,
+};
+
+// restores the graphics state when dropped, including when unwinding
+pub struct StateGuard<'a, C: GraphicsContext + ?Sized> {
+    context: &'a mut C,
+}
+
+impl<C: GraphicsContext + ?Sized> Deref for StateGuard<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.context
+    }
+}
+
+impl<C: GraphicsContext + ?Sized> DerefMut for StateGuard<'_, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.context
+    }
+}
+
+impl<C: GraphicsContext + ?Sized> Drop for StateGuard<'_, C> {
+    fn drop(&mut self) {
+        self.context.restore_state();
+    }
+}
+
+// ends the transparency layer when dropped
+pub struct TransparencyLayerGuard<'a, C: GraphicsContext + ?Sized> {
+    context: &'a mut C,
+}
+
+impl<C: GraphicsContext + ?Sized> Deref for TransparencyLayerGuard<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.context
+    }
+}
+
+impl<C: GraphicsContext + ?Sized> DerefMut for TransparencyLayerGuard<'_, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.context
+    }
+}
+
+impl<C: GraphicsContext + ?Sized> Drop for TransparencyLayerGuard<'_, C> {
+    fn drop(&mut self) {
+        self.context.end_transparency_layer();
+    }
+}
+
+// ends the page when dropped
+pub struct PageGuard<'a, C: GraphicsContext + ?Sized> {
+    context: &'a mut C,
+}
+
+impl<C: GraphicsContext + ?Sized> Deref for PageGuard<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.context
+    }
+}
+
+impl<C: GraphicsContext + ?Sized> DerefMut for PageGuard<'_, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.context
+    }
+}
+
+impl<C: GraphicsContext + ?Sized> Drop for PageGuard<'_, C> {
+    fn drop(&mut self) {
+        self.context.end_page();
+    }
+}
+
+// scoped versions of the paired calls, available on every context including trait objects
+pub trait ScopedGraphicsContext: GraphicsContext {
+    fn save_state_scoped(&mut self) -> StateGuard<'_, Self>;
+    fn begin_transparency_layer_scoped(&mut self) -> TransparencyLayerGuard<'_, Self>;
+    fn begin_transparency_layer_with_rect_scoped(&mut self, rect: CGRect) -> TransparencyLayerGuard<'_, Self>;
+    fn begin_page_scoped(&mut self, media_box: &CGRect) -> PageGuard<'_, Self>;
+
+    fn with_saved_state<R, F: FnOnce(&mut Self) -> R>(&mut self, f: F) -> R {
+        let mut guard = self.save_state_scoped();
+        f(&mut guard)
+    }
+
+    fn with_transparency_layer<R, F: FnOnce(&mut Self) -> R>(&mut self, f: F) -> R {
+        let mut guard = self.begin_transparency_layer_scoped();
+        f(&mut guard)
+    }
+
+    fn with_transparency_layer_in_rect<R, F: FnOnce(&mut Self) -> R>(&mut self, rect: CGRect, f: F) -> R {
+        let mut guard = self.begin_transparency_layer_with_rect_scoped(rect);
+        f(&mut guard)
+    }
+
+    fn with_page<R, F: FnOnce(&mut Self) -> R>(&mut self, media_box: &CGRect, f: F) -> R {
+        let mut guard = self.begin_page_scoped(media_box);
+        f(&mut guard)
+    }
+}
+
+impl<C: GraphicsContext + ?Sized> ScopedGraphicsContext for C {
+    fn save_state_scoped(&mut self) -> StateGuard<'_, Self> {
+        self.save_state();
+        StateGuard {
+            context: self,
+        }
+    }
+
+    fn begin_transparency_layer_scoped(&mut self) -> TransparencyLayerGuard<'_, Self> {
+        self.begin_transparency_layer();
+        TransparencyLayerGuard {
+            context: self,
+        }
+    }
+
+    fn begin_transparency_layer_with_rect_scoped(&mut self, rect: CGRect) -> TransparencyLayerGuard<'_, Self> {
+        self.begin_transparency_layer_with_rect(rect);
+        TransparencyLayerGuard {
+            context: self,
+        }
+    }
+
+    fn begin_page_scoped(&mut self, media_box: &CGRect) -> PageGuard<'_, Self> {
+        self.begin_page(media_box);
+        PageGuard {
+            context: self,
+        }
+    }
+}
+
+// counts the paired calls made through it, in debug builds unbalanced counts panic when it is dropped
+pub struct CheckedContext<'a, C: GraphicsContext + ?Sized> {
+    context: &'a mut C,
+    state_depth: isize,
+    layer_depth: isize,
+    page_depth: isize,
+}
+
+impl<'a, C: GraphicsContext + ?Sized> CheckedContext<'a, C> {
+    pub fn new(context: &'a mut C) -> CheckedContext<'a, C> {
+        CheckedContext {
+            context,
+            state_depth: 0,
+            layer_depth: 0,
+            page_depth: 0,
+        }
+    }
+
+    pub fn state_depth(&self) -> isize {
+        self.state_depth
+    }
+
+    pub fn layer_depth(&self) -> isize {
+        self.layer_depth
+    }
+
+    pub fn page_depth(&self) -> isize {
+        self.page_depth
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.state_depth == 0 && self.layer_depth == 0 && self.page_depth == 0
+    }
+}
+
+impl<C: GraphicsContext + ?Sized> Drop for CheckedContext<'_, C> {
+    fn drop(&mut self) {
+        // a second panic while unwinding would abort
+        if cfg!(debug_assertions) && !self.is_balanced() && !std::thread::panicking() {
+            panic!(
+                "unbalanced graphics context: state depth {}, transparency layer depth {}, page depth {}",
+                self.state_depth, self.layer_depth, self.page_depth
+            );
+        }
+    }
+}
+
+impl<C: GraphicsContext + ?Sized> GraphicsContext for CheckedContext<'_, C> {
+    fn save_state(&mut self) {
+        self.state_depth += 1;
+        self.context.save_state()
+    }
+
+    fn restore_state(&mut self) {
+        self.state_depth -= 1;
+        self.context.restore_state()
+    }
+
+    fn scale(&mut self, sx: CGFloat, sy: CGFloat) {
+        self.context.scale(sx, sy)
+    }
+
+    fn translate(&mut self, tx: CGFloat, ty: CGFloat) {
+        self.context.translate(tx, ty)
+    }
+
+    fn rotate(&mut self, angle: CGFloat) {
+        self.context.rotate(angle)
+    }
+
+    fn concat_ctm(&mut self, transform: CGAffineTransform) {
+        self.context.concat_ctm(transform)
+    }
+
+    fn set_line_width(&mut self, width: CGFloat) {
+        self.context.set_line_width(width)
+    }
+
+    fn set_line_cap(&mut self, cap: CGLineCap) {
+        self.context.set_line_cap(cap)
+    }
+
+    fn set_line_join(&mut self, join: CGLineJoin) {
+        self.context.set_line_join(join)
+    }
+
+    fn set_line_dash(&mut self, phase: CGFloat, lengths: &[CGFloat]) {
+        self.context.set_line_dash(phase, lengths)
+    }
+
+    fn set_miter_limit(&mut self, limit: CGFloat) {
+        self.context.set_miter_limit(limit)
+    }
+
+    fn set_flatness(&mut self, flatness: CGFloat) {
+        self.context.set_flatness(flatness)
+    }
+
+    fn set_alpha(&mut self, alpha: CGFloat) {
+        self.context.set_alpha(alpha)
+    }
+
+    fn set_blend_mode(&mut self, mode: CGBlendMode) {
+        self.context.set_blend_mode(mode)
+    }
+
+    fn begin_path(&mut self) {
+        self.context.begin_path()
+    }
+
+    fn close_path(&mut self) {
+        self.context.close_path()
+    }
+
+    fn move_to_point(&mut self, x: CGFloat, y: CGFloat) {
+        self.context.move_to_point(x, y)
+    }
+
+    fn add_line_to_point(&mut self, x: CGFloat, y: CGFloat) {
+        self.context.add_line_to_point(x, y)
+    }
+
+    fn add_curve_to_point(&mut self, cp1x: CGFloat, cp1y: CGFloat, cp2x: CGFloat, cp2y: CGFloat, x: CGFloat, y: CGFloat) {
+        self.context.add_curve_to_point(cp1x, cp1y, cp2x, cp2y, x, y)
+    }
+
+    fn add_quad_curve_to_point(&mut self, cpx: CGFloat, cpy: CGFloat, x: CGFloat, y: CGFloat) {
+        self.context.add_quad_curve_to_point(cpx, cpy, x, y)
+    }
+
+    fn add_rect(&mut self, rect: CGRect) {
+        self.context.add_rect(rect)
+    }
+
+    fn add_rects(&mut self, rects: &[CGRect]) {
+        self.context.add_rects(rects)
+    }
+
+    fn add_lines(&mut self, points: &[CGPoint]) {
+        self.context.add_lines(points)
+    }
+
+    fn add_ellipse_in_rect(&mut self, rect: CGRect) {
+        self.context.add_ellipse_in_rect(rect)
+    }
+
+    fn add_arc(&mut self, x: CGFloat, y: CGFloat, radius: CGFloat, start_angle: CGFloat, end_angle: CGFloat, clockwise: bool) {
+        self.context.add_arc(x, y, radius, start_angle, end_angle, clockwise)
+    }
+
+    fn add_arc_to_point(&mut self, x1: CGFloat, y1: CGFloat, x2: CGFloat, y2: CGFloat, radius: CGFloat) {
+        self.context.add_arc_to_point(x1, y1, x2, y2, radius)
+    }
+
+    fn add_path(&mut self, path: &Path) {
+        self.context.add_path(path)
+    }
+
+    fn replace_path_with_stroked_path(&mut self) {
+        self.context.replace_path_with_stroked_path()
+    }
+
+    fn draw_path(&mut self, mode: CGPathDrawingMode) {
+        self.context.draw_path(mode)
+    }
+
+    fn fill_path(&mut self) {
+        self.context.fill_path()
+    }
+
+    fn eo_fill_path(&mut self) {
+        self.context.eo_fill_path()
+    }
+
+    fn stroke_path(&mut self) {
+        self.context.stroke_path()
+    }
+
+    fn fill_rect(&mut self, rect: CGRect) {
+        self.context.fill_rect(rect)
+    }
+
+    fn fill_rects(&mut self, rects: &[CGRect]) {
+        self.context.fill_rects(rects)
+    }
+
+    fn stroke_rect(&mut self, rect: CGRect) {
+        self.context.stroke_rect(rect)
+    }
+
+    fn stroke_rect_with_width(&mut self, rect: CGRect, width: CGFloat) {
+        self.context.stroke_rect_with_width(rect, width)
+    }
+
+    fn clear_rect(&mut self, rect: CGRect) {
+        self.context.clear_rect(rect)
+    }
+
+    fn fill_ellipse_in_rect(&mut self, rect: CGRect) {
+        self.context.fill_ellipse_in_rect(rect)
+    }
+
+    fn stroke_ellipse_in_rect(&mut self, rect: CGRect) {
+        self.context.stroke_ellipse_in_rect(rect)
+    }
+
+    fn stroke_line_segments(&mut self, points: &[CGPoint]) {
+        self.context.stroke_line_segments(points)
+    }
+
+    fn clip(&mut self) {
+        self.context.clip()
+    }
+
+    fn eo_clip(&mut self) {
+        self.context.eo_clip()
+    }
+
+    fn reset_clip(&mut self) {
+        self.context.reset_clip()
+    }
+
+    #[cfg(target_os = "macos")]
+    fn clip_to_mask(&mut self, rect: CGRect, image: &CGImage) {
+        self.context.clip_to_mask(rect, image)
+    }
+
+    fn clip_to_rect(&mut self, rect: CGRect) {
+        self.context.clip_to_rect(rect)
+    }
+
+    fn clip_to_rects(&mut self, rects: &[CGRect]) {
+        self.context.clip_to_rects(rects)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_fill_color(&mut self, color: &CGColor) {
+        self.context.set_fill_color(color)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_stroke_color(&mut self, color: &CGColor) {
+        self.context.set_stroke_color(color)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_fill_color_space(&mut self, space: &CGColorSpace) {
+        self.context.set_fill_color_space(space)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_stroke_color_space(&mut self, space: &CGColorSpace) {
+        self.context.set_stroke_color_space(space)
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn set_fill_pattern(&mut self, pattern: &CGPattern, components: &[CGFloat]) {
+        unsafe { self.context.set_fill_pattern(pattern, components) }
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn set_stroke_pattern(&mut self, pattern: &CGPattern, components: &[CGFloat]) {
+        unsafe { self.context.set_stroke_pattern(pattern, components) }
+    }
+
+    fn set_pattern_phase(&mut self, phase: CGSize) {
+        self.context.set_pattern_phase(phase)
+    }
+
+    fn set_gray_fill_color(&mut self, gray: CGFloat, alpha: CGFloat) {
+        self.context.set_gray_fill_color(gray, alpha)
+    }
+
+    fn set_gray_stroke_color(&mut self, gray: CGFloat, alpha: CGFloat) {
+        self.context.set_gray_stroke_color(gray, alpha)
+    }
+
+    fn set_rgb_fill_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
+        self.context.set_rgb_fill_color(red, green, blue, alpha)
+    }
+
+    fn set_rgb_stroke_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
+        self.context.set_rgb_stroke_color(red, green, blue, alpha)
+    }
+
+    fn set_cmyk_fill_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) {
+        self.context.set_cmyk_fill_color(cyan, magenta, yellow, black, alpha)
+    }
+
+    fn set_cmyk_stroke_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) {
+        self.context.set_cmyk_stroke_color(cyan, magenta, yellow, black, alpha)
+    }
+
+    fn set_rendering_intent(&mut self, intent: CGColorRenderingIntent) {
+        self.context.set_rendering_intent(intent)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_image(&mut self, rect: CGRect, image: &CGImage) {
+        self.context.draw_image(rect, image)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_tiled_image(&mut self, rect: CGRect, image: &CGImage) {
+        self.context.draw_tiled_image(rect, image)
+    }
+
+    fn set_interpolation_quality(&mut self, quality: CGInterpolationQuality) {
+        self.context.set_interpolation_quality(quality)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_linear_gradient(&mut self, gradient: &CGGradient, start_point: CGPoint, end_point: CGPoint, options: CGGradientDrawingOptions) {
+        self.context.draw_linear_gradient(gradient, start_point, end_point, options)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_radial_gradient(
+        &mut self,
+        gradient: &CGGradient,
+        start_center: CGPoint,
+        start_radius: CGFloat,
+        end_center: CGPoint,
+        end_radius: CGFloat,
+        options: CGGradientDrawingOptions,
+    ) {
+        self.context.draw_radial_gradient(gradient, start_center, start_radius, end_center, end_radius, options)
+    }
+
+    fn set_shadow(&mut self, offset: CGSize, blur: CGFloat) {
+        self.context.set_shadow(offset, blur)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_shadow_with_color(&mut self, offset: CGSize, blur: CGFloat, color: &CGColor) {
+        self.context.set_shadow_with_color(offset, blur, color)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_shading(&mut self, shading: &CGShading) {
+        self.context.draw_shading(shading)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_font(&mut self, font: &CGFont) {
+        self.context.set_font(font)
+    }
+
+    fn set_font_size(&mut self, size: CGFloat) {
+        self.context.set_font_size(size)
+    }
+
+    fn set_text_matrix(&mut self, t: &CGAffineTransform) {
+        self.context.set_text_matrix(t)
+    }
+
+    fn set_text_drawing_mode(&mut self, mode: CGTextDrawingMode) {
+        self.context.set_text_drawing_mode(mode)
+    }
+
+    fn set_text_position(&mut self, x: CGFloat, y: CGFloat) {
+        self.context.set_text_position(x, y)
+    }
+
+    fn set_allows_font_smoothing(&mut self, allows_font_smoothing: bool) {
+        self.context.set_allows_font_smoothing(allows_font_smoothing)
+    }
+
+    fn set_should_smooth_fonts(&mut self, should_smooth_fonts: bool) {
+        self.context.set_should_smooth_fonts(should_smooth_fonts)
+    }
+
+    fn set_allows_antialiasing(&mut self, allows_antialiasing: bool) {
+        self.context.set_allows_antialiasing(allows_antialiasing)
+    }
+
+    fn set_should_antialias(&mut self, should_antialias: bool) {
+        self.context.set_should_antialias(should_antialias)
+    }
+
+    fn set_allows_font_subpixel_quantization(&mut self, allows_font_subpixel_quantization: bool) {
+        self.context.set_allows_font_subpixel_quantization(allows_font_subpixel_quantization)
+    }
+
+    fn set_should_subpixel_quantize_fonts(&mut self, should_subpixel_quantize_fonts: bool) {
+        self.context.set_should_subpixel_quantize_fonts(should_subpixel_quantize_fonts)
+    }
+
+    fn set_allows_font_subpixel_positioning(&mut self, allows_font_subpixel_positioning: bool) {
+        self.context.set_allows_font_subpixel_positioning(allows_font_subpixel_positioning)
+    }
+
+    fn set_should_subpixel_position_fonts(&mut self, should_subpixel_position_fonts: bool) {
+        self.context.set_should_subpixel_position_fonts(should_subpixel_position_fonts)
+    }
+
+    fn set_font_smoothing_style(&mut self, style: i32) {
+        self.context.set_font_smoothing_style(style)
+    }
+
+    fn show_glyphs_at_positions(&mut self, glyphs: &[CGGlyph], positions: &[CGPoint]) {
+        self.context.show_glyphs_at_positions(glyphs, positions)
+    }
+
+    fn begin_transparency_layer(&mut self) {
+        self.layer_depth += 1;
+        self.context.begin_transparency_layer()
+    }
+
+    fn begin_transparency_layer_with_rect(&mut self, rect: CGRect) {
+        self.layer_depth += 1;
+        self.context.begin_transparency_layer_with_rect(rect)
+    }
+
+    fn end_transparency_layer(&mut self) {
+        self.layer_depth -= 1;
+        self.context.end_transparency_layer()
+    }
+
+    fn flush(&mut self) {
+        self.context.flush()
+    }
+
+    fn synchronize(&mut self) {
+        self.context.synchronize()
+    }
+
+    fn begin_page(&mut self, media_box: &CGRect) {
+        self.page_depth += 1;
+        self.context.begin_page(media_box)
+    }
+
+    fn end_page(&mut self) {
+        self.page_depth -= 1;
+        self.context.end_page()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use super::*;
+    use crate::recording_context::{DrawingCommand, RecordingContext};
+
+    // every restore, end of layer and end of page closes the innermost open call of its kind
+    fn assert_paired(commands: &[DrawingCommand]) {
+        let mut open = Vec::new();
+        for command in commands {
+            match command {
+                DrawingCommand::SaveState => open.push("state"),
+                DrawingCommand::BeginTransparencyLayer | DrawingCommand::BeginTransparencyLayerWithRect(_) => open.push("layer"),
+                DrawingCommand::BeginPage(_) => open.push("page"),
+                DrawingCommand::RestoreState => assert_eq!(open.pop(), Some("state"), "{:?}", commands),
+                DrawingCommand::EndTransparencyLayer => assert_eq!(open.pop(), Some("layer"), "{:?}", commands),
+                DrawingCommand::EndPage => assert_eq!(open.pop(), Some("page"), "{:?}", commands),
+                _ => {}
+            }
+        }
+        assert!(open.is_empty(), "{:?}", commands);
+    }
+
+    fn draw_unless(context: &mut RecordingContext, skip: bool) -> Option<()> {
+        let mut context = context.save_state_scoped();
+        context.set_alpha(0.5);
+        if skip {
+            return None;
+        }
+        context.fill_rect(CGRect::new(0.0, 0.0, 1.0, 1.0));
+        Some(())
+    }
+
+    #[test]
+    fn with_saved_state_restores() {
+        let mut context = RecordingContext::new();
+        let value = context.with_saved_state(|context| {
+            context.set_alpha(0.5);
+            1
+        });
+        assert_eq!(value, 1);
+        assert_eq!(context.commands(), &[DrawingCommand::SaveState, DrawingCommand::SetAlpha(0.5), DrawingCommand::RestoreState]);
+    }
+
+    #[test]
+    fn early_return_restores() {
+        let mut context = RecordingContext::new();
+        assert_eq!(draw_unless(&mut context, true), None);
+        assert_eq!(context.commands(), &[DrawingCommand::SaveState, DrawingCommand::SetAlpha(0.5), DrawingCommand::RestoreState]);
+        assert_eq!(draw_unless(&mut context, false), Some(()));
+        assert_eq!(context.len(), 7);
+        assert_paired(context.commands());
+
+        let mut context = RecordingContext::new();
+        let value = context.with_saved_state(|context| {
+            for alpha in [0.25, 0.5, 0.75] {
+                context.set_alpha(alpha);
+                if alpha == 0.5 {
+                    return alpha;
+                }
+            }
+            1.0
+        });
+        assert_eq!(value, 0.5);
+        assert_eq!(context.commands().last(), Some(&DrawingCommand::RestoreState));
+        assert_paired(context.commands());
+    }
+
+    #[test]
+    fn panic_restores() {
+        let mut context = RecordingContext::new();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            context.with_saved_state(|context| {
+                context.with_transparency_layer(|context| {
+                    context.set_alpha(0.5);
+                    panic!("drawing failed");
+                })
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(
+            context.commands(),
+            &[
+                DrawingCommand::SaveState,
+                DrawingCommand::BeginTransparencyLayer,
+                DrawingCommand::SetAlpha(0.5),
+                DrawingCommand::EndTransparencyLayer,
+                DrawingCommand::RestoreState,
+            ]
+        );
+
+        let mut context = RecordingContext::new();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let mut page = context.begin_page_scoped(&CGRect::new(0.0, 0.0, 100.0, 100.0));
+            let mut state = page.save_state_scoped();
+            state.fill_rect(CGRect::new(0.0, 0.0, 1.0, 1.0));
+            panic!("drawing failed");
+        }));
+        assert!(result.is_err());
+        assert_eq!(context.len(), 5);
+        assert_paired(context.commands());
+    }
+
+    #[test]
+    fn guards_on_trait_objects() {
+        let mut recording = RecordingContext::new();
+        let context: &mut dyn GraphicsContext = &mut recording;
+        context.with_transparency_layer_in_rect(CGRect::new(0.0, 0.0, 10.0, 10.0), |context| {
+            let mut context = context.save_state_scoped();
+            context.set_alpha(0.5);
+        });
+        assert_eq!(recording.len(), 5);
+        assert_paired(recording.commands());
+    }
+
+    #[test]
+    fn checked_context_counts_depth() {
+        let mut recording = RecordingContext::new();
+        let mut context = CheckedContext::new(&mut recording);
+        context.save_state();
+        context.begin_transparency_layer();
+        assert_eq!((context.state_depth(), context.layer_depth(), context.page_depth()), (1, 1, 0));
+        assert!(!context.is_balanced());
+        context.with_page(&CGRect::new(0.0, 0.0, 100.0, 100.0), |context| {
+            assert_eq!(context.page_depth(), 1);
+        });
+        context.end_transparency_layer();
+        context.restore_state();
+        assert!(context.is_balanced());
+        drop(context);
+        assert_paired(recording.commands());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "unbalanced graphics context: state depth 1")]
+    fn checked_context_panics_when_unbalanced() {
+        let mut recording = RecordingContext::new();
+        let mut context = CheckedContext::new(&mut recording);
+        context.save_state();
+        context.save_state();
+        context.restore_state();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "unbalanced graphics context: state depth -1")]
+    fn checked_context_panics_on_extra_restore() {
+        let mut recording = RecordingContext::new();
+        let mut context = CheckedContext::new(&mut recording);
+        context.with_saved_state(|context| context.restore_state());
+    }
+}
@@ -0,0 +1,742 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+// zlib streams (RFC 1950) of deflate blocks (RFC 1951)
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const NICE_MATCH: usize = 128;
+const LAZY_MATCH: usize = 32;
+const MAX_CHAIN: usize = 128;
+const HASH_BITS: u32 = 15;
+const BLOCK_TOKENS: usize = 1 << 14;
+const NO_POSITION: u32 = u32::MAX;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+#[derive(Clone, Copy)]
+enum Token {
+    Literal(u8),
+    Match(u16, u16),
+}
+
+pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        output: Vec::with_capacity(data.len() / 2 + 64),
+        buffer: 0,
+        count: 0,
+    };
+    // 32k window, default compression level
+    writer.output.extend_from_slice(&[0x78, 0x9c]);
+    let tokens = find_tokens(data);
+    let mut start = 0;
+    let mut chunks = tokens.chunks(BLOCK_TOKENS).peekable();
+    if chunks.peek().is_none() {
+        write_block(&mut writer, &[], &[], true);
+    }
+    while let Some(chunk) = chunks.next() {
+        let length = chunk
+            .iter()
+            .map(|token| match token {
+                Token::Literal(_) => 1,
+                Token::Match(length, _) => *length as usize,
+            })
+            .sum::<usize>();
+        write_block(&mut writer, chunk, &data[start..start + length], chunks.peek().is_none());
+        start += length;
+    }
+    writer.align();
+    writer.output.extend_from_slice(&adler32(data).to_be_bytes());
+    writer.output
+}
+
+// returns None for malformed streams and for streams that inflate to more than limit bytes
+pub(crate) fn decompress(data: &[u8], limit: usize) -> Option<Vec<u8>> {
+    if data.len() < 6 {
+        return None;
+    }
+    let (method, flags) = (data[0], data[1]);
+    if method & 0x0f != 8 || method >> 4 > 7 || (((method as u16) << 8) | flags as u16) % 31 != 0 || flags & 0x20 != 0 {
+        return None;
+    }
+    let mut reader = BitReader {
+        data: &data[2..],
+        position: 0,
+        buffer: 0,
+        count: 0,
+        padding: 0,
+    };
+    let mut output = Vec::new();
+    inflate(&mut reader, &mut output, limit)?;
+    reader.align()?;
+    let end = 2 + reader.byte_position();
+    let checksum = data.get(end..end + 4)?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&output) {
+        return None;
+    }
+    Some(output)
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // largest run of bytes before the sums can overflow
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn hash(data: &[u8], position: usize) -> usize {
+    let value = (data[position] as u32) | (data[position + 1] as u32) << 8 | (data[position + 2] as u32) << 16;
+    (value.wrapping_mul(0x9e3779b1) >> (32 - HASH_BITS)) as usize
+}
+
+// lz77 with hash chains and one step of lazy matching
+fn find_tokens(data: &[u8]) -> Vec<Token> {
+    let mut head = vec![NO_POSITION; 1 << HASH_BITS];
+    let mut previous = vec![NO_POSITION; WINDOW_SIZE];
+    let mut tokens = Vec::with_capacity(data.len() / 4);
+
+    let insert = |position: usize, head: &mut [u32], previous: &mut [u32]| {
+        if position + MIN_MATCH <= data.len() {
+            let hash = hash(data, position);
+            previous[position % WINDOW_SIZE] = head[hash];
+            head[hash] = position as u32;
+        }
+    };
+    let find = |position: usize, head: &[u32], previous: &[u32]| -> (usize, usize) {
+        if position + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let max_length = (data.len() - position).min(MAX_MATCH);
+        let (mut best_length, mut best_distance) = (0, 0);
+        let mut candidate = head[hash(data, position)];
+        let mut chain = MAX_CHAIN;
+        while candidate != NO_POSITION && chain > 0 {
+            let start = candidate as usize;
+            if position - start > WINDOW_SIZE {
+                break;
+            }
+            if data[start + best_length.min(max_length - 1)] == data[position + best_length.min(max_length - 1)] {
+                let length = data[start..start + max_length].iter().zip(&data[position..position + max_length]).take_while(|(a, b)| a == b).count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = position - start;
+                    if length >= NICE_MATCH.min(max_length) {
+                        break;
+                    }
+                }
+            }
+            let next = previous[start % WINDOW_SIZE];
+            if next == NO_POSITION || next >= candidate {
+                break;
+            }
+            candidate = next;
+            chain -= 1;
+        }
+        (best_length, best_distance)
+    };
+
+    let mut position = 0;
+    let mut matched = find(position, &head, &previous);
+    insert(position, &mut head, &mut previous);
+    while position < data.len() {
+        let (length, distance) = matched;
+        if length >= MIN_MATCH {
+            let mut inserted = position + 1;
+            if length < LAZY_MATCH && position + 1 < data.len() {
+                let next = find(position + 1, &head, &previous);
+                insert(position + 1, &mut head, &mut previous);
+                if next.0 > length {
+                    tokens.push(Token::Literal(data[position]));
+                    position += 1;
+                    matched = next;
+                    continue;
+                }
+                inserted += 1;
+            }
+            tokens.push(Token::Match(length as u16, distance as u16));
+            for next in inserted..position + length {
+                insert(next, &mut head, &mut previous);
+            }
+            position += length;
+        } else {
+            tokens.push(Token::Literal(data[position]));
+            position += 1;
+        }
+        if position < data.len() {
+            matched = find(position, &head, &previous);
+            insert(position, &mut head, &mut previous);
+        }
+    }
+    tokens
+}
+
+fn length_symbol(length: usize) -> usize {
+    LENGTH_BASE.partition_point(|base| *base as usize <= length) - 1
+}
+
+fn distance_symbol(distance: usize) -> usize {
+    DISTANCE_BASE.partition_point(|base| *base as usize <= distance) - 1
+}
+
+fn write_block(writer: &mut BitWriter, tokens: &[Token], raw: &[u8], last: bool) {
+    let mut literal_frequencies = [0u32; 286];
+    let mut distance_frequencies = [0u32; 30];
+    literal_frequencies[256] = 1;
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => literal_frequencies[byte as usize] += 1,
+            Token::Match(length, distance) => {
+                literal_frequencies[257 + length_symbol(length as usize)] += 1;
+                distance_frequencies[distance_symbol(distance as usize)] += 1;
+            }
+        }
+    }
+
+    let literal_lengths = code_lengths(&literal_frequencies, 15);
+    let distance_lengths = code_lengths(&distance_frequencies, 15);
+    let literal_count = 257.max(literal_lengths.iter().rposition(|length| *length != 0).map_or(0, |index| index + 1));
+    let distance_count = 1.max(distance_lengths.iter().rposition(|length| *length != 0).map_or(0, |index| index + 1));
+    let mut lengths = literal_lengths[..literal_count].to_vec();
+    lengths.extend_from_slice(&distance_lengths[..distance_count]);
+    let runs = run_length_codes(&lengths);
+    let mut code_length_frequencies = [0u32; 19];
+    for (symbol, _) in &runs {
+        code_length_frequencies[*symbol as usize] += 1;
+    }
+    let code_length_lengths = code_lengths(&code_length_frequencies, 7);
+    let code_length_count = 4.max(CODE_LENGTH_ORDER.iter().rposition(|symbol| code_length_lengths[*symbol] != 0).map_or(0, |index| index + 1));
+
+    let data_cost = |literal_lengths: &[u8], distance_lengths: &[u8]| -> usize {
+        let literal_cost = literal_frequencies
+            .iter()
+            .enumerate()
+            .map(|(symbol, frequency)| {
+                let extra = if symbol > 256 {
+                    LENGTH_EXTRA[symbol - 257] as usize
+                } else {
+                    0
+                };
+                *frequency as usize * (literal_lengths[symbol] as usize + extra)
+            })
+            .sum::<usize>();
+        let distance_cost = distance_frequencies
+            .iter()
+            .enumerate()
+            .map(|(symbol, frequency)| *frequency as usize * (distance_lengths[symbol] as usize + DISTANCE_EXTRA[symbol] as usize))
+            .sum::<usize>();
+        literal_cost + distance_cost
+    };
+    let (fixed_literal_lengths, fixed_distance_lengths) = fixed_lengths();
+    let fixed_cost = 3 + data_cost(&fixed_literal_lengths, &fixed_distance_lengths);
+    let header_cost = 14
+        + 3 * code_length_count
+        + runs
+            .iter()
+            .map(|(symbol, _)| {
+                code_length_lengths[*symbol as usize] as usize
+                    + match symbol {
+                        16 => 2,
+                        17 => 3,
+                        18 => 7,
+                        _ => 0,
+                    }
+            })
+            .sum::<usize>();
+    let dynamic_cost = 3 + header_cost + data_cost(&literal_lengths, &distance_lengths);
+    let stored_cost = raw.len().div_ceil(65535).max(1) * (3 + 7 + 32) + raw.len() * 8;
+
+    if stored_cost < fixed_cost.min(dynamic_cost) {
+        let mut chunks = raw.chunks(65535).peekable();
+        while let Some(chunk) = chunks.next() {
+            writer.write((chunks.peek().is_none() && last) as u32, 3);
+            writer.align();
+            writer.output.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+            writer.output.extend_from_slice(&(!(chunk.len() as u16)).to_le_bytes());
+            writer.output.extend_from_slice(chunk);
+        }
+    } else if fixed_cost <= dynamic_cost {
+        writer.write(last as u32 | 1 << 1, 3);
+        write_tokens(writer, tokens, &fixed_literal_lengths, &fixed_distance_lengths);
+    } else {
+        writer.write(last as u32 | 2 << 1, 3);
+        writer.write((literal_count - 257) as u32, 5);
+        writer.write((distance_count - 1) as u32, 5);
+        writer.write((code_length_count - 4) as u32, 4);
+        for symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+            writer.write(code_length_lengths[*symbol] as u32, 3);
+        }
+        let codes = canonical_codes(&code_length_lengths);
+        for (symbol, extra) in runs {
+            writer.write(codes[symbol as usize] as u32, code_length_lengths[symbol as usize] as u32);
+            match symbol {
+                16 => writer.write(extra as u32, 2),
+                17 => writer.write(extra as u32, 3),
+                18 => writer.write(extra as u32, 7),
+                _ => {}
+            }
+        }
+        write_tokens(writer, tokens, &literal_lengths, &distance_lengths);
+    }
+}
+
+fn write_tokens(writer: &mut BitWriter, tokens: &[Token], literal_lengths: &[u8], distance_lengths: &[u8]) {
+    let literal_codes = canonical_codes(literal_lengths);
+    let distance_codes = canonical_codes(distance_lengths);
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => writer.write(literal_codes[byte as usize] as u32, literal_lengths[byte as usize] as u32),
+            Token::Match(length, distance) => {
+                let (length, distance) = (length as usize, distance as usize);
+                let symbol = length_symbol(length);
+                writer.write(literal_codes[257 + symbol] as u32, literal_lengths[257 + symbol] as u32);
+                writer.write((length - LENGTH_BASE[symbol] as usize) as u32, LENGTH_EXTRA[symbol] as u32);
+                let symbol = distance_symbol(distance);
+                writer.write(distance_codes[symbol] as u32, distance_lengths[symbol] as u32);
+                writer.write((distance - DISTANCE_BASE[symbol] as usize) as u32, DISTANCE_EXTRA[symbol] as u32);
+            }
+        }
+    }
+    writer.write(literal_codes[256] as u32, literal_lengths[256] as u32);
+}
+
+fn fixed_lengths() -> ([u8; 288], [u8; 30]) {
+    let mut literal_lengths = [8; 288];
+    literal_lengths[144..256].fill(9);
+    literal_lengths[256..280].fill(7);
+    (literal_lengths, [5; 30])
+}
+
+// huffman code lengths limited to limit bits, frequencies are flattened until the tree is shallow enough
+pub(crate) fn code_lengths(frequencies: &[u32], limit: u8) -> Vec<u8> {
+    let mut frequencies = frequencies.to_vec();
+    // a tree needs two codes to be complete
+    let mut used = frequencies.iter().filter(|frequency| **frequency != 0).count();
+    for frequency in frequencies.iter_mut() {
+        if used >= 2 {
+            break;
+        }
+        if *frequency == 0 {
+            *frequency = 1;
+            used += 1;
+        }
+    }
+    loop {
+        let lengths = huffman_lengths(&frequencies);
+        if lengths.iter().all(|length| *length <= limit) {
+            return lengths;
+        }
+        for frequency in frequencies.iter_mut().filter(|frequency| **frequency != 0) {
+            *frequency = (*frequency >> 1) | 1;
+        }
+    }
+}
+
+fn huffman_lengths(frequencies: &[u32]) -> Vec<u8> {
+    let symbols = frequencies.iter().enumerate().filter(|(_, frequency)| **frequency != 0).map(|(symbol, _)| symbol).collect::<Vec<_>>();
+    let mut parents = vec![usize::MAX; symbols.len()];
+    let mut heap = symbols.iter().enumerate().map(|(node, symbol)| Reverse((frequencies[*symbol] as u64, node))).collect::<BinaryHeap<_>>();
+    while heap.len() > 1 {
+        let Reverse((first_weight, first)) = heap.pop().unwrap();
+        let Reverse((second_weight, second)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[first] = node;
+        parents[second] = node;
+        heap.push(Reverse((first_weight + second_weight, node)));
+    }
+    // parents are created after their children, so depths can be resolved from the root down
+    let mut depths = vec![0u32; parents.len()];
+    for node in (0..parents.len()).rev() {
+        if parents[node] != usize::MAX {
+            depths[node] = depths[parents[node]] + 1;
+        }
+    }
+    let mut lengths = vec![0u8; frequencies.len()];
+    for (node, symbol) in symbols.iter().enumerate() {
+        lengths[*symbol] = depths[node].min(u8::MAX as u32) as u8;
+    }
+    lengths
+}
+
+// codes are bit reversed as deflate writes them starting with the least significant bit
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut counts = [0u16; 16];
+    for length in lengths {
+        counts[*length as usize] += 1;
+    }
+    counts[0] = 0;
+    let mut next_codes = [0u16; 16];
+    let mut code = 0;
+    for bits in 1..16 {
+        code = (code + counts[bits - 1]) << 1;
+        next_codes[bits] = code;
+    }
+    lengths
+        .iter()
+        .map(|length| {
+            if *length == 0 {
+                return 0;
+            }
+            let code = next_codes[*length as usize];
+            next_codes[*length as usize] += 1;
+            code.reverse_bits() >> (16 - *length as u32)
+        })
+        .collect()
+}
+
+// code length symbols with the value of their extra bits
+fn run_length_codes(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut runs = Vec::new();
+    let mut index = 0;
+    while index < lengths.len() {
+        let length = lengths[index];
+        let mut run = lengths[index..].iter().take_while(|next| **next == length).count();
+        index += run;
+        if length == 0 {
+            while run >= 11 {
+                let count = run.min(138);
+                runs.push((18, (count - 11) as u8));
+                run -= count;
+            }
+            if run >= 3 {
+                runs.push((17, (run - 3) as u8));
+                run = 0;
+            }
+        } else {
+            runs.push((length, 0));
+            run -= 1;
+            while run >= 3 {
+                let count = run.min(6);
+                runs.push((16, (count - 3) as u8));
+                run -= count;
+            }
+        }
+        runs.extend(std::iter::repeat((length, 0)).take(run));
+    }
+    runs
+}
+
+struct BitWriter {
+    output: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, bits: u32, count: u32) {
+        self.buffer |= (bits as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.output.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.output.push(self.buffer as u8);
+            self.buffer = 0;
+            self.count = 0;
+        }
+    }
+}
+
+// bytes past the end of the data read as zero padding, consuming any of the padding fails
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u64,
+    count: u32,
+    padding: u32,
+}
+
+impl BitReader<'_> {
+    fn refill(&mut self) {
+        while self.count <= 56 {
+            match self.data.get(self.position) {
+                Some(byte) => {
+                    self.buffer |= (*byte as u64) << self.count;
+                    self.position += 1;
+                }
+                None => self.padding += 8,
+            }
+            self.count += 8;
+        }
+    }
+
+    fn peek(&mut self, count: u32) -> u32 {
+        if self.count < count {
+            self.refill();
+        }
+        (self.buffer & ((1u64 << count) - 1)) as u32
+    }
+
+    fn consume(&mut self, count: u32) -> Option<()> {
+        self.buffer >>= count;
+        self.count -= count;
+        if self.count < self.padding {
+            return None;
+        }
+        Some(())
+    }
+
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        let bits = self.peek(count);
+        self.consume(count)?;
+        Some(bits)
+    }
+
+    fn align(&mut self) -> Option<()> {
+        self.consume(self.count % 8)
+    }
+
+    fn byte_position(&self) -> usize {
+        self.position - (self.count - self.padding) as usize / 8
+    }
+
+    // the reader must be aligned
+    fn read_bytes(&mut self, mut length: usize, output: &mut Vec<u8>) -> Option<()> {
+        while length > 0 && self.count > self.padding {
+            output.push(self.bits(8)? as u8);
+            length -= 1;
+        }
+        let start = self.byte_position();
+        output.extend_from_slice(self.data.get(start..start + length)?);
+        self.position = start + length;
+        self.buffer = 0;
+        self.count = 0;
+        self.padding = 0;
+        Some(())
+    }
+}
+
+// lookup table indexed by the next bits of the stream, entries hold the symbol and its code length
+struct Huffman {
+    table: Vec<u16>,
+    bits: u32,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Option<Huffman> {
+        let bits = lengths.iter().copied().max().unwrap_or(0).max(1) as u32;
+        let mut counts = [0i32; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        // over subscribed codes are invalid, incomplete ones only fail when a missing code is read
+        let mut left = 1;
+        for count in &counts[1..] {
+            left = (left << 1) - count;
+            if left < 0 {
+                return None;
+            }
+        }
+        let codes = canonical_codes(lengths);
+        let mut table = vec![0u16; 1 << bits];
+        for (symbol, length) in lengths.iter().enumerate().filter(|(_, length)| **length != 0) {
+            let entry = (symbol as u16) << 4 | *length as u16;
+            for index in (codes[symbol] as usize..table.len()).step_by(1 << length) {
+                table[index] = entry;
+            }
+        }
+        Some(Huffman {
+            table,
+            bits,
+        })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Option<usize> {
+        let entry = self.table[reader.peek(self.bits) as usize];
+        if entry == 0 {
+            return None;
+        }
+        reader.consume((entry & 15) as u32)?;
+        Some((entry >> 4) as usize)
+    }
+}
+
+fn inflate(reader: &mut BitReader, output: &mut Vec<u8>, limit: usize) -> Option<()> {
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align()?;
+                let length = reader.bits(16)?;
+                if reader.bits(16)? != !length & 0xffff || output.len() + length as usize > limit {
+                    return None;
+                }
+                reader.read_bytes(length as usize, output)?;
+            }
+            1 => {
+                let (literal_lengths, distance_lengths) = fixed_lengths();
+                inflate_block(reader, output, limit, &Huffman::new(&literal_lengths)?, &Huffman::new(&distance_lengths)?)?;
+            }
+            2 => {
+                let literal_count = reader.bits(5)? as usize + 257;
+                let distance_count = reader.bits(5)? as usize + 1;
+                let code_length_count = reader.bits(4)? as usize + 4;
+                let mut code_length_lengths = [0u8; 19];
+                for symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+                    code_length_lengths[*symbol] = reader.bits(3)? as u8;
+                }
+                let code_lengths = Huffman::new(&code_length_lengths)?;
+                let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                while lengths.len() < literal_count + distance_count {
+                    let (length, count) = match code_lengths.decode(reader)? {
+                        16 => (*lengths.last()?, 3 + reader.bits(2)? as usize),
+                        17 => (0, 3 + reader.bits(3)? as usize),
+                        18 => (0, 11 + reader.bits(7)? as usize),
+                        length => (length as u8, 1),
+                    };
+                    if lengths.len() + count > literal_count + distance_count {
+                        return None;
+                    }
+                    lengths.extend(std::iter::repeat(length).take(count));
+                }
+                if lengths[256] == 0 {
+                    return None;
+                }
+                inflate_block(reader, output, limit, &Huffman::new(&lengths[..literal_count])?, &Huffman::new(&lengths[literal_count..])?)?;
+            }
+            _ => return None,
+        }
+        if last {
+            return Some(());
+        }
+    }
+}
+
+fn inflate_block(reader: &mut BitReader, output: &mut Vec<u8>, limit: usize, literals: &Huffman, distances: &Huffman) -> Option<()> {
+    loop {
+        let symbol = literals.decode(reader)?;
+        if symbol < 256 {
+            if output.len() >= limit {
+                return None;
+            }
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Some(());
+        }
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return None;
+        }
+        let length = LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+        let symbol = distances.decode(reader)?;
+        if symbol >= DISTANCE_BASE.len() {
+            return None;
+        }
+        let distance = DISTANCE_BASE[symbol] as usize + reader.bits(DISTANCE_EXTRA[symbol] as u32)? as usize;
+        if distance > output.len() || output.len() + length > limit {
+            return None;
+        }
+        let start = output.len() - distance;
+        if distance >= length {
+            output.extend_from_within(start..start + length);
+        } else {
+            for index in start..start + length {
+                output.push(output[index]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len()).step_by(2).map(|index| u8::from_str_radix(&text[index..index + 2], 16).unwrap()).collect()
+    }
+
+    // pseudo random bytes with runs, so both literals and matches are exercised
+    fn sample(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491u32;
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            match state % 4 {
+                0 if data.len() > 300 => {
+                    let start = data.len() - 1 - (state as usize >> 8) % 300;
+                    for index in start..start + 3 + (state as usize >> 20) % 40 {
+                        data.push(data[index]);
+                    }
+                }
+                _ => data.push(b'a' + (state >> 24) as u8 % 16),
+            }
+        }
+        data.truncate(len);
+        data
+    }
+
+    #[test]
+    fn inflates_stored_fixed_and_dynamic_blocks() {
+        // written by zlib at levels 0 and 9
+        let stored = hex("7801010c00f3ff73746f72656420626c6f636b1f8004bd");
+        assert_eq!(decompress(&stored, 100).unwrap(), b"stored block");
+        let fixed = hex("78da4bcbac484d51484390003a190671");
+        assert_eq!(decompress(&fixed, 100).unwrap(), b"fixed fixed fixed");
+        let dynamic = hex(concat!(
+            "78da0dcbc90180200c05d1567e1f5623ca1205c28e50bd39cf9be63472a7eb852a3c230c7f787a48153c744193eccfbd70b33d904e71",
+            "6141099ad41c0c0d2d69eb084fb97391d7d61f1c061ede"
+        ));
+        assert_eq!((dynamic[2] >> 1) & 3, 2);
+        assert_eq!(decompress(&dynamic, 100).unwrap(), &b"the quick brown fox jumps over the lazy dog; pack my box with five dozen liquor jugs"[..]);
+    }
+
+    #[test]
+    fn round_trips() {
+        for len in [0, 1, 2, 300, 70_000, 300_000] {
+            let data = sample(len);
+            let compressed = compress(&data);
+            assert!(len < 1000 || compressed.len() < len / 2, "{} -> {}", len, compressed.len());
+            assert_eq!(decompress(&compressed, len).unwrap(), data);
+        }
+        let zeros = vec![0; 100_000];
+        assert!(compress(&zeros).len() < 200);
+        assert_eq!(decompress(&compress(&zeros), zeros.len()).unwrap(), zeros);
+    }
+
+    #[test]
+    fn rejects_malformed_streams() {
+        let data = sample(1000);
+        let compressed = compress(&data);
+        assert_eq!(decompress(&compressed, 999), None);
+        assert_eq!(decompress(&compressed[..compressed.len() - 1], 1000), None);
+        assert_eq!(decompress(&compressed[..compressed.len() / 2], 1000), None);
+        let mut corrupt = compressed.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert_eq!(decompress(&corrupt, 1000), None);
+        // the header check bits and a preset dictionary
+        assert_eq!(decompress(&[0x78, 0x9d, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01], 10), None);
+        assert_eq!(decompress(&[0x78, 0xbb, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01], 10), None);
+        assert_eq!(decompress(&[0x78, 0x9c, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01], 10), Some(Vec::new()));
+        // a stored block whose length does not match its complement
+        assert_eq!(decompress(&hex("7801010c00f3fe73746f72656420626c6f636b1f8004bd"), 100), None);
+        // the reserved block type
+        assert_eq!(decompress(&[0x78, 0x9c, 0x07, 0x00, 0x00, 0x00, 0x00, 0x01], 10), None);
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
 use core_foundation::{
     base::TCFType,
     dictionary::{CFDictionary, CFDictionaryRef},
@@ -62,15 +64,15 @@ pub const CGRectZero: CGRect = CGRect {
 };
 pub const CGRectNull: CGRect = CGRect {
     origin: CGPoint {
-        x: CGFloat::MAX,
-        y: CGFloat::MAX,
+        x: CGFloat::INFINITY,
+        y: CGFloat::INFINITY,
     },
     size: CGSizeZero,
 };
 pub const CGRectInfinite: CGRect = CGRect {
     origin: CGPoint {
-        x: CGFloat::MIN,
-        y: CGFloat::MIN,
+        x: -CGFloat::MAX / 2.0,
+        y: -CGFloat::MAX / 2.0,
     },
     size: CGSize {
         width: CGFloat::MAX,
@@ -125,10 +127,6 @@ impl CGPoint {
             None
         }
     }
-
-    pub fn apply_transform(&self, transform: &CGAffineTransform) -> CGPoint {
-        unsafe { CGPointApplyAffineTransform(*self, *transform) }
-    }
 }
 
 impl CGSize {
@@ -148,9 +146,14 @@ impl CGSize {
             None
         }
     }
+}
 
-    pub fn apply_transform(&self, transform: &CGAffineTransform) -> CGSize {
-        unsafe { CGSizeApplyAffineTransform(*self, *transform) }
+impl CGVector {
+    pub fn new(dx: CGFloat, dy: CGFloat) -> CGVector {
+        CGVector {
+            dx,
+            dy,
+        }
     }
 }
 
@@ -171,80 +174,677 @@ impl CGRect {
             None
         }
     }
+}
+
+// there is no CoreGraphics to call into on other platforms, so they always get the native math
+cfg_if! {
+    if #[cfg(any(feature = "pure-geometry", not(target_os = "macos")))] {
+        impl CGPoint {
+            pub fn apply_transform(&self, transform: &CGAffineTransform) -> CGPoint {
+                CGPoint {
+                    x: transform.a * self.x + transform.c * self.y + transform.tx,
+                    y: transform.b * self.x + transform.d * self.y + transform.ty,
+                }
+            }
+        }
+
+        impl CGSize {
+            pub fn apply_transform(&self, transform: &CGAffineTransform) -> CGSize {
+                CGSize {
+                    width: transform.a * self.width + transform.c * self.height,
+                    height: transform.b * self.width + transform.d * self.height,
+                }
+            }
+        }
+
+        impl CGRect {
+            fn min_x(&self) -> CGFloat {
+                self.origin.x + self.size.width.min(0.0)
+            }
+
+            fn max_x(&self) -> CGFloat {
+                self.origin.x + self.size.width.max(0.0)
+            }
+
+            fn min_y(&self) -> CGFloat {
+                self.origin.y + self.size.height.min(0.0)
+            }
+
+            fn max_y(&self) -> CGFloat {
+                self.origin.y + self.size.height.max(0.0)
+            }
+
+            pub fn min(&self) -> (CGFloat, CGFloat) {
+                (self.min_x(), self.min_y())
+            }
+
+            pub fn mid(&self) -> (CGFloat, CGFloat) {
+                ((self.min_x() + self.max_x()) * 0.5, (self.min_y() + self.max_y()) * 0.5)
+            }
+
+            pub fn max(&self) -> (CGFloat, CGFloat) {
+                (self.max_x(), self.max_y())
+            }
+
+            pub fn width(&self) -> CGFloat {
+                self.size.width.abs()
+            }
+
+            pub fn height(&self) -> CGFloat {
+                self.size.height.abs()
+            }
+
+            pub fn standardize(&self) -> CGRect {
+                if self.is_null() {
+                    return CGRectNull;
+                }
+                CGRect::new(self.min_x(), self.min_y(), self.width(), self.height())
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.is_null() || self.size.width == 0.0 || self.size.height == 0.0
+            }
+
+            pub fn is_null(&self) -> bool {
+                self.origin.x == CGFloat::INFINITY || self.origin.y == CGFloat::INFINITY
+            }
+
+            pub fn is_infinite(&self) -> bool {
+                *self == CGRectInfinite
+            }
+
+            pub fn inset(&self, dx: CGFloat, dy: CGFloat) -> CGRect {
+                if self.is_null() || self.is_infinite() {
+                    return *self;
+                }
+                let rect = self.standardize();
+                let rect = CGRect::new(rect.origin.x + dx, rect.origin.y + dy, rect.size.width - 2.0 * dx, rect.size.height - 2.0 * dy);
+                if rect.size.width < 0.0 || rect.size.height < 0.0 {
+                    CGRectNull
+                } else {
+                    rect
+                }
+            }
+
+            pub fn integral(&self) -> CGRect {
+                if self.is_null() || self.is_infinite() {
+                    return *self;
+                }
+                let rect = self.standardize();
+                let x = rect.origin.x.floor();
+                let y = rect.origin.y.floor();
+                CGRect::new(x, y, (rect.origin.x + rect.size.width).ceil() - x, (rect.origin.y + rect.size.height).ceil() - y)
+            }
+
+            pub fn union(&self, rect: &CGRect) -> CGRect {
+                if self.is_null() {
+                    return rect.standardize();
+                }
+                if rect.is_null() {
+                    return self.standardize();
+                }
+                let min_x = self.min_x().min(rect.min_x());
+                let min_y = self.min_y().min(rect.min_y());
+                let max_x = self.max_x().max(rect.max_x());
+                let max_y = self.max_y().max(rect.max_y());
+                CGRect::new(min_x, min_y, max_x - min_x, max_y - min_y)
+            }
+
+            pub fn intersection(&self, rect: &CGRect) -> CGRect {
+                if self.is_null() || rect.is_null() {
+                    return CGRectNull;
+                }
+                let min_x = self.min_x().max(rect.min_x());
+                let min_y = self.min_y().max(rect.min_y());
+                let max_x = self.max_x().min(rect.max_x());
+                let max_y = self.max_y().min(rect.max_y());
+                if min_x > max_x || min_y > max_y {
+                    return CGRectNull;
+                }
+                CGRect::new(min_x, min_y, max_x - min_x, max_y - min_y)
+            }
+
+            pub fn offset(&self, dx: CGFloat, dy: CGFloat) -> CGRect {
+                if self.is_null() || self.is_infinite() {
+                    return *self;
+                }
+                let rect = self.standardize();
+                CGRect::new(rect.origin.x + dx, rect.origin.y + dy, rect.size.width, rect.size.height)
+            }
+
+            pub fn divide(&self, slice: &mut CGRect, remainder: &mut CGRect, amount: CGFloat, edge: CGRectEdge) {
+                if self.is_null() {
+                    *slice = CGRectNull;
+                    *remainder = CGRectNull;
+                    return;
+                }
+                let rect = self.standardize();
+                let (mut first, mut second) = (rect, rect);
+                match edge {
+                    CGRectEdge::MinX | CGRectEdge::MaxX => {
+                        let amount = if edge == CGRectEdge::MinX {
+                            amount
+                        } else {
+                            rect.size.width - amount
+                        };
+                        let split = amount.max(0.0).min(rect.size.width);
+                        first.size.width = split;
+                        second.origin.x = rect.origin.x + split;
+                        second.size.width = rect.size.width - split;
+                    }
+                    CGRectEdge::MinY | CGRectEdge::MaxY => {
+                        let amount = if edge == CGRectEdge::MinY {
+                            amount
+                        } else {
+                            rect.size.height - amount
+                        };
+                        let split = amount.max(0.0).min(rect.size.height);
+                        first.size.height = split;
+                        second.origin.y = rect.origin.y + split;
+                        second.size.height = rect.size.height - split;
+                    }
+                }
+                match edge {
+                    CGRectEdge::MinX | CGRectEdge::MinY => {
+                        *slice = first;
+                        *remainder = second;
+                    }
+                    CGRectEdge::MaxX | CGRectEdge::MaxY => {
+                        *slice = second;
+                        *remainder = first;
+                    }
+                }
+            }
+
+            pub fn contains_point(&self, point: &CGPoint) -> bool {
+                if self.is_empty() {
+                    return false;
+                }
+                point.x >= self.min_x() && point.x < self.max_x() && point.y >= self.min_y() && point.y < self.max_y()
+            }
+
+            pub fn contains_rect(&self, rect: &CGRect) -> bool {
+                self.union(rect) == self.standardize()
+            }
+
+            // rects that only share an edge do not intersect
+            pub fn intersects_rect(&self, rect: &CGRect) -> bool {
+                !self.intersection(rect).is_empty()
+            }
+
+            pub fn apply_transform(&self, transform: &CGAffineTransform) -> CGRect {
+                if self.is_null() || self.is_infinite() {
+                    return *self;
+                }
+                let (min_x, min_y) = self.min();
+                let (max_x, max_y) = self.max();
+                let corners = [
+                    CGPoint::new(min_x, min_y).apply_transform(transform),
+                    CGPoint::new(max_x, min_y).apply_transform(transform),
+                    CGPoint::new(min_x, max_y).apply_transform(transform),
+                    CGPoint::new(max_x, max_y).apply_transform(transform),
+                ];
+                let (mut min_x, mut min_y, mut max_x, mut max_y) = (corners[0].x, corners[0].y, corners[0].x, corners[0].y);
+                for corner in &corners[1..] {
+                    min_x = min_x.min(corner.x);
+                    min_y = min_y.min(corner.y);
+                    max_x = max_x.max(corner.x);
+                    max_y = max_y.max(corner.y);
+                }
+                CGRect::new(min_x, min_y, max_x - min_x, max_y - min_y)
+            }
+        }
+    } else {
+        impl CGPoint {
+            pub fn apply_transform(&self, transform: &CGAffineTransform) -> CGPoint {
+                unsafe { CGPointApplyAffineTransform(*self, *transform) }
+            }
+        }
+
+        impl CGSize {
+            pub fn apply_transform(&self, transform: &CGAffineTransform) -> CGSize {
+                unsafe { CGSizeApplyAffineTransform(*self, *transform) }
+            }
+        }
+
+        impl CGRect {
+            pub fn min(&self) -> (CGFloat, CGFloat) {
+                unsafe { (CGRectGetMinX(*self), CGRectGetMinY(*self)) }
+            }
+            pub fn mid(&self) -> (CGFloat, CGFloat) {
+                unsafe { (CGRectGetMidX(*self), CGRectGetMidY(*self)) }
+            }
+
+            pub fn max(&self) -> (CGFloat, CGFloat) {
+                unsafe { (CGRectGetMaxX(*self), CGRectGetMaxY(*self)) }
+            }
+
+            pub fn width(&self) -> CGFloat {
+                unsafe { CGRectGetWidth(*self) }
+            }
+
+            pub fn height(&self) -> CGFloat {
+                unsafe { CGRectGetHeight(*self) }
+            }
+
+            pub fn standardize(&self) -> CGRect {
+                unsafe { CGRectStandardize(*self) }
+            }
+
+            pub fn is_empty(&self) -> bool {
+                unsafe { CGRectIsEmpty(*self) }
+            }
+
+            pub fn is_null(&self) -> bool {
+                unsafe { CGRectIsNull(*self) }
+            }
+
+            pub fn is_infinite(&self) -> bool {
+                unsafe { CGRectIsInfinite(*self) }
+            }
+
+            pub fn inset(&self, dx: CGFloat, dy: CGFloat) -> CGRect {
+                unsafe { CGRectInset(*self, dx, dy) }
+            }
+
+            pub fn integral(&self) -> CGRect {
+                unsafe { CGRectIntegral(*self) }
+            }
+
+            pub fn union(&self, rect: &CGRect) -> CGRect {
+                unsafe { CGRectUnion(*self, *rect) }
+            }
+
+            pub fn intersection(&self, rect: &CGRect) -> CGRect {
+                unsafe { CGRectIntersection(*self, *rect) }
+            }
+
+            pub fn offset(&self, dx: CGFloat, dy: CGFloat) -> CGRect {
+                unsafe { CGRectOffset(*self, dx, dy) }
+            }
+
+            pub fn divide(&self, slice: &mut CGRect, remainder: &mut CGRect, amount: CGFloat, edge: CGRectEdge) {
+                unsafe { CGRectDivide(*self, slice, remainder, amount, edge) }
+            }
+
+            pub fn contains_point(&self, point: &CGPoint) -> bool {
+                unsafe { CGRectContainsPoint(*self, *point) }
+            }
+
+            pub fn contains_rect(&self, rect: &CGRect) -> bool {
+                unsafe { CGRectContainsRect(*self, *rect) }
+            }
+
+            pub fn intersects_rect(&self, rect: &CGRect) -> bool {
+                unsafe { CGRectIntersectsRect(*self, *rect) }
+            }
+
+            pub fn apply_transform(&self, transform: &CGAffineTransform) -> CGRect {
+                unsafe { CGRectApplyAffineTransform(*self, *transform) }
+            }
+        }
+    }
+}
+
+impl CGPoint {
+    pub fn to_vector(&self) -> CGVector {
+        CGVector::new(self.x, self.y)
+    }
+
+    pub fn distance(&self, point: &CGPoint) -> CGFloat {
+        (*point - *self).length()
+    }
+
+    pub fn distance_squared(&self, point: &CGPoint) -> CGFloat {
+        (*point - *self).length_squared()
+    }
+
+    pub fn lerp(&self, point: &CGPoint, t: CGFloat) -> CGPoint {
+        CGPoint::new(self.x + (point.x - self.x) * t, self.y + (point.y - self.y) * t)
+    }
+
+    pub fn midpoint(&self, point: &CGPoint) -> CGPoint {
+        self.lerp(point, 0.5)
+    }
+
+    pub fn angle_to(&self, point: &CGPoint) -> CGFloat {
+        (*point - *self).angle()
+    }
+
+    pub fn rotate_around(&self, center: &CGPoint, angle: CGFloat) -> CGPoint {
+        *center + (*self - *center).rotate(angle)
+    }
+}
+
+impl CGVector {
+    pub fn to_point(&self) -> CGPoint {
+        CGPoint::new(self.dx, self.dy)
+    }
+
+    pub fn dot(&self, vector: &CGVector) -> CGFloat {
+        self.dx * vector.dx + self.dy * vector.dy
+    }
+
+    pub fn cross(&self, vector: &CGVector) -> CGFloat {
+        self.dx * vector.dy - self.dy * vector.dx
+    }
+
+    pub fn length(&self) -> CGFloat {
+        self.dx.hypot(self.dy)
+    }
+
+    pub fn length_squared(&self) -> CGFloat {
+        self.dot(self)
+    }
+
+    pub fn normalize(&self) -> CGVector {
+        let length = self.length();
+        if length == 0.0 {
+            *self
+        } else {
+            *self / length
+        }
+    }
+
+    pub fn lerp(&self, vector: &CGVector, t: CGFloat) -> CGVector {
+        CGVector::new(self.dx + (vector.dx - self.dx) * t, self.dy + (vector.dy - self.dy) * t)
+    }
+
+    pub fn angle(&self) -> CGFloat {
+        self.dy.atan2(self.dx)
+    }
+
+    pub fn angle_to(&self, vector: &CGVector) -> CGFloat {
+        self.cross(vector).atan2(self.dot(vector))
+    }
+
+    pub fn rotate(&self, angle: CGFloat) -> CGVector {
+        let (sin, cos) = angle.sin_cos();
+        CGVector::new(self.dx * cos - self.dy * sin, self.dx * sin + self.dy * cos)
+    }
+
+    pub fn perpendicular(&self) -> CGVector {
+        CGVector::new(-self.dy, self.dx)
+    }
+
+    pub fn apply_transform(&self, transform: &CGAffineTransform) -> CGVector {
+        CGVector::new(transform.a * self.dx + transform.c * self.dy, transform.b * self.dx + transform.d * self.dy)
+    }
+}
+
+impl From<CGVector> for CGPoint {
+    fn from(vector: CGVector) -> CGPoint {
+        vector.to_point()
+    }
+}
+
+impl From<CGPoint> for CGVector {
+    fn from(point: CGPoint) -> CGVector {
+        point.to_vector()
+    }
+}
+
+impl From<CGSize> for CGVector {
+    fn from(size: CGSize) -> CGVector {
+        CGVector::new(size.width, size.height)
+    }
+}
+
+impl From<CGVector> for CGSize {
+    fn from(vector: CGVector) -> CGSize {
+        CGSize::new(vector.dx, vector.dy)
+    }
+}
+
+impl Add<CGVector> for CGPoint {
+    type Output = CGPoint;
+
+    fn add(self, rhs: CGVector) -> CGPoint {
+        CGPoint::new(self.x + rhs.dx, self.y + rhs.dy)
+    }
+}
+
+impl AddAssign<CGVector> for CGPoint {
+    fn add_assign(&mut self, rhs: CGVector) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<CGVector> for CGPoint {
+    type Output = CGPoint;
+
+    fn sub(self, rhs: CGVector) -> CGPoint {
+        CGPoint::new(self.x - rhs.dx, self.y - rhs.dy)
+    }
+}
+
+impl SubAssign<CGVector> for CGPoint {
+    fn sub_assign(&mut self, rhs: CGVector) {
+        *self = *self - rhs;
+    }
+}
+
+impl Add<CGSize> for CGPoint {
+    type Output = CGPoint;
+
+    fn add(self, rhs: CGSize) -> CGPoint {
+        CGPoint::new(self.x + rhs.width, self.y + rhs.height)
+    }
+}
+
+impl AddAssign<CGSize> for CGPoint {
+    fn add_assign(&mut self, rhs: CGSize) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<CGSize> for CGPoint {
+    type Output = CGPoint;
+
+    fn sub(self, rhs: CGSize) -> CGPoint {
+        CGPoint::new(self.x - rhs.width, self.y - rhs.height)
+    }
+}
+
+impl SubAssign<CGSize> for CGPoint {
+    fn sub_assign(&mut self, rhs: CGSize) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sub for CGPoint {
+    type Output = CGVector;
+
+    fn sub(self, rhs: CGPoint) -> CGVector {
+        CGVector::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
 
-    pub fn min(&self) -> (CGFloat, CGFloat) {
-        unsafe { (CGRectGetMinX(*self), CGRectGetMinY(*self)) }
+impl Mul<CGFloat> for CGPoint {
+    type Output = CGPoint;
+
+    fn mul(self, rhs: CGFloat) -> CGPoint {
+        CGPoint::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl Mul<CGPoint> for CGFloat {
+    type Output = CGPoint;
+
+    fn mul(self, rhs: CGPoint) -> CGPoint {
+        rhs * self
+    }
+}
+
+impl MulAssign<CGFloat> for CGPoint {
+    fn mul_assign(&mut self, rhs: CGFloat) {
+        *self = *self * rhs;
+    }
+}
+
+impl Div<CGFloat> for CGPoint {
+    type Output = CGPoint;
+
+    fn div(self, rhs: CGFloat) -> CGPoint {
+        CGPoint::new(self.x / rhs, self.y / rhs)
+    }
+}
+
+impl DivAssign<CGFloat> for CGPoint {
+    fn div_assign(&mut self, rhs: CGFloat) {
+        *self = *self / rhs;
     }
-    pub fn mid(&self) -> (CGFloat, CGFloat) {
-        unsafe { (CGRectGetMidX(*self), CGRectGetMidY(*self)) }
+}
+
+impl Neg for CGPoint {
+    type Output = CGPoint;
+
+    fn neg(self) -> CGPoint {
+        CGPoint::new(-self.x, -self.y)
+    }
+}
+
+impl Add for CGSize {
+    type Output = CGSize;
+
+    fn add(self, rhs: CGSize) -> CGSize {
+        CGSize::new(self.width + rhs.width, self.height + rhs.height)
+    }
+}
+
+impl AddAssign for CGSize {
+    fn add_assign(&mut self, rhs: CGSize) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for CGSize {
+    type Output = CGSize;
+
+    fn sub(self, rhs: CGSize) -> CGSize {
+        CGSize::new(self.width - rhs.width, self.height - rhs.height)
     }
+}
 
-    pub fn max(&self) -> (CGFloat, CGFloat) {
-        unsafe { (CGRectGetMaxX(*self), CGRectGetMaxY(*self)) }
+impl SubAssign for CGSize {
+    fn sub_assign(&mut self, rhs: CGSize) {
+        *self = *self - rhs;
     }
+}
 
-    pub fn width(&self) -> CGFloat {
-        unsafe { CGRectGetWidth(*self) }
+impl Mul<CGFloat> for CGSize {
+    type Output = CGSize;
+
+    fn mul(self, rhs: CGFloat) -> CGSize {
+        CGSize::new(self.width * rhs, self.height * rhs)
     }
+}
 
-    pub fn height(&self) -> CGFloat {
-        unsafe { CGRectGetHeight(*self) }
+impl Mul<CGSize> for CGFloat {
+    type Output = CGSize;
+
+    fn mul(self, rhs: CGSize) -> CGSize {
+        rhs * self
     }
+}
 
-    pub fn standardize(&self) -> CGRect {
-        unsafe { CGRectStandardize(*self) }
+impl MulAssign<CGFloat> for CGSize {
+    fn mul_assign(&mut self, rhs: CGFloat) {
+        *self = *self * rhs;
     }
+}
+
+impl Div<CGFloat> for CGSize {
+    type Output = CGSize;
 
-    pub fn is_empty(&self) -> bool {
-        unsafe { CGRectIsEmpty(*self) }
+    fn div(self, rhs: CGFloat) -> CGSize {
+        CGSize::new(self.width / rhs, self.height / rhs)
     }
+}
 
-    pub fn is_null(&self) -> bool {
-        unsafe { CGRectIsNull(*self) }
+impl DivAssign<CGFloat> for CGSize {
+    fn div_assign(&mut self, rhs: CGFloat) {
+        *self = *self / rhs;
     }
+}
 
-    pub fn is_infinite(&self) -> bool {
-        unsafe { CGRectIsInfinite(*self) }
+impl Neg for CGSize {
+    type Output = CGSize;
+
+    fn neg(self) -> CGSize {
+        CGSize::new(-self.width, -self.height)
     }
+}
 
-    pub fn inset(&self, dx: CGFloat, dy: CGFloat) -> CGRect {
-        unsafe { CGRectInset(*self, dx, dy) }
+impl Add for CGVector {
+    type Output = CGVector;
+
+    fn add(self, rhs: CGVector) -> CGVector {
+        CGVector::new(self.dx + rhs.dx, self.dy + rhs.dy)
     }
+}
 
-    pub fn integral(&self) -> CGRect {
-        unsafe { CGRectIntegral(*self) }
+impl AddAssign for CGVector {
+    fn add_assign(&mut self, rhs: CGVector) {
+        *self = *self + rhs;
     }
+}
 
-    pub fn union(&self, rect: &CGRect) -> CGRect {
-        unsafe { CGRectUnion(*self, *rect) }
+impl Sub for CGVector {
+    type Output = CGVector;
+
+    fn sub(self, rhs: CGVector) -> CGVector {
+        CGVector::new(self.dx - rhs.dx, self.dy - rhs.dy)
     }
+}
 
-    pub fn intersection(&self, rect: &CGRect) -> CGRect {
-        unsafe { CGRectIntersection(*self, *rect) }
+impl SubAssign for CGVector {
+    fn sub_assign(&mut self, rhs: CGVector) {
+        *self = *self - rhs;
     }
+}
+
+impl Mul<CGFloat> for CGVector {
+    type Output = CGVector;
 
-    pub fn offset(&self, dx: CGFloat, dy: CGFloat) -> CGRect {
-        unsafe { CGRectOffset(*self, dx, dy) }
+    fn mul(self, rhs: CGFloat) -> CGVector {
+        CGVector::new(self.dx * rhs, self.dy * rhs)
     }
+}
+
+impl Mul<CGVector> for CGFloat {
+    type Output = CGVector;
 
-    pub fn divide(&self, slice: &mut CGRect, remainder: &mut CGRect, amount: CGFloat, edge: CGRectEdge) {
-        unsafe { CGRectDivide(*self, slice, remainder, amount, edge) }
+    fn mul(self, rhs: CGVector) -> CGVector {
+        rhs * self
     }
+}
 
-    pub fn contains_point(&self, point: &CGPoint) -> bool {
-        unsafe { CGRectContainsPoint(*self, *point) }
+impl MulAssign<CGFloat> for CGVector {
+    fn mul_assign(&mut self, rhs: CGFloat) {
+        *self = *self * rhs;
     }
+}
+
+impl Div<CGFloat> for CGVector {
+    type Output = CGVector;
 
-    pub fn contains_rect(&self, rect: &CGRect) -> bool {
-        unsafe { CGRectContainsRect(*self, *rect) }
+    fn div(self, rhs: CGFloat) -> CGVector {
+        CGVector::new(self.dx / rhs, self.dy / rhs)
     }
+}
 
-    pub fn intersects_rect(&self, rect: &CGRect) -> bool {
-        unsafe { CGRectIntersectsRect(*self, *rect) }
+impl DivAssign<CGFloat> for CGVector {
+    fn div_assign(&mut self, rhs: CGFloat) {
+        *self = *self / rhs;
     }
+}
 
-    pub fn apply_transform(&self, transform: &CGAffineTransform) -> CGRect {
-        unsafe { CGRectApplyAffineTransform(*self, *transform) }
+impl Neg for CGVector {
+    type Output = CGVector;
+
+    fn neg(self) -> CGVector {
+        CGVector::new(-self.dx, -self.dy)
     }
 }
 
@@ -287,3 +887,207 @@ cfg_if!(
         }
     }
 );
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    use super::*;
+
+    #[test]
+    fn null_and_infinite_constants() {
+        assert!(CGRectNull.is_null());
+        assert!(!CGRectNull.is_infinite());
+        assert!(CGRectNull.is_empty());
+        assert!(CGRectInfinite.is_infinite());
+        assert!(!CGRectInfinite.is_null());
+        assert!(!CGRectInfinite.is_empty());
+        assert!(!CGRectZero.is_null());
+        assert!(CGRectZero.is_empty());
+    }
+
+    #[test]
+    fn standardize() {
+        let rect = CGRect::new(10.0, 20.0, -4.0, -6.0);
+        assert_eq!(rect.standardize(), CGRect::new(6.0, 14.0, 4.0, 6.0));
+        assert_eq!(rect.min(), (6.0, 14.0));
+        assert_eq!(rect.max(), (10.0, 20.0));
+        assert_eq!(rect.width(), 4.0);
+        assert_eq!(rect.height(), 6.0);
+        assert!(CGRectNull.standardize().is_null());
+        assert!(CGRectInfinite.standardize().is_infinite());
+    }
+
+    #[test]
+    fn union() {
+        let a = CGRect::new(0.0, 0.0, 10.0, 10.0);
+        let b = CGRect::new(20.0, 5.0, -5.0, 10.0);
+        assert_eq!(a.union(&b), CGRect::new(0.0, 0.0, 20.0, 15.0));
+        assert_eq!(a.union(&CGRectNull), a);
+        assert_eq!(CGRectNull.union(&b), b.standardize());
+        assert!(CGRectNull.union(&CGRectNull).is_null());
+        assert!(a.union(&CGRectInfinite).is_infinite());
+    }
+
+    #[test]
+    fn intersection() {
+        let a = CGRect::new(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(a.intersection(&CGRect::new(5.0, 5.0, 10.0, 10.0)), CGRect::new(5.0, 5.0, 5.0, 5.0));
+        assert_eq!(a.intersection(&CGRect::new(15.0, 15.0, -10.0, -10.0)), CGRect::new(5.0, 5.0, 5.0, 5.0));
+        assert!(a.intersection(&CGRect::new(20.0, 0.0, 10.0, 10.0)).is_null());
+        assert!(a.intersection(&CGRectNull).is_null());
+        assert!(CGRectNull.intersection(&CGRectInfinite).is_null());
+        assert_eq!(a.intersection(&CGRectInfinite), a);
+        assert!(CGRectInfinite.intersection(&CGRectInfinite).is_infinite());
+    }
+
+    #[test]
+    fn intersects_rect() {
+        let a = CGRect::new(0.0, 0.0, 10.0, 10.0);
+        assert!(a.intersects_rect(&CGRect::new(5.0, 5.0, 10.0, 10.0)));
+        assert!(!a.intersects_rect(&CGRect::new(20.0, 0.0, 10.0, 10.0)));
+        // edge and corner adjacent rects do not intersect
+        assert!(!a.intersects_rect(&CGRect::new(10.0, 0.0, 10.0, 10.0)));
+        assert!(!a.intersects_rect(&CGRect::new(0.0, 10.0, 10.0, 10.0)));
+        assert!(!a.intersects_rect(&CGRect::new(10.0, 10.0, 10.0, 10.0)));
+        assert!(!a.intersects_rect(&CGRectNull));
+        assert!(!CGRectNull.intersects_rect(&CGRectNull));
+        assert!(a.intersects_rect(&CGRectInfinite));
+    }
+
+    #[test]
+    fn divide() {
+        let rect = CGRect::new(0.0, 0.0, 10.0, 20.0);
+        let mut slice = CGRectZero;
+        let mut remainder = CGRectZero;
+        rect.divide(&mut slice, &mut remainder, 3.0, CGRectEdge::MinX);
+        assert_eq!((slice, remainder), (CGRect::new(0.0, 0.0, 3.0, 20.0), CGRect::new(3.0, 0.0, 7.0, 20.0)));
+        rect.divide(&mut slice, &mut remainder, 3.0, CGRectEdge::MaxX);
+        assert_eq!((slice, remainder), (CGRect::new(7.0, 0.0, 3.0, 20.0), CGRect::new(0.0, 0.0, 7.0, 20.0)));
+        rect.divide(&mut slice, &mut remainder, 5.0, CGRectEdge::MinY);
+        assert_eq!((slice, remainder), (CGRect::new(0.0, 0.0, 10.0, 5.0), CGRect::new(0.0, 5.0, 10.0, 15.0)));
+        rect.divide(&mut slice, &mut remainder, 5.0, CGRectEdge::MaxY);
+        assert_eq!((slice, remainder), (CGRect::new(0.0, 15.0, 10.0, 5.0), CGRect::new(0.0, 0.0, 10.0, 15.0)));
+        // the amount is clamped to the rect
+        rect.divide(&mut slice, &mut remainder, 30.0, CGRectEdge::MinX);
+        assert_eq!((slice, remainder), (rect, CGRect::new(10.0, 0.0, 0.0, 20.0)));
+        rect.divide(&mut slice, &mut remainder, -3.0, CGRectEdge::MinX);
+        assert_eq!((slice, remainder), (CGRect::new(0.0, 0.0, 0.0, 20.0), rect));
+        // negative sizes are standardized first
+        CGRect::new(10.0, 20.0, -10.0, -20.0).divide(&mut slice, &mut remainder, 3.0, CGRectEdge::MinX);
+        assert_eq!((slice, remainder), (CGRect::new(0.0, 0.0, 3.0, 20.0), CGRect::new(3.0, 0.0, 7.0, 20.0)));
+        CGRectNull.divide(&mut slice, &mut remainder, 3.0, CGRectEdge::MinX);
+        assert!(slice.is_null() && remainder.is_null());
+    }
+
+    #[test]
+    fn inset() {
+        let rect = CGRect::new(0.0, 0.0, 10.0, 20.0);
+        assert_eq!(rect.inset(2.0, 3.0), CGRect::new(2.0, 3.0, 6.0, 14.0));
+        assert_eq!(rect.inset(-2.0, -3.0), CGRect::new(-2.0, -3.0, 14.0, 26.0));
+        assert_eq!(CGRect::new(10.0, 20.0, -10.0, -20.0).inset(2.0, 3.0), CGRect::new(2.0, 3.0, 6.0, 14.0));
+        assert!(rect.inset(6.0, 0.0).is_null());
+        assert!(CGRectNull.inset(1.0, 1.0).is_null());
+        assert!(CGRectInfinite.inset(1.0, 1.0).is_infinite());
+    }
+
+    #[test]
+    fn integral() {
+        assert_eq!(CGRect::new(0.5, 1.25, 2.0, 3.5).integral(), CGRect::new(0.0, 1.0, 3.0, 4.0));
+        assert_eq!(CGRect::new(-0.5, -1.5, 1.0, 1.0).integral(), CGRect::new(-1.0, -2.0, 2.0, 2.0));
+        assert_eq!(CGRect::new(2.5, 4.5, -2.0, -3.0).integral(), CGRect::new(0.0, 1.0, 3.0, 4.0));
+        assert!(CGRectNull.integral().is_null());
+        assert!(CGRectInfinite.integral().is_infinite());
+    }
+
+    fn assert_close(lhs: CGFloat, rhs: CGFloat) {
+        assert!((lhs - rhs).abs() < 1e-12, "{} != {}", lhs, rhs);
+    }
+
+    #[test]
+    fn point_operators() {
+        let mut point = CGPoint::new(1.0, 2.0);
+        assert_eq!(point + CGVector::new(3.0, 4.0), CGPoint::new(4.0, 6.0));
+        assert_eq!(point - CGVector::new(3.0, 4.0), CGPoint::new(-2.0, -2.0));
+        assert_eq!(point + CGSize::new(3.0, 4.0), CGPoint::new(4.0, 6.0));
+        assert_eq!(point - CGSize::new(3.0, 4.0), CGPoint::new(-2.0, -2.0));
+        assert_eq!(CGPoint::new(4.0, 6.0) - point, CGVector::new(3.0, 4.0));
+        assert_eq!(point * 2.0, CGPoint::new(2.0, 4.0));
+        assert_eq!(2.0 * point, CGPoint::new(2.0, 4.0));
+        assert_eq!(point / 2.0, CGPoint::new(0.5, 1.0));
+        assert_eq!(-point, CGPoint::new(-1.0, -2.0));
+        point += CGVector::new(1.0, 1.0);
+        point -= CGSize::new(0.5, 0.5);
+        point *= 2.0;
+        point /= 4.0;
+        assert_eq!(point, CGPoint::new(0.75, 1.25));
+    }
+
+    #[test]
+    fn size_operators() {
+        let mut size = CGSize::new(4.0, 6.0);
+        assert_eq!(size + CGSize::new(1.0, 2.0), CGSize::new(5.0, 8.0));
+        assert_eq!(size - CGSize::new(1.0, 2.0), CGSize::new(3.0, 4.0));
+        assert_eq!(size * 0.5, CGSize::new(2.0, 3.0));
+        assert_eq!(0.5 * size, CGSize::new(2.0, 3.0));
+        assert_eq!(size / 2.0, CGSize::new(2.0, 3.0));
+        assert_eq!(-size, CGSize::new(-4.0, -6.0));
+        size += CGSize::new(1.0, 1.0);
+        size -= CGSize::new(3.0, 3.0);
+        size *= 3.0;
+        size /= 2.0;
+        assert_eq!(size, CGSize::new(3.0, 6.0));
+    }
+
+    #[test]
+    fn vector_operators() {
+        let mut vector = CGVector::new(3.0, 4.0);
+        assert_eq!(vector + CGVector::new(1.0, 1.0), CGVector::new(4.0, 5.0));
+        assert_eq!(vector - CGVector::new(1.0, 1.0), CGVector::new(2.0, 3.0));
+        assert_eq!(vector * 2.0, CGVector::new(6.0, 8.0));
+        assert_eq!(2.0 * vector, CGVector::new(6.0, 8.0));
+        assert_eq!(vector / 2.0, CGVector::new(1.5, 2.0));
+        assert_eq!(-vector, CGVector::new(-3.0, -4.0));
+        vector += CGVector::new(1.0, 0.0);
+        vector -= CGVector::new(0.0, 2.0);
+        vector *= 3.0;
+        vector /= 6.0;
+        assert_eq!(vector, CGVector::new(2.0, 1.0));
+    }
+
+    #[test]
+    fn vector_algebra() {
+        let x = CGVector::new(1.0, 0.0);
+        let y = CGVector::new(0.0, 2.0);
+        assert_eq!(x.dot(&y), 0.0);
+        assert_eq!(CGVector::new(3.0, 4.0).dot(&CGVector::new(2.0, -1.0)), 2.0);
+        // the cross product is positive for a counter-clockwise turn
+        assert_eq!(x.cross(&y), 2.0);
+        assert_eq!(y.cross(&x), -2.0);
+        assert_eq!(CGVector::new(3.0, 4.0).length(), 5.0);
+        assert_eq!(CGVector::new(3.0, 4.0).normalize(), CGVector::new(0.6, 0.8));
+        assert_eq!(CGVector::new(0.0, 0.0).normalize(), CGVector::new(0.0, 0.0));
+        assert_close(x.angle_to(&y), FRAC_PI_2 as CGFloat);
+        assert_close(y.angle_to(&x), -FRAC_PI_2 as CGFloat);
+        assert_close(x.angle_to(&CGVector::new(-1.0, 0.0)), PI as CGFloat);
+        let rotated = x.rotate(FRAC_PI_2 as CGFloat);
+        assert_close(rotated.dx, 0.0);
+        assert_close(rotated.dy, 1.0);
+    }
+
+    #[test]
+    fn point_helpers() {
+        let origin = CGPoint::new(1.0, 1.0);
+        let point = CGPoint::new(4.0, 5.0);
+        assert_eq!(origin.distance(&point), 5.0);
+        assert_eq!(origin.distance_squared(&point), 25.0);
+        assert_eq!(origin.midpoint(&point), CGPoint::new(2.5, 3.0));
+        assert_eq!(origin.lerp(&point, 2.0), CGPoint::new(7.0, 9.0));
+        assert_close(origin.angle_to(&CGPoint::new(1.0, 3.0)), FRAC_PI_2 as CGFloat);
+        assert_close(origin.angle_to(&CGPoint::new(0.0, 0.0)), -3.0 * FRAC_PI_4 as CGFloat);
+        let rotated = CGPoint::new(2.0, 1.0).rotate_around(&origin, PI as CGFloat);
+        assert_close(rotated.x, 0.0);
+        assert_close(rotated.y, 1.0);
+        assert_eq!(CGPoint::from(point.to_vector()), point);
+    }
+}
@@ -0,0 +1,527 @@
+use crate::{
+    affine_transform::CGAffineTransform,
+    base::CGFloat,
+    color_space::CGColorRenderingIntent,
+    context::{CGBlendMode, CGInterpolationQuality, CGPathDrawingMode, CGTextDrawingMode},
+    font::CGGlyph,
+    geometry::{CGPoint, CGRect, CGSize},
+    path::{CGLineCap, CGLineJoin},
+    path_builder::Path,
+};
+#[cfg(target_os = "macos")]
+use crate::{
+    color::CGColor,
+    color_space::CGColorSpace,
+    context::CGContext,
+    font::CGFont,
+    gradient::{CGGradient, CGGradientDrawingOptions},
+    image::CGImage,
+    pattern::CGPattern,
+    shading::CGShading,
+};
+
+// the drawing operations of CGContext, so other backends can stand in for it
+// operations taking framework objects are only available where the framework is
+pub trait GraphicsContext {
+    fn save_state(&mut self);
+    fn restore_state(&mut self);
+    fn scale(&mut self, sx: CGFloat, sy: CGFloat);
+    fn translate(&mut self, tx: CGFloat, ty: CGFloat);
+    fn rotate(&mut self, angle: CGFloat);
+    fn concat_ctm(&mut self, transform: CGAffineTransform);
+    fn set_line_width(&mut self, width: CGFloat);
+    fn set_line_cap(&mut self, cap: CGLineCap);
+    fn set_line_join(&mut self, join: CGLineJoin);
+    fn set_line_dash(&mut self, phase: CGFloat, lengths: &[CGFloat]);
+    fn set_miter_limit(&mut self, limit: CGFloat);
+    fn set_flatness(&mut self, flatness: CGFloat);
+    fn set_alpha(&mut self, alpha: CGFloat);
+    fn set_blend_mode(&mut self, mode: CGBlendMode);
+    fn begin_path(&mut self);
+    fn close_path(&mut self);
+    fn move_to_point(&mut self, x: CGFloat, y: CGFloat);
+    fn add_line_to_point(&mut self, x: CGFloat, y: CGFloat);
+    fn add_curve_to_point(&mut self, cp1x: CGFloat, cp1y: CGFloat, cp2x: CGFloat, cp2y: CGFloat, x: CGFloat, y: CGFloat);
+    fn add_quad_curve_to_point(&mut self, cpx: CGFloat, cpy: CGFloat, x: CGFloat, y: CGFloat);
+    fn add_rect(&mut self, rect: CGRect);
+    fn add_rects(&mut self, rects: &[CGRect]);
+    fn add_lines(&mut self, points: &[CGPoint]);
+    fn add_ellipse_in_rect(&mut self, rect: CGRect);
+    fn add_arc(&mut self, x: CGFloat, y: CGFloat, radius: CGFloat, start_angle: CGFloat, end_angle: CGFloat, clockwise: bool);
+    fn add_arc_to_point(&mut self, x1: CGFloat, y1: CGFloat, x2: CGFloat, y2: CGFloat, radius: CGFloat);
+    fn add_path(&mut self, path: &Path);
+    fn replace_path_with_stroked_path(&mut self);
+    fn draw_path(&mut self, mode: CGPathDrawingMode);
+    fn fill_path(&mut self);
+    fn eo_fill_path(&mut self);
+    fn stroke_path(&mut self);
+    fn fill_rect(&mut self, rect: CGRect);
+    fn fill_rects(&mut self, rects: &[CGRect]);
+    fn stroke_rect(&mut self, rect: CGRect);
+    fn stroke_rect_with_width(&mut self, rect: CGRect, width: CGFloat);
+    fn clear_rect(&mut self, rect: CGRect);
+    fn fill_ellipse_in_rect(&mut self, rect: CGRect);
+    fn stroke_ellipse_in_rect(&mut self, rect: CGRect);
+    fn stroke_line_segments(&mut self, points: &[CGPoint]);
+    fn clip(&mut self);
+    fn eo_clip(&mut self);
+    fn reset_clip(&mut self);
+    #[cfg(target_os = "macos")]
+    fn clip_to_mask(&mut self, rect: CGRect, image: &CGImage);
+    fn clip_to_rect(&mut self, rect: CGRect);
+    fn clip_to_rects(&mut self, rects: &[CGRect]);
+    #[cfg(target_os = "macos")]
+    fn set_fill_color(&mut self, color: &CGColor);
+    #[cfg(target_os = "macos")]
+    fn set_stroke_color(&mut self, color: &CGColor);
+    #[cfg(target_os = "macos")]
+    fn set_fill_color_space(&mut self, space: &CGColorSpace);
+    #[cfg(target_os = "macos")]
+    fn set_stroke_color_space(&mut self, space: &CGColorSpace);
+    /// # Safety
+    /// `components` must hold a value for each component of the pattern color space plus alpha
+    #[cfg(target_os = "macos")]
+    unsafe fn set_fill_pattern(&mut self, pattern: &CGPattern, components: &[CGFloat]);
+    /// # Safety
+    /// `components` must hold a value for each component of the pattern color space plus alpha
+    #[cfg(target_os = "macos")]
+    unsafe fn set_stroke_pattern(&mut self, pattern: &CGPattern, components: &[CGFloat]);
+    fn set_pattern_phase(&mut self, phase: CGSize);
+    fn set_gray_fill_color(&mut self, gray: CGFloat, alpha: CGFloat);
+    fn set_gray_stroke_color(&mut self, gray: CGFloat, alpha: CGFloat);
+    fn set_rgb_fill_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat);
+    fn set_rgb_stroke_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat);
+    fn set_cmyk_fill_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat);
+    fn set_cmyk_stroke_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat);
+    fn set_rendering_intent(&mut self, intent: CGColorRenderingIntent);
+    #[cfg(target_os = "macos")]
+    fn draw_image(&mut self, rect: CGRect, image: &CGImage);
+    #[cfg(target_os = "macos")]
+    fn draw_tiled_image(&mut self, rect: CGRect, image: &CGImage);
+    fn set_interpolation_quality(&mut self, quality: CGInterpolationQuality);
+    #[cfg(target_os = "macos")]
+    fn draw_linear_gradient(&mut self, gradient: &CGGradient, start_point: CGPoint, end_point: CGPoint, options: CGGradientDrawingOptions);
+    #[cfg(target_os = "macos")]
+    fn draw_radial_gradient(
+        &mut self,
+        gradient: &CGGradient,
+        start_center: CGPoint,
+        start_radius: CGFloat,
+        end_center: CGPoint,
+        end_radius: CGFloat,
+        options: CGGradientDrawingOptions,
+    );
+    fn set_shadow(&mut self, offset: CGSize, blur: CGFloat);
+    #[cfg(target_os = "macos")]
+    fn set_shadow_with_color(&mut self, offset: CGSize, blur: CGFloat, color: &CGColor);
+    #[cfg(target_os = "macos")]
+    fn draw_shading(&mut self, shading: &CGShading);
+    #[cfg(target_os = "macos")]
+    fn set_font(&mut self, font: &CGFont);
+    fn set_font_size(&mut self, size: CGFloat);
+    fn set_text_matrix(&mut self, t: &CGAffineTransform);
+    fn set_text_drawing_mode(&mut self, mode: CGTextDrawingMode);
+    fn set_text_position(&mut self, x: CGFloat, y: CGFloat);
+    fn set_allows_font_smoothing(&mut self, allows_font_smoothing: bool);
+    fn set_should_smooth_fonts(&mut self, should_smooth_fonts: bool);
+    fn set_allows_antialiasing(&mut self, allows_antialiasing: bool);
+    fn set_should_antialias(&mut self, should_antialias: bool);
+    fn set_allows_font_subpixel_quantization(&mut self, allows_font_subpixel_quantization: bool);
+    fn set_should_subpixel_quantize_fonts(&mut self, should_subpixel_quantize_fonts: bool);
+    fn set_allows_font_subpixel_positioning(&mut self, allows_font_subpixel_positioning: bool);
+    fn set_should_subpixel_position_fonts(&mut self, should_subpixel_position_fonts: bool);
+    fn set_font_smoothing_style(&mut self, style: i32);
+    fn show_glyphs_at_positions(&mut self, glyphs: &[CGGlyph], positions: &[CGPoint]);
+    fn begin_transparency_layer(&mut self);
+    fn begin_transparency_layer_with_rect(&mut self, rect: CGRect);
+    fn end_transparency_layer(&mut self);
+    fn flush(&mut self);
+    fn synchronize(&mut self);
+    fn begin_page(&mut self, media_box: &CGRect);
+    fn end_page(&mut self);
+}
+
+#[cfg(target_os = "macos")]
+impl GraphicsContext for CGContext {
+    fn save_state(&mut self) {
+        CGContext::save_state(self)
+    }
+
+    fn restore_state(&mut self) {
+        CGContext::restore_state(self)
+    }
+
+    fn scale(&mut self, sx: CGFloat, sy: CGFloat) {
+        CGContext::scale(self, sx, sy)
+    }
+
+    fn translate(&mut self, tx: CGFloat, ty: CGFloat) {
+        CGContext::translate(self, tx, ty)
+    }
+
+    fn rotate(&mut self, angle: CGFloat) {
+        CGContext::rotate(self, angle)
+    }
+
+    fn concat_ctm(&mut self, transform: CGAffineTransform) {
+        CGContext::concat_ctm(self, transform)
+    }
+
+    fn set_line_width(&mut self, width: CGFloat) {
+        CGContext::set_line_width(self, width)
+    }
+
+    fn set_line_cap(&mut self, cap: CGLineCap) {
+        CGContext::set_line_cap(self, cap)
+    }
+
+    fn set_line_join(&mut self, join: CGLineJoin) {
+        CGContext::set_line_join(self, join)
+    }
+
+    fn set_line_dash(&mut self, phase: CGFloat, lengths: &[CGFloat]) {
+        CGContext::set_line_dash(self, phase, lengths)
+    }
+
+    fn set_miter_limit(&mut self, limit: CGFloat) {
+        CGContext::set_miter_limit(self, limit)
+    }
+
+    fn set_flatness(&mut self, flatness: CGFloat) {
+        CGContext::set_flatness(self, flatness)
+    }
+
+    fn set_alpha(&mut self, alpha: CGFloat) {
+        CGContext::set_alpha(self, alpha)
+    }
+
+    fn set_blend_mode(&mut self, mode: CGBlendMode) {
+        CGContext::set_blend_mode(self, mode)
+    }
+
+    fn begin_path(&mut self) {
+        CGContext::begin_path(self)
+    }
+
+    fn close_path(&mut self) {
+        CGContext::close_path(self)
+    }
+
+    fn move_to_point(&mut self, x: CGFloat, y: CGFloat) {
+        CGContext::move_to_point(self, x, y)
+    }
+
+    fn add_line_to_point(&mut self, x: CGFloat, y: CGFloat) {
+        CGContext::add_line_to_point(self, x, y)
+    }
+
+    fn add_curve_to_point(&mut self, cp1x: CGFloat, cp1y: CGFloat, cp2x: CGFloat, cp2y: CGFloat, x: CGFloat, y: CGFloat) {
+        CGContext::add_curve_to_point(self, cp1x, cp1y, cp2x, cp2y, x, y)
+    }
+
+    fn add_quad_curve_to_point(&mut self, cpx: CGFloat, cpy: CGFloat, x: CGFloat, y: CGFloat) {
+        CGContext::add_quad_curve_to_point(self, cpx, cpy, x, y)
+    }
+
+    fn add_rect(&mut self, rect: CGRect) {
+        CGContext::add_rect(self, rect)
+    }
+
+    fn add_rects(&mut self, rects: &[CGRect]) {
+        CGContext::add_rects(self, rects)
+    }
+
+    fn add_lines(&mut self, points: &[CGPoint]) {
+        CGContext::add_lines(self, points)
+    }
+
+    fn add_ellipse_in_rect(&mut self, rect: CGRect) {
+        CGContext::add_ellipse_in_rect(self, rect)
+    }
+
+    fn add_arc(&mut self, x: CGFloat, y: CGFloat, radius: CGFloat, start_angle: CGFloat, end_angle: CGFloat, clockwise: bool) {
+        CGContext::add_arc(self, x, y, radius, start_angle, end_angle, clockwise)
+    }
+
+    fn add_arc_to_point(&mut self, x1: CGFloat, y1: CGFloat, x2: CGFloat, y2: CGFloat, radius: CGFloat) {
+        CGContext::add_arc_to_point(self, x1, y1, x2, y2, radius)
+    }
+
+    fn add_path(&mut self, path: &Path) {
+        CGContext::add_path(self, &path.to_cg_path())
+    }
+
+    fn replace_path_with_stroked_path(&mut self) {
+        CGContext::replace_path_with_stroked_path(self)
+    }
+
+    fn draw_path(&mut self, mode: CGPathDrawingMode) {
+        CGContext::draw_path(self, mode)
+    }
+
+    fn fill_path(&mut self) {
+        CGContext::fill_path(self)
+    }
+
+    fn eo_fill_path(&mut self) {
+        CGContext::eo_fill_path(self)
+    }
+
+    fn stroke_path(&mut self) {
+        CGContext::stroke_path(self)
+    }
+
+    fn fill_rect(&mut self, rect: CGRect) {
+        CGContext::fill_rect(self, rect)
+    }
+
+    fn fill_rects(&mut self, rects: &[CGRect]) {
+        CGContext::fill_rects(self, rects)
+    }
+
+    fn stroke_rect(&mut self, rect: CGRect) {
+        CGContext::stroke_rect(self, rect)
+    }
+
+    fn stroke_rect_with_width(&mut self, rect: CGRect, width: CGFloat) {
+        CGContext::stroke_rect_with_width(self, rect, width)
+    }
+
+    fn clear_rect(&mut self, rect: CGRect) {
+        CGContext::clear_rect(self, rect)
+    }
+
+    fn fill_ellipse_in_rect(&mut self, rect: CGRect) {
+        CGContext::fill_ellipse_in_rect(self, rect)
+    }
+
+    fn stroke_ellipse_in_rect(&mut self, rect: CGRect) {
+        CGContext::stroke_ellipse_in_rect(self, rect)
+    }
+
+    fn stroke_line_segments(&mut self, points: &[CGPoint]) {
+        CGContext::stroke_line_segments(self, points)
+    }
+
+    fn clip(&mut self) {
+        CGContext::clip(self)
+    }
+
+    fn eo_clip(&mut self) {
+        CGContext::eo_clip(self)
+    }
+
+    fn reset_clip(&mut self) {
+        CGContext::reset_clip(self)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn clip_to_mask(&mut self, rect: CGRect, image: &CGImage) {
+        CGContext::clip_to_mask(self, rect, image)
+    }
+
+    fn clip_to_rect(&mut self, rect: CGRect) {
+        CGContext::clip_to_rect(self, rect)
+    }
+
+    fn clip_to_rects(&mut self, rects: &[CGRect]) {
+        CGContext::clip_to_rects(self, rects)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_fill_color(&mut self, color: &CGColor) {
+        CGContext::set_fill_color(self, color)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_stroke_color(&mut self, color: &CGColor) {
+        CGContext::set_stroke_color(self, color)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_fill_color_space(&mut self, space: &CGColorSpace) {
+        CGContext::set_fill_color_space(self, space)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_stroke_color_space(&mut self, space: &CGColorSpace) {
+        CGContext::set_stroke_color_space(self, space)
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn set_fill_pattern(&mut self, pattern: &CGPattern, components: &[CGFloat]) {
+        unsafe { CGContext::set_fill_pattern(self, pattern, components) }
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn set_stroke_pattern(&mut self, pattern: &CGPattern, components: &[CGFloat]) {
+        unsafe { CGContext::set_stroke_pattern(self, pattern, components) }
+    }
+
+    fn set_pattern_phase(&mut self, phase: CGSize) {
+        CGContext::set_pattern_phase(self, phase)
+    }
+
+    fn set_gray_fill_color(&mut self, gray: CGFloat, alpha: CGFloat) {
+        CGContext::set_gray_fill_color(self, gray, alpha)
+    }
+
+    fn set_gray_stroke_color(&mut self, gray: CGFloat, alpha: CGFloat) {
+        CGContext::set_gray_stroke_color(self, gray, alpha)
+    }
+
+    fn set_rgb_fill_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
+        CGContext::set_rgb_fill_color(self, red, green, blue, alpha)
+    }
+
+    fn set_rgb_stroke_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
+        CGContext::set_rgb_stroke_color(self, red, green, blue, alpha)
+    }
+
+    fn set_cmyk_fill_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) {
+        CGContext::set_cmyk_fill_color(self, cyan, magenta, yellow, black, alpha)
+    }
+
+    fn set_cmyk_stroke_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) {
+        CGContext::set_cmyk_stroke_color(self, cyan, magenta, yellow, black, alpha)
+    }
+
+    fn set_rendering_intent(&mut self, intent: CGColorRenderingIntent) {
+        CGContext::set_rendering_intent(self, intent)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_image(&mut self, rect: CGRect, image: &CGImage) {
+        CGContext::draw_image(self, rect, image)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_tiled_image(&mut self, rect: CGRect, image: &CGImage) {
+        CGContext::draw_tiled_image(self, rect, image)
+    }
+
+    fn set_interpolation_quality(&mut self, quality: CGInterpolationQuality) {
+        CGContext::set_interpolation_quality(self, quality)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_linear_gradient(&mut self, gradient: &CGGradient, start_point: CGPoint, end_point: CGPoint, options: CGGradientDrawingOptions) {
+        CGContext::draw_linear_gradient(self, gradient, start_point, end_point, options)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_radial_gradient(
+        &mut self,
+        gradient: &CGGradient,
+        start_center: CGPoint,
+        start_radius: CGFloat,
+        end_center: CGPoint,
+        end_radius: CGFloat,
+        options: CGGradientDrawingOptions,
+    ) {
+        CGContext::draw_radial_gradient(self, gradient, start_center, start_radius, end_center, end_radius, options)
+    }
+
+    fn set_shadow(&mut self, offset: CGSize, blur: CGFloat) {
+        CGContext::set_shadow(self, offset, blur)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_shadow_with_color(&mut self, offset: CGSize, blur: CGFloat, color: &CGColor) {
+        CGContext::set_shadow_with_color(self, offset, blur, color)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_shading(&mut self, shading: &CGShading) {
+        CGContext::draw_shading(self, shading)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_font(&mut self, font: &CGFont) {
+        CGContext::set_font(self, font)
+    }
+
+    fn set_font_size(&mut self, size: CGFloat) {
+        CGContext::set_font_size(self, size)
+    }
+
+    fn set_text_matrix(&mut self, t: &CGAffineTransform) {
+        CGContext::set_text_matrix(self, t)
+    }
+
+    fn set_text_drawing_mode(&mut self, mode: CGTextDrawingMode) {
+        CGContext::set_text_drawing_mode(self, mode)
+    }
+
+    fn set_text_position(&mut self, x: CGFloat, y: CGFloat) {
+        CGContext::set_text_position(self, x, y)
+    }
+
+    fn set_allows_font_smoothing(&mut self, allows_font_smoothing: bool) {
+        CGContext::set_allows_font_smoothing(self, allows_font_smoothing)
+    }
+
+    fn set_should_smooth_fonts(&mut self, should_smooth_fonts: bool) {
+        CGContext::set_should_smooth_fonts(self, should_smooth_fonts)
+    }
+
+    fn set_allows_antialiasing(&mut self, allows_antialiasing: bool) {
+        CGContext::set_allows_antialiasing(self, allows_antialiasing)
+    }
+
+    fn set_should_antialias(&mut self, should_antialias: bool) {
+        CGContext::set_should_antialias(self, should_antialias)
+    }
+
+    fn set_allows_font_subpixel_quantization(&mut self, allows_font_subpixel_quantization: bool) {
+        CGContext::set_allows_font_subpixel_quantization(self, allows_font_subpixel_quantization)
+    }
+
+    fn set_should_subpixel_quantize_fonts(&mut self, should_subpixel_quantize_fonts: bool) {
+        CGContext::set_should_subpixel_quantize_fonts(self, should_subpixel_quantize_fonts)
+    }
+
+    fn set_allows_font_subpixel_positioning(&mut self, allows_font_subpixel_positioning: bool) {
+        CGContext::set_allows_font_subpixel_positioning(self, allows_font_subpixel_positioning)
+    }
+
+    fn set_should_subpixel_position_fonts(&mut self, should_subpixel_position_fonts: bool) {
+        CGContext::set_should_subpixel_position_fonts(self, should_subpixel_position_fonts)
+    }
+
+    fn set_font_smoothing_style(&mut self, style: i32) {
+        CGContext::set_font_smoothing_style(self, style)
+    }
+
+    fn show_glyphs_at_positions(&mut self, glyphs: &[CGGlyph], positions: &[CGPoint]) {
+        CGContext::show_glyphs_at_positions(self, glyphs, positions)
+    }
+
+    fn begin_transparency_layer(&mut self) {
+        CGContext::begin_transparency_layer(self, None)
+    }
+
+    fn begin_transparency_layer_with_rect(&mut self, rect: CGRect) {
+        CGContext::begin_transparency_layer_with_rect(self, rect, None)
+    }
+
+    fn end_transparency_layer(&mut self) {
+        CGContext::end_transparency_layer(self)
+    }
+
+    fn flush(&mut self) {
+        CGContext::flush(self)
+    }
+
+    fn synchronize(&mut self) {
+        CGContext::synchronize(self)
+    }
+
+    fn begin_page(&mut self, media_box: &CGRect) {
+        CGContext::begin_page(self, media_box)
+    }
+
+    fn end_page(&mut self) {
+        CGContext::end_page(self)
+    }
+}
@@ -0,0 +1,931 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use core_foundation::data::CFData;
+
+use crate::{
+    base::CGFloat,
+    color_model::{apply, bradford_adaptation, invert, multiply, ColorModel, Matrix3, TransferFunction, D50},
+    color_space::{CGColorRenderingIntent, CGColorSpace, CGColorSpaceModel},
+};
+
+const HEADER_SIZE: usize = 128;
+const TAG_ENTRY_SIZE: usize = 12;
+const FILE_SIGNATURE: [u8; 4] = *b"acsp";
+// entries of the tables curves without an exact encoding are written as
+const CURVE_TABLE_SIZE: usize = 4096;
+
+pub const DESCRIPTION_TAG: [u8; 4] = *b"desc";
+pub const COPYRIGHT_TAG: [u8; 4] = *b"cprt";
+pub const MEDIA_WHITE_POINT_TAG: [u8; 4] = *b"wtpt";
+pub const CHROMATIC_ADAPTATION_TAG: [u8; 4] = *b"chad";
+pub const RED_COLORANT_TAG: [u8; 4] = *b"rXYZ";
+pub const GREEN_COLORANT_TAG: [u8; 4] = *b"gXYZ";
+pub const BLUE_COLORANT_TAG: [u8; 4] = *b"bXYZ";
+pub const RED_TRC_TAG: [u8; 4] = *b"rTRC";
+pub const GREEN_TRC_TAG: [u8; 4] = *b"gTRC";
+pub const BLUE_TRC_TAG: [u8; 4] = *b"bTRC";
+pub const GRAY_TRC_TAG: [u8; 4] = *b"kTRC";
+
+const XYZ_TYPE: [u8; 4] = *b"XYZ ";
+const CURVE_TYPE: [u8; 4] = *b"curv";
+const PARAMETRIC_CURVE_TYPE: [u8; 4] = *b"para";
+const TEXT_TYPE: [u8; 4] = *b"text";
+const TEXT_DESCRIPTION_TYPE: [u8; 4] = *b"desc";
+const MULTI_LOCALIZED_UNICODE_TYPE: [u8; 4] = *b"mluc";
+const S15_FIXED16_ARRAY_TYPE: [u8; 4] = *b"sf32";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IccError {
+    InvalidSignature,
+    InvalidHeader,
+    UnsupportedVersion,
+    InvalidTagTable,
+    InvalidTag,
+    UnexpectedEnd,
+}
+
+impl Display for IccError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            IccError::InvalidSignature => write!(f, "not an ICC profile"),
+            IccError::InvalidHeader => write!(f, "invalid profile header"),
+            IccError::UnsupportedVersion => write!(f, "unsupported profile version"),
+            IccError::InvalidTagTable => write!(f, "invalid tag table"),
+            IccError::InvalidTag => write!(f, "invalid tag data"),
+            IccError::UnexpectedEnd => write!(f, "unexpected end of data"),
+        }
+    }
+}
+
+impl Error for IccError {}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IccProfileClass {
+    Input,
+    Display,
+    Output,
+    DeviceLink,
+    ColorSpace,
+    Abstract,
+    NamedColor,
+}
+
+impl IccProfileClass {
+    fn from_signature(signature: [u8; 4]) -> Option<IccProfileClass> {
+        match &signature {
+            b"scnr" => Some(IccProfileClass::Input),
+            b"mntr" => Some(IccProfileClass::Display),
+            b"prtr" => Some(IccProfileClass::Output),
+            b"link" => Some(IccProfileClass::DeviceLink),
+            b"spac" => Some(IccProfileClass::ColorSpace),
+            b"abst" => Some(IccProfileClass::Abstract),
+            b"nmcl" => Some(IccProfileClass::NamedColor),
+            _ => None,
+        }
+    }
+
+    pub fn signature(&self) -> [u8; 4] {
+        match self {
+            IccProfileClass::Input => *b"scnr",
+            IccProfileClass::Display => *b"mntr",
+            IccProfileClass::Output => *b"prtr",
+            IccProfileClass::DeviceLink => *b"link",
+            IccProfileClass::ColorSpace => *b"spac",
+            IccProfileClass::Abstract => *b"abst",
+            IccProfileClass::NamedColor => *b"nmcl",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IccHeader {
+    pub cmm: [u8; 4],
+    // major version, minor version and bug fix revision
+    pub version: [u8; 3],
+    pub class: IccProfileClass,
+    // data color space signature, such as "RGB ", "GRAY" or "CMYK"
+    pub color_space: [u8; 4],
+    // profile connection space signature, "XYZ " or "Lab "
+    pub pcs: [u8; 4],
+    // year, month, day, hours, minutes and seconds in UTC
+    pub date: [u16; 6],
+    pub platform: [u8; 4],
+    pub flags: u32,
+    pub manufacturer: [u8; 4],
+    pub model: [u8; 4],
+    pub attributes: u64,
+    pub rendering_intent: CGColorRenderingIntent,
+    pub illuminant: [CGFloat; 3],
+    pub creator: [u8; 4],
+    // MD5 of the profile, all zero when it was not computed
+    pub id: [u8; 16],
+}
+
+impl IccHeader {
+    pub fn color_space_model(&self) -> CGColorSpaceModel {
+        match &self.color_space {
+            b"GRAY" => CGColorSpaceModel::Monochrome,
+            b"RGB " => CGColorSpaceModel::RGB,
+            b"CMYK" => CGColorSpaceModel::CMYK,
+            b"Lab " => CGColorSpaceModel::Lab,
+            b"XYZ " => CGColorSpaceModel::XYZ,
+            _ => CGColorSpaceModel::Unknown,
+        }
+    }
+}
+
+// a tone reproduction curve from encoded device values to linear light
+#[derive(Clone, Debug, PartialEq)]
+pub enum Curve {
+    Function(TransferFunction),
+    // samples over 0 to 1 scaled to 0 to 65535, assumed to be non-decreasing, an empty table is the identity
+    Table(Vec<u16>),
+}
+
+impl Curve {
+    pub fn to_linear(&self, value: CGFloat) -> CGFloat {
+        match self {
+            Curve::Function(function) => function.to_linear(value),
+            Curve::Table(table) if table.len() < 2 => value,
+            Curve::Table(table) => {
+                let last = table.len() - 1;
+                let position = value.clamp(0.0, 1.0) * last as CGFloat;
+                let index = (position as usize).min(last - 1);
+                let fraction = position - index as CGFloat;
+                let low = table[index] as CGFloat;
+                let high = table[index + 1] as CGFloat;
+                (low + (high - low) * fraction) / 65535.0
+            }
+        }
+    }
+
+    pub fn from_linear(&self, value: CGFloat) -> CGFloat {
+        match self {
+            Curve::Function(function) => function.from_linear(value),
+            Curve::Table(table) if table.len() < 2 => value,
+            Curve::Table(table) => {
+                let target = value.clamp(0.0, 1.0) * 65535.0;
+                let index = table.partition_point(|entry| (*entry as CGFloat) < target);
+                if index == 0 {
+                    return 0.0;
+                }
+                if index == table.len() {
+                    return 1.0;
+                }
+                let low = table[index - 1] as CGFloat;
+                let high = table[index] as CGFloat;
+                let fraction = if high > low {
+                    (target - low) / (high - low)
+                } else {
+                    0.0
+                };
+                ((index - 1) as CGFloat + fraction) / (table.len() - 1) as CGFloat
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LocalizedText {
+    // ISO 639-1 language and ISO 3166-1 country codes
+    pub language: [u8; 2],
+    pub country: [u8; 2],
+    pub text: String,
+}
+
+// the decoded element of a tag, by tag type
+#[derive(Clone, Debug, PartialEq)]
+pub enum IccTagData {
+    XYZ(Vec<[CGFloat; 3]>),
+    // curv and para
+    Curve(Curve),
+    // the copyright of v2 profiles
+    Text(String),
+    // the description of v2 profiles, only the ASCII description is kept
+    TextDescription(String),
+    // the text tags of v4 profiles
+    MultiLocalizedUnicode(Vec<LocalizedText>),
+    S15Fixed16Array(Vec<CGFloat>),
+    // any other type, kept as the element bytes including the type signature
+    Other(Vec<u8>),
+}
+
+impl IccTagData {
+    // the English text of localized tags, or their first text
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            IccTagData::Text(text) | IccTagData::TextDescription(text) => Some(text),
+            IccTagData::MultiLocalizedUnicode(records) => {
+                records.iter().find(|record| &record.language == b"en").or_else(|| records.first()).map(|record| record.text.as_str())
+            }
+            _ => None,
+        }
+    }
+}
+
+// the header and tags of an ICC profile, tags of the types above are decoded and others are kept as they are
+#[derive(Clone, Debug, PartialEq)]
+pub struct IccProfile {
+    header: IccHeader,
+    // in the order of the tag table
+    tags: Vec<([u8; 4], IccTagData)>,
+}
+
+impl IccProfile {
+    // a v4 profile with the description, white point and chromatic adaptation tags, the creation date is left zero
+    // so generated profiles are reproducible
+    fn new(class: IccProfileClass, color_space: [u8; 4], description: &str, white_point: &[CGFloat; 3]) -> IccProfile {
+        let adaptation = bradford_adaptation(white_point, &D50);
+        let localized = |text: &str| {
+            IccTagData::MultiLocalizedUnicode(vec![LocalizedText {
+                language: *b"en",
+                country: *b"US",
+                text: text.to_string(),
+            }])
+        };
+        IccProfile {
+            header: IccHeader {
+                cmm: [0; 4],
+                version: [4, 3, 0],
+                class,
+                color_space,
+                pcs: *b"XYZ ",
+                date: [0; 6],
+                platform: [0; 4],
+                flags: 0,
+                manufacturer: [0; 4],
+                model: [0; 4],
+                attributes: 0,
+                rendering_intent: CGColorRenderingIntent::Perceptual,
+                illuminant: D50,
+                creator: [0; 4],
+                id: [0; 16],
+            },
+            tags: vec![
+                (DESCRIPTION_TAG, localized(description)),
+                (COPYRIGHT_TAG, localized("No copyright, use freely")),
+                (MEDIA_WHITE_POINT_TAG, IccTagData::XYZ(vec![D50])),
+                (CHROMATIC_ADAPTATION_TAG, IccTagData::S15Fixed16Array(adaptation.concat())),
+            ],
+        }
+    }
+
+    // a display profile for linear RGB to XYZ relative to the white point, the colorants are adapted to the D50
+    // connection space by Bradford
+    pub fn new_rgb(description: &str, white_point: &[CGFloat; 3], matrix: &Matrix3, transfer: [Curve; 3]) -> IccProfile {
+        let colorants = multiply(&bradford_adaptation(white_point, &D50), matrix);
+        let colorant = |index: usize| IccTagData::XYZ(vec![colorants.map(|row| row[index])]);
+        let [red, green, blue] = transfer;
+        let mut profile = IccProfile::new(IccProfileClass::Display, *b"RGB ", description, white_point);
+        profile.tags.extend(vec![
+            (RED_COLORANT_TAG, colorant(0)),
+            (GREEN_COLORANT_TAG, colorant(1)),
+            (BLUE_COLORANT_TAG, colorant(2)),
+            (RED_TRC_TAG, IccTagData::Curve(red)),
+            (GREEN_TRC_TAG, IccTagData::Curve(green)),
+            (BLUE_TRC_TAG, IccTagData::Curve(blue)),
+        ]);
+        profile
+    }
+
+    pub fn new_gray(description: &str, white_point: &[CGFloat; 3], transfer: Curve) -> IccProfile {
+        let mut profile = IccProfile::new(IccProfileClass::Display, *b"GRAY", description, white_point);
+        profile.tags.push((GRAY_TRC_TAG, IccTagData::Curve(transfer)));
+        profile
+    }
+
+    // monochrome and RGB models, extended range is not representable and is dropped
+    pub fn from_color_model(model: &ColorModel, description: &str) -> Option<IccProfile> {
+        match model {
+            ColorModel::Monochrome {
+                white_point,
+                transfer,
+                ..
+            } => Some(IccProfile::new_gray(description, white_point, Curve::Function(*transfer))),
+            ColorModel::RGB {
+                white_point,
+                matrix,
+                transfer,
+                ..
+            } => Some(IccProfile::new_rgb(description, white_point, matrix, transfer.map(Curve::Function))),
+            _ => None,
+        }
+    }
+
+    pub fn from_color_space(space: &CGColorSpace) -> Option<IccProfile> {
+        IccProfile::decode(space.copy_icc_profile()?.bytes()).ok()
+    }
+
+    pub fn to_color_space(&self) -> Option<CGColorSpace> {
+        CGColorSpace::from_icc_profile(&CFData::from_buffer(&self.encode()))
+    }
+
+    pub fn decode(data: &[u8]) -> Result<IccProfile, IccError> {
+        decode(data)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        encode(self)
+    }
+
+    pub fn header(&self) -> &IccHeader {
+        &self.header
+    }
+
+    pub fn header_mut(&mut self) -> &mut IccHeader {
+        &mut self.header
+    }
+
+    pub fn tags(&self) -> &[([u8; 4], IccTagData)] {
+        &self.tags
+    }
+
+    pub fn tag(&self, signature: [u8; 4]) -> Option<&IccTagData> {
+        self.tags.iter().find(|(tag, _)| *tag == signature).map(|(_, data)| data)
+    }
+
+    // replaces the tag with the same signature or adds it at the end
+    pub fn set_tag(&mut self, signature: [u8; 4], data: IccTagData) {
+        match self.tags.iter_mut().find(|(tag, _)| *tag == signature) {
+            Some(tag) => tag.1 = data,
+            None => self.tags.push((signature, data)),
+        }
+    }
+
+    pub fn remove_tag(&mut self, signature: [u8; 4]) -> Option<IccTagData> {
+        let index = self.tags.iter().position(|(tag, _)| *tag == signature)?;
+        Some(self.tags.remove(index).1)
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.tag(DESCRIPTION_TAG)?.text()
+    }
+
+    pub fn copyright(&self) -> Option<&str> {
+        self.tag(COPYRIGHT_TAG)?.text()
+    }
+
+    pub fn media_white_point(&self) -> Option<[CGFloat; 3]> {
+        self.xyz(MEDIA_WHITE_POINT_TAG)
+    }
+
+    // the matrix from XYZ under the adopted white to the D50 connection space
+    pub fn chromatic_adaptation(&self) -> Option<Matrix3> {
+        match self.tag(CHROMATIC_ADAPTATION_TAG)? {
+            IccTagData::S15Fixed16Array(values) if values.len() == 9 => Some([
+                [values[0], values[1], values[2]],
+                [values[3], values[4], values[5]],
+                [values[6], values[7], values[8]],
+            ]),
+            _ => None,
+        }
+    }
+
+    // the white the device values are relative to, recovered from chad, or the media white point of v2 profiles
+    // without one
+    pub fn adopted_white_point(&self) -> [CGFloat; 3] {
+        self.chromatic_adaptation()
+            .and_then(|adaptation| invert(&adaptation))
+            .map(|inverse| apply(&inverse, &D50))
+            .or_else(|| self.media_white_point())
+            .unwrap_or(D50)
+    }
+
+    // the matrix from linear RGB to the D50 connection space, with the red, green and blue colorants as columns
+    pub fn colorant_matrix(&self) -> Option<Matrix3> {
+        let [red, green, blue] = [self.xyz(RED_COLORANT_TAG)?, self.xyz(GREEN_COLORANT_TAG)?, self.xyz(BLUE_COLORANT_TAG)?];
+        Some([0, 1, 2].map(|row| [red[row], green[row], blue[row]]))
+    }
+
+    pub fn rgb_trc(&self) -> Option<[&Curve; 3]> {
+        Some([self.curve(RED_TRC_TAG)?, self.curve(GREEN_TRC_TAG)?, self.curve(BLUE_TRC_TAG)?])
+    }
+
+    pub fn gray_trc(&self) -> Option<&Curve> {
+        self.curve(GRAY_TRC_TAG)
+    }
+
+    // the colorimetric model of matrix/TRC profiles whose curves are all functions, relative to the adopted white
+    pub fn color_model(&self) -> Option<ColorModel> {
+        let function = |curve: &Curve| match curve {
+            Curve::Function(function) => Some(*function),
+            Curve::Table(_) => None,
+        };
+        let white_point = self.adopted_white_point();
+        match self.header.color_space_model() {
+            CGColorSpaceModel::Monochrome => Some(ColorModel::Monochrome {
+                white_point,
+                transfer: function(self.gray_trc()?)?,
+                extended_range: false,
+            }),
+            CGColorSpaceModel::RGB => {
+                let [red, green, blue] = self.rgb_trc()?;
+                let adaptation = self.chromatic_adaptation().unwrap_or_else(|| bradford_adaptation(&white_point, &D50));
+                Some(ColorModel::RGB {
+                    white_point,
+                    matrix: multiply(&invert(&adaptation)?, &self.colorant_matrix()?),
+                    transfer: [function(red)?, function(green)?, function(blue)?],
+                    extended_range: false,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn xyz(&self, signature: [u8; 4]) -> Option<[CGFloat; 3]> {
+        match self.tag(signature)? {
+            IccTagData::XYZ(values) => values.first().copied(),
+            _ => None,
+        }
+    }
+
+    fn curve(&self, signature: [u8; 4]) -> Option<&Curve> {
+        match self.tag(signature)? {
+            IccTagData::Curve(curve) => Some(curve),
+            _ => None,
+        }
+    }
+}
+
+// accepts v2 to v4 profiles, data past the size in the header is ignored
+pub fn decode(data: &[u8]) -> Result<IccProfile, IccError> {
+    if data.len() < HEADER_SIZE + 4 {
+        return Err(IccError::UnexpectedEnd);
+    }
+    let size = read_u32(data, 0)? as usize;
+    if size < HEADER_SIZE + 4 {
+        return Err(IccError::InvalidHeader);
+    }
+    let data = data.get(..size).ok_or(IccError::UnexpectedEnd)?;
+    if signature(data, 36)? != FILE_SIGNATURE {
+        return Err(IccError::InvalidSignature);
+    }
+    let version = [data[8], data[9] >> 4, data[9] & 0x0f];
+    if !(2..=4).contains(&version[0]) {
+        return Err(IccError::UnsupportedVersion);
+    }
+    let rendering_intent = match read_u32(data, 64)? & 0xffff {
+        0 => CGColorRenderingIntent::Perceptual,
+        1 => CGColorRenderingIntent::RelativeColorimetric,
+        2 => CGColorRenderingIntent::Saturation,
+        3 => CGColorRenderingIntent::AbsoluteColorimetric,
+        _ => return Err(IccError::InvalidHeader),
+    };
+    let mut date = [0; 6];
+    for (index, value) in date.iter_mut().enumerate() {
+        *value = read_u16(data, 24 + index * 2)?;
+    }
+    let mut id = [0; 16];
+    id.copy_from_slice(&data[84..100]);
+    let header = IccHeader {
+        cmm: signature(data, 4)?,
+        version,
+        class: IccProfileClass::from_signature(signature(data, 12)?).ok_or(IccError::InvalidHeader)?,
+        color_space: signature(data, 16)?,
+        pcs: signature(data, 20)?,
+        date,
+        platform: signature(data, 40)?,
+        flags: read_u32(data, 44)?,
+        manufacturer: signature(data, 48)?,
+        model: signature(data, 52)?,
+        attributes: (read_u32(data, 56)? as u64) << 32 | read_u32(data, 60)? as u64,
+        rendering_intent,
+        illuminant: read_xyz(&data[68..80]),
+        creator: signature(data, 80)?,
+        id,
+    };
+    let count = read_u32(data, HEADER_SIZE)? as usize;
+    if count > (size - HEADER_SIZE - 4) / TAG_ENTRY_SIZE {
+        return Err(IccError::InvalidTagTable);
+    }
+    let tags = (0..count)
+        .map(|index| {
+            let entry = HEADER_SIZE + 4 + index * TAG_ENTRY_SIZE;
+            let offset = read_u32(data, entry + 4)? as usize;
+            let length = read_u32(data, entry + 8)? as usize;
+            let element = offset.checked_add(length).and_then(|end| data.get(offset..end)).ok_or(IccError::InvalidTagTable)?;
+            Ok((signature(data, entry)?, decode_tag(element)?))
+        })
+        .collect::<Result<Vec<_>, IccError>>()?;
+    Ok(IccProfile {
+        header,
+        tags,
+    })
+}
+
+fn decode_tag(element: &[u8]) -> Result<IccTagData, IccError> {
+    let body = element.get(8..).ok_or(IccError::InvalidTag)?;
+    let data = match signature(element, 0)? {
+        XYZ_TYPE => IccTagData::XYZ(body.chunks_exact(12).map(read_xyz).collect()),
+        CURVE_TYPE => {
+            let count = read_u32(body, 0)? as usize;
+            let entries = count.checked_mul(2).and_then(|length| body.get(4..4 + length)).ok_or(IccError::InvalidTag)?;
+            IccTagData::Curve(match count {
+                0 => Curve::Function(TransferFunction::Linear),
+                1 => Curve::Function(TransferFunction::Gamma(read_u16(entries, 0)? as CGFloat / 256.0)),
+                _ => Curve::Table(entries.chunks_exact(2).map(|entry| u16::from_be_bytes([entry[0], entry[1]])).collect()),
+            })
+        }
+        PARAMETRIC_CURVE_TYPE => {
+            let function_type = read_u16(body, 0)? as usize;
+            let count = *[1, 3, 4, 5, 7].get(function_type).ok_or(IccError::InvalidTag)?;
+            let values = body.get(4..4 + count * 4).ok_or(IccError::InvalidTag)?;
+            let values = values.chunks_exact(4).map(read_s15_fixed16).collect::<Vec<_>>();
+            let g = values[0];
+            let function = match function_type {
+                0 => TransferFunction::Gamma(g),
+                1 | 2 if values[1] == 0.0 => return Err(IccError::InvalidTag),
+                // below -b / a the first two types are zero and the constant c
+                1 | 2 => {
+                    let offset = values.get(3).copied().unwrap_or(0.0);
+                    TransferFunction::Parametric {
+                        g,
+                        a: values[1],
+                        b: values[2],
+                        c: 0.0,
+                        d: -values[2] / values[1],
+                        e: offset,
+                        f: offset,
+                    }
+                }
+                _ => TransferFunction::Parametric {
+                    g,
+                    a: values[1],
+                    b: values[2],
+                    c: values[3],
+                    d: values[4],
+                    e: values.get(5).copied().unwrap_or(0.0),
+                    f: values.get(6).copied().unwrap_or(0.0),
+                },
+            };
+            IccTagData::Curve(Curve::Function(function))
+        }
+        TEXT_TYPE => IccTagData::Text(read_ascii(body)),
+        TEXT_DESCRIPTION_TYPE => {
+            let count = read_u32(body, 0)? as usize;
+            let text = count.checked_add(4).and_then(|end| body.get(4..end)).ok_or(IccError::InvalidTag)?;
+            IccTagData::TextDescription(read_ascii(text))
+        }
+        MULTI_LOCALIZED_UNICODE_TYPE => {
+            let count = read_u32(body, 0)? as usize;
+            let record_size = read_u32(body, 4)? as usize;
+            if record_size < 12 {
+                return Err(IccError::InvalidTag);
+            }
+            let records = (0..count)
+                .map(|index| {
+                    let record = body.get(8 + index * record_size..8 + index * record_size + 12).ok_or(IccError::InvalidTag)?;
+                    let length = read_u32(record, 4)? as usize;
+                    let offset = read_u32(record, 8)? as usize;
+                    let text = offset.checked_add(length).and_then(|end| element.get(offset..end)).ok_or(IccError::InvalidTag)?;
+                    let units = text.chunks_exact(2).map(|unit| u16::from_be_bytes([unit[0], unit[1]])).collect::<Vec<_>>();
+                    Ok(LocalizedText {
+                        language: [record[0], record[1]],
+                        country: [record[2], record[3]],
+                        text: String::from_utf16_lossy(&units),
+                    })
+                })
+                .collect::<Result<Vec<_>, IccError>>()?;
+            IccTagData::MultiLocalizedUnicode(records)
+        }
+        S15_FIXED16_ARRAY_TYPE => IccTagData::S15Fixed16Array(body.chunks_exact(4).map(read_s15_fixed16).collect()),
+        _ => IccTagData::Other(element.to_vec()),
+    };
+    Ok(data)
+}
+
+// tags with identical elements share them, as the curves of gray balanced RGB profiles usually do
+pub fn encode(profile: &IccProfile) -> Vec<u8> {
+    let mut elements: Vec<Vec<u8>> = Vec::new();
+    let mut entries = Vec::with_capacity(profile.tags.len());
+    for (signature, data) in &profile.tags {
+        let element = encode_tag(data);
+        let index = match elements.iter().position(|existing| *existing == element) {
+            Some(index) => index,
+            None => {
+                elements.push(element);
+                elements.len() - 1
+            }
+        };
+        entries.push((*signature, index));
+    }
+    let mut offsets = Vec::with_capacity(elements.len());
+    let mut size = HEADER_SIZE + 4 + entries.len() * TAG_ENTRY_SIZE;
+    for element in &elements {
+        offsets.push(size);
+        size += (element.len() + 3) & !3;
+    }
+
+    let header = &profile.header;
+    let mut data = Vec::with_capacity(size);
+    data.extend_from_slice(&(size as u32).to_be_bytes());
+    data.extend_from_slice(&header.cmm);
+    data.extend_from_slice(&[header.version[0], header.version[1] << 4 | (header.version[2] & 0x0f), 0, 0]);
+    data.extend_from_slice(&header.class.signature());
+    data.extend_from_slice(&header.color_space);
+    data.extend_from_slice(&header.pcs);
+    for value in &header.date {
+        data.extend_from_slice(&value.to_be_bytes());
+    }
+    data.extend_from_slice(&FILE_SIGNATURE);
+    data.extend_from_slice(&header.platform);
+    data.extend_from_slice(&header.flags.to_be_bytes());
+    data.extend_from_slice(&header.manufacturer);
+    data.extend_from_slice(&header.model);
+    data.extend_from_slice(&header.attributes.to_be_bytes());
+    let rendering_intent: u32 = match header.rendering_intent {
+        CGColorRenderingIntent::Default | CGColorRenderingIntent::Perceptual => 0,
+        CGColorRenderingIntent::RelativeColorimetric => 1,
+        CGColorRenderingIntent::Saturation => 2,
+        CGColorRenderingIntent::AbsoluteColorimetric => 3,
+    };
+    data.extend_from_slice(&rendering_intent.to_be_bytes());
+    write_xyz(&mut data, &header.illuminant);
+    data.extend_from_slice(&header.creator);
+    data.extend_from_slice(&header.id);
+    data.resize(HEADER_SIZE, 0);
+
+    data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    for (signature, index) in &entries {
+        data.extend_from_slice(signature);
+        data.extend_from_slice(&(offsets[*index] as u32).to_be_bytes());
+        data.extend_from_slice(&(elements[*index].len() as u32).to_be_bytes());
+    }
+    for element in &elements {
+        data.extend_from_slice(element);
+        data.resize((data.len() + 3) & !3, 0);
+    }
+    data
+}
+
+// gamma curves use the u8Fixed8 encoding of curv when it is exact, the HDR functions have no parametric form and
+// are written as tables normalized to the value of the full signal
+fn encode_tag(data: &IccTagData) -> Vec<u8> {
+    let start = |signature: [u8; 4]| {
+        let mut element = signature.to_vec();
+        element.extend_from_slice(&[0; 4]);
+        element
+    };
+    match data {
+        IccTagData::XYZ(values) => {
+            let mut element = start(XYZ_TYPE);
+            for value in values {
+                write_xyz(&mut element, value);
+            }
+            element
+        }
+        IccTagData::Curve(curve) => {
+            let table = match curve {
+                Curve::Function(TransferFunction::Linear) => Vec::new(),
+                Curve::Function(TransferFunction::Gamma(gamma)) if (gamma * 256.0).round() / 256.0 == *gamma && *gamma < 256.0 => {
+                    let mut element = start(CURVE_TYPE);
+                    element.extend_from_slice(&1u32.to_be_bytes());
+                    element.extend_from_slice(&((gamma * 256.0) as u16).to_be_bytes());
+                    return element;
+                }
+                Curve::Function(TransferFunction::Gamma(gamma)) => return encode_parametric_curve(0, &[*gamma]),
+                Curve::Function(TransferFunction::Parametric {
+                    g,
+                    a,
+                    b,
+                    c,
+                    d,
+                    e,
+                    f,
+                }) => {
+                    return match *e == 0.0 && *f == 0.0 {
+                        true => encode_parametric_curve(3, &[*g, *a, *b, *c, *d]),
+                        false => encode_parametric_curve(4, &[*g, *a, *b, *c, *d, *e, *f]),
+                    };
+                }
+                Curve::Function(function) => {
+                    let scale = function.to_linear(1.0);
+                    (0..CURVE_TABLE_SIZE)
+                        .map(|index| {
+                            let linear = function.to_linear(index as CGFloat / (CURVE_TABLE_SIZE - 1) as CGFloat) / scale;
+                            (linear.clamp(0.0, 1.0) * 65535.0).round() as u16
+                        })
+                        .collect()
+                }
+                Curve::Table(table) => table.clone(),
+            };
+            let mut element = start(CURVE_TYPE);
+            element.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            for entry in table {
+                element.extend_from_slice(&entry.to_be_bytes());
+            }
+            element
+        }
+        IccTagData::Text(text) => {
+            let mut element = start(TEXT_TYPE);
+            element.extend(text.chars().map(ascii));
+            element.push(0);
+            element
+        }
+        IccTagData::TextDescription(text) => {
+            let mut element = start(TEXT_DESCRIPTION_TYPE);
+            element.extend_from_slice(&(text.chars().count() as u32 + 1).to_be_bytes());
+            element.extend(text.chars().map(ascii));
+            element.push(0);
+            // empty Unicode and ScriptCode descriptions
+            element.extend_from_slice(&[0; 8]);
+            element.extend_from_slice(&[0; 3]);
+            element.extend_from_slice(&[0; 67]);
+            element
+        }
+        IccTagData::MultiLocalizedUnicode(records) => {
+            let mut element = start(MULTI_LOCALIZED_UNICODE_TYPE);
+            element.extend_from_slice(&(records.len() as u32).to_be_bytes());
+            element.extend_from_slice(&12u32.to_be_bytes());
+            let texts = records.iter().map(|record| record.text.encode_utf16().collect::<Vec<_>>()).collect::<Vec<_>>();
+            let mut offset = 16 + records.len() * 12;
+            for (record, text) in records.iter().zip(&texts) {
+                element.extend_from_slice(&record.language);
+                element.extend_from_slice(&record.country);
+                element.extend_from_slice(&(text.len() as u32 * 2).to_be_bytes());
+                element.extend_from_slice(&(offset as u32).to_be_bytes());
+                offset += text.len() * 2;
+            }
+            for unit in texts.iter().flatten() {
+                element.extend_from_slice(&unit.to_be_bytes());
+            }
+            element
+        }
+        IccTagData::S15Fixed16Array(values) => {
+            let mut element = start(S15_FIXED16_ARRAY_TYPE);
+            for value in values {
+                element.extend_from_slice(&s15_fixed16(*value));
+            }
+            element
+        }
+        IccTagData::Other(element) => element.clone(),
+    }
+}
+
+fn encode_parametric_curve(function_type: u16, values: &[CGFloat]) -> Vec<u8> {
+    let mut element = PARAMETRIC_CURVE_TYPE.to_vec();
+    element.extend_from_slice(&[0; 4]);
+    element.extend_from_slice(&function_type.to_be_bytes());
+    element.extend_from_slice(&[0; 2]);
+    for value in values {
+        element.extend_from_slice(&s15_fixed16(*value));
+    }
+    element
+}
+
+fn signature(data: &[u8], position: usize) -> Result<[u8; 4], IccError> {
+    let bytes = data.get(position..position + 4).ok_or(IccError::InvalidTag)?;
+    Ok([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_u16(data: &[u8], position: usize) -> Result<u16, IccError> {
+    let bytes = data.get(position..position + 2).ok_or(IccError::InvalidTag)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], position: usize) -> Result<u32, IccError> {
+    signature(data, position).map(u32::from_be_bytes)
+}
+
+fn read_s15_fixed16(bytes: &[u8]) -> CGFloat {
+    i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as CGFloat / 65536.0
+}
+
+fn read_xyz(bytes: &[u8]) -> [CGFloat; 3] {
+    [
+        read_s15_fixed16(&bytes[0..4]),
+        read_s15_fixed16(&bytes[4..8]),
+        read_s15_fixed16(&bytes[8..12]),
+    ]
+}
+
+// up to the first NUL
+fn read_ascii(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn s15_fixed16(value: CGFloat) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn write_xyz(data: &mut Vec<u8>, xyz: &[CGFloat; 3]) {
+    for value in xyz {
+        data.extend_from_slice(&s15_fixed16(*value));
+    }
+}
+
+fn ascii(character: char) -> u8 {
+    match character.is_ascii() {
+        true => character as u8,
+        false => b'?',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const D65: [CGFloat; 3] = [0.95047, 1.0, 1.08883];
+
+    fn srgb_matrix() -> Matrix3 {
+        match ColorModel::srgb() {
+            ColorModel::RGB {
+                matrix,
+                ..
+            } => matrix,
+            _ => unreachable!(),
+        }
+    }
+
+    fn assert_close(actual: &[CGFloat], expected: &[CGFloat]) {
+        // s15Fixed16Number has a resolution of 1 / 65536
+        for (actual_value, expected_value) in actual.iter().zip(expected) {
+            assert!((actual_value - expected_value).abs() < 1e-4, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn rgb_round_trip() {
+        let transfer = [
+            Curve::Function(TransferFunction::SRGB),
+            Curve::Function(TransferFunction::Gamma(1.5)),
+            Curve::Table(vec![0, 1000, 40000, 65535]),
+        ];
+        let profile = IccProfile::new_rgb("Test RGB", &D65, &srgb_matrix(), transfer);
+        let data = profile.encode();
+        assert_eq!(read_u32(&data, 0), Ok(data.len() as u32));
+        let decoded = IccProfile::decode(&data).unwrap();
+        assert_eq!(decoded.encode(), data);
+        assert_eq!(decoded.header().color_space_model(), CGColorSpaceModel::RGB);
+        assert_eq!(decoded.header().class, IccProfileClass::Display);
+        assert_eq!(decoded.header().version, [4, 3, 0]);
+        assert_eq!(decoded.description(), Some("Test RGB"));
+        assert_eq!(
+            decoded.tags().iter().map(|(signature, _)| *signature).collect::<Vec<_>>(),
+            profile.tags().iter().map(|(signature, _)| *signature).collect::<Vec<_>>()
+        );
+        assert_close(&decoded.adopted_white_point(), &D65);
+        let [red, green, blue] = decoded.rgb_trc().unwrap();
+        assert_eq!(green, &Curve::Function(TransferFunction::Gamma(1.5)));
+        assert_eq!(blue, &Curve::Table(vec![0, 1000, 40000, 65535]));
+        for step in 0..=10 {
+            let value = step as CGFloat / 10.0;
+            assert!((red.to_linear(value) - TransferFunction::SRGB.to_linear(value)).abs() < 1e-4);
+        }
+        let matrix = multiply(&invert(&decoded.chromatic_adaptation().unwrap()).unwrap(), &decoded.colorant_matrix().unwrap());
+        assert_close(&matrix.concat(), &srgb_matrix().concat());
+    }
+
+    #[test]
+    fn gray_round_trip() {
+        let profile = IccProfile::new_gray("Test Gray", &D50, Curve::Function(TransferFunction::Gamma(2.0)));
+        let data = profile.encode();
+        let decoded = IccProfile::decode(&data).unwrap();
+        assert_eq!(decoded.encode(), data);
+        assert_eq!(decoded.header().color_space_model(), CGColorSpaceModel::Monochrome);
+        assert_eq!(decoded.description(), Some("Test Gray"));
+        assert_eq!(decoded.gray_trc(), Some(&Curve::Function(TransferFunction::Gamma(2.0))));
+        match decoded.color_model() {
+            Some(ColorModel::Monochrome {
+                white_point,
+                transfer,
+                ..
+            }) => {
+                assert_close(&white_point, &D50);
+                assert_eq!(transfer, TransferFunction::Gamma(2.0));
+            }
+            model => panic!("{:?}", model),
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_and_corrupt_data() {
+        let data = IccProfile::new_gray("Test Gray", &D50, Curve::Function(TransferFunction::Linear)).encode();
+        assert_eq!(IccProfile::decode(&[]), Err(IccError::UnexpectedEnd));
+        assert_eq!(IccProfile::decode(&data[..HEADER_SIZE]), Err(IccError::UnexpectedEnd));
+        assert_eq!(IccProfile::decode(&data[..data.len() - 1]), Err(IccError::UnexpectedEnd));
+
+        let corrupt = |position: usize, bytes: &[u8]| {
+            let mut data = data.clone();
+            data[position..position + bytes.len()].copy_from_slice(bytes);
+            IccProfile::decode(&data)
+        };
+        assert_eq!(corrupt(0, &[0, 0, 0, 4]), Err(IccError::InvalidHeader));
+        assert_eq!(corrupt(36, b"xcsp"), Err(IccError::InvalidSignature));
+        assert_eq!(corrupt(8, &[5]), Err(IccError::UnsupportedVersion));
+        assert_eq!(corrupt(8, &[1]), Err(IccError::UnsupportedVersion));
+        assert_eq!(corrupt(12, b"abcd"), Err(IccError::InvalidHeader));
+        assert_eq!(corrupt(64, &[0, 0, 0, 9]), Err(IccError::InvalidHeader));
+        assert_eq!(corrupt(HEADER_SIZE, &[0, 0, 1, 0]), Err(IccError::InvalidTagTable));
+        // the offset of the first tag past the end of the profile
+        assert_eq!(corrupt(HEADER_SIZE + 8, &[0, 1, 0, 0]), Err(IccError::InvalidTagTable));
+        // the first tag as a curve with more entries than it holds, and as a parametric curve of an unknown type
+        let tag = read_u32(&data, HEADER_SIZE + 8).unwrap() as usize;
+        assert_eq!(corrupt(tag, b"curv\0\0\0\0\xff\xff\xff\xff"), Err(IccError::InvalidTag));
+        assert_eq!(corrupt(tag, b"para\0\0\0\0\0\x05\0\0"), Err(IccError::InvalidTag));
+    }
+}
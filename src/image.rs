@@ -10,9 +10,13 @@ use objc2::encode::{Encoding, RefEncode};
 
 use crate::{
     base::CGFloat,
-    color_space::{CGColorRenderingIntent, CGColorSpace, CGColorSpaceRef},
+    bitmap::{Bitmap, BitmapView},
+    color_space::{CGColorRenderingIntent, CGColorSpace, CGColorSpaceModel, CGColorSpaceRef},
+    context::{CGBlendMode, CGContext},
     data_provider::{CGDataProvider, CGDataProviderRef},
     geometry::CGRect,
+    pixel_conversion::PixelLayout,
+    pixel_format::PixelFormat,
 };
 
 #[repr(C)]
@@ -36,7 +40,7 @@ pub const kCGImageByteOrder32Little: u32 = 2 << 12;
 pub const kCGImageByteOrder16Big: u32 = 3 << 12;
 pub const kCGImageByteOrder32Big: u32 = 4 << 12;
 
-pub const kCGImagePixelFormatMask: u32 = 0xF000;
+pub const kCGImagePixelFormatMask: u32 = 0xF0000;
 pub const kCGImagePixelFormatPacked: u32 = 0 << 16;
 pub const kCGImagePixelFormatRGB555: u32 = 1 << 16;
 pub const kCGImagePixelFormatRGB565: u32 = 2 << 16;
@@ -463,9 +467,110 @@ impl CGImage {
             }
         }
     }
+
+    // component values stay in the image's color space when it has the color model of the format,
+    // otherwise the image is color matched to the device color space of the format's model
+    pub fn to_bitmap(&self, format: PixelFormat) -> Option<Bitmap> {
+        self.copy_to_bitmap(format).or_else(|| self.draw_to_bitmap(format))
+    }
+
+    // converts the provider data directly when no decoding or color matching is needed
+    fn copy_to_bitmap(&self, format: PixelFormat) -> Option<Bitmap> {
+        if self.is_mask() || unsafe { !CGImageGetDecode(self.as_concrete_TypeRef()).is_null() } {
+            return None;
+        }
+        let space = self.color_space()?;
+        let model = space.model();
+        if model == CGColorSpaceModel::Indexed || format.color_space_model().is_some_and(|format_model| format_model != model) {
+            return None;
+        }
+        let layout = PixelLayout::new(space.number_of_components(), self.bits_per_component(), self.bits_per_pixel(), self.bitmap_info()).ok()?;
+        let data = self.data_provider()?.copy_data()?;
+        let view = BitmapView::new(data.bytes(), self.width(), self.height(), self.bytes_per_row(), Some(model), layout).ok()?;
+        view.to_bitmap(format).ok()
+    }
+
+    fn draw_to_bitmap(&self, format: PixelFormat) -> Option<Bitmap> {
+        let space = match format.color_space_model() {
+            None => None,
+            Some(model) => match self.color_space() {
+                Some(space) if space.model() == model => Some(space),
+                _ => Some(match model {
+                    CGColorSpaceModel::Monochrome => CGColorSpace::new_device_gray()?,
+                    CGColorSpaceModel::RGB => CGColorSpace::new_device_rgb()?,
+                    CGColorSpaceModel::CMYK => CGColorSpace::new_device_cmyk()?,
+                    _ => return None,
+                }),
+            },
+        };
+        let (width, height) = (self.width(), self.height());
+        let mut context = CGContext::new_bitmap_context_with_pixel_format(width, height, 0, space.as_ref(), format)?;
+        context.set_blend_mode(CGBlendMode::Copy);
+        context.draw_image(CGRect::new(0.0, 0.0, width as CGFloat, height as CGFloat), self);
+        let bytes_per_row = context.bytes_per_row();
+        Bitmap::from_bytes(context.data(), width, height, bytes_per_row, format.color_space_model(), format.into()).ok()
+    }
 }
 
 #[cfg(feature = "objc")]
 unsafe impl RefEncode for __CGImage {
     const ENCODING_REF: Encoding = Encoding::Pointer(&Encoding::Struct("CGImage", &[]));
 }
+
+#[cfg(all(test, target_os = "macos"))]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn new_image(
+        width: usize,
+        height: usize,
+        bits_per_component: usize,
+        bits_per_pixel: usize,
+        space: &CGColorSpace,
+        bitmap_info: u32,
+        data: Vec<u8>,
+    ) -> CGImage {
+        let bytes_per_row = data.len() / height;
+        let provider = CGDataProvider::from_buffer(Arc::new(data)).unwrap();
+        CGImage::new(
+            width,
+            height,
+            bits_per_component,
+            bits_per_pixel,
+            bytes_per_row,
+            Some(space),
+            bitmap_info,
+            Some(&provider),
+            None,
+            false,
+            CGColorRenderingIntent::Default,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn copies_pixels_in_the_color_space_of_the_image() {
+        let space = CGColorSpace::new_device_rgb().unwrap();
+        let image = new_image(2, 1, 8, 32, &space, kCGImageAlphaLast, vec![255, 128, 0, 128, 0, 0, 255, 255]);
+        let bitmap = image.to_bitmap(PixelFormat::RGBA8888).unwrap();
+        assert_eq!(bitmap.row(0), Some(&[128, 64, 0, 128, 0, 0, 255, 255][..]));
+
+        let image = new_image(1, 1, 16, 64, &space, kCGImageAlphaNoneSkipLast, vec![0xff, 0xff, 0x80, 0x80, 0, 0, 0, 0]);
+        assert_eq!(image.to_bitmap(PixelFormat::RGBA8888).unwrap().row(0), Some(&[255, 128, 0, 255][..]));
+        assert_eq!(image.to_bitmap(PixelFormat::Alpha8).unwrap().row(0), Some(&[255][..]));
+    }
+
+    #[test]
+    fn draws_images_that_need_color_matching() {
+        let space = CGColorSpace::new_device_gray().unwrap();
+        let image = new_image(2, 1, 8, 8, &space, kCGImageAlphaNone, vec![0, 255]);
+        let bitmap = image.to_bitmap(PixelFormat::RGBX8888).unwrap();
+        assert_eq!(bitmap.color_model(), Some(CGColorSpaceModel::RGB));
+        let row = bitmap.row(0).unwrap();
+        assert_eq!(&row[..3], &[0, 0, 0]);
+        assert!(row[4..7].iter().all(|component| *component >= 254));
+        assert_eq!(image.to_bitmap(PixelFormat::Gray8).unwrap().row(0), Some(&[0, 255][..]));
+    }
+}
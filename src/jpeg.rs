@@ -0,0 +1,1383 @@
+#[cfg(target_os = "macos")]
+use std::sync::Arc;
+use std::{
+    error::Error,
+    f32::consts::PI,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    bitmap::{Bitmap, BitmapView},
+    color_space::CGColorSpaceModel,
+    deflate,
+    image::{CGBitmapInfo, CGImageAlphaInfo},
+    pixel_conversion::{PixelConverter, PixelLayout},
+    pixel_format::PixelFormat,
+};
+#[cfg(target_os = "macos")]
+use crate::{
+    color_space::{CGColorRenderingIntent, CGColorSpace, CGColorSpaceNames},
+    data_provider::CGDataProvider,
+    image::CGImage,
+};
+
+const MAX_DIMENSION: usize = u16::MAX as usize;
+const MAX_PIXELS: usize = 1 << 28;
+
+// natural order index of each coefficient in zigzag order
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43,
+    36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// quantization tables from annex K of the specification, in natural order
+const LUMINANCE_QUANTIZATION: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56, 14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56,
+    68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113, 92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+const CHROMINANCE_QUANTIZATION: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99, 47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JpegError {
+    InvalidSignature,
+    InvalidMarker,
+    InvalidSegment,
+    InvalidHeader,
+    InvalidSize,
+    InvalidData,
+    MissingTable,
+    UnsupportedFormat,
+    UnexpectedEnd,
+}
+
+impl Display for JpegError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            JpegError::InvalidSignature => write!(f, "not a JPEG file"),
+            JpegError::InvalidMarker => write!(f, "invalid or misplaced marker"),
+            JpegError::InvalidSegment => write!(f, "invalid marker segment"),
+            JpegError::InvalidHeader => write!(f, "invalid frame or scan header"),
+            JpegError::InvalidSize => write!(f, "image size is zero or too large"),
+            JpegError::InvalidData => write!(f, "invalid entropy coded data"),
+            JpegError::MissingTable => write!(f, "missing quantization or huffman table"),
+            JpegError::UnsupportedFormat => write!(f, "unsupported JPEG format"),
+            JpegError::UnexpectedEnd => write!(f, "unexpected end of data"),
+        }
+    }
+}
+
+impl Error for JpegError {}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChromaSubsampling {
+    Yuv444,
+    Yuv422,
+    Yuv420,
+}
+
+impl ChromaSubsampling {
+    // horizontal and vertical luma sampling factors, chroma is sampled once per MCU
+    fn factors(&self) -> (usize, usize) {
+        match self {
+            ChromaSubsampling::Yuv444 => (1, 1),
+            ChromaSubsampling::Yuv422 => (2, 1),
+            ChromaSubsampling::Yuv420 => (2, 2),
+        }
+    }
+}
+
+// values of the EXIF orientation tag, named like CGImagePropertyOrientation
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Orientation {
+    Up            = 1,
+    UpMirrored    = 2,
+    Down          = 3,
+    DownMirrored  = 4,
+    LeftMirrored  = 5,
+    Right         = 6,
+    RightMirrored = 7,
+    Left          = 8,
+}
+
+impl Orientation {
+    fn from_exif(value: u16) -> Option<Orientation> {
+        match value {
+            1 => Some(Orientation::Up),
+            2 => Some(Orientation::UpMirrored),
+            3 => Some(Orientation::Down),
+            4 => Some(Orientation::DownMirrored),
+            5 => Some(Orientation::LeftMirrored),
+            6 => Some(Orientation::Right),
+            7 => Some(Orientation::RightMirrored),
+            8 => Some(Orientation::Left),
+            _ => None,
+        }
+    }
+
+    fn swaps_dimensions(&self) -> bool {
+        matches!(self, Orientation::LeftMirrored | Orientation::Right | Orientation::RightMirrored | Orientation::Left)
+    }
+
+    // position in the stored image of the pixel shown at x, y
+    fn source(&self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Orientation::Up => (x, y),
+            Orientation::UpMirrored => (width - 1 - x, y),
+            Orientation::Down => (width - 1 - x, height - 1 - y),
+            Orientation::DownMirrored => (x, height - 1 - y),
+            Orientation::LeftMirrored => (y, x),
+            Orientation::Right => (y, height - 1 - x),
+            Orientation::RightMirrored => (width - 1 - y, height - 1 - x),
+            Orientation::Left => (width - 1 - y, x),
+        }
+    }
+}
+
+// a decoded image is upright, gray files decode to Gray8, color files to RGBX8888 and Adobe CMYK files to CMYK8
+#[derive(Clone, Debug)]
+pub struct JpegImage {
+    bitmap: Bitmap,
+    orientation: Orientation,
+}
+
+impl JpegImage {
+    pub fn decode(data: &[u8]) -> Result<JpegImage, JpegError> {
+        decode(data)
+    }
+
+    pub fn encode(&self, quality: u8, subsampling: ChromaSubsampling) -> Result<Vec<u8>, JpegError> {
+        encode(&self.bitmap.as_view(), quality, subsampling)
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn from_image(image: &CGImage) -> Option<JpegImage> {
+        let format = match image.color_space().map(|space| space.model()) {
+            Some(CGColorSpaceModel::Monochrome) => PixelFormat::Gray8,
+            _ => PixelFormat::RGBX8888,
+        };
+        Some(JpegImage {
+            bitmap: image.to_bitmap(format)?,
+            orientation: Orientation::Up,
+        })
+    }
+
+    // JPEG files carry no color space here, RGB is taken as sRGB and gray as gamma 2.2 gray
+    #[cfg(target_os = "macos")]
+    pub fn to_image(&self) -> Option<CGImage> {
+        let bitmap = &self.bitmap;
+        let space = match bitmap.color_model()? {
+            CGColorSpaceModel::Monochrome => CGColorSpace::from_name(&CGColorSpaceNames::GenericGrayGamma2_2.into()),
+            CGColorSpaceModel::CMYK => CGColorSpace::new_device_cmyk(),
+            _ => CGColorSpace::from_name(&CGColorSpaceNames::SRGB.into()),
+        }?;
+        let provider = CGDataProvider::from_buffer(Arc::new(bitmap.to_vec()))?;
+        CGImage::new(
+            bitmap.width(),
+            bitmap.height(),
+            bitmap.bits_per_component(),
+            bitmap.bits_per_pixel(),
+            bitmap.bytes_per_row(),
+            Some(&space),
+            bitmap.bitmap_info().bits(),
+            Some(&provider),
+            None,
+            true,
+            CGColorRenderingIntent::Default,
+        )
+    }
+
+    pub fn bitmap(&self) -> &Bitmap {
+        &self.bitmap
+    }
+
+    pub fn into_bitmap(self) -> Bitmap {
+        self.bitmap
+    }
+
+    // the orientation the file was stored with, the bitmap has already been turned upright
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+}
+
+// writes a baseline file with optimized huffman tables, gray bitmaps are written as a single component
+// and subsampling only applies to RGB bitmaps, alpha is composited over black
+pub fn encode(bitmap: &BitmapView<'_>, quality: u8, subsampling: ChromaSubsampling) -> Result<Vec<u8>, JpegError> {
+    let (width, height) = (bitmap.width(), bitmap.height());
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(JpegError::InvalidSize);
+    }
+    let components = match bitmap.color_model() {
+        Some(CGColorSpaceModel::Monochrome) => 1,
+        Some(CGColorSpaceModel::RGB) => 3,
+        _ => return Err(JpegError::UnsupportedFormat),
+    };
+    let layout = PixelLayout::new(components, 8, 8 * components, CGBitmapInfo::from_bits_retain(CGImageAlphaInfo::AlphaNone as u32))
+        .map_err(|_| JpegError::UnsupportedFormat)?;
+    let converter = PixelConverter::new(*bitmap.layout(), layout).map_err(|_| JpegError::UnsupportedFormat)?;
+    let (h_max, v_max) = match components {
+        1 => (1, 1),
+        _ => subsampling.factors(),
+    };
+    let (mcus_x, mcus_y) = (width.div_ceil(8 * h_max), height.div_ceil(8 * v_max));
+
+    // level shifted planes padded to whole MCUs by repeating the last row and column
+    let (plane_width, plane_height) = (mcus_x * 8 * h_max, mcus_y * 8 * v_max);
+    let mut planes = vec![vec![0f32; plane_width * plane_height]; components];
+    let mut row = vec![0u8; width * components];
+    for y in 0..plane_height {
+        if y < height {
+            converter.convert_row(bitmap.row(y).unwrap(), &mut row, width).map_err(|_| JpegError::UnsupportedFormat)?;
+        }
+        for x in 0..plane_width {
+            let pixel = &row[x.min(width - 1) * components..];
+            let index = y * plane_width + x;
+            if components == 1 {
+                planes[0][index] = pixel[0] as f32 - 128.0;
+            } else {
+                let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+                planes[0][index] = 0.299 * r + 0.587 * g + 0.114 * b - 128.0;
+                planes[1][index] = -0.168736 * r - 0.331264 * g + 0.5 * b;
+                planes[2][index] = 0.5 * r - 0.418688 * g - 0.081312 * b;
+            }
+        }
+    }
+    // chroma is averaged over the luma samples of each chroma sample
+    let (chroma_width, chroma_height) = (mcus_x * 8, mcus_y * 8);
+    for plane in planes.iter_mut().skip(1) {
+        if h_max == 1 && v_max == 1 {
+            continue;
+        }
+        let mut chroma = vec![0f32; chroma_width * chroma_height];
+        for (y, row) in chroma.chunks_exact_mut(chroma_width).enumerate() {
+            for (x, sample) in row.iter_mut().enumerate() {
+                let mut sum = 0.0;
+                for dy in 0..v_max {
+                    let start = (y * v_max + dy) * plane_width + x * h_max;
+                    sum += plane[start..start + h_max].iter().sum::<f32>();
+                }
+                *sample = sum / (h_max * v_max) as f32;
+            }
+        }
+        *plane = chroma;
+    }
+
+    let quality = quality.clamp(1, 100) as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - 2 * quality
+    };
+    let scale_table = |table: &[u16; 64]| table.map(|value| ((value as u32 * scale + 50) / 100).clamp(1, 255) as u16);
+    let quantization = [scale_table(&LUMINANCE_QUANTIZATION), scale_table(&CHROMINANCE_QUANTIZATION)];
+
+    let cosines = cosines();
+    let mut blocks = Vec::with_capacity(mcus_x * mcus_y * (h_max * v_max + components - 1));
+    for mcu_y in 0..mcus_y {
+        for mcu_x in 0..mcus_x {
+            for (component, plane) in planes.iter().enumerate() {
+                let (h, v) = if component == 0 {
+                    (h_max, v_max)
+                } else {
+                    (1, 1)
+                };
+                let stride = mcus_x * h * 8;
+                for block_y in 0..v {
+                    for block_x in 0..h {
+                        let start = ((mcu_y * v + block_y) * stride + mcu_x * h + block_x) * 8;
+                        let coefficients = forward_dct(plane, start, stride, &cosines);
+                        let table = &quantization[(component != 0) as usize];
+                        let mut block = [0i16; 64];
+                        for (index, natural) in ZIGZAG.iter().enumerate() {
+                            block[index] = (coefficients[*natural] / table[*natural] as f32).round() as i16;
+                        }
+                        blocks.push((component, block));
+                    }
+                }
+            }
+        }
+    }
+
+    // optimized tables for luma dc, luma ac, chroma dc and chroma ac
+    let mut frequencies = vec![[0u32; 257]; 4];
+    for_each_symbol(&blocks, |table, symbol, _, _| frequencies[table][symbol as usize] += 1);
+    let tables = frequencies.iter().map(HuffmanEncoder::new).collect::<Vec<_>>();
+
+    let mut output = vec![0xff, 0xd8];
+    write_segment(&mut output, 0xe0, &[b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0]);
+    for (index, table) in quantization.iter().take(components.min(2)).enumerate() {
+        let mut segment = vec![index as u8];
+        segment.extend(ZIGZAG.iter().map(|natural| table[*natural] as u8));
+        write_segment(&mut output, 0xdb, &segment);
+    }
+    let mut frame = vec![8];
+    frame.extend_from_slice(&(height as u16).to_be_bytes());
+    frame.extend_from_slice(&(width as u16).to_be_bytes());
+    frame.push(components as u8);
+    for component in 0..components {
+        let (h, v) = if component == 0 {
+            (h_max, v_max)
+        } else {
+            (1, 1)
+        };
+        frame.extend_from_slice(&[component as u8 + 1, (h << 4 | v) as u8, (component != 0) as u8]);
+    }
+    write_segment(&mut output, 0xc0, &frame);
+    for (index, table) in tables.iter().enumerate().take(components.min(2) * 2) {
+        let mut segment = vec![(((index % 2) << 4) | (index / 2)) as u8];
+        segment.extend_from_slice(&table.counts);
+        segment.extend_from_slice(&table.values);
+        write_segment(&mut output, 0xc4, &segment);
+    }
+    let mut scan = vec![components as u8];
+    for component in 0..components {
+        let table = (component != 0) as u8;
+        scan.extend_from_slice(&[component as u8 + 1, table << 4 | table]);
+    }
+    scan.extend_from_slice(&[0, 63, 0]);
+    write_segment(&mut output, 0xda, &scan);
+    let mut writer = BitWriter {
+        output,
+        buffer: 0,
+        count: 0,
+    };
+    for_each_symbol(&blocks, |table, symbol, bits, count| {
+        let (code, length) = tables[table].codes[symbol as usize];
+        writer.write(code as u32, length as u32);
+        writer.write(bits as u32, count);
+    });
+    writer.flush();
+    let mut output = writer.output;
+    output.extend_from_slice(&[0xff, 0xd9]);
+    Ok(output)
+}
+
+// the table, symbol and extra bits of each block in zigzag order
+fn for_each_symbol(blocks: &[(usize, [i16; 64])], mut emit: impl FnMut(usize, u8, u16, u32)) {
+    let mut predictions = [0i32; 3];
+    for (component, block) in blocks {
+        let table = (*component != 0) as usize * 2;
+        let difference = block[0] as i32 - predictions[*component];
+        predictions[*component] = block[0] as i32;
+        let (size, bits) = magnitude(difference);
+        emit(table, size as u8, bits, size);
+        let mut run = 0;
+        for coefficient in &block[1..] {
+            if *coefficient == 0 {
+                run += 1;
+                continue;
+            }
+            while run > 15 {
+                emit(table + 1, 0xf0, 0, 0);
+                run -= 16;
+            }
+            let (size, bits) = magnitude(*coefficient as i32);
+            emit(table + 1, (run << 4 | size) as u8, bits, size);
+            run = 0;
+        }
+        if run > 0 {
+            emit(table + 1, 0, 0, 0);
+        }
+    }
+}
+
+// size category and extra bits of a coefficient, negative values are stored one less in ones complement
+fn magnitude(value: i32) -> (u32, u16) {
+    let size = 32 - value.unsigned_abs().leading_zeros();
+    let bits = if value < 0 {
+        value - 1
+    } else {
+        value
+    };
+    (size, (bits & ((1 << size) - 1)) as u16)
+}
+
+fn write_segment(output: &mut Vec<u8>, marker: u8, data: &[u8]) {
+    output.extend_from_slice(&[0xff, marker]);
+    output.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+    output.extend_from_slice(data);
+}
+
+// cosines[u][x] of the orthonormal 8 point DCT
+fn cosines() -> [[f32; 8]; 8] {
+    let mut cosines = [[0f32; 8]; 8];
+    for (u, row) in cosines.iter_mut().enumerate() {
+        let scale = if u == 0 {
+            (0.125f32).sqrt()
+        } else {
+            0.5
+        };
+        for (x, cosine) in row.iter_mut().enumerate() {
+            *cosine = scale * ((2 * x + 1) as f32 * u as f32 * PI / 16.0).cos();
+        }
+    }
+    cosines
+}
+
+fn forward_dct(plane: &[f32], start: usize, stride: usize, cosines: &[[f32; 8]; 8]) -> [f32; 64] {
+    let mut rows = [0f32; 64];
+    for y in 0..8 {
+        let samples = &plane[start + y * stride..start + y * stride + 8];
+        for u in 0..8 {
+            rows[y * 8 + u] = samples.iter().zip(&cosines[u]).map(|(sample, cosine)| sample * cosine).sum();
+        }
+    }
+    let mut coefficients = [0f32; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            coefficients[v * 8 + u] = (0..8).map(|y| rows[y * 8 + u] * cosines[v][y]).sum();
+        }
+    }
+    coefficients
+}
+
+fn inverse_dct(coefficients: &[f32; 64], output: &mut [u8], stride: usize, cosines: &[[f32; 8]; 8]) {
+    let mut rows = [0f32; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            rows[v * 8 + x] = (0..8).map(|u| coefficients[v * 8 + u] * cosines[u][x]).sum();
+        }
+    }
+    for y in 0..8 {
+        for x in 0..8 {
+            let sample = (0..8).map(|v| rows[v * 8 + x] * cosines[v][y]).sum::<f32>() + 128.0;
+            output[y * stride + x] = sample.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+struct HuffmanEncoder {
+    counts: [u8; 16],
+    values: Vec<u8>,
+    codes: Vec<(u16, u8)>,
+}
+
+impl HuffmanEncoder {
+    // symbol 256 reserves the code of all ones, which JPEG does not allow
+    fn new(frequencies: &[u32; 257]) -> HuffmanEncoder {
+        let mut frequencies = *frequencies;
+        frequencies[256] = 1;
+        let mut lengths = deflate::code_lengths(&frequencies, 16);
+        let longest = lengths.iter().enumerate().max_by_key(|(symbol, length)| (**length, *symbol)).map(|(symbol, _)| symbol).unwrap();
+        lengths.swap(longest, 256);
+        let mut counts = [0u8; 16];
+        let mut values = Vec::new();
+        let mut codes = vec![(0, 0); 256];
+        let mut code = 0u16;
+        for length in 1..=16 {
+            for (symbol, _) in lengths[..256].iter().enumerate().filter(|(_, symbol_length)| **symbol_length == length) {
+                counts[length as usize - 1] += 1;
+                values.push(symbol as u8);
+                codes[symbol] = (code, length);
+                code += 1;
+            }
+            code <<= 1;
+        }
+        HuffmanEncoder {
+            counts,
+            values,
+            codes,
+        }
+    }
+}
+
+// writes the most significant bit first and stuffs a zero byte after each 0xff
+struct BitWriter {
+    output: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, bits: u32, count: u32) {
+        self.buffer = (self.buffer << count) | bits;
+        self.count += count;
+        while self.count >= 8 {
+            let byte = (self.buffer >> (self.count - 8)) as u8;
+            self.output.push(byte);
+            if byte == 0xff {
+                self.output.push(0);
+            }
+            self.count -= 8;
+        }
+        self.buffer &= (1 << self.count) - 1;
+    }
+
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.write((1 << (8 - self.count)) - 1, 8 - self.count);
+        }
+    }
+}
+
+pub fn decode(data: &[u8]) -> Result<JpegImage, JpegError> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return Err(JpegError::InvalidSignature);
+    }
+    let mut decoder = Decoder {
+        quantization: [None; 4],
+        dc_tables: Default::default(),
+        ac_tables: Default::default(),
+        frame: None,
+        restart_interval: 0,
+        adobe_transform: None,
+        orientation: None,
+        scans: 0,
+    };
+    let mut position = 2;
+    loop {
+        // markers may be padded with any number of 0xff bytes, truncated files end where their data does
+        let Some(&byte) = data.get(position) else {
+            if decoder.scans == 0 {
+                return Err(JpegError::UnexpectedEnd);
+            }
+            break;
+        };
+        if byte != 0xff {
+            return Err(JpegError::InvalidMarker);
+        }
+        while data.get(position) == Some(&0xff) {
+            position += 1;
+        }
+        let marker = *data.get(position).ok_or(JpegError::UnexpectedEnd)?;
+        position += 1;
+        match marker {
+            0xd9 => break,
+            0x01 | 0xd0..=0xd7 => continue,
+            0x00 | 0xd8 => return Err(JpegError::InvalidMarker),
+            _ => {}
+        }
+        let length =
+            data.get(position..position + 2).map(|length| u16::from_be_bytes([length[0], length[1]]) as usize).ok_or(JpegError::UnexpectedEnd)?;
+        let segment = data.get(position + 2..position + length).ok_or(if length < 2 {
+            JpegError::InvalidSegment
+        } else {
+            JpegError::UnexpectedEnd
+        })?;
+        position += length;
+        match marker {
+            0xc0..=0xc2 => decoder.read_frame(segment, marker == 0xc2)?,
+            0xc4 => decoder.read_huffman_tables(segment)?,
+            0xc3 | 0xc5..=0xcf => return Err(JpegError::UnsupportedFormat),
+            0xda => {
+                let scan = decoder.read_scan_header(segment)?;
+                position += decoder.decode_scan(&scan, &data[position..])?;
+                decoder.scans += 1;
+            }
+            0xdb => decoder.read_quantization_tables(segment)?,
+            0xdd => {
+                if segment.len() != 2 {
+                    return Err(JpegError::InvalidSegment);
+                }
+                decoder.restart_interval = u16::from_be_bytes([segment[0], segment[1]]) as usize;
+            }
+            0xe1 if decoder.orientation.is_none() => decoder.orientation = exif_orientation(segment),
+            0xee if segment.len() >= 12 && segment.starts_with(b"Adobe") => decoder.adobe_transform = Some(segment[11]),
+            _ => {}
+        }
+    }
+    if decoder.scans == 0 {
+        return Err(JpegError::InvalidMarker);
+    }
+    let bitmap = decoder.render()?;
+    let orientation = decoder.orientation.unwrap_or(Orientation::Up);
+    Ok(JpegImage {
+        bitmap: orient(&bitmap, orientation)?,
+        orientation,
+    })
+}
+
+// the orientation tag of the first image file directory
+fn exif_orientation(segment: &[u8]) -> Option<Orientation> {
+    let tiff = segment.strip_prefix(b"Exif\0\0")?;
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let read_u16 = |offset: usize| {
+        let bytes = tiff.get(offset..offset + 2)?;
+        Some(match big_endian {
+            true => u16::from_be_bytes([bytes[0], bytes[1]]),
+            false => u16::from_le_bytes([bytes[0], bytes[1]]),
+        })
+    };
+    let read_u32 = |offset: usize| {
+        Some(((read_u16(offset)? as u32) << 16 | read_u16(offset + 2)? as u32, (read_u16(offset + 2)? as u32) << 16 | read_u16(offset)? as u32))
+    };
+    if read_u16(2)? != 42 {
+        return None;
+    }
+    let (big_endian_offset, little_endian_offset) = read_u32(4)?;
+    let directory = match big_endian {
+        true => big_endian_offset,
+        false => little_endian_offset,
+    } as usize;
+    for entry in 0..read_u16(directory)? as usize {
+        let offset = directory + 2 + entry * 12;
+        // a short value is stored at the start of the value field
+        if read_u16(offset)? == 0x0112 && read_u16(offset + 2)? == 3 {
+            return Orientation::from_exif(read_u16(offset + 8)?);
+        }
+    }
+    None
+}
+
+fn orient(bitmap: &Bitmap, orientation: Orientation) -> Result<Bitmap, JpegError> {
+    if orientation == Orientation::Up {
+        return Ok(bitmap.clone());
+    }
+    let (width, height) = (bitmap.width(), bitmap.height());
+    let (oriented_width, oriented_height) = match orientation.swaps_dimensions() {
+        true => (height, width),
+        false => (width, height),
+    };
+    let bytes_per_pixel = bitmap.bits_per_pixel() / 8;
+    let bytes_per_row =
+        oriented_width.checked_mul(bytes_per_pixel).and_then(|len| len.checked_add(15)).map(|len| len / 16 * 16).ok_or(JpegError::InvalidSize)?;
+    let mut oriented = Bitmap::with_layout(oriented_width, oriented_height, bytes_per_row, bitmap.color_model(), *bitmap.layout())
+        .map_err(|_| JpegError::InvalidSize)?;
+    for y in 0..oriented_height {
+        let row = oriented.row_mut(y).unwrap();
+        for (x, pixel) in row.chunks_exact_mut(bytes_per_pixel).enumerate() {
+            let (source_x, source_y) = orientation.source(x, y, width, height);
+            let start = source_x * bytes_per_pixel;
+            pixel.copy_from_slice(&bitmap.row(source_y).unwrap()[start..start + bytes_per_pixel]);
+        }
+    }
+    Ok(oriented)
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quantization: usize,
+    // blocks per row and rows of blocks covering whole MCUs
+    stride: usize,
+    rows: usize,
+    // blocks covering the component's samples, used by scans of a single component
+    width_blocks: usize,
+    height_blocks: usize,
+    width: usize,
+    height: usize,
+    // allocated by the first scan of the component
+    coefficients: Vec<[i16; 64]>,
+}
+
+struct Frame {
+    width: usize,
+    height: usize,
+    progressive: bool,
+    h_max: usize,
+    v_max: usize,
+    mcus_x: usize,
+    mcus_y: usize,
+    components: Vec<Component>,
+}
+
+struct ScanComponent {
+    index: usize,
+    dc_table: usize,
+    ac_table: usize,
+}
+
+struct Scan {
+    components: Vec<ScanComponent>,
+    start: usize,
+    end: usize,
+    high: u32,
+    low: u32,
+}
+
+struct Decoder {
+    quantization: [Option<[u16; 64]>; 4],
+    dc_tables: [Option<HuffmanDecoder>; 4],
+    ac_tables: [Option<HuffmanDecoder>; 4],
+    frame: Option<Frame>,
+    restart_interval: usize,
+    adobe_transform: Option<u8>,
+    orientation: Option<Orientation>,
+    scans: usize,
+}
+
+impl Decoder {
+    fn read_frame(&mut self, segment: &[u8], progressive: bool) -> Result<(), JpegError> {
+        if self.frame.is_some() {
+            return Err(JpegError::InvalidMarker);
+        }
+        if segment.len() < 6 {
+            return Err(JpegError::InvalidHeader);
+        }
+        let height = u16::from_be_bytes([segment[1], segment[2]]) as usize;
+        let width = u16::from_be_bytes([segment[3], segment[4]]) as usize;
+        let count = segment[5] as usize;
+        // 12 bit samples and heights defined by a DNL marker are not supported
+        if segment[0] != 8 || height == 0 || !matches!(count, 1 | 3 | 4) {
+            return Err(JpegError::UnsupportedFormat);
+        }
+        if width == 0 || width * height > MAX_PIXELS {
+            return Err(JpegError::InvalidSize);
+        }
+        if segment.len() != 6 + count * 3 {
+            return Err(JpegError::InvalidHeader);
+        }
+        let mut components = Vec::with_capacity(count);
+        for specification in segment[6..].chunks_exact(3) {
+            let (h, v, quantization) = ((specification[1] >> 4) as usize, (specification[1] & 15) as usize, specification[2] as usize);
+            if !(1..=4).contains(&h)
+                || !(1..=4).contains(&v)
+                || quantization > 3
+                || components.iter().any(|component: &Component| component.id == specification[0])
+            {
+                return Err(JpegError::InvalidHeader);
+            }
+            components.push(Component {
+                id: specification[0],
+                h,
+                v,
+                quantization,
+                stride: 0,
+                rows: 0,
+                width_blocks: 0,
+                height_blocks: 0,
+                width: 0,
+                height: 0,
+                coefficients: Vec::new(),
+            });
+        }
+        let h_max = components.iter().map(|component| component.h).max().unwrap();
+        let v_max = components.iter().map(|component| component.v).max().unwrap();
+        let (mcus_x, mcus_y) = (width.div_ceil(8 * h_max), height.div_ceil(8 * v_max));
+        for component in components.iter_mut() {
+            component.stride = mcus_x * component.h;
+            component.rows = mcus_y * component.v;
+            component.width = (width * component.h).div_ceil(h_max);
+            component.height = (height * component.v).div_ceil(v_max);
+            component.width_blocks = component.width.div_ceil(8);
+            component.height_blocks = component.height.div_ceil(8);
+        }
+        self.frame = Some(Frame {
+            width,
+            height,
+            progressive,
+            h_max,
+            v_max,
+            mcus_x,
+            mcus_y,
+            components,
+        });
+        Ok(())
+    }
+
+    fn read_huffman_tables(&mut self, mut segment: &[u8]) -> Result<(), JpegError> {
+        while !segment.is_empty() {
+            if segment.len() < 17 {
+                return Err(JpegError::InvalidSegment);
+            }
+            let (class, index) = (segment[0] >> 4, (segment[0] & 15) as usize);
+            let mut counts = [0u8; 16];
+            counts.copy_from_slice(&segment[1..17]);
+            let total = counts.iter().map(|count| *count as usize).sum::<usize>();
+            if class > 1 || index > 3 || total > 256 || segment.len() < 17 + total {
+                return Err(JpegError::InvalidSegment);
+            }
+            let table = HuffmanDecoder::new(&counts, &segment[17..17 + total]).ok_or(JpegError::InvalidSegment)?;
+            match class {
+                0 => self.dc_tables[index] = Some(table),
+                _ => self.ac_tables[index] = Some(table),
+            }
+            segment = &segment[17 + total..];
+        }
+        Ok(())
+    }
+
+    fn read_quantization_tables(&mut self, mut segment: &[u8]) -> Result<(), JpegError> {
+        while !segment.is_empty() {
+            let (precision, index) = (segment[0] >> 4, (segment[0] & 15) as usize);
+            let size = if precision == 0 {
+                64
+            } else {
+                128
+            };
+            if precision > 1 || index > 3 || segment.len() < 1 + size {
+                return Err(JpegError::InvalidSegment);
+            }
+            let mut table = [0u16; 64];
+            for (position, natural) in ZIGZAG.iter().enumerate() {
+                table[*natural] = match precision {
+                    0 => segment[1 + position] as u16,
+                    _ => u16::from_be_bytes([segment[1 + position * 2], segment[2 + position * 2]]),
+                };
+            }
+            self.quantization[index] = Some(table);
+            segment = &segment[1 + size..];
+        }
+        Ok(())
+    }
+
+    fn read_scan_header(&self, segment: &[u8]) -> Result<Scan, JpegError> {
+        let frame = self.frame.as_ref().ok_or(JpegError::InvalidMarker)?;
+        let count = *segment.first().ok_or(JpegError::InvalidHeader)? as usize;
+        if !(1..=4).contains(&count) || segment.len() != 4 + count * 2 {
+            return Err(JpegError::InvalidHeader);
+        }
+        let mut components = Vec::with_capacity(count);
+        for specification in segment[1..1 + count * 2].chunks_exact(2) {
+            let index = frame.components.iter().position(|component| component.id == specification[0]).ok_or(JpegError::InvalidHeader)?;
+            let (dc_table, ac_table) = ((specification[1] >> 4) as usize, (specification[1] & 15) as usize);
+            if dc_table > 3 || ac_table > 3 || components.iter().any(|component: &ScanComponent| component.index == index) {
+                return Err(JpegError::InvalidHeader);
+            }
+            components.push(ScanComponent {
+                index,
+                dc_table,
+                ac_table,
+            });
+        }
+        let parameters = &segment[1 + count * 2..];
+        let scan = Scan {
+            components,
+            start: parameters[0] as usize,
+            end: parameters[1] as usize,
+            high: (parameters[2] >> 4) as u32,
+            low: (parameters[2] & 15) as u32,
+        };
+        let valid = if frame.progressive {
+            // dc scans may interleave components, ac scans code a single one
+            (scan.start == 0 && scan.end == 0 || scan.start > 0 && scan.start <= scan.end && scan.end < 64 && count == 1)
+                && scan.low < 14
+                && scan.high < 14
+        } else {
+            scan.start == 0 && scan.end == 63 && scan.high == 0 && scan.low == 0
+        };
+        // interleaved scans hold at most 10 blocks per MCU
+        let blocks = scan.components.iter().map(|component| frame.components[component.index].h * frame.components[component.index].v).sum::<usize>();
+        if !valid || (count > 1 && blocks > 10) {
+            return Err(JpegError::InvalidHeader);
+        }
+        for component in &scan.components {
+            let needs_dc = scan.start == 0 && scan.high == 0;
+            if (needs_dc && self.dc_tables[component.dc_table].is_none()) || (scan.end > 0 && self.ac_tables[component.ac_table].is_none()) {
+                return Err(JpegError::MissingTable);
+            }
+        }
+        Ok(scan)
+    }
+
+    // returns the length of the entropy coded data
+    fn decode_scan(&mut self, scan: &Scan, data: &[u8]) -> Result<usize, JpegError> {
+        let frame = self.frame.as_mut().ok_or(JpegError::InvalidMarker)?;
+        // every coded block takes at least one bit, so a frame far larger than the data after it is rejected
+        // before its coefficients are allocated
+        for scan_component in &scan.components {
+            let component = &mut frame.components[scan_component.index];
+            if component.coefficients.is_empty() {
+                if component.width_blocks * component.height_blocks > data.len().saturating_mul(8) {
+                    return Err(JpegError::UnexpectedEnd);
+                }
+                component.coefficients = vec![[0; 64]; component.stride * component.rows];
+            }
+        }
+        let single = scan.components.len() == 1;
+        let (mcus_x, mcus_y) = match single {
+            true => {
+                let component = &frame.components[scan.components[0].index];
+                (component.width_blocks, component.height_blocks)
+            }
+            false => (frame.mcus_x, frame.mcus_y),
+        };
+        let mut reader = BitReader {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+            marker: false,
+        };
+        let mut predictions = [0i32; 4];
+        let mut end_of_band_run = 0u32;
+        for mcu in 0..mcus_x * mcus_y {
+            if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval == 0 {
+                reader.restart();
+                predictions = [0; 4];
+                end_of_band_run = 0;
+            }
+            let (mcu_x, mcu_y) = (mcu % mcus_x, mcu / mcus_x);
+            for (scan_index, scan_component) in scan.components.iter().enumerate() {
+                let component = &mut frame.components[scan_component.index];
+                let (h, v) = match single {
+                    true => (1, 1),
+                    false => (component.h, component.v),
+                };
+                let dc_table = self.dc_tables[scan_component.dc_table].as_ref();
+                let ac_table = self.ac_tables[scan_component.ac_table].as_ref();
+                for block_y in 0..v {
+                    for block_x in 0..h {
+                        let index = (mcu_y * v + block_y) * component.stride + mcu_x * h + block_x;
+                        let block = &mut component.coefficients[index];
+                        if !frame.progressive {
+                            decode_dc_first(&mut reader, block, dc_table.unwrap(), &mut predictions[scan_index], 0)?;
+                            decode_ac_first(&mut reader, block, ac_table.unwrap(), 1, 63, 0, &mut end_of_band_run)?;
+                        } else if scan.start == 0 && scan.high == 0 {
+                            decode_dc_first(&mut reader, block, dc_table.unwrap(), &mut predictions[scan_index], scan.low)?;
+                        } else if scan.start == 0 {
+                            if reader.bits(1) == 1 {
+                                block[0] |= 1 << scan.low;
+                            }
+                        } else if scan.high == 0 {
+                            decode_ac_first(&mut reader, block, ac_table.unwrap(), scan.start, scan.end, scan.low, &mut end_of_band_run)?;
+                        } else {
+                            decode_ac_refine(&mut reader, block, ac_table.unwrap(), scan.start, scan.end, scan.low, &mut end_of_band_run)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(reader.end())
+    }
+
+    fn render(&self) -> Result<Bitmap, JpegError> {
+        let frame = self.frame.as_ref().ok_or(JpegError::InvalidMarker)?;
+        let cosines = cosines();
+        let mut planes = Vec::with_capacity(frame.components.len());
+        for component in &frame.components {
+            let quantization = self.quantization[component.quantization].as_ref().ok_or(JpegError::MissingTable)?;
+            // a component no scan has coded
+            if component.coefficients.is_empty() {
+                return Err(JpegError::UnexpectedEnd);
+            }
+            let plane_width = component.stride * 8;
+            let mut plane = vec![0u8; plane_width * component.rows * 8];
+            for (index, block) in component.coefficients.iter().enumerate() {
+                let (block_x, block_y) = (index % component.stride, index / component.stride);
+                if block_x >= component.width_blocks || block_y >= component.height_blocks {
+                    continue;
+                }
+                let mut coefficients = [0f32; 64];
+                for (coefficient, (value, step)) in coefficients.iter_mut().zip(block.iter().zip(quantization)) {
+                    *coefficient = *value as f32 * *step as f32;
+                }
+                inverse_dct(&coefficients, &mut plane[block_y * 8 * plane_width + block_x * 8..], plane_width, &cosines);
+            }
+            planes.push(plane);
+        }
+
+        let components = frame.components.len();
+        let format = match components {
+            1 => PixelFormat::Gray8,
+            3 => PixelFormat::RGBX8888,
+            _ => PixelFormat::CMYK8,
+        };
+        let mut bitmap = Bitmap::new(frame.width, frame.height, format).ok_or(JpegError::InvalidSize)?;
+        let ids = frame.components.iter().map(|component| component.id).collect::<Vec<_>>();
+        let ycbcr = match (components, self.adobe_transform) {
+            (1, _) => false,
+            (3, None) => ids != b"RGB",
+            (3, Some(transform)) => transform != 0,
+            (_, transform) => transform == Some(2),
+        };
+        // Adobe stores CMYK inverted
+        let inverted = components == 4 && self.adobe_transform.is_some();
+        let mut rows = vec![vec![0u8; frame.width]; components];
+        for y in 0..frame.height {
+            for ((row, plane), component) in rows.iter_mut().zip(&planes).zip(&frame.components) {
+                upsample_row(plane, component, frame, y, row);
+            }
+            let output = bitmap.row_mut(y).unwrap();
+            for x in 0..frame.width {
+                let pixel = &mut output[x * format.bytes_per_pixel()..(x + 1) * format.bytes_per_pixel()];
+                if components == 1 {
+                    pixel[0] = rows[0][x];
+                    continue;
+                }
+                let (mut first, mut second, mut third) = (rows[0][x], rows[1][x], rows[2][x]);
+                if ycbcr {
+                    let (luma, blue, red) = (first as f32, second as f32 - 128.0, third as f32 - 128.0);
+                    let clamp = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+                    first = clamp(luma + 1.402 * red);
+                    second = clamp(luma - 0.344136 * blue - 0.714136 * red);
+                    third = clamp(luma + 1.772 * blue);
+                }
+                if components == 3 {
+                    pixel.copy_from_slice(&[first, second, third, 0xff]);
+                    continue;
+                }
+                // YCCK is converted to RGB and the complement taken as CMY
+                if ycbcr {
+                    (first, second, third) = (255 - first, 255 - second, 255 - third);
+                }
+                let mut cmyk = [first, second, third, rows[3][x]];
+                if inverted {
+                    cmyk = cmyk.map(|value| 255 - value);
+                }
+                pixel.copy_from_slice(&cmyk);
+            }
+        }
+        Ok(bitmap)
+    }
+}
+
+// bilinear upsampling with samples centered on the pixels they cover
+fn upsample_row(plane: &[u8], component: &Component, frame: &Frame, y: usize, output: &mut [u8]) {
+    let plane_width = component.stride * 8;
+    if component.h == frame.h_max && component.v == frame.v_max {
+        output.copy_from_slice(&plane[y * plane_width..y * plane_width + frame.width]);
+        return;
+    }
+    let position = |target: usize, factor: usize, max: usize, size: usize| {
+        let position = ((target as f32 + 0.5) * factor as f32 / max as f32 - 0.5).clamp(0.0, (size - 1) as f32);
+        let first = position as usize;
+        (first, (first + 1).min(size - 1), position - first as f32)
+    };
+    let (top, bottom, vertical) = position(y, component.v, frame.v_max, component.height);
+    let (top, bottom) = (&plane[top * plane_width..], &plane[bottom * plane_width..]);
+    for (x, sample) in output.iter_mut().enumerate() {
+        let (left, right, horizontal) = position(x, component.h, frame.h_max, component.width);
+        let upper = top[left] as f32 + (top[right] as f32 - top[left] as f32) * horizontal;
+        let lower = bottom[left] as f32 + (bottom[right] as f32 - bottom[left] as f32) * horizontal;
+        *sample = (upper + (lower - upper) * vertical).round() as u8;
+    }
+}
+
+fn decode_dc_first(reader: &mut BitReader, block: &mut [i16; 64], table: &HuffmanDecoder, prediction: &mut i32, low: u32) -> Result<(), JpegError> {
+    let size = table.decode(reader)?;
+    if size > 11 {
+        return Err(JpegError::InvalidData);
+    }
+    *prediction = prediction.wrapping_add(reader.receive_extend(size as u32));
+    block[0] = (*prediction << low) as i16;
+    Ok(())
+}
+
+fn decode_ac_first(
+    reader: &mut BitReader,
+    block: &mut [i16; 64],
+    table: &HuffmanDecoder,
+    start: usize,
+    end: usize,
+    low: u32,
+    end_of_band_run: &mut u32,
+) -> Result<(), JpegError> {
+    if *end_of_band_run > 0 {
+        *end_of_band_run -= 1;
+        return Ok(());
+    }
+    let mut index = start;
+    while index <= end {
+        let symbol = table.decode(reader)?;
+        let (run, size) = ((symbol >> 4) as usize, (symbol & 15) as u32);
+        if size == 0 {
+            if run < 15 {
+                *end_of_band_run = (1 << run) - 1 + reader.bits(run as u32);
+                break;
+            }
+            index += 16;
+            continue;
+        }
+        index += run;
+        if index > end {
+            return Err(JpegError::InvalidData);
+        }
+        block[ZIGZAG[index]] = (reader.receive_extend(size) << low) as i16;
+        index += 1;
+    }
+    Ok(())
+}
+
+// adds a bit of precision to coefficients that are already nonzero and places the ones that become nonzero
+fn decode_ac_refine(
+    reader: &mut BitReader,
+    block: &mut [i16; 64],
+    table: &HuffmanDecoder,
+    start: usize,
+    end: usize,
+    low: u32,
+    end_of_band_run: &mut u32,
+) -> Result<(), JpegError> {
+    let bit = 1i16 << low;
+    let refine = |reader: &mut BitReader, coefficient: &mut i16| {
+        if reader.bits(1) == 1 && *coefficient & bit == 0 {
+            *coefficient += if *coefficient > 0 {
+                bit
+            } else {
+                -bit
+            };
+        }
+    };
+    let mut index = start;
+    if *end_of_band_run == 0 {
+        while index <= end {
+            let symbol = table.decode(reader)?;
+            let (mut run, size) = ((symbol >> 4) as u32, symbol & 15);
+            let mut value = 0;
+            if size == 0 {
+                if run < 15 {
+                    *end_of_band_run = (1 << run) + reader.bits(run);
+                    break;
+                }
+            } else {
+                if size != 1 {
+                    return Err(JpegError::InvalidData);
+                }
+                value = if reader.bits(1) == 1 {
+                    bit
+                } else {
+                    -bit
+                };
+            }
+            // skips run zero coefficients, refining the nonzero ones on the way
+            while index <= end {
+                let coefficient = &mut block[ZIGZAG[index]];
+                index += 1;
+                if *coefficient != 0 {
+                    refine(reader, coefficient);
+                } else if run == 0 {
+                    *coefficient = value;
+                    break;
+                } else {
+                    run -= 1;
+                }
+            }
+        }
+    }
+    if *end_of_band_run > 0 {
+        while index <= end {
+            let coefficient = &mut block[ZIGZAG[index]];
+            if *coefficient != 0 {
+                refine(reader, coefficient);
+            }
+            index += 1;
+        }
+        *end_of_band_run -= 1;
+    }
+    Ok(())
+}
+
+// reads the most significant bit first, removing stuffed zero bytes and stopping at markers
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u64,
+    count: u32,
+    marker: bool,
+}
+
+impl BitReader<'_> {
+    fn fill(&mut self) {
+        while self.count <= 56 {
+            let mut byte = 0;
+            if !self.marker && self.position < self.data.len() {
+                byte = self.data[self.position];
+                if byte != 0xff {
+                    self.position += 1;
+                } else if self.data.get(self.position + 1) == Some(&0) {
+                    self.position += 2;
+                } else {
+                    self.marker = true;
+                    byte = 0;
+                }
+            }
+            self.buffer |= (byte as u64) << (56 - self.count);
+            self.count += 8;
+        }
+    }
+
+    fn peek(&mut self, count: u32) -> u32 {
+        if self.count < count {
+            self.fill();
+        }
+        (self.buffer >> (64 - count)) as u32
+    }
+
+    fn consume(&mut self, count: u32) {
+        self.buffer <<= count;
+        self.count -= count;
+    }
+
+    fn bits(&mut self, count: u32) -> u32 {
+        if count == 0 {
+            return 0;
+        }
+        let bits = self.peek(count);
+        self.consume(count);
+        bits
+    }
+
+    fn receive_extend(&mut self, size: u32) -> i32 {
+        let value = self.bits(size) as i32;
+        if size > 0 && value < 1 << (size - 1) {
+            value - (1 << size) + 1
+        } else {
+            value
+        }
+    }
+
+    // skips to the data after the next restart marker
+    fn restart(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+        self.marker = false;
+        while self.position + 1 < self.data.len() {
+            if self.data[self.position] == 0xff {
+                match self.data[self.position + 1] {
+                    0xd0..=0xd7 => {
+                        self.position += 2;
+                        return;
+                    }
+                    0x00 | 0xff => {}
+                    _ => {
+                        self.marker = true;
+                        return;
+                    }
+                }
+            }
+            self.position += 1;
+        }
+    }
+
+    // position of the marker that ends the entropy coded data
+    fn end(&self) -> usize {
+        let mut position = self.position;
+        while position + 1 < self.data.len() {
+            if self.data[position] == 0xff && !matches!(self.data[position + 1], 0x00 | 0xd0..=0xd7 | 0xff) {
+                return position;
+            }
+            position += 1;
+        }
+        self.data.len()
+    }
+}
+
+// codes up to 9 bits are looked up directly, longer ones are compared against the largest code of each length
+struct HuffmanDecoder {
+    lookup: Vec<u16>,
+    max_codes: [i32; 17],
+    offsets: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl HuffmanDecoder {
+    fn new(counts: &[u8; 16], values: &[u8]) -> Option<HuffmanDecoder> {
+        let mut lookup = vec![0u16; 512];
+        let mut max_codes = [-1i32; 17];
+        let mut offsets = [0i32; 17];
+        let (mut code, mut index) = (0u32, 0usize);
+        for length in 1..=16 {
+            let count = counts[length - 1] as usize;
+            offsets[length] = index as i32 - code as i32;
+            // over subscribed lengths would also run past the lookup table
+            if code as usize + count > 1 << length {
+                return None;
+            }
+            for _ in 0..count {
+                if length <= 9 {
+                    let first = (code << (9 - length)) as usize;
+                    lookup[first..first + (1 << (9 - length))].fill((length as u16) << 8 | values[index] as u16);
+                }
+                code += 1;
+                index += 1;
+            }
+            if count > 0 {
+                max_codes[length] = code as i32 - 1;
+            }
+            code <<= 1;
+        }
+        Some(HuffmanDecoder {
+            lookup,
+            max_codes,
+            offsets,
+            values: values.to_vec(),
+        })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u8, JpegError> {
+        let bits = reader.peek(16);
+        let entry = self.lookup[(bits >> 7) as usize];
+        if entry != 0 {
+            reader.consume((entry >> 8) as u32);
+            return Ok(entry as u8);
+        }
+        for length in 10..=16 {
+            let code = (bits >> (16 - length)) as i32;
+            if code <= self.max_codes[length] {
+                reader.consume(length as u32);
+                return self.values.get((code + self.offsets[length]) as usize).copied().ok_or(JpegError::InvalidData);
+            }
+        }
+        Err(JpegError::InvalidData)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: usize, height: usize) -> Bitmap {
+        let mut bitmap = Bitmap::new(width, height, PixelFormat::RGBX8888).unwrap();
+        for y in 0..height {
+            let row: Vec<u8> = (0..width).flat_map(|x| [(x * 255 / width) as u8, (y * 255 / height) as u8, 128, 255]).collect();
+            bitmap.row_mut(y).unwrap().copy_from_slice(&row);
+        }
+        bitmap
+    }
+
+    #[test]
+    fn round_trip() {
+        let original = gradient(37, 21);
+        for subsampling in [ChromaSubsampling::Yuv444, ChromaSubsampling::Yuv422, ChromaSubsampling::Yuv420] {
+            let decoded = decode(&encode(&original.as_view(), 95, subsampling).unwrap()).unwrap();
+            assert_eq!(decoded.orientation(), Orientation::Up);
+            let bitmap = decoded.bitmap();
+            assert_eq!((bitmap.width(), bitmap.height(), bitmap.color_model()), (37, 21, Some(CGColorSpaceModel::RGB)));
+            let error = original.rows().zip(bitmap.rows()).flat_map(|(a, b)| a.iter().zip(b)).map(|(a, b)| (*a as i32 - *b as i32).abs()).max();
+            assert!(error.unwrap() <= 12, "{:?} {:?}", subsampling, error);
+        }
+    }
+
+    #[test]
+    fn rejects_over_subscribed_huffman_tables() {
+        // three codes of length 1
+        let mut data = vec![0xff, 0xd8, 0xff, 0xc4, 0x00, 0x16, 0x00, 3];
+        data.extend_from_slice(&[0; 15]);
+        data.extend_from_slice(&[0, 1, 2]);
+        assert_eq!(decode(&data).err(), Some(JpegError::InvalidSegment));
+        assert!(HuffmanDecoder::new(&[2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], &[0, 1]).is_some());
+        assert!(HuffmanDecoder::new(&[1, 1, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], &[0, 1, 2, 3, 4]).is_none());
+        assert!(HuffmanDecoder::new(&[1, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], &[0, 1, 2, 3]).is_none());
+        // long codes are not in the lookup table but are still checked
+        let mut counts = [0; 16];
+        counts[11] = 255;
+        assert!(HuffmanDecoder::new(&counts, &[0; 255]).is_some());
+        counts[0] = 2;
+        assert!(HuffmanDecoder::new(&counts, &[0; 257]).is_none());
+    }
+
+    #[test]
+    fn rejects_frames_larger_than_their_data() {
+        let mut data = encode(&gradient(16, 16).as_view(), 75, ChromaSubsampling::Yuv420).unwrap();
+        let frame = data.windows(2).position(|marker| marker == [0xff, 0xc0]).unwrap();
+        data[frame + 5..frame + 9].copy_from_slice(&[0x10, 0x00, 0xff, 0xff]);
+        assert_eq!(decode(&data).err(), Some(JpegError::UnexpectedEnd));
+    }
+
+    #[test]
+    fn dc_prediction_wraps() {
+        // a single code of length 1 for a difference of 11 bits, followed by the largest difference
+        let table = HuffmanDecoder::new(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], &[11]).unwrap();
+        let data = [0x7f, 0xf0];
+        let mut reader = BitReader {
+            data: &data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+            marker: false,
+        };
+        let (mut block, mut prediction) = ([0; 64], i32::MAX);
+        decode_dc_first(&mut reader, &mut block, &table, &mut prediction, 0).unwrap();
+        assert_eq!(prediction, i32::MIN + 2046);
+    }
+}
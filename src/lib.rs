@@ -2,6 +2,7 @@
 
 #[macro_use]
 extern crate bitflags;
+#[cfg(target_os = "macos")]
 extern crate block;
 #[macro_use]
 extern crate cfg_if;
@@ -22,12 +23,18 @@ extern "C" {}
 
 pub mod affine_transform;
 pub mod base;
+pub mod bitmap;
 pub mod bitmap_context;
 pub mod color;
+pub mod color_conversion;
 pub mod color_conversion_info;
+pub mod color_css;
+pub mod color_model;
 pub mod color_space;
 pub mod context;
+pub mod context_guard;
 pub mod data_provider;
+mod deflate;
 cfg_if!(
     if #[cfg(all(target_os = "macos", feature = "display"))] {
         pub mod direct_display;
@@ -52,12 +59,29 @@ pub mod font;
 pub mod function;
 pub mod geometry;
 pub mod gradient;
+pub mod graphics_context;
+pub mod icc;
 pub mod image;
+pub mod jpeg;
 pub mod layer;
+pub mod named_color_space;
 pub mod path;
+pub mod path_boolean;
+pub mod path_builder;
+pub mod path_hit_test;
+pub mod path_measure;
+pub mod path_segment;
+pub mod path_stroke;
+pub mod path_svg;
 pub mod pattern;
+pub mod pixel_conversion;
+pub mod pixel_format;
+pub mod png;
+pub mod raster_context;
+pub mod recording_context;
 #[cfg(all(target_os = "macos", any(feature = "display", feature = "event")))]
 pub mod remote_operation;
+pub mod resample;
 pub mod shading;
 cfg_if!(
     if #[cfg(all(target_os = "macos", feature = "window"))] {
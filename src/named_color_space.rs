@@ -0,0 +1,333 @@
+use crate::{
+    base::CGFloat,
+    color_model::{rgb_to_xyz_matrix, xy_to_xyz, ColorModel, TransferFunction, D50},
+    color_space::{CGColorSpaceModel, CGColorSpaceNames},
+};
+
+const SRGB_PRIMARIES: [[CGFloat; 2]; 3] = [[0.64, 0.33], [0.30, 0.60], [0.15, 0.06]];
+const P3_PRIMARIES: [[CGFloat; 2]; 3] = [[0.680, 0.320], [0.265, 0.690], [0.150, 0.060]];
+const ITUR_2020_PRIMARIES: [[CGFloat; 2]; 3] = [[0.708, 0.292], [0.170, 0.797], [0.131, 0.046]];
+const ADOBE_RGB_PRIMARIES: [[CGFloat; 2]; 3] = [[0.64, 0.33], [0.21, 0.71], [0.15, 0.06]];
+const ROMM_RGB_PRIMARIES: [[CGFloat; 2]; 3] = [[0.7347, 0.2653], [0.1596, 0.8404], [0.0366, 0.0001]];
+const ACES_AP1_PRIMARIES: [[CGFloat; 2]; 3] = [[0.713, 0.293], [0.165, 0.830], [0.128, 0.044]];
+const GENERIC_RGB_PRIMARIES: [[CGFloat; 2]; 3] = [[0.630, 0.340], [0.295, 0.605], [0.155, 0.077]];
+
+const D50_WHITE: [CGFloat; 2] = [0.3457, 0.3585];
+const D65_WHITE: [CGFloat; 2] = [0.3127, 0.3290];
+const DCI_WHITE: [CGFloat; 2] = [0.314, 0.351];
+const ACES_WHITE: [CGFloat; 2] = [0.32168, 0.33767];
+
+// the BT.709 and BT.2020 camera curve
+const ITUR_709_TRANSFER: TransferFunction = TransferFunction::Parametric {
+    g: 1.0 / 0.45,
+    a: 1.0 / 1.099,
+    b: 0.099 / 1.099,
+    c: 1.0 / 4.5,
+    d: 0.081,
+    e: 0.0,
+    f: 0.0,
+};
+const ROMM_RGB_TRANSFER: TransferFunction = TransferFunction::Parametric {
+    g: 1.8,
+    a: 1.0,
+    b: 0.0,
+    c: 1.0 / 16.0,
+    d: 1.0 / 32.0,
+    e: 0.0,
+    f: 0.0,
+};
+
+const NAMES: [CGColorSpaceNames; 34] = [
+    CGColorSpaceNames::GenericGray,
+    CGColorSpaceNames::GenericRGB,
+    CGColorSpaceNames::GenericCMYK,
+    CGColorSpaceNames::DisplayP3,
+    CGColorSpaceNames::GenericRGBLinear,
+    CGColorSpaceNames::AdobeRGB1998,
+    CGColorSpaceNames::SRGB,
+    CGColorSpaceNames::GenericGrayGamma2_2,
+    CGColorSpaceNames::GenericXYZ,
+    CGColorSpaceNames::GenericLab,
+    CGColorSpaceNames::ACESCGLinear,
+    CGColorSpaceNames::ITUR_709,
+    CGColorSpaceNames::ITUR_709_PQ,
+    CGColorSpaceNames::ITUR_709_HLG,
+    CGColorSpaceNames::ITUR_2020,
+    CGColorSpaceNames::ITUR_2020_sRGBGamma,
+    CGColorSpaceNames::ROMMRGB,
+    CGColorSpaceNames::DCIP3,
+    CGColorSpaceNames::LinearITUR_2020,
+    CGColorSpaceNames::ExtendedITUR_2020,
+    CGColorSpaceNames::ExtendedLinearITUR_2020,
+    CGColorSpaceNames::LinearDisplayP3,
+    CGColorSpaceNames::ExtendedDisplayP3,
+    CGColorSpaceNames::ExtendedLinearDisplayP3,
+    CGColorSpaceNames::ITUR_2100_PQ,
+    CGColorSpaceNames::ITUR_2100_HLG,
+    CGColorSpaceNames::DisplayP3_PQ,
+    CGColorSpaceNames::DisplayP3_HLG,
+    CGColorSpaceNames::ExtendedSRGB,
+    CGColorSpaceNames::LinearSRGB,
+    CGColorSpaceNames::ExtendedLinearSRGB,
+    CGColorSpaceNames::ExtendedGray,
+    CGColorSpaceNames::LinearGray,
+    CGColorSpaceNames::ExtendedLinearGray,
+];
+
+// the colorimetry of a named color space, white points and primaries are CIE xy chromaticities
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NamedColorSpace {
+    name: Option<CGColorSpaceNames>,
+    model: CGColorSpaceModel,
+    primaries: Option<[[CGFloat; 2]; 3]>,
+    white_point: [CGFloat; 2],
+    transfer: TransferFunction,
+    extended_range: bool,
+}
+
+impl From<CGColorSpaceNames> for NamedColorSpace {
+    fn from(name: CGColorSpaceNames) -> NamedColorSpace {
+        NamedColorSpace::new(name)
+    }
+}
+
+impl NamedColorSpace {
+    pub fn new(name: CGColorSpaceNames) -> NamedColorSpace {
+        use CGColorSpaceNames::*;
+
+        let (model, primaries, white_point, transfer, extended_range) = match name {
+            GenericGray => (CGColorSpaceModel::Monochrome, None, D50_WHITE, TransferFunction::Gamma(1.8), false),
+            GenericGrayGamma2_2 => (CGColorSpaceModel::Monochrome, None, D50_WHITE, TransferFunction::Gamma(2.2), false),
+            ExtendedGray => (CGColorSpaceModel::Monochrome, None, D50_WHITE, TransferFunction::Gamma(2.2), true),
+            LinearGray => (CGColorSpaceModel::Monochrome, None, D50_WHITE, TransferFunction::Linear, false),
+            ExtendedLinearGray => (CGColorSpaceModel::Monochrome, None, D50_WHITE, TransferFunction::Linear, true),
+            GenericCMYK => (CGColorSpaceModel::CMYK, None, D65_WHITE, TransferFunction::Linear, false),
+            GenericXYZ => (CGColorSpaceModel::XYZ, None, D50_WHITE, TransferFunction::Linear, false),
+            GenericLab => (CGColorSpaceModel::Lab, None, D50_WHITE, TransferFunction::Linear, false),
+            GenericRGB => (CGColorSpaceModel::RGB, Some(GENERIC_RGB_PRIMARIES), D65_WHITE, TransferFunction::Gamma(1.8), false),
+            GenericRGBLinear => (CGColorSpaceModel::RGB, Some(GENERIC_RGB_PRIMARIES), D65_WHITE, TransferFunction::Linear, false),
+            SRGB => (CGColorSpaceModel::RGB, Some(SRGB_PRIMARIES), D65_WHITE, TransferFunction::SRGB, false),
+            ExtendedSRGB => (CGColorSpaceModel::RGB, Some(SRGB_PRIMARIES), D65_WHITE, TransferFunction::SRGB, true),
+            LinearSRGB => (CGColorSpaceModel::RGB, Some(SRGB_PRIMARIES), D65_WHITE, TransferFunction::Linear, false),
+            ExtendedLinearSRGB => (CGColorSpaceModel::RGB, Some(SRGB_PRIMARIES), D65_WHITE, TransferFunction::Linear, true),
+            ITUR_709 => (CGColorSpaceModel::RGB, Some(SRGB_PRIMARIES), D65_WHITE, ITUR_709_TRANSFER, false),
+            ITUR_709_PQ => (CGColorSpaceModel::RGB, Some(SRGB_PRIMARIES), D65_WHITE, TransferFunction::Pq, false),
+            ITUR_709_HLG => (CGColorSpaceModel::RGB, Some(SRGB_PRIMARIES), D65_WHITE, TransferFunction::Hlg, false),
+            DisplayP3 => (CGColorSpaceModel::RGB, Some(P3_PRIMARIES), D65_WHITE, TransferFunction::SRGB, false),
+            ExtendedDisplayP3 => (CGColorSpaceModel::RGB, Some(P3_PRIMARIES), D65_WHITE, TransferFunction::SRGB, true),
+            LinearDisplayP3 => (CGColorSpaceModel::RGB, Some(P3_PRIMARIES), D65_WHITE, TransferFunction::Linear, false),
+            ExtendedLinearDisplayP3 => (CGColorSpaceModel::RGB, Some(P3_PRIMARIES), D65_WHITE, TransferFunction::Linear, true),
+            DisplayP3_PQ => (CGColorSpaceModel::RGB, Some(P3_PRIMARIES), D65_WHITE, TransferFunction::Pq, false),
+            DisplayP3_HLG => (CGColorSpaceModel::RGB, Some(P3_PRIMARIES), D65_WHITE, TransferFunction::Hlg, false),
+            DCIP3 => (CGColorSpaceModel::RGB, Some(P3_PRIMARIES), DCI_WHITE, TransferFunction::Gamma(2.6), false),
+            ITUR_2020 => (CGColorSpaceModel::RGB, Some(ITUR_2020_PRIMARIES), D65_WHITE, ITUR_709_TRANSFER, false),
+            ITUR_2020_sRGBGamma => (CGColorSpaceModel::RGB, Some(ITUR_2020_PRIMARIES), D65_WHITE, TransferFunction::SRGB, false),
+            ExtendedITUR_2020 => (CGColorSpaceModel::RGB, Some(ITUR_2020_PRIMARIES), D65_WHITE, ITUR_709_TRANSFER, true),
+            LinearITUR_2020 => (CGColorSpaceModel::RGB, Some(ITUR_2020_PRIMARIES), D65_WHITE, TransferFunction::Linear, false),
+            ExtendedLinearITUR_2020 => (CGColorSpaceModel::RGB, Some(ITUR_2020_PRIMARIES), D65_WHITE, TransferFunction::Linear, true),
+            ITUR_2100_PQ => (CGColorSpaceModel::RGB, Some(ITUR_2020_PRIMARIES), D65_WHITE, TransferFunction::Pq, false),
+            ITUR_2100_HLG => (CGColorSpaceModel::RGB, Some(ITUR_2020_PRIMARIES), D65_WHITE, TransferFunction::Hlg, false),
+            AdobeRGB1998 => (CGColorSpaceModel::RGB, Some(ADOBE_RGB_PRIMARIES), D65_WHITE, TransferFunction::Gamma(563.0 / 256.0), false),
+            ROMMRGB => (CGColorSpaceModel::RGB, Some(ROMM_RGB_PRIMARIES), D50_WHITE, ROMM_RGB_TRANSFER, false),
+            ACESCGLinear => (CGColorSpaceModel::RGB, Some(ACES_AP1_PRIMARIES), ACES_WHITE, TransferFunction::Linear, false),
+        };
+        NamedColorSpace {
+            name: Some(name),
+            model,
+            primaries,
+            white_point,
+            transfer,
+            extended_range,
+        }
+    }
+
+    // None for spaces derived from a named one that have no name of their own
+    pub fn name(&self) -> Option<CGColorSpaceNames> {
+        self.name
+    }
+
+    pub fn model(&self) -> CGColorSpaceModel {
+        self.model
+    }
+
+    pub fn number_of_components(&self) -> usize {
+        match self.model {
+            CGColorSpaceModel::Monochrome => 1,
+            CGColorSpaceModel::CMYK => 4,
+            _ => 3,
+        }
+    }
+
+    pub fn primaries(&self) -> Option<[[CGFloat; 2]; 3]> {
+        self.primaries
+    }
+
+    pub fn white_point(&self) -> [CGFloat; 2] {
+        self.white_point
+    }
+
+    pub fn transfer_function(&self) -> TransferFunction {
+        self.transfer
+    }
+
+    pub fn is_linear(&self) -> bool {
+        self.transfer == TransferFunction::Linear
+    }
+
+    pub fn uses_extended_range(&self) -> bool {
+        self.extended_range
+    }
+
+    // extended range RGB spaces and those whose gamut covers more of the xy chromaticity diagram than sRGB
+    pub fn is_wide_gamut_rgb(&self) -> bool {
+        let Some(primaries) = self.primaries else {
+            return false;
+        };
+        let area = |[red, green, blue]: &[[CGFloat; 2]; 3]| {
+            ((red[0] * (green[1] - blue[1]) + green[0] * (blue[1] - red[1]) + blue[0] * (red[1] - green[1])) / 2.0).abs()
+        };
+        self.extended_range || area(&primaries) > area(&SRGB_PRIMARIES) * 1.01
+    }
+
+    pub fn is_pq_based(&self) -> bool {
+        self.transfer == TransferFunction::Pq
+    }
+
+    pub fn is_hlg_based(&self) -> bool {
+        self.transfer == TransferFunction::Hlg
+    }
+
+    pub fn uses_itur_2100_tf(&self) -> bool {
+        self.is_pq_based() || self.is_hlg_based()
+    }
+
+    // the same space with a linear transfer function, named when Core Graphics has a name for it,
+    // None for models without a transfer function
+    pub fn new_linearized(&self) -> Option<NamedColorSpace> {
+        if !matches!(self.model, CGColorSpaceModel::Monochrome | CGColorSpaceModel::RGB) {
+            return None;
+        }
+        let linearized = NamedColorSpace {
+            name: None,
+            transfer: TransferFunction::Linear,
+            ..*self
+        };
+        let name = NAMES.iter().copied().find(|name| {
+            NamedColorSpace {
+                name: None,
+                ..NamedColorSpace::new(*name)
+            } == linearized
+        });
+        Some(NamedColorSpace {
+            name,
+            ..linearized
+        })
+    }
+
+    // the description used by color model conversions
+    pub fn color_model(&self) -> ColorModel {
+        let white_point = xy_to_xyz(&self.white_point);
+        match (self.model, self.primaries) {
+            (CGColorSpaceModel::Monochrome, _) => ColorModel::Monochrome {
+                white_point,
+                transfer: self.transfer,
+                extended_range: self.extended_range,
+            },
+            (CGColorSpaceModel::RGB, Some(primaries)) => ColorModel::RGB {
+                white_point,
+                matrix: rgb_to_xyz_matrix(&primaries, &self.white_point).unwrap(),
+                transfer: [self.transfer; 3],
+                extended_range: self.extended_range,
+            },
+            (CGColorSpaceModel::Lab, _) => ColorModel::new_lab(&D50, None),
+            (CGColorSpaceModel::XYZ, _) => ColorModel::XYZ,
+            _ => ColorModel::CMYK,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pq_round_trip() {
+        let pq = TransferFunction::Pq;
+        assert!((pq.to_linear(1.0) - 100.0).abs() < 1e-9);
+        assert_eq!(pq.to_linear(0.0), 0.0);
+        for step in 0..=100 {
+            let signal = step as CGFloat / 100.0;
+            // black encodes to c1 ^ m2, a little above zero
+            assert!((pq.from_linear(pq.to_linear(signal)) - signal).abs() < 1e-6, "{}", signal);
+            assert_eq!(pq.to_linear(-signal), -pq.to_linear(signal));
+        }
+    }
+
+    #[test]
+    fn hlg_round_trip() {
+        let hlg = TransferFunction::Hlg;
+        assert!((hlg.to_linear(0.5) - 1.0).abs() < 1e-9);
+        assert!((hlg.to_linear(1.0) - 12.0).abs() < 1e-5);
+        for step in 0..=100 {
+            let signal = step as CGFloat / 100.0;
+            assert!((hlg.from_linear(hlg.to_linear(signal)) - signal).abs() < 1e-9, "{}", signal);
+            assert_eq!(hlg.to_linear(-signal), -hlg.to_linear(signal));
+        }
+    }
+
+    #[test]
+    fn flags() {
+        use CGColorSpaceNames::*;
+
+        // wide gamut, PQ, HLG, extended range
+        let expected = [
+            (GenericGray, false, false, false, false),
+            (ExtendedLinearGray, false, false, false, true),
+            (GenericCMYK, false, false, false, false),
+            (GenericLab, false, false, false, false),
+            (SRGB, false, false, false, false),
+            (LinearSRGB, false, false, false, false),
+            (ExtendedSRGB, true, false, false, true),
+            (ITUR_709, false, false, false, false),
+            (ITUR_709_PQ, false, true, false, false),
+            (ITUR_709_HLG, false, false, true, false),
+            (DisplayP3, true, false, false, false),
+            (ExtendedLinearDisplayP3, true, false, false, true),
+            (DisplayP3_PQ, true, true, false, false),
+            (DisplayP3_HLG, true, false, true, false),
+            (DCIP3, true, false, false, false),
+            (ITUR_2020, true, false, false, false),
+            (ITUR_2100_PQ, true, true, false, false),
+            (ITUR_2100_HLG, true, false, true, false),
+            (AdobeRGB1998, true, false, false, false),
+            (ROMMRGB, true, false, false, false),
+            (ACESCGLinear, true, false, false, false),
+        ];
+        for (name, wide_gamut, pq, hlg, extended_range) in expected {
+            let space = NamedColorSpace::new(name);
+            assert_eq!(
+                (space.is_wide_gamut_rgb(), space.is_pq_based(), space.is_hlg_based(), space.uses_extended_range()),
+                (wide_gamut, pq, hlg, extended_range),
+                "{:?}",
+                name
+            );
+            assert_eq!(space.uses_itur_2100_tf(), pq || hlg, "{:?}", name);
+        }
+        for name in NAMES {
+            let space = NamedColorSpace::new(name);
+            assert_eq!(space.name(), Some(name));
+            assert_eq!(space.primaries().is_some(), space.model() == CGColorSpaceModel::RGB, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn linearized() {
+        let linear = NamedColorSpace::new(CGColorSpaceNames::ExtendedSRGB).new_linearized().unwrap();
+        assert_eq!(linear.name(), Some(CGColorSpaceNames::ExtendedLinearSRGB));
+        assert!(linear.is_linear() && linear.uses_extended_range());
+        let linear = NamedColorSpace::new(CGColorSpaceNames::ITUR_2100_PQ).new_linearized().unwrap();
+        assert_eq!(linear.name(), Some(CGColorSpaceNames::LinearITUR_2020));
+        assert!(!linear.is_pq_based());
+        assert_eq!(NamedColorSpace::new(CGColorSpaceNames::GenericCMYK).new_linearized(), None);
+    }
+}
@@ -340,6 +340,61 @@ impl CGPath {
         }
     }
 
+    pub fn new_copy_by_normalizing_path(&self, even_odd_fill_rule: bool) -> Option<CGPath> {
+        unsafe {
+            let path = CGPathCreateCopyByNormalizingPath(self.as_concrete_TypeRef(), even_odd_fill_rule);
+            if path.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_create_rule(path))
+            }
+        }
+    }
+
+    pub fn new_copy_by_unioning_path(&self, mask_path: &CGPath, even_odd_fill_rule: bool) -> Option<CGPath> {
+        unsafe {
+            let path = CGPathCreateCopyByUnioningPath(self.as_concrete_TypeRef(), mask_path.as_concrete_TypeRef(), even_odd_fill_rule);
+            if path.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_create_rule(path))
+            }
+        }
+    }
+
+    pub fn new_copy_by_intersecting_path(&self, mask_path: &CGPath, even_odd_fill_rule: bool) -> Option<CGPath> {
+        unsafe {
+            let path = CGPathCreateCopyByIntersectingPath(self.as_concrete_TypeRef(), mask_path.as_concrete_TypeRef(), even_odd_fill_rule);
+            if path.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_create_rule(path))
+            }
+        }
+    }
+
+    pub fn new_copy_by_subtracting_path(&self, mask_path: &CGPath, even_odd_fill_rule: bool) -> Option<CGPath> {
+        unsafe {
+            let path = CGPathCreateCopyBySubtractingPath(self.as_concrete_TypeRef(), mask_path.as_concrete_TypeRef(), even_odd_fill_rule);
+            if path.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_create_rule(path))
+            }
+        }
+    }
+
+    pub fn new_copy_by_symmetric_difference_of_path(&self, mask_path: &CGPath, even_odd_fill_rule: bool) -> Option<CGPath> {
+        unsafe {
+            let path = CGPathCreateCopyBySymmetricDifferenceOfPath(self.as_concrete_TypeRef(), mask_path.as_concrete_TypeRef(), even_odd_fill_rule);
+            if path.is_null() {
+                None
+            } else {
+                Some(TCFType::wrap_under_create_rule(path))
+            }
+        }
+    }
+
     pub fn equal(&self, path: &CGPath) -> bool {
         unsafe { CGPathEqualToPath(self.as_concrete_TypeRef(), path.as_concrete_TypeRef()) }
     }
@@ -374,6 +429,10 @@ impl CGPath {
         unsafe { CGPathContainsPoint(self.as_concrete_TypeRef(), transform.map_or(null(), |t| t as *const CGAffineTransform), point, eo_fill) }
     }
 
+    pub fn intersects_path(&self, path: &CGPath, even_odd_fill_rule: bool) -> bool {
+        unsafe { CGPathIntersectsPath(self.as_concrete_TypeRef(), path.as_concrete_TypeRef(), even_odd_fill_rule) }
+    }
+
     pub fn apply<F>(&self, closure: F)
     where
         F: FnMut(CGPathElementRef),
@@ -0,0 +1,468 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    base::CGFloat,
+    geometry::{CGPoint, CGVector},
+    path_builder::{Path, PathElement, FLATTENING_TOLERANCE},
+    path_segment::Bounds,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PathOperation {
+    Union,
+    Intersection,
+    Subtraction,
+    SymmetricDifference,
+}
+
+impl PathOperation {
+    fn apply(&self, inside_path: bool, inside_mask: bool) -> bool {
+        match self {
+            PathOperation::Union => inside_path || inside_mask,
+            PathOperation::Intersection => inside_path && inside_mask,
+            PathOperation::Subtraction => inside_path && !inside_mask,
+            PathOperation::SymmetricDifference => inside_path != inside_mask,
+        }
+    }
+}
+
+// curves are flattened within FLATTENING_TOLERANCE, so the results are made of MoveTo, LineTo and Close
+// elements only, call path_operation directly to choose another tolerance
+impl Path {
+    pub fn new_copy_by_normalizing_path(&self, even_odd_fill_rule: bool) -> Path {
+        path_operation(self, &Path::new(), PathOperation::Union, even_odd_fill_rule, FLATTENING_TOLERANCE)
+    }
+
+    pub fn new_copy_by_unioning_path(&self, mask_path: &Path, even_odd_fill_rule: bool) -> Path {
+        path_operation(self, mask_path, PathOperation::Union, even_odd_fill_rule, FLATTENING_TOLERANCE)
+    }
+
+    pub fn new_copy_by_intersecting_path(&self, mask_path: &Path, even_odd_fill_rule: bool) -> Path {
+        path_operation(self, mask_path, PathOperation::Intersection, even_odd_fill_rule, FLATTENING_TOLERANCE)
+    }
+
+    pub fn new_copy_by_subtracting_path(&self, mask_path: &Path, even_odd_fill_rule: bool) -> Path {
+        path_operation(self, mask_path, PathOperation::Subtraction, even_odd_fill_rule, FLATTENING_TOLERANCE)
+    }
+
+    pub fn new_copy_by_symmetric_difference_of_path(&self, mask_path: &Path, even_odd_fill_rule: bool) -> Path {
+        path_operation(self, mask_path, PathOperation::SymmetricDifference, even_odd_fill_rule, FLATTENING_TOLERANCE)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Edge {
+    pub(crate) from: CGPoint,
+    pub(crate) to: CGPoint,
+}
+
+impl Edge {
+    // +1 for an upward crossing of the horizontal ray to the right of point, -1 for a downward one
+    pub(crate) fn winding_contribution(&self, point: CGPoint) -> i32 {
+        let (a, b) = (self.from, self.to);
+        let side = (b - a).cross(&(point - a));
+        if a.y <= point.y {
+            if b.y > point.y && side > 0.0 {
+                return 1;
+            }
+        } else if b.y <= point.y && side < 0.0 {
+            return -1;
+        }
+        0
+    }
+}
+
+// curves are flattened and every subpath is implicitly closed, as when filling
+pub(crate) fn polygon_edges(path: &Path, tolerance: CGFloat) -> Vec<Edge> {
+    let mut edges = Vec::new();
+    for subpath in path.subpaths() {
+        let mut last = subpath.start;
+        for segment in &subpath.segments {
+            segment.flatten(tolerance, |point| {
+                if point != last {
+                    edges.push(Edge {
+                        from: last,
+                        to: point,
+                    });
+                }
+                last = point;
+            });
+        }
+        if last != subpath.start {
+            edges.push(Edge {
+                from: last,
+                to: subpath.start,
+            });
+        }
+    }
+    edges
+}
+
+pub(crate) fn winding_number(edges: &[Edge], point: CGPoint) -> i32 {
+    edges.iter().map(|edge| edge.winding_contribution(point)).sum()
+}
+
+pub(crate) fn is_inside(winding: i32, even_odd_fill_rule: bool) -> bool {
+    if even_odd_fill_rule {
+        winding % 2 != 0
+    } else {
+        winding != 0
+    }
+}
+
+pub fn path_operation(path: &Path, mask_path: &Path, operation: PathOperation, even_odd_fill_rule: bool, tolerance: CGFloat) -> Path {
+    let path_edges = polygon_edges(path, tolerance);
+    let mask_edges = polygon_edges(mask_path, tolerance);
+
+    if path_edges.is_empty() && mask_edges.is_empty() {
+        return Path::new();
+    }
+    let mut bounds = Bounds::new();
+    for edge in path_edges.iter().chain(mask_edges.iter()) {
+        bounds.add_all(&[edge.from, edge.to]);
+    }
+    let extent = bounds.rect();
+    let scale = extent.size.width.max(extent.size.height).max(1.0);
+    let grid = Grid {
+        quantum: scale * 1e-10,
+    };
+
+    // split every edge where it meets another one, points that fall into the same grid cell are merged
+    // into the first one seen, so the original vertices are kept exactly
+    let all_edges: Vec<Edge> = path_edges.iter().chain(mask_edges.iter()).copied().collect();
+    let mut vertices = HashMap::new();
+    for edge in &all_edges {
+        vertices.entry(grid.key(edge.from)).or_insert(edge.from);
+        vertices.entry(grid.key(edge.to)).or_insert(edge.to);
+    }
+    let splits = split_points(&all_edges, grid.quantum);
+    let mut pieces = Vec::new();
+    let mut seen = HashSet::new();
+    for (edge, mut points) in all_edges.iter().zip(splits) {
+        let direction = edge.to - edge.from;
+        points.push(edge.from);
+        points.push(edge.to);
+        points.sort_by(|a, b| (*a - edge.from).dot(&direction).total_cmp(&(*b - edge.from).dot(&direction)));
+        for pair in points.windows(2) {
+            let (from_key, to_key) = (grid.key(pair[0]), grid.key(pair[1]));
+            if from_key == to_key {
+                continue;
+            }
+            // orientation is irrelevant for classification, both sides are sampled below
+            let key = if from_key < to_key {
+                (from_key, to_key)
+            } else {
+                (to_key, from_key)
+            };
+            if seen.insert(key) {
+                pieces.push((*vertices.entry(from_key).or_insert(pair[0]), *vertices.entry(to_key).or_insert(pair[1])));
+            }
+        }
+    }
+
+    // keep the pieces that separate the inside of the result from its outside, with the inside on the left
+    let mut kept = Vec::new();
+    for (from, to) in pieces {
+        let direction = to - from;
+        let offset = (scale * 1e-9).min(direction.length() * 1e-3);
+        let normal = direction.normalize().perpendicular() * offset;
+        let mid = from.midpoint(&to);
+        let (left, right) = (mid + normal, mid - normal);
+        let inside = |point: CGPoint| {
+            operation.apply(
+                is_inside(winding_number(&path_edges, point), even_odd_fill_rule),
+                is_inside(winding_number(&mask_edges, point), even_odd_fill_rule),
+            )
+        };
+        match (inside(left), inside(right)) {
+            (true, false) => kept.push((from, to)),
+            (false, true) => kept.push((to, from)),
+            _ => {}
+        }
+    }
+
+    Path::from_elements(link_loops(&kept, &grid))
+}
+
+type GridKey = (i64, i64);
+
+struct Grid {
+    quantum: CGFloat,
+}
+
+impl Grid {
+    fn key(&self, point: CGPoint) -> GridKey {
+        ((point.x / self.quantum).round() as i64, (point.y / self.quantum).round() as i64)
+    }
+}
+
+fn split_points(edges: &[Edge], tolerance: CGFloat) -> Vec<Vec<CGPoint>> {
+    let mut splits = vec![Vec::new(); edges.len()];
+    let min_x = |edge: &Edge| edge.from.x.min(edge.to.x);
+    let mut order: Vec<usize> = (0..edges.len()).collect();
+    order.sort_by(|a, b| min_x(&edges[*a]).total_cmp(&min_x(&edges[*b])));
+    for (position, &i) in order.iter().enumerate() {
+        let (p, r) = (edges[i].from, edges[i].to - edges[i].from);
+        let max_x = edges[i].from.x.max(edges[i].to.x);
+        for &j in &order[position + 1..] {
+            if min_x(&edges[j]) > max_x {
+                break;
+            }
+            if !boxes_overlap(&edges[i], &edges[j]) {
+                continue;
+            }
+            let (q, s) = (edges[j].from, edges[j].to - edges[j].from);
+            let denominator = r.cross(&s);
+            let qp = q - p;
+            if denominator.abs() > 1e-12 * r.length() * s.length() {
+                let t = qp.cross(&s) / denominator;
+                let u = qp.cross(&r) / denominator;
+                if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+                    let point = p + r * t;
+                    splits[i].push(point);
+                    splits[j].push(point);
+                }
+            } else if qp.cross(&r).abs() <= tolerance * r.length() {
+                // collinear overlap, each edge is split at the endpoints of the other
+                for point in [q, q + s] {
+                    if within(p, r, point) {
+                        splits[i].push(point);
+                    }
+                }
+                for point in [p, p + r] {
+                    if within(q, s, point) {
+                        splits[j].push(point);
+                    }
+                }
+            }
+        }
+    }
+    splits
+}
+
+fn within(origin: CGPoint, direction: CGVector, point: CGPoint) -> bool {
+    let t = (point - origin).dot(&direction) / direction.length_squared();
+    t > 0.0 && t < 1.0
+}
+
+fn boxes_overlap(a: &Edge, b: &Edge) -> bool {
+    a.from.x.min(a.to.x) <= b.from.x.max(b.to.x)
+        && b.from.x.min(b.to.x) <= a.from.x.max(a.to.x)
+        && a.from.y.min(a.to.y) <= b.from.y.max(b.to.y)
+        && b.from.y.min(b.to.y) <= a.from.y.max(a.to.y)
+}
+
+// every vertex of an oriented region boundary has as many incoming as outgoing edges, so walking
+// unused outgoing edges always returns to the starting vertex
+fn link_loops(edges: &[(CGPoint, CGPoint)], grid: &Grid) -> Vec<PathElement> {
+    let mut outgoing: HashMap<GridKey, Vec<usize>> = HashMap::new();
+    for (index, (from, _)) in edges.iter().enumerate() {
+        outgoing.entry(grid.key(*from)).or_default().push(index);
+    }
+    let mut used = vec![false; edges.len()];
+    let mut elements = Vec::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+        let start_key = grid.key(edges[first].0);
+        let mut ring = vec![edges[first].0];
+        let mut current = first;
+        loop {
+            used[current] = true;
+            let (from, to) = edges[current];
+            let key = grid.key(to);
+            if key == start_key {
+                break;
+            }
+            ring.push(to);
+            // prefer the sharpest right turn so that touching loops are kept apart
+            let incoming = to - from;
+            let next = outgoing.get(&key).and_then(|candidates| {
+                candidates.iter().copied().filter(|candidate| !used[*candidate]).min_by(|a, b| {
+                    let turn_a = incoming.angle_to(&(edges[*a].1 - edges[*a].0));
+                    let turn_b = incoming.angle_to(&(edges[*b].1 - edges[*b].0));
+                    turn_a.total_cmp(&turn_b)
+                })
+            });
+            match next {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+        simplify_ring(&mut ring);
+        if ring.len() < 3 {
+            continue;
+        }
+        elements.push(PathElement::MoveTo(ring[0]));
+        elements.extend(ring[1..].iter().map(|point| PathElement::LineTo(*point)));
+        elements.push(PathElement::Close);
+    }
+    elements
+}
+
+// drops vertices that lie on the straight line between their neighbours
+fn simplify_ring(ring: &mut Vec<CGPoint>) {
+    let mut index = 0;
+    while ring.len() > 2 && index < ring.len() {
+        let count = ring.len();
+        let previous = ring[(index + count - 1) % count];
+        let next = ring[(index + 1) % count];
+        let (a, b) = (ring[index] - previous, next - ring[index]);
+        if a.cross(&b).abs() <= 1e-12 * a.length() * b.length() && a.dot(&b) > 0.0 {
+            ring.remove(index);
+            index = index.saturating_sub(1);
+        } else {
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::CGRect;
+
+    fn rect(x: CGFloat, y: CGFloat, width: CGFloat, height: CGFloat) -> Path {
+        Path::from_rect(CGRect::new(x, y, width, height), None)
+    }
+
+    // signed area, holes wound the other way subtract from it
+    fn area(path: &Path) -> CGFloat {
+        let mut area = 0.0;
+        for subpath in path.subpaths() {
+            for segment in &subpath.segments {
+                area += segment.start().to_vector().cross(&segment.end().to_vector()) * 0.5;
+            }
+        }
+        area
+    }
+
+    fn contains(path: &Path, x: CGFloat, y: CGFloat) -> bool {
+        is_inside(winding_number(&polygon_edges(path, FLATTENING_TOLERANCE), CGPoint::new(x, y)), false)
+    }
+
+    fn ring_count(path: &Path) -> usize {
+        path.iter().filter(|element| matches!(element, PathElement::MoveTo(_))).count()
+    }
+
+    fn assert_area(path: &Path, expected: CGFloat) {
+        assert!((area(path) - expected).abs() < 1e-9, "{} != {}", area(path), expected);
+    }
+
+    #[test]
+    fn overlapping_squares() {
+        let a = rect(0.0, 0.0, 10.0, 10.0);
+        let b = rect(5.0, 5.0, 10.0, 10.0);
+        let cases = [
+            (PathOperation::Union, 175.0, [true, true, true]),
+            (PathOperation::Intersection, 25.0, [false, true, false]),
+            (PathOperation::Subtraction, 75.0, [true, false, false]),
+            (PathOperation::SymmetricDifference, 150.0, [true, false, true]),
+        ];
+        for (operation, expected_area, expected_inside) in cases {
+            for even_odd_fill_rule in [false, true] {
+                let result = path_operation(&a, &b, operation, even_odd_fill_rule, FLATTENING_TOLERANCE);
+                assert_area(&result, expected_area);
+                assert_eq!(
+                    [contains(&result, 2.0, 2.0), contains(&result, 7.0, 7.0), contains(&result, 12.0, 12.0)],
+                    expected_inside,
+                    "{:?}",
+                    operation
+                );
+            }
+        }
+        assert_eq!(ring_count(&a.new_copy_by_symmetric_difference_of_path(&b, false)), 2);
+        assert_eq!(a.new_copy_by_unioning_path(&b, false), path_operation(&a, &b, PathOperation::Union, false, FLATTENING_TOLERANCE));
+    }
+
+    #[test]
+    fn donut_fill_rules() {
+        // both squares are wound the same way, so the hole only exists under the even-odd rule
+        let mut donut = rect(0.0, 0.0, 30.0, 30.0).into_builder();
+        donut.add_rect(None, CGRect::new(10.0, 10.0, 10.0, 10.0));
+        let donut = donut.build();
+
+        let even_odd = donut.new_copy_by_normalizing_path(true);
+        assert_area(&even_odd, 800.0);
+        assert_eq!(ring_count(&even_odd), 2);
+        assert!(contains(&even_odd, 5.0, 5.0));
+        assert!(!contains(&even_odd, 15.0, 15.0));
+
+        let nonzero = donut.new_copy_by_normalizing_path(false);
+        assert_area(&nonzero, 900.0);
+        assert_eq!(ring_count(&nonzero), 1);
+        assert!(contains(&nonzero, 15.0, 15.0));
+
+        // the fill rule applies to each operand before combining them
+        let result = donut.new_copy_by_intersecting_path(&rect(5.0, 5.0, 20.0, 20.0), true);
+        assert_area(&result, 300.0);
+        assert!(!contains(&result, 15.0, 15.0));
+        let result = donut.new_copy_by_intersecting_path(&rect(5.0, 5.0, 20.0, 20.0), false);
+        assert_area(&result, 400.0);
+    }
+
+    #[test]
+    fn disjoint_paths() {
+        let a = rect(0.0, 0.0, 10.0, 10.0);
+        let b = rect(20.0, 0.0, 10.0, 10.0);
+        let union = a.new_copy_by_unioning_path(&b, false);
+        assert_area(&union, 200.0);
+        assert_eq!(ring_count(&union), 2);
+        assert!(a.new_copy_by_intersecting_path(&b, false).is_empty());
+        assert_area(&a.new_copy_by_subtracting_path(&b, false), 100.0);
+        assert_area(&a.new_copy_by_symmetric_difference_of_path(&b, false), 200.0);
+    }
+
+    #[test]
+    fn identical_paths() {
+        let a = rect(0.0, 0.0, 10.0, 10.0);
+        let union = a.new_copy_by_unioning_path(&a, false);
+        assert_area(&union, 100.0);
+        // a single ring with four corners
+        assert_eq!(union.len(), 5);
+        assert_area(&a.new_copy_by_intersecting_path(&a, false), 100.0);
+        assert!(a.new_copy_by_subtracting_path(&a, false).is_empty());
+        assert!(a.new_copy_by_symmetric_difference_of_path(&a, false).is_empty());
+    }
+
+    #[test]
+    fn nested_paths() {
+        let outer = rect(0.0, 0.0, 10.0, 10.0);
+        let inner = rect(2.0, 2.0, 4.0, 4.0);
+        assert_area(&outer.new_copy_by_unioning_path(&inner, false), 100.0);
+        assert_area(&outer.new_copy_by_intersecting_path(&inner, false), 16.0);
+        let subtraction = outer.new_copy_by_subtracting_path(&inner, false);
+        assert_area(&subtraction, 84.0);
+        assert_eq!(ring_count(&subtraction), 2);
+        assert!(!contains(&subtraction, 3.0, 3.0));
+        assert!(inner.new_copy_by_subtracting_path(&outer, false).is_empty());
+        assert_area(&outer.new_copy_by_symmetric_difference_of_path(&inner, false), 84.0);
+    }
+
+    #[test]
+    fn collinear_shared_edges() {
+        let a = rect(0.0, 0.0, 10.0, 10.0);
+        // the shared edge disappears and the collinear sides merge into a single rectangle
+        let union = a.new_copy_by_unioning_path(&rect(10.0, 0.0, 10.0, 10.0), false);
+        assert_area(&union, 200.0);
+        assert_eq!(union.len(), 5);
+        assert!(a.new_copy_by_intersecting_path(&rect(10.0, 0.0, 10.0, 10.0), false).is_empty());
+        assert_area(&a.new_copy_by_subtracting_path(&rect(10.0, 0.0, 10.0, 10.0), false), 100.0);
+
+        // a partially shared edge is split where the other edge ends
+        let union = a.new_copy_by_unioning_path(&rect(10.0, 5.0, 10.0, 10.0), false);
+        assert_area(&union, 200.0);
+        assert_eq!(ring_count(&union), 1);
+        assert_eq!(union.len(), 9);
+    }
+
+    #[test]
+    fn curves_are_flattened() {
+        let circle = Path::from_ellipse_in_rect(CGRect::new(0.0, 0.0, 20.0, 20.0), None);
+        let result = circle.new_copy_by_unioning_path(&rect(10.0, 0.0, 20.0, 20.0), false);
+        assert!(result.iter().all(|element| matches!(element, PathElement::MoveTo(_) | PathElement::LineTo(_) | PathElement::Close)));
+        let expected = 400.0 + 50.0 * std::f64::consts::PI as CGFloat;
+        assert!((area(&result) - expected).abs() < 0.5, "{}", area(&result));
+    }
+}
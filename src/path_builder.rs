@@ -0,0 +1,478 @@
+use std::{f64::consts::PI, slice::Iter};
+
+#[cfg(target_os = "macos")]
+use crate::path::{CGMutablePath, CGPath};
+use crate::{
+    affine_transform::CGAffineTransform,
+    base::CGFloat,
+    geometry::{CGPoint, CGRect, CGVector},
+    path::CGPathElementType,
+};
+
+// 4/3 * (sqrt(2) - 1), the control point distance for a quarter ellipse
+const KAPPA: CGFloat = 0.552_284_749_830_793_6;
+// default user space flattening error of the native path operations
+pub(crate) const FLATTENING_TOLERANCE: CGFloat = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathElement {
+    MoveTo(CGPoint),
+    LineTo(CGPoint),
+    QuadTo(CGPoint, CGPoint),
+    CurveTo(CGPoint, CGPoint, CGPoint),
+    Close,
+}
+
+impl PathElement {
+    pub fn element_type(&self) -> CGPathElementType {
+        match self {
+            PathElement::MoveTo(_) => CGPathElementType::MoveToPoint,
+            PathElement::LineTo(_) => CGPathElementType::AddLineToPoint,
+            PathElement::QuadTo(..) => CGPathElementType::AddQuadCurveToPoint,
+            PathElement::CurveTo(..) => CGPathElementType::AddCurveToPoint,
+            PathElement::Close => CGPathElementType::CloseSubpath,
+        }
+    }
+
+    pub fn end_point(&self) -> Option<CGPoint> {
+        match *self {
+            PathElement::MoveTo(point) | PathElement::LineTo(point) | PathElement::QuadTo(_, point) | PathElement::CurveTo(_, _, point) => {
+                Some(point)
+            }
+            PathElement::Close => None,
+        }
+    }
+
+    pub fn apply_transform(&self, transform: &CGAffineTransform) -> PathElement {
+        match *self {
+            PathElement::MoveTo(point) => PathElement::MoveTo(point.apply_transform(transform)),
+            PathElement::LineTo(point) => PathElement::LineTo(point.apply_transform(transform)),
+            PathElement::QuadTo(control, point) => PathElement::QuadTo(control.apply_transform(transform), point.apply_transform(transform)),
+            PathElement::CurveTo(control1, control2, point) => {
+                PathElement::CurveTo(control1.apply_transform(transform), control2.apply_transform(transform), point.apply_transform(transform))
+            }
+            PathElement::Close => PathElement::Close,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    elements: Vec<PathElement>,
+}
+
+impl Path {
+    pub fn new() -> Path {
+        Path::default()
+    }
+
+    pub fn from_elements(elements: Vec<PathElement>) -> Path {
+        Path {
+            elements,
+        }
+    }
+
+    pub fn from_rect(rect: CGRect, transform: Option<&CGAffineTransform>) -> Path {
+        let mut builder = PathBuilder::new();
+        builder.add_rect(transform, rect);
+        builder.build()
+    }
+
+    pub fn from_ellipse_in_rect(rect: CGRect, transform: Option<&CGAffineTransform>) -> Path {
+        let mut builder = PathBuilder::new();
+        builder.add_ellipse_in_rect(transform, rect);
+        builder.build()
+    }
+
+    pub fn from_rounded_rect(rect: CGRect, corner_width: CGFloat, corner_height: CGFloat, transform: Option<&CGAffineTransform>) -> Path {
+        let mut builder = PathBuilder::new();
+        builder.add_rounded_rect(transform, rect, corner_width, corner_height);
+        builder.build()
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn from_cg_path(path: &CGPath) -> Path {
+        let mut elements = Vec::new();
+        path.apply(|element| {
+            let points = element.points();
+            elements.push(match element.element_type {
+                CGPathElementType::MoveToPoint => PathElement::MoveTo(points[0]),
+                CGPathElementType::AddLineToPoint => PathElement::LineTo(points[0]),
+                CGPathElementType::AddQuadCurveToPoint => PathElement::QuadTo(points[0], points[1]),
+                CGPathElementType::AddCurveToPoint => PathElement::CurveTo(points[0], points[1], points[2]),
+                CGPathElementType::CloseSubpath => PathElement::Close,
+            });
+        });
+        Path::from_elements(elements)
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn to_cg_path(&self) -> CGPath {
+        self.to_cg_mutable_path().to_immutable()
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn to_cg_mutable_path(&self) -> CGMutablePath {
+        let path = CGMutablePath::new();
+        for element in &self.elements {
+            match *element {
+                PathElement::MoveTo(point) => path.move_to_point(None, point.x, point.y),
+                PathElement::LineTo(point) => path.add_line_to_point(None, point.x, point.y),
+                PathElement::QuadTo(control, point) => path.add_quad_curve_to_point(None, control.x, control.y, point.x, point.y),
+                PathElement::CurveTo(control1, control2, point) => {
+                    path.add_curve_to_point(None, control1.x, control1.y, control2.x, control2.y, point.x, point.y)
+                }
+                PathElement::Close => path.close_subpath(),
+            }
+        }
+        path
+    }
+
+    pub fn elements(&self) -> &[PathElement] {
+        &self.elements
+    }
+
+    pub fn iter(&self) -> Iter<'_, PathElement> {
+        self.elements.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn current_point(&self) -> Option<CGPoint> {
+        current_point(&self.elements)
+    }
+
+    pub fn apply_transform(&self, transform: &CGAffineTransform) -> Path {
+        Path::from_elements(self.elements.iter().map(|element| element.apply_transform(transform)).collect())
+    }
+
+    pub fn into_builder(self) -> PathBuilder {
+        PathBuilder {
+            path: self,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Path {
+    type Item = &'a PathElement;
+    type IntoIter = Iter<'a, PathElement>;
+
+    fn into_iter(self) -> Iter<'a, PathElement> {
+        self.elements.iter()
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl From<&CGPath> for Path {
+    fn from(path: &CGPath) -> Path {
+        Path::from_cg_path(path)
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl From<&Path> for CGPath {
+    fn from(path: &Path) -> CGPath {
+        path.to_cg_path()
+    }
+}
+
+impl From<PathBuilder> for Path {
+    fn from(builder: PathBuilder) -> Path {
+        builder.build()
+    }
+}
+
+fn current_point(elements: &[PathElement]) -> Option<CGPoint> {
+    match elements.last()? {
+        PathElement::Close => subpath_start(elements),
+        element => element.end_point(),
+    }
+}
+
+fn subpath_start(elements: &[PathElement]) -> Option<CGPoint> {
+    elements.iter().rev().find_map(|element| match element {
+        PathElement::MoveTo(point) => Some(*point),
+        _ => None,
+    })
+}
+
+fn transform_point(transform: Option<&CGAffineTransform>, x: CGFloat, y: CGFloat) -> CGPoint {
+    let point = CGPoint::new(x, y);
+    match transform {
+        Some(transform) => point.apply_transform(transform),
+        None => point,
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PathBuilder {
+    path: Path,
+}
+
+impl PathBuilder {
+    pub fn new() -> PathBuilder {
+        PathBuilder::default()
+    }
+
+    pub fn build(self) -> Path {
+        self.path
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    pub fn current_point(&self) -> Option<CGPoint> {
+        self.path.current_point()
+    }
+
+    pub fn push(&mut self, element: PathElement) {
+        match element {
+            PathElement::MoveTo(_) => self.path.elements.push(element),
+            _ if self.current_point().is_some() => self.path.elements.push(element),
+            _ => {}
+        }
+    }
+
+    pub fn move_to_point(&mut self, transform: Option<&CGAffineTransform>, x: CGFloat, y: CGFloat) {
+        self.push(PathElement::MoveTo(transform_point(transform, x, y)));
+    }
+
+    pub fn add_line_to_point(&mut self, transform: Option<&CGAffineTransform>, x: CGFloat, y: CGFloat) {
+        self.push(PathElement::LineTo(transform_point(transform, x, y)));
+    }
+
+    pub fn add_quad_curve_to_point(&mut self, transform: Option<&CGAffineTransform>, cpx: CGFloat, cpy: CGFloat, x: CGFloat, y: CGFloat) {
+        self.push(PathElement::QuadTo(transform_point(transform, cpx, cpy), transform_point(transform, x, y)));
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_curve_to_point(
+        &mut self,
+        transform: Option<&CGAffineTransform>,
+        cp1x: CGFloat,
+        cp1y: CGFloat,
+        cp2x: CGFloat,
+        cp2y: CGFloat,
+        x: CGFloat,
+        y: CGFloat,
+    ) {
+        self.push(PathElement::CurveTo(
+            transform_point(transform, cp1x, cp1y),
+            transform_point(transform, cp2x, cp2y),
+            transform_point(transform, x, y),
+        ));
+    }
+
+    pub fn close_subpath(&mut self) {
+        self.push(PathElement::Close);
+    }
+
+    pub fn add_rect(&mut self, transform: Option<&CGAffineTransform>, rect: CGRect) {
+        let (min_x, min_y) = rect.min();
+        let (max_x, max_y) = rect.max();
+        self.move_to_point(transform, min_x, min_y);
+        self.add_line_to_point(transform, max_x, min_y);
+        self.add_line_to_point(transform, max_x, max_y);
+        self.add_line_to_point(transform, min_x, max_y);
+        self.close_subpath();
+    }
+
+    pub fn add_rects(&mut self, transform: Option<&CGAffineTransform>, rects: &[CGRect]) {
+        for rect in rects {
+            self.add_rect(transform, *rect);
+        }
+    }
+
+    pub fn add_lines(&mut self, transform: Option<&CGAffineTransform>, points: &[CGPoint]) {
+        if let Some((first, rest)) = points.split_first() {
+            self.move_to_point(transform, first.x, first.y);
+            for point in rest {
+                self.add_line_to_point(transform, point.x, point.y);
+            }
+        }
+    }
+
+    pub fn add_ellipse_in_rect(&mut self, transform: Option<&CGAffineTransform>, rect: CGRect) {
+        let (min_x, min_y) = rect.min();
+        let (mid_x, mid_y) = rect.mid();
+        let (max_x, max_y) = rect.max();
+        let (kx, ky) = (rect.width() * 0.5 * KAPPA, rect.height() * 0.5 * KAPPA);
+        self.move_to_point(transform, max_x, mid_y);
+        self.add_curve_to_point(transform, max_x, mid_y + ky, mid_x + kx, max_y, mid_x, max_y);
+        self.add_curve_to_point(transform, mid_x - kx, max_y, min_x, mid_y + ky, min_x, mid_y);
+        self.add_curve_to_point(transform, min_x, mid_y - ky, mid_x - kx, min_y, mid_x, min_y);
+        self.add_curve_to_point(transform, mid_x + kx, min_y, max_x, mid_y - ky, max_x, mid_y);
+        self.close_subpath();
+    }
+
+    pub fn add_rounded_rect(&mut self, transform: Option<&CGAffineTransform>, rect: CGRect, corner_width: CGFloat, corner_height: CGFloat) {
+        let rect = rect.standardize();
+        let corner_width = corner_width.max(0.0).min(rect.width() * 0.5);
+        let corner_height = corner_height.max(0.0).min(rect.height() * 0.5);
+        if corner_width == 0.0 || corner_height == 0.0 {
+            self.add_rect(transform, rect);
+            return;
+        }
+        let (min_x, min_y) = rect.min();
+        let (max_x, max_y) = rect.max();
+        let (kx, ky) = (corner_width * KAPPA, corner_height * KAPPA);
+        self.move_to_point(transform, min_x + corner_width, min_y);
+        self.add_line_to_point(transform, max_x - corner_width, min_y);
+        self.add_curve_to_point(transform, max_x - corner_width + kx, min_y, max_x, min_y + corner_height - ky, max_x, min_y + corner_height);
+        self.add_line_to_point(transform, max_x, max_y - corner_height);
+        self.add_curve_to_point(transform, max_x, max_y - corner_height + ky, max_x - corner_width + kx, max_y, max_x - corner_width, max_y);
+        self.add_line_to_point(transform, min_x + corner_width, max_y);
+        self.add_curve_to_point(transform, min_x + corner_width - kx, max_y, min_x, max_y - corner_height + ky, min_x, max_y - corner_height);
+        self.add_line_to_point(transform, min_x, min_y + corner_height);
+        self.add_curve_to_point(transform, min_x, min_y + corner_height - ky, min_x + corner_width - kx, min_y, min_x + corner_width, min_y);
+        self.close_subpath();
+    }
+
+    pub fn add_relative_arc(
+        &mut self,
+        transform: Option<&CGAffineTransform>,
+        x: CGFloat,
+        y: CGFloat,
+        radius: CGFloat,
+        start_angle: CGFloat,
+        delta: CGFloat,
+    ) {
+        let center = CGPoint::new(x, y);
+        let (sin, cos) = start_angle.sin_cos();
+        let start = center + CGVector::new(cos, sin) * radius;
+        match self.current_point() {
+            Some(current) if current == transform_point(transform, start.x, start.y) => {}
+            Some(_) => self.add_line_to_point(transform, start.x, start.y),
+            None => self.move_to_point(transform, start.x, start.y),
+        }
+        if delta == 0.0 {
+            return;
+        }
+        // split the arc into segments of at most a quarter turn, each approximated by one cubic curve
+        let segments = (delta.abs() / (PI / 2.0) - 1e-9).ceil().max(1.0);
+        let step = delta / segments;
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+        let mut angle = start_angle;
+        for _ in 0..segments as usize {
+            let (sin0, cos0) = angle.sin_cos();
+            let (sin1, cos1) = (angle + step).sin_cos();
+            let control1 = center + CGVector::new(cos0 - k * sin0, sin0 + k * cos0) * radius;
+            let control2 = center + CGVector::new(cos1 + k * sin1, sin1 - k * cos1) * radius;
+            let end = center + CGVector::new(cos1, sin1) * radius;
+            self.add_curve_to_point(transform, control1.x, control1.y, control2.x, control2.y, end.x, end.y);
+            angle += step;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_arc(
+        &mut self,
+        transform: Option<&CGAffineTransform>,
+        x: CGFloat,
+        y: CGFloat,
+        radius: CGFloat,
+        start_angle: CGFloat,
+        end_angle: CGFloat,
+        clockwise: bool,
+    ) {
+        let full_turn = 2.0 * PI;
+        let mut delta = end_angle - start_angle;
+        if clockwise {
+            if delta > 0.0 {
+                delta = delta.rem_euclid(full_turn) - full_turn;
+            }
+            delta = delta.max(-full_turn);
+        } else {
+            if delta < 0.0 {
+                delta = delta.rem_euclid(full_turn);
+            }
+            delta = delta.min(full_turn);
+        }
+        self.add_relative_arc(transform, x, y, radius, start_angle, delta);
+    }
+
+    pub fn add_arc_to_point(&mut self, transform: Option<&CGAffineTransform>, x1: CGFloat, y1: CGFloat, x2: CGFloat, y2: CGFloat, radius: CGFloat) {
+        let current = match self.current_point() {
+            Some(point) => point,
+            None => return,
+        };
+        // the current point is stored in path space, the arc is constructed in user space
+        let p0 = match transform {
+            Some(transform) => current.apply_transform(&transform.invert()),
+            None => current,
+        };
+        let p1 = CGPoint::new(x1, y1);
+        let p2 = CGPoint::new(x2, y2);
+        let v1 = (p0 - p1).normalize();
+        let v2 = (p2 - p1).normalize();
+        let cross = v1.cross(&v2);
+        if radius <= 0.0 || p0 == p1 || p1 == p2 || cross == 0.0 {
+            self.add_line_to_point(transform, x1, y1);
+            return;
+        }
+        let half_angle = v1.dot(&v2).clamp(-1.0, 1.0).acos() * 0.5;
+        let tangent_distance = radius / half_angle.tan();
+        let start = p1 + v1 * tangent_distance;
+        let end = p1 + v2 * tangent_distance;
+        let center = p1 + (v1 + v2).normalize() * (radius / half_angle.sin());
+        self.add_arc(transform, center.x, center.y, radius, center.angle_to(&start), center.angle_to(&end), cross > 0.0);
+    }
+
+    pub fn add_path(&mut self, transform: Option<&CGAffineTransform>, path: &Path) {
+        for element in path {
+            self.push(match transform {
+                Some(transform) => element.apply_transform(transform),
+                None => *element,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rect_is_built_from_standardized_corners() {
+        let path = Path::from_rect(CGRect::new(10.0, 20.0, -10.0, 5.0), None);
+        let expected = [
+            PathElement::MoveTo(CGPoint::new(0.0, 20.0)),
+            PathElement::LineTo(CGPoint::new(10.0, 20.0)),
+            PathElement::LineTo(CGPoint::new(10.0, 25.0)),
+            PathElement::LineTo(CGPoint::new(0.0, 25.0)),
+            PathElement::Close,
+        ];
+        assert_eq!(path.elements(), &expected);
+    }
+
+    #[test]
+    fn elements_without_current_point_are_dropped() {
+        let mut builder = PathBuilder::new();
+        builder.add_line_to_point(None, 1.0, 1.0);
+        builder.close_subpath();
+        assert!(builder.is_empty());
+        builder.move_to_point(Some(&CGAffineTransform::new_translate(5.0, 0.0)), 1.0, 1.0);
+        assert_eq!(builder.current_point(), Some(CGPoint::new(6.0, 1.0)));
+    }
+
+    #[test]
+    fn arc_ends_on_the_circle() {
+        let mut builder = PathBuilder::new();
+        builder.add_arc(None, 0.0, 0.0, 2.0, 0.0, PI, false);
+        let path = builder.build();
+        assert_eq!(path.elements().len(), 3);
+        let end = path.current_point().unwrap();
+        assert!((end.x + 2.0).abs() < 1e-12 && end.y.abs() < 1e-12);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{
+    affine_transform::CGAffineTransform,
+    base::CGFloat,
+    geometry::CGPoint,
+    path_boolean::{is_inside, polygon_edges, winding_number, Edge},
+    path_builder::{Path, FLATTENING_TOLERANCE},
+    path_stroke::StrokeStyle,
+};
+
+impl Path {
+    pub fn contains_point(&self, transform: Option<&CGAffineTransform>, point: CGPoint, eo_fill: bool) -> bool {
+        let edges = transformed_edges(self, transform);
+        is_inside(winding_number(&edges, point), eo_fill)
+    }
+
+    pub fn winding_number_at_point(&self, transform: Option<&CGAffineTransform>, point: CGPoint) -> i32 {
+        winding_number(&transformed_edges(self, transform), point)
+    }
+
+    // the distance from point to the nearest segment of the path, closing segments included
+    pub fn distance_to_point(&self, transform: Option<&CGAffineTransform>, point: CGPoint) -> Option<CGFloat> {
+        let transformed;
+        let path = match transform {
+            Some(transform) => {
+                transformed = self.apply_transform(transform);
+                &transformed
+            }
+            None => self,
+        };
+        let mut distance: Option<CGFloat> = None;
+        for subpath in path.subpaths() {
+            let mut nearest = point.distance(&subpath.start);
+            let mut last = subpath.start;
+            for segment in &subpath.segments {
+                segment.flatten(FLATTENING_TOLERANCE, |next| {
+                    nearest = nearest.min(distance_to_line_segment(point, last, next));
+                    last = next;
+                });
+            }
+            distance = Some(distance.map_or(nearest, |distance| distance.min(nearest)));
+        }
+        distance
+    }
+
+    pub fn is_point_near_stroke(&self, transform: Option<&CGAffineTransform>, point: CGPoint, tolerance: CGFloat) -> bool {
+        self.distance_to_point(transform, point).is_some_and(|distance| distance <= tolerance)
+    }
+
+    // whether the filled regions of both paths overlap, like CGPathIntersectsPath
+    pub fn intersects_path(&self, path: &Path, even_odd_fill_rule: bool) -> bool {
+        let edges = polygon_edges(self, FLATTENING_TOLERANCE);
+        let other_edges = polygon_edges(path, FLATTENING_TOLERANCE);
+        if edges.is_empty() || other_edges.is_empty() {
+            return false;
+        }
+        if edges.iter().any(|edge| other_edges.iter().any(|other| edges_cross(edge, other))) {
+            return true;
+        }
+        // without crossings one region either contains the other or they are disjoint
+        edges.iter().any(|edge| is_inside(winding_number(&other_edges, edge.from), even_odd_fill_rule))
+            || other_edges.iter().any(|edge| is_inside(winding_number(&edges, edge.from), even_odd_fill_rule))
+    }
+}
+
+impl StrokeStyle {
+    // tests against the stroke outline, so caps, joins and dashes are taken into account
+    pub fn contains_point(&self, path: &Path, transform: Option<&CGAffineTransform>, point: CGPoint) -> bool {
+        self.stroke(path).contains_point(transform, point, false)
+    }
+}
+
+fn transformed_edges(path: &Path, transform: Option<&CGAffineTransform>) -> Vec<Edge> {
+    match transform {
+        Some(transform) => polygon_edges(&path.apply_transform(transform), FLATTENING_TOLERANCE),
+        None => polygon_edges(path, FLATTENING_TOLERANCE),
+    }
+}
+
+fn distance_to_line_segment(point: CGPoint, from: CGPoint, to: CGPoint) -> CGFloat {
+    let direction = to - from;
+    let length_squared = direction.length_squared();
+    if length_squared == 0.0 {
+        return point.distance(&from);
+    }
+    let t = ((point - from).dot(&direction) / length_squared).clamp(0.0, 1.0);
+    point.distance(&(from + direction * t))
+}
+
+fn edges_cross(a: &Edge, b: &Edge) -> bool {
+    let side = |edge: &Edge, point: CGPoint| (edge.to - edge.from).cross(&(point - edge.from));
+    let (d1, d2) = (side(a, b.from), side(a, b.to));
+    let (d3, d4) = (side(b, a.from), side(b, a.to));
+    // touching edges are treated as crossing, matching the closed regions CoreGraphics tests
+    d1 * d2 <= 0.0 && d3 * d4 <= 0.0 && !(d1 == 0.0 && d2 == 0.0 && !collinear_overlap(a, b))
+}
+
+fn collinear_overlap(a: &Edge, b: &Edge) -> bool {
+    let direction = a.to - a.from;
+    let project = |point: CGPoint| (point - a.from).dot(&direction);
+    let (b0, b1) = (project(b.from), project(b.to));
+    b0.max(b1) >= 0.0 && b0.min(b1) <= direction.length_squared()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        geometry::CGRect,
+        path::{CGLineCap, CGLineJoin},
+    };
+
+    fn square(x: CGFloat, y: CGFloat, size: CGFloat) -> Path {
+        Path::from_rect(CGRect::new(x, y, size, size), None)
+    }
+
+    #[test]
+    fn fill_rules() {
+        let mut elements = square(0.0, 0.0, 10.0).elements().to_vec();
+        elements.extend_from_slice(square(2.0, 2.0, 6.0).elements());
+        let path = Path::from_elements(elements);
+        let center = CGPoint::new(5.0, 5.0);
+        assert_eq!(path.winding_number_at_point(None, center), 2);
+        assert!(path.contains_point(None, center, false));
+        assert!(!path.contains_point(None, center, true));
+        assert!(!path.contains_point(Some(&CGAffineTransform::new_translate(20.0, 0.0)), center, false));
+    }
+
+    #[test]
+    fn distance_to_stroke() {
+        let path = square(0.0, 0.0, 10.0);
+        assert_eq!(path.distance_to_point(None, CGPoint::new(5.0, 12.0)), Some(2.0));
+        assert!(path.is_point_near_stroke(None, CGPoint::new(-0.5, 5.0), 0.5));
+        assert!(!path.is_point_near_stroke(None, CGPoint::new(5.0, 5.0), 0.5));
+        assert_eq!(Path::new().distance_to_point(None, CGPoint::new(0.0, 0.0)), None);
+        let style = StrokeStyle::new(4.0, CGLineCap::Butt, CGLineJoin::Miter, 10.0);
+        assert!(style.contains_point(&path, None, CGPoint::new(11.5, 5.0)));
+    }
+
+    #[test]
+    fn intersections() {
+        let path = square(0.0, 0.0, 10.0);
+        assert!(path.intersects_path(&square(5.0, 5.0, 10.0), false));
+        assert!(path.intersects_path(&square(10.0, 0.0, 10.0), false));
+        assert!(!path.intersects_path(&square(11.0, 0.0, 10.0), false));
+        // containment without any crossing edges
+        assert!(path.intersects_path(&square(2.0, 2.0, 2.0), false));
+        assert!(square(2.0, 2.0, 2.0).intersects_path(&path, true));
+    }
+}
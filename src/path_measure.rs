@@ -0,0 +1,171 @@
+use crate::{
+    base::CGFloat,
+    geometry::{CGPoint, CGRect, CGVector},
+    path_builder::{Path, PathElement},
+    path_segment::{Bounds, PathSegment},
+};
+
+impl Path {
+    pub fn flatten(&self, flattening_threshold: CGFloat) -> Path {
+        let mut elements = Vec::with_capacity(self.len());
+        let mut last = None;
+        let mut start = None;
+        for element in self {
+            match *element {
+                PathElement::MoveTo(point) => {
+                    start = Some(point);
+                    last = Some(point);
+                    elements.push(*element);
+                }
+                PathElement::Close => {
+                    last = start;
+                    elements.push(*element);
+                }
+                _ => {
+                    let p0 = last.unwrap_or_else(|| element.end_point().unwrap_or_default());
+                    let segment = match *element {
+                        PathElement::QuadTo(p1, p2) => PathSegment::Quad(p0, p1, p2),
+                        PathElement::CurveTo(p1, p2, p3) => PathSegment::Cubic(p0, p1, p2, p3),
+                        _ => PathSegment::Line(p0, element.end_point().unwrap_or_default()),
+                    };
+                    segment.flatten(flattening_threshold, |point| elements.push(PathElement::LineTo(point)));
+                    last = Some(segment.end());
+                }
+            }
+        }
+        Path::from_elements(elements)
+    }
+
+    // includes the control points, like CGPathGetBoundingBox
+    pub fn bounding_box(&self) -> CGRect {
+        let mut bounds = Bounds::new();
+        for element in self {
+            match *element {
+                PathElement::MoveTo(p0) | PathElement::LineTo(p0) => bounds.add(p0),
+                PathElement::QuadTo(p0, p1) => bounds.add_all(&[p0, p1]),
+                PathElement::CurveTo(p0, p1, p2) => bounds.add_all(&[p0, p1, p2]),
+                PathElement::Close => {}
+            }
+        }
+        bounds.rect()
+    }
+
+    // the tight bounds of the curves, like CGPathGetPathBoundingBox
+    pub fn path_bounding_box(&self) -> CGRect {
+        let mut bounds = Bounds::new();
+        for subpath in self.subpaths() {
+            bounds.add(subpath.start);
+            for segment in &subpath.segments {
+                bounds.add_rect(&segment.bounding_box());
+            }
+        }
+        bounds.rect()
+    }
+
+    pub fn length(&self) -> CGFloat {
+        PathMeasure::new(self).length()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeasuredSegment {
+    pub segment: PathSegment,
+    pub offset: CGFloat,
+    pub length: CGFloat,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PathMeasure {
+    segments: Vec<MeasuredSegment>,
+    length: CGFloat,
+}
+
+impl PathMeasure {
+    pub fn new(path: &Path) -> PathMeasure {
+        let mut segments = Vec::new();
+        let mut offset = 0.0;
+        for segment in path.segments() {
+            let length = segment.length();
+            segments.push(MeasuredSegment {
+                segment,
+                offset,
+                length,
+            });
+            offset += length;
+        }
+        PathMeasure {
+            segments,
+            length: offset,
+        }
+    }
+
+    pub fn length(&self) -> CGFloat {
+        self.length
+    }
+
+    pub fn segments(&self) -> &[MeasuredSegment] {
+        &self.segments
+    }
+
+    pub fn segment_lengths(&self) -> Vec<CGFloat> {
+        self.segments.iter().map(|segment| segment.length).collect()
+    }
+
+    pub fn position_at(&self, distance: CGFloat) -> Option<CGPoint> {
+        self.position_and_tangent_at(distance).map(|(position, _)| position)
+    }
+
+    pub fn tangent_at(&self, distance: CGFloat) -> Option<CGVector> {
+        self.position_and_tangent_at(distance).map(|(_, tangent)| tangent)
+    }
+
+    // distances outside of the path are clamped to its ends
+    pub fn position_and_tangent_at(&self, distance: CGFloat) -> Option<(CGPoint, CGVector)> {
+        let distance = distance.max(0.0).min(self.length);
+        let index = self.segments.partition_point(|segment| segment.offset + segment.length < distance).min(self.segments.len().checked_sub(1)?);
+        let measured = &self.segments[index];
+        let t = measured.segment.parameter_at_length(distance - measured.offset);
+        Some((measured.segment.point_at(t), measured.segment.tangent_at(t)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flatten_replaces_curves_with_lines() {
+        let path: Path = "M0,0 Q5,10 10,0 Z".parse().unwrap();
+        let flattened = path.flatten(0.1);
+        assert!(flattened.elements().len() > 3);
+        assert!(flattened.elements()[1..flattened.len() - 1].iter().all(|element| matches!(element, PathElement::LineTo(_))));
+        assert_eq!(flattened.elements().last(), Some(&PathElement::Close));
+        assert_eq!(flattened.current_point(), Some(CGPoint::new(0.0, 0.0)));
+    }
+
+    #[test]
+    fn bounding_boxes() {
+        let path: Path = "M0,0 Q5,10 10,0".parse().unwrap();
+        assert_eq!(path.bounding_box(), CGRect::new(0.0, 0.0, 10.0, 10.0));
+        assert_eq!(path.path_bounding_box(), CGRect::new(0.0, 0.0, 10.0, 5.0));
+        assert!(Path::new().bounding_box().is_null());
+    }
+
+    #[test]
+    fn positions_along_a_closed_square() {
+        let measure = PathMeasure::new(&Path::from_rect(CGRect::new(0.0, 0.0, 10.0, 10.0), None));
+        assert_eq!(measure.length(), 40.0);
+        assert_eq!(measure.segment_lengths(), vec![10.0; 4]);
+        assert_eq!(measure.position_and_tangent_at(15.0), Some((CGPoint::new(10.0, 5.0), CGVector::new(0.0, 1.0))));
+        assert_eq!(measure.position_at(-1.0), Some(CGPoint::new(0.0, 0.0)));
+        assert_eq!(measure.position_at(100.0), Some(CGPoint::new(0.0, 0.0)));
+        assert_eq!(PathMeasure::new(&Path::new()).position_at(0.0), None);
+    }
+
+    #[test]
+    fn circle_length() {
+        let circle = Path::from_ellipse_in_rect(CGRect::new(0.0, 0.0, 2.0, 2.0), None);
+        // the cubic approximation of a quarter circle is slightly longer than the arc
+        assert!((circle.length() - 2.0 * std::f64::consts::PI).abs() < 1e-3);
+    }
+}
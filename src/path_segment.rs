@@ -0,0 +1,473 @@
+use crate::{
+    base::CGFloat,
+    geometry::{CGPoint, CGRect, CGRectNull, CGVector},
+    path_builder::{Path, PathElement},
+};
+
+// abscissae and weights of the 8 point Gauss-Legendre rule on [-1, 1]
+const GAUSS_LEGENDRE: [(CGFloat, CGFloat); 8] = [
+    (-0.960_289_856_497_536_2, 0.101_228_536_290_376_26),
+    (-0.796_666_477_413_626_7, 0.222_381_034_453_374_47),
+    (-0.525_532_409_916_329, 0.313_706_645_877_887_3),
+    (-0.183_434_642_495_649_8, 0.362_683_783_378_362),
+    (0.183_434_642_495_649_8, 0.362_683_783_378_362),
+    (0.525_532_409_916_329, 0.313_706_645_877_887_3),
+    (0.796_666_477_413_626_7, 0.222_381_034_453_374_47),
+    (0.960_289_856_497_536_2, 0.101_228_536_290_376_26),
+];
+
+const LENGTH_TOLERANCE: CGFloat = 1e-9;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathSegment {
+    Line(CGPoint, CGPoint),
+    Quad(CGPoint, CGPoint, CGPoint),
+    Cubic(CGPoint, CGPoint, CGPoint, CGPoint),
+}
+
+impl PathSegment {
+    pub fn start(&self) -> CGPoint {
+        match *self {
+            PathSegment::Line(p0, _) | PathSegment::Quad(p0, _, _) | PathSegment::Cubic(p0, _, _, _) => p0,
+        }
+    }
+
+    pub fn end(&self) -> CGPoint {
+        match *self {
+            PathSegment::Line(_, p1) => p1,
+            PathSegment::Quad(_, _, p2) => p2,
+            PathSegment::Cubic(_, _, _, p3) => p3,
+        }
+    }
+
+    pub fn to_element(&self) -> PathElement {
+        match *self {
+            PathSegment::Line(_, p1) => PathElement::LineTo(p1),
+            PathSegment::Quad(_, p1, p2) => PathElement::QuadTo(p1, p2),
+            PathSegment::Cubic(_, p1, p2, p3) => PathElement::CurveTo(p1, p2, p3),
+        }
+    }
+
+    pub fn point_at(&self, t: CGFloat) -> CGPoint {
+        let mt = 1.0 - t;
+        match *self {
+            PathSegment::Line(p0, p1) => p0.lerp(&p1, t),
+            PathSegment::Quad(p0, p1, p2) => {
+                CGPoint::new(mt * mt * p0.x + 2.0 * mt * t * p1.x + t * t * p2.x, mt * mt * p0.y + 2.0 * mt * t * p1.y + t * t * p2.y)
+            }
+            PathSegment::Cubic(p0, p1, p2, p3) => {
+                let (a, b, c, d) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+                CGPoint::new(a * p0.x + b * p1.x + c * p2.x + d * p3.x, a * p0.y + b * p1.y + c * p2.y + d * p3.y)
+            }
+        }
+    }
+
+    pub fn derivative_at(&self, t: CGFloat) -> CGVector {
+        let mt = 1.0 - t;
+        match *self {
+            PathSegment::Line(p0, p1) => p1 - p0,
+            PathSegment::Quad(p0, p1, p2) => (p1 - p0) * (2.0 * mt) + (p2 - p1) * (2.0 * t),
+            PathSegment::Cubic(p0, p1, p2, p3) => (p1 - p0) * (3.0 * mt * mt) + (p2 - p1) * (6.0 * mt * t) + (p3 - p2) * (3.0 * t * t),
+        }
+    }
+
+    // unit tangent, falling back to the chord of the control polygon where the derivative vanishes
+    pub fn tangent_at(&self, t: CGFloat) -> CGVector {
+        let derivative = self.derivative_at(t);
+        if derivative.length_squared() > 0.0 {
+            return derivative.normalize();
+        }
+        let (start, end) = match *self {
+            PathSegment::Line(p0, p1) | PathSegment::Quad(p0, _, p1) => (p0, p1),
+            PathSegment::Cubic(p0, _, p2, _) if t < 0.5 && p2 != p0 => (p0, p2),
+            PathSegment::Cubic(_, p1, _, p3) if t >= 0.5 && p1 != p3 => (p1, p3),
+            PathSegment::Cubic(p0, _, _, p3) => (p0, p3),
+        };
+        (end - start).normalize()
+    }
+
+    pub fn split(&self, t: CGFloat) -> (PathSegment, PathSegment) {
+        match *self {
+            PathSegment::Line(p0, p1) => {
+                let p = p0.lerp(&p1, t);
+                (PathSegment::Line(p0, p), PathSegment::Line(p, p1))
+            }
+            PathSegment::Quad(p0, p1, p2) => {
+                let p01 = p0.lerp(&p1, t);
+                let p12 = p1.lerp(&p2, t);
+                let p = p01.lerp(&p12, t);
+                (PathSegment::Quad(p0, p01, p), PathSegment::Quad(p, p12, p2))
+            }
+            PathSegment::Cubic(p0, p1, p2, p3) => {
+                let p01 = p0.lerp(&p1, t);
+                let p12 = p1.lerp(&p2, t);
+                let p23 = p2.lerp(&p3, t);
+                let p012 = p01.lerp(&p12, t);
+                let p123 = p12.lerp(&p23, t);
+                let p = p012.lerp(&p123, t);
+                (PathSegment::Cubic(p0, p01, p012, p), PathSegment::Cubic(p, p123, p23, p3))
+            }
+        }
+    }
+
+    pub fn subsegment(&self, t0: CGFloat, t1: CGFloat) -> PathSegment {
+        if t0 <= 0.0 {
+            return self.split(t1).0;
+        }
+        let (_, tail) = self.split(t0);
+        if t1 >= 1.0 {
+            return tail;
+        }
+        tail.split((t1 - t0) / (1.0 - t0)).0
+    }
+
+    pub fn reversed(&self) -> PathSegment {
+        match *self {
+            PathSegment::Line(p0, p1) => PathSegment::Line(p1, p0),
+            PathSegment::Quad(p0, p1, p2) => PathSegment::Quad(p2, p1, p0),
+            PathSegment::Cubic(p0, p1, p2, p3) => PathSegment::Cubic(p3, p2, p1, p0),
+        }
+    }
+
+    // parameters in (0, 1) where the x or y derivative vanishes
+    pub fn extrema(&self) -> Vec<CGFloat> {
+        let mut roots = Vec::new();
+        match *self {
+            PathSegment::Line(..) => {}
+            PathSegment::Quad(p0, p1, p2) => {
+                for (a, b, c) in [(p0.x, p1.x, p2.x), (p0.y, p1.y, p2.y)] {
+                    let denominator = a - 2.0 * b + c;
+                    if denominator != 0.0 {
+                        roots.push((a - b) / denominator);
+                    }
+                }
+            }
+            PathSegment::Cubic(p0, p1, p2, p3) => {
+                for (a, b, c, d) in [(p0.x, p1.x, p2.x, p3.x), (p0.y, p1.y, p2.y, p3.y)] {
+                    solve_quadratic(-a + 3.0 * b - 3.0 * c + d, 2.0 * (a - 2.0 * b + c), b - a, &mut roots);
+                }
+            }
+        }
+        roots.retain(|t| *t > 0.0 && *t < 1.0);
+        roots.sort_by(|a, b| a.total_cmp(b));
+        roots
+    }
+
+    pub fn bounding_box(&self) -> CGRect {
+        let mut bounds = Bounds::new();
+        bounds.add(self.start());
+        bounds.add(self.end());
+        for t in self.extrema() {
+            bounds.add(self.point_at(t));
+        }
+        bounds.rect()
+    }
+
+    pub fn control_bounding_box(&self) -> CGRect {
+        let mut bounds = Bounds::new();
+        match *self {
+            PathSegment::Line(p0, p1) => bounds.add_all(&[p0, p1]),
+            PathSegment::Quad(p0, p1, p2) => bounds.add_all(&[p0, p1, p2]),
+            PathSegment::Cubic(p0, p1, p2, p3) => bounds.add_all(&[p0, p1, p2, p3]),
+        }
+        bounds.rect()
+    }
+
+    // number of line segments needed to stay within tolerance of the curve, after Wang's formula
+    pub fn flattening_steps(&self, tolerance: CGFloat) -> usize {
+        let tolerance = tolerance.max(CGFloat::EPSILON);
+        let steps = match *self {
+            PathSegment::Line(..) => return 1,
+            PathSegment::Quad(p0, p1, p2) => ((p0 - p1) - (p1 - p2)).length() / (4.0 * tolerance),
+            PathSegment::Cubic(p0, p1, p2, p3) => {
+                let dd = ((p0 - p1) - (p1 - p2)).length().max(((p1 - p2) - (p2 - p3)).length());
+                0.75 * dd / tolerance
+            }
+        };
+        (steps.sqrt().ceil() as usize).clamp(1, 1 << 16)
+    }
+
+    pub fn flatten<F>(&self, tolerance: CGFloat, mut f: F)
+    where
+        F: FnMut(CGPoint),
+    {
+        let steps = self.flattening_steps(tolerance);
+        for i in 1..steps {
+            f(self.point_at(i as CGFloat / steps as CGFloat));
+        }
+        f(self.end());
+    }
+
+    pub fn length(&self) -> CGFloat {
+        self.length_between(0.0, 1.0)
+    }
+
+    pub fn length_between(&self, t0: CGFloat, t1: CGFloat) -> CGFloat {
+        match *self {
+            PathSegment::Line(p0, p1) => p0.distance(&p1) * (t1 - t0),
+            _ => {
+                let estimate = self.gauss_legendre(t0, t1);
+                self.adaptive_length(t0, t1, estimate, 0)
+            }
+        }
+    }
+
+    // parameter at which the arc length measured from the start reaches distance
+    pub fn parameter_at_length(&self, distance: CGFloat) -> CGFloat {
+        let length = self.length();
+        if distance <= 0.0 || length == 0.0 {
+            return 0.0;
+        }
+        if distance >= length {
+            return 1.0;
+        }
+        if let PathSegment::Line(..) = self {
+            return distance / length;
+        }
+        let (mut low, mut high) = (0.0, 1.0);
+        let mut t = distance / length;
+        for _ in 0..64 {
+            let error = self.length_between(0.0, t) - distance;
+            if error.abs() < LENGTH_TOLERANCE * length.max(1.0) {
+                break;
+            }
+            if error > 0.0 {
+                high = t;
+            } else {
+                low = t;
+            }
+            let speed = self.derivative_at(t).length();
+            let next = t - error / speed;
+            t = if speed > 0.0 && next > low && next < high {
+                next
+            } else {
+                (low + high) * 0.5
+            };
+        }
+        t
+    }
+
+    fn gauss_legendre(&self, t0: CGFloat, t1: CGFloat) -> CGFloat {
+        let half = (t1 - t0) * 0.5;
+        let mid = (t1 + t0) * 0.5;
+        GAUSS_LEGENDRE.iter().map(|(x, w)| w * self.derivative_at(mid + half * x).length()).sum::<CGFloat>() * half
+    }
+
+    fn adaptive_length(&self, t0: CGFloat, t1: CGFloat, estimate: CGFloat, depth: usize) -> CGFloat {
+        let mid = (t0 + t1) * 0.5;
+        let left = self.gauss_legendre(t0, mid);
+        let right = self.gauss_legendre(mid, t1);
+        if depth >= 16 || (left + right - estimate).abs() <= LENGTH_TOLERANCE * estimate.max(1.0) {
+            left + right
+        } else {
+            self.adaptive_length(t0, mid, left, depth + 1) + self.adaptive_length(mid, t1, right, depth + 1)
+        }
+    }
+}
+
+fn solve_quadratic(a: CGFloat, b: CGFloat, c: CGFloat, roots: &mut Vec<CGFloat>) {
+    if a.abs() < 1e-12 {
+        if b != 0.0 {
+            roots.push(-c / b);
+        }
+        return;
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return;
+    }
+    // avoid cancellation between -b and the square root
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    roots.push(q / a);
+    if q != 0.0 {
+        roots.push(c / q);
+    }
+}
+
+pub(crate) struct Bounds {
+    min: CGPoint,
+    max: CGPoint,
+    empty: bool,
+}
+
+impl Bounds {
+    pub(crate) fn new() -> Bounds {
+        Bounds {
+            min: CGPoint::default(),
+            max: CGPoint::default(),
+            empty: true,
+        }
+    }
+
+    pub(crate) fn add(&mut self, point: CGPoint) {
+        if self.empty {
+            self.min = point;
+            self.max = point;
+            self.empty = false;
+        } else {
+            self.min = CGPoint::new(self.min.x.min(point.x), self.min.y.min(point.y));
+            self.max = CGPoint::new(self.max.x.max(point.x), self.max.y.max(point.y));
+        }
+    }
+
+    pub(crate) fn add_all(&mut self, points: &[CGPoint]) {
+        for point in points {
+            self.add(*point);
+        }
+    }
+
+    pub(crate) fn add_rect(&mut self, rect: &CGRect) {
+        if rect.origin.x != CGFloat::INFINITY && rect.origin.y != CGFloat::INFINITY {
+            self.add(rect.origin);
+            self.add(CGPoint::new(rect.origin.x + rect.size.width, rect.origin.y + rect.size.height));
+        }
+    }
+
+    pub(crate) fn rect(&self) -> CGRect {
+        if self.empty {
+            CGRectNull
+        } else {
+            CGRect::new(self.min.x, self.min.y, self.max.x - self.min.x, self.max.y - self.min.y)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subpath {
+    pub start: CGPoint,
+    pub segments: Vec<PathSegment>,
+    pub closed: bool,
+}
+
+impl Subpath {
+    fn new(start: CGPoint) -> Subpath {
+        Subpath {
+            start,
+            segments: Vec::new(),
+            closed: false,
+        }
+    }
+
+    pub fn end(&self) -> CGPoint {
+        self.segments.last().map_or(self.start, |segment| segment.end())
+    }
+
+    pub fn to_path(&self) -> Path {
+        let mut elements = Vec::with_capacity(self.segments.len() + 2);
+        elements.push(PathElement::MoveTo(self.start));
+        elements.extend(self.segments.iter().map(|segment| segment.to_element()));
+        if self.closed {
+            elements.push(PathElement::Close);
+        }
+        Path::from_elements(elements)
+    }
+}
+
+impl Path {
+    // closing a subpath adds an explicit line back to its start when the two points differ
+    pub fn subpaths(&self) -> Vec<Subpath> {
+        let mut subpaths = Vec::new();
+        let mut current: Option<Subpath> = None;
+        for element in self {
+            if let PathElement::MoveTo(point) = *element {
+                subpaths.extend(current.take());
+                current = Some(Subpath::new(point));
+                continue;
+            }
+            let subpath = match current.as_mut() {
+                Some(subpath) if subpath.closed => {
+                    let start = subpath.start;
+                    subpaths.extend(current.take());
+                    current.insert(Subpath::new(start))
+                }
+                Some(subpath) => subpath,
+                None => match element.end_point() {
+                    Some(point) => current.insert(Subpath::new(point)),
+                    None => continue,
+                },
+            };
+            let last = subpath.end();
+            match *element {
+                PathElement::MoveTo(_) => {}
+                PathElement::LineTo(p1) => subpath.segments.push(PathSegment::Line(last, p1)),
+                PathElement::QuadTo(p1, p2) => subpath.segments.push(PathSegment::Quad(last, p1, p2)),
+                PathElement::CurveTo(p1, p2, p3) => subpath.segments.push(PathSegment::Cubic(last, p1, p2, p3)),
+                PathElement::Close => {
+                    if last != subpath.start {
+                        subpath.segments.push(PathSegment::Line(last, subpath.start));
+                    }
+                    subpath.closed = true;
+                }
+            }
+        }
+        subpaths.extend(current);
+        subpaths
+    }
+
+    pub fn segments(&self) -> Vec<PathSegment> {
+        self.subpaths().into_iter().flat_map(|subpath| subpath.segments).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cubic() -> PathSegment {
+        PathSegment::Cubic(CGPoint::new(0.0, 0.0), CGPoint::new(0.0, 10.0), CGPoint::new(10.0, 10.0), CGPoint::new(10.0, 0.0))
+    }
+
+    #[test]
+    fn cubic_bounds_use_the_extrema() {
+        assert_eq!(cubic().extrema(), vec![0.5]);
+        assert_eq!(cubic().bounding_box(), CGRect::new(0.0, 0.0, 10.0, 7.5));
+        assert_eq!(cubic().control_bounding_box(), CGRect::new(0.0, 0.0, 10.0, 10.0));
+    }
+
+    #[test]
+    fn split_halves_meet_on_the_curve() {
+        let (first, second) = cubic().split(0.25);
+        assert_eq!(first.start(), cubic().start());
+        assert_eq!(second.end(), cubic().end());
+        assert_eq!(first.end(), cubic().point_at(0.25));
+        assert_eq!(second.start(), cubic().point_at(0.25));
+    }
+
+    #[test]
+    fn flattening_stays_within_tolerance() {
+        let segment = cubic();
+        let steps = segment.flattening_steps(0.01);
+        let mut points = vec![segment.start()];
+        segment.flatten(0.01, |point| points.push(point));
+        assert_eq!(points.len(), steps + 1);
+        assert_eq!(*points.last().unwrap(), segment.end());
+        // compare the midpoint of every chord with the curve at the middle parameter
+        for (i, chord) in points.windows(2).enumerate() {
+            let t = (i as CGFloat + 0.5) / steps as CGFloat;
+            assert!(chord[0].midpoint(&chord[1]).distance(&segment.point_at(t)) <= 0.01);
+        }
+    }
+
+    #[test]
+    fn lengths() {
+        let line = PathSegment::Line(CGPoint::new(0.0, 0.0), CGPoint::new(3.0, 4.0));
+        assert_eq!(line.length(), 5.0);
+        assert_eq!(line.parameter_at_length(2.5), 0.5);
+        // a degree elevated line measures the same as the line itself
+        let quad = PathSegment::Quad(CGPoint::new(0.0, 0.0), CGPoint::new(1.5, 2.0), CGPoint::new(3.0, 4.0));
+        assert!((quad.length() - 5.0).abs() < 1e-9);
+        let t = cubic().parameter_at_length(cubic().length() * 0.5);
+        assert!((t - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn closing_adds_a_line_back_to_the_start() {
+        let path: Path = "M0,0 L10,0 L10,10 Z L5,5".parse().unwrap();
+        let subpaths = path.subpaths();
+        assert_eq!(subpaths.len(), 2);
+        assert!(subpaths[0].closed);
+        assert_eq!(subpaths[0].segments.len(), 3);
+        assert_eq!(subpaths[1].start, CGPoint::new(0.0, 0.0));
+        assert_eq!(subpaths[1].segments, vec![PathSegment::Line(CGPoint::new(0.0, 0.0), CGPoint::new(5.0, 5.0))]);
+    }
+}
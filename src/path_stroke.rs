@@ -0,0 +1,470 @@
+use std::f64::consts::PI;
+
+use crate::{
+    affine_transform::CGAffineTransform,
+    base::CGFloat,
+    geometry::{CGPoint, CGVector},
+    path::{CGLineCap, CGLineJoin},
+    path_builder::{Path, PathElement, FLATTENING_TOLERANCE},
+    path_segment::Subpath,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrokeStyle {
+    pub line_width: CGFloat,
+    pub line_cap: CGLineCap,
+    pub line_join: CGLineJoin,
+    pub miter_limit: CGFloat,
+    pub dash_phase: CGFloat,
+    pub dash_lengths: Vec<CGFloat>,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> StrokeStyle {
+        StrokeStyle {
+            line_width: 1.0,
+            line_cap: CGLineCap::Butt,
+            line_join: CGLineJoin::Miter,
+            miter_limit: 10.0,
+            dash_phase: 0.0,
+            dash_lengths: Vec::new(),
+        }
+    }
+}
+
+impl StrokeStyle {
+    pub fn new(line_width: CGFloat, line_cap: CGLineCap, line_join: CGLineJoin, miter_limit: CGFloat) -> StrokeStyle {
+        StrokeStyle {
+            line_width,
+            line_cap,
+            line_join,
+            miter_limit,
+            ..StrokeStyle::default()
+        }
+    }
+
+    pub fn with_dash(mut self, phase: CGFloat, lengths: &[CGFloat]) -> StrokeStyle {
+        self.dash_phase = phase;
+        self.dash_lengths = lengths.to_vec();
+        self
+    }
+
+    // the outline is meant to be filled with the nonzero winding rule
+    pub fn stroke(&self, path: &Path) -> Path {
+        self.stroke_with_tolerance(path, FLATTENING_TOLERANCE)
+    }
+
+    pub fn stroke_with_tolerance(&self, path: &Path, tolerance: CGFloat) -> Path {
+        let dashed;
+        let path = if self.dash_lengths.is_empty() {
+            path
+        } else {
+            dashed = dash(path, self.dash_phase, &self.dash_lengths);
+            &dashed
+        };
+        let mut stroker = Stroker {
+            style: self,
+            half_width: self.line_width.abs() * 0.5,
+            elements: Vec::new(),
+        };
+        if stroker.half_width > 0.0 {
+            for subpath in path.subpaths() {
+                stroker.stroke_subpath(&subpath, tolerance);
+            }
+        }
+        Path::from_elements(stroker.elements)
+    }
+}
+
+impl Path {
+    pub fn new_copy_by_stroking_path(
+        &self,
+        transform: Option<&CGAffineTransform>,
+        line_width: CGFloat,
+        line_cap: CGLineCap,
+        line_join: CGLineJoin,
+        miter_limit: CGFloat,
+    ) -> Path {
+        // keep the flattening error constant in the transformed space
+        let scale = transform.map_or(1.0, |t| (t.a * t.a + t.b * t.b).max(t.c * t.c + t.d * t.d).sqrt());
+        let tolerance = if scale > 0.0 {
+            FLATTENING_TOLERANCE / scale
+        } else {
+            FLATTENING_TOLERANCE
+        };
+        let outline = StrokeStyle::new(line_width, line_cap, line_join, miter_limit).stroke_with_tolerance(self, tolerance);
+        match transform {
+            Some(transform) => outline.apply_transform(transform),
+            None => outline,
+        }
+    }
+
+    pub fn new_copy_by_dashing_path(&self, transform: Option<&CGAffineTransform>, phase: CGFloat, lengths: &[CGFloat]) -> Path {
+        let dashed = dash(self, phase, lengths);
+        match transform {
+            Some(transform) => dashed.apply_transform(transform),
+            None => dashed,
+        }
+    }
+}
+
+struct Stroker<'a> {
+    style: &'a StrokeStyle,
+    half_width: CGFloat,
+    elements: Vec<PathElement>,
+}
+
+impl Stroker<'_> {
+    fn stroke_subpath(&mut self, subpath: &Subpath, tolerance: CGFloat) {
+        // flattened vertices, flagged when they sit on a boundary between two path segments
+        let mut points = vec![subpath.start];
+        let mut corners = vec![true];
+        for segment in &subpath.segments {
+            segment.flatten(tolerance, |point| {
+                if Some(&point) != points.last() {
+                    points.push(point);
+                    corners.push(false);
+                }
+            });
+            if let Some(corner) = corners.last_mut() {
+                *corner = true;
+            }
+        }
+        if subpath.closed && points.len() > 2 && points.first() == points.last() {
+            points.pop();
+            corners.pop();
+        }
+
+        if points.len() < 2 {
+            if !subpath.segments.is_empty() || subpath.closed {
+                self.add_dot(subpath.start);
+            }
+            return;
+        }
+        if subpath.closed {
+            self.add_closed_side(&points, &corners);
+            points.reverse();
+            corners.reverse();
+            self.add_closed_side(&points, &corners);
+        } else {
+            let count = points.len();
+            let first_direction = direction(points[0], points[1]);
+            let last_direction = direction(points[count - 2], points[count - 1]);
+            self.elements.push(PathElement::MoveTo(points[0] + left_normal(first_direction) * self.half_width));
+            self.add_open_side(&points, &corners);
+            self.add_cap(points[count - 1], last_direction);
+            points.reverse();
+            corners.reverse();
+            self.add_open_side(&points, &corners);
+            self.add_cap(points[count - 1], -first_direction);
+            self.elements.push(PathElement::Close);
+        }
+    }
+
+    fn add_open_side(&mut self, points: &[CGPoint], corners: &[bool]) {
+        for i in 1..points.len() - 1 {
+            self.add_join(points[i], direction(points[i - 1], points[i]), direction(points[i], points[i + 1]), corners[i]);
+        }
+        let count = points.len();
+        let last = points[count - 1] + left_normal(direction(points[count - 2], points[count - 1])) * self.half_width;
+        self.elements.push(PathElement::LineTo(last));
+    }
+
+    fn add_closed_side(&mut self, points: &[CGPoint], corners: &[bool]) {
+        let count = points.len();
+        self.elements.push(PathElement::MoveTo(points[0] + left_normal(direction(points[0], points[1])) * self.half_width));
+        for i in 1..=count {
+            let (previous, current, next) = (points[i - 1], points[i % count], points[(i + 1) % count]);
+            self.add_join(current, direction(previous, current), direction(current, next), corners[i % count]);
+        }
+        self.elements.push(PathElement::Close);
+    }
+
+    // appends the left offset of the vertex, ending at the start of the outgoing offset edge
+    fn add_join(&mut self, point: CGPoint, incoming: CGVector, outgoing: CGVector, corner: bool) {
+        let w = self.half_width;
+        let (n0, n1) = (left_normal(incoming), left_normal(outgoing));
+        let cross = incoming.cross(&outgoing);
+        let dot = incoming.dot(&outgoing);
+        if cross.abs() < 1e-12 && dot > 0.0 {
+            return;
+        }
+        self.elements.push(PathElement::LineTo(point + n0 * w));
+        if cross > 0.0 {
+            // inner side of the turn, route through the vertex so that the nonzero fill covers the overlap
+            self.elements.push(PathElement::LineTo(point));
+            self.elements.push(PathElement::LineTo(point + n1 * w));
+            return;
+        }
+        let join = if corner {
+            self.style.line_join
+        } else if 1.0 + dot < 1e-6 {
+            CGLineJoin::Round
+        } else {
+            CGLineJoin::Miter
+        };
+        match join {
+            CGLineJoin::Miter => {
+                let miter_ratio = 1.0 / ((1.0 + dot) * 0.5).sqrt();
+                if !corner || miter_ratio <= self.style.miter_limit {
+                    self.elements.push(PathElement::LineTo(point + (n0 + n1) * (w / (1.0 + dot))));
+                }
+            }
+            CGLineJoin::Round => {
+                let sweep = if cross.abs() < 1e-12 {
+                    -PI
+                } else {
+                    n0.angle_to(&n1)
+                };
+                add_arc(&mut self.elements, point, w, n0.angle(), sweep);
+            }
+            CGLineJoin::Bevel => {}
+        }
+        self.elements.push(PathElement::LineTo(point + n1 * w));
+    }
+
+    fn add_cap(&mut self, point: CGPoint, direction: CGVector) {
+        let w = self.half_width;
+        let normal = left_normal(direction);
+        match self.style.line_cap {
+            CGLineCap::Butt => {}
+            CGLineCap::Square => {
+                self.elements.push(PathElement::LineTo(point + (normal + direction) * w));
+                self.elements.push(PathElement::LineTo(point + (direction - normal) * w));
+            }
+            CGLineCap::Round => add_arc(&mut self.elements, point, w, normal.angle(), -PI),
+        }
+        self.elements.push(PathElement::LineTo(point - normal * w));
+    }
+
+    fn add_dot(&mut self, point: CGPoint) {
+        let w = self.half_width;
+        match self.style.line_cap {
+            CGLineCap::Butt => {}
+            CGLineCap::Square => {
+                self.elements.push(PathElement::MoveTo(point + CGVector::new(-w, -w)));
+                self.elements.push(PathElement::LineTo(point + CGVector::new(w, -w)));
+                self.elements.push(PathElement::LineTo(point + CGVector::new(w, w)));
+                self.elements.push(PathElement::LineTo(point + CGVector::new(-w, w)));
+                self.elements.push(PathElement::Close);
+            }
+            CGLineCap::Round => {
+                self.elements.push(PathElement::MoveTo(point + CGVector::new(w, 0.0)));
+                add_arc(&mut self.elements, point, w, 0.0, 2.0 * PI);
+                self.elements.push(PathElement::Close);
+            }
+        }
+    }
+}
+
+fn direction(from: CGPoint, to: CGPoint) -> CGVector {
+    (to - from).normalize()
+}
+
+fn left_normal(direction: CGVector) -> CGVector {
+    direction.perpendicular()
+}
+
+// cubic approximation of a circular arc starting on the circle at start_angle
+fn add_arc(elements: &mut Vec<PathElement>, center: CGPoint, radius: CGFloat, start_angle: CGFloat, sweep: CGFloat) {
+    let segments = (sweep.abs() / (PI / 2.0) - 1e-9).ceil().max(1.0) as usize;
+    let step = sweep / segments as CGFloat;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let mut angle = start_angle;
+    for _ in 0..segments {
+        let (sin0, cos0) = angle.sin_cos();
+        let (sin1, cos1) = (angle + step).sin_cos();
+        elements.push(PathElement::CurveTo(
+            center + CGVector::new(cos0 - k * sin0, sin0 + k * cos0) * radius,
+            center + CGVector::new(cos1 + k * sin1, sin1 - k * cos1) * radius,
+            center + CGVector::new(cos1, sin1) * radius,
+        ));
+        angle += step;
+    }
+}
+
+struct DashState<'a> {
+    lengths: &'a [CGFloat],
+    index: usize,
+    on: bool,
+    remaining: CGFloat,
+}
+
+impl<'a> DashState<'a> {
+    fn new(lengths: &'a [CGFloat], phase: CGFloat) -> DashState<'a> {
+        let mut state = DashState {
+            lengths,
+            index: 0,
+            on: true,
+            remaining: lengths[0],
+        };
+        // an odd number of lengths alternates between on and off over two passes of the array
+        let total: CGFloat = lengths.iter().sum();
+        let cycle = if lengths.len() % 2 == 0 {
+            total
+        } else {
+            total * 2.0
+        };
+        let mut phase = phase.rem_euclid(cycle);
+        while phase > 0.0 {
+            if phase >= state.remaining {
+                phase -= state.remaining;
+                state.advance();
+            } else {
+                state.remaining -= phase;
+                phase = 0.0;
+            }
+        }
+        state
+    }
+
+    fn advance(&mut self) {
+        self.index = (self.index + 1) % self.lengths.len();
+        self.on = !self.on;
+        self.remaining = self.lengths[self.index];
+    }
+}
+
+fn dash(path: &Path, phase: CGFloat, lengths: &[CGFloat]) -> Path {
+    if lengths.is_empty() || lengths.iter().any(|length| *length < 0.0 || !length.is_finite()) || lengths.iter().sum::<CGFloat>() <= 0.0 {
+        return path.clone();
+    }
+    let mut elements = Vec::new();
+    for subpath in path.subpaths() {
+        let mut state = DashState::new(lengths, phase);
+        let mut drawing = false;
+        for segment in &subpath.segments {
+            let length = segment.length();
+            let mut position = 0.0;
+            loop {
+                if state.on && state.remaining == 0.0 {
+                    // zero length dashes still produce caps
+                    let point = segment.point_at(segment.parameter_at_length(position));
+                    elements.push(PathElement::MoveTo(point));
+                    elements.push(PathElement::LineTo(point));
+                    drawing = false;
+                    state.advance();
+                    continue;
+                }
+                let step = state.remaining.min(length - position);
+                if state.on && step > 0.0 {
+                    let t0 = segment.parameter_at_length(position);
+                    let t1 = segment.parameter_at_length(position + step);
+                    if !drawing {
+                        elements.push(PathElement::MoveTo(segment.point_at(t0)));
+                        drawing = true;
+                    }
+                    elements.push(segment.subsegment(t0, t1).to_element());
+                }
+                position += step;
+                state.remaining -= step;
+                if state.remaining <= 0.0 {
+                    if state.on {
+                        drawing = false;
+                    }
+                    state.advance();
+                }
+                if position >= length {
+                    break;
+                }
+            }
+        }
+        if state.on && state.remaining == 0.0 && !subpath.segments.is_empty() {
+            let point = subpath.end();
+            elements.push(PathElement::MoveTo(point));
+            elements.push(PathElement::LineTo(point));
+        }
+    }
+    Path::from_elements(elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::CGRect;
+
+    fn line() -> Path {
+        Path::from_elements(vec![PathElement::MoveTo(CGPoint::new(0.0, 0.0)), PathElement::LineTo(CGPoint::new(10.0, 0.0))])
+    }
+
+    fn corner() -> Path {
+        "M0,0 L10,0 L10,10".parse().unwrap()
+    }
+
+    // nonzero winding number of the flattened outline, with every subpath implicitly closed
+    fn covers(outline: &Path, x: CGFloat, y: CGFloat) -> bool {
+        let point = CGPoint::new(x, y);
+        let mut winding = 0;
+        for subpath in outline.subpaths() {
+            let mut points = vec![subpath.start];
+            for segment in &subpath.segments {
+                segment.flatten(0.01, |next| points.push(next));
+            }
+            points.push(subpath.start);
+            for line in points.windows(2) {
+                let side = (line[1] - line[0]).cross(&(point - line[0]));
+                if line[0].y <= y && line[1].y > y && side > 0.0 {
+                    winding += 1;
+                } else if line[0].y > y && line[1].y <= y && side < 0.0 {
+                    winding -= 1;
+                }
+            }
+        }
+        winding != 0
+    }
+
+    #[test]
+    fn butt_cap_outline_is_the_line_rectangle() {
+        let outline = StrokeStyle::new(2.0, CGLineCap::Butt, CGLineJoin::Miter, 10.0).stroke(&line());
+        assert_eq!(outline.path_bounding_box(), CGRect::new(0.0, -1.0, 10.0, 2.0));
+    }
+
+    #[test]
+    fn caps_extend_past_the_ends() {
+        let square = StrokeStyle::new(2.0, CGLineCap::Square, CGLineJoin::Miter, 10.0).stroke(&line());
+        assert_eq!(square.path_bounding_box(), CGRect::new(-1.0, -1.0, 12.0, 2.0));
+        let round = StrokeStyle::new(2.0, CGLineCap::Round, CGLineJoin::Miter, 10.0).stroke(&line());
+        let bounds = round.path_bounding_box();
+        assert!((bounds.origin.x + 1.0).abs() < 1e-9 && (bounds.size.width - 12.0).abs() < 1e-9);
+        assert!(covers(&round, -0.5, 0.5) && !covers(&round, -0.9, 0.9));
+    }
+
+    #[test]
+    fn joins() {
+        let miter = StrokeStyle::new(2.0, CGLineCap::Butt, CGLineJoin::Miter, 10.0).stroke(&corner());
+        assert!(covers(&miter, 10.9, -0.9));
+        let bevel = StrokeStyle::new(2.0, CGLineCap::Butt, CGLineJoin::Bevel, 10.0).stroke(&corner());
+        assert!(!covers(&bevel, 10.9, -0.9) && covers(&bevel, 10.4, -0.4));
+        let round = StrokeStyle::new(2.0, CGLineCap::Butt, CGLineJoin::Round, 10.0).stroke(&corner());
+        assert!(!covers(&round, 10.9, -0.9) && covers(&round, 10.6, -0.6));
+        // the miter length of a right angle is sqrt(2) times the line width, beyond the limit it falls back to a bevel
+        let limited = StrokeStyle::new(2.0, CGLineCap::Butt, CGLineJoin::Miter, 1.4).stroke(&corner());
+        assert!(!covers(&limited, 10.9, -0.9));
+    }
+
+    #[test]
+    fn closed_square_has_an_outline_and_a_hole() {
+        let outline = StrokeStyle::default().stroke(&Path::from_rect(CGRect::new(0.0, 0.0, 10.0, 10.0), None));
+        assert_eq!(outline.subpaths().len(), 2);
+        assert!(covers(&outline, -0.4, 5.0) && covers(&outline, 0.0, 0.0));
+        assert!(!covers(&outline, 5.0, 5.0) && !covers(&outline, -0.6, 5.0));
+    }
+
+    #[test]
+    fn dashes() {
+        let dashed = line().new_copy_by_dashing_path(None, 1.0, &[2.0, 1.0]);
+        let starts: Vec<_> = dashed.subpaths().iter().map(|subpath| (subpath.start.x, subpath.end().x)).collect();
+        assert_eq!(starts, vec![(0.0, 1.0), (2.0, 4.0), (5.0, 7.0), (8.0, 10.0)]);
+        // zero length dashes still get round caps
+        let dots = StrokeStyle::new(2.0, CGLineCap::Round, CGLineJoin::Miter, 10.0).with_dash(0.0, &[0.0, 5.0]).stroke(&line());
+        assert_eq!(dots.subpaths().len(), 3);
+        assert!(covers(&dots, 5.0, 0.9) && !covers(&dots, 2.5, 0.0));
+    }
+
+    #[test]
+    fn transformed_stroke() {
+        let transform = CGAffineTransform::new_scale(2.0, 2.0);
+        let outline = line().new_copy_by_stroking_path(Some(&transform), 2.0, CGLineCap::Butt, CGLineJoin::Miter, 10.0);
+        assert_eq!(outline.path_bounding_box(), CGRect::new(0.0, -2.0, 20.0, 4.0));
+    }
+}
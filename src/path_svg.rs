@@ -0,0 +1,447 @@
+use std::{
+    error::Error,
+    f64::consts::PI,
+    fmt::{self, Display, Formatter, Write},
+    str::FromStr,
+};
+
+use crate::{
+    affine_transform::CGAffineTransform,
+    base::CGFloat,
+    geometry::{CGPoint, CGVector},
+    path_builder::{Path, PathBuilder, PathElement},
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SvgPathError {
+    UnexpectedCharacter(usize),
+    UnexpectedEnd,
+    MissingMoveTo(usize),
+}
+
+impl Display for SvgPathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SvgPathError::UnexpectedCharacter(position) => write!(f, "unexpected character at position {}", position),
+            SvgPathError::UnexpectedEnd => write!(f, "unexpected end of path data"),
+            SvgPathError::MissingMoveTo(position) => write!(f, "path data must start with a moveto command, found command at position {}", position),
+        }
+    }
+}
+
+impl Error for SvgPathError {}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a str) -> Parser<'a> {
+        Parser {
+            data: data.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r' | b'\x0C') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn skip_separator(&mut self) {
+        self.skip_whitespace();
+        if self.peek() == Some(b',') {
+            self.position += 1;
+            self.skip_whitespace();
+        }
+    }
+
+    fn at_number(&mut self) -> bool {
+        self.skip_whitespace();
+        matches!(self.peek(), Some(b'0'..=b'9' | b'-' | b'+' | b'.'))
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.position;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    fn number(&mut self) -> Result<CGFloat, SvgPathError> {
+        self.skip_whitespace();
+        let start = self.position;
+        if let Some(b'-' | b'+') = self.peek() {
+            self.position += 1;
+        }
+        let mut digits = self.digits();
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            digits += self.digits();
+        }
+        if digits == 0 {
+            return Err(self.error_at(start));
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            let mantissa_end = self.position;
+            self.position += 1;
+            if let Some(b'-' | b'+') = self.peek() {
+                self.position += 1;
+            }
+            if self.digits() == 0 {
+                self.position = mantissa_end;
+            }
+        }
+        // the scanned range only contains ASCII digits, signs, dots and exponents
+        let number = std::str::from_utf8(&self.data[start..self.position]).map_err(|_| self.error_at(start))?;
+        let number = number.parse::<CGFloat>().map_err(|_| self.error_at(start))?;
+        self.skip_separator();
+        Ok(number)
+    }
+
+    fn flag(&mut self) -> Result<bool, SvgPathError> {
+        self.skip_whitespace();
+        let flag = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(self.error_at(self.position)),
+        };
+        self.position += 1;
+        self.skip_separator();
+        Ok(flag)
+    }
+
+    fn point(&mut self) -> Result<CGPoint, SvgPathError> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Ok(CGPoint::new(x, y))
+    }
+
+    fn error_at(&self, position: usize) -> SvgPathError {
+        if position >= self.data.len() {
+            SvgPathError::UnexpectedEnd
+        } else {
+            SvgPathError::UnexpectedCharacter(position)
+        }
+    }
+}
+
+pub fn parse_svg_path_data(data: &str) -> Result<Path, SvgPathError> {
+    let mut parser = Parser::new(data);
+    let mut builder = PathBuilder::new();
+    let mut command: Option<u8> = None;
+    let mut current = CGPoint::default();
+    let mut subpath_start = CGPoint::default();
+    // the reflected control point for a following S/s or T/t command
+    let mut last_control: Option<(u8, CGPoint)> = None;
+    let mut closed = false;
+
+    loop {
+        parser.skip_whitespace();
+        let position = parser.position;
+        let next = match parser.peek() {
+            None => break,
+            Some(next) => next,
+        };
+        let cmd = if next.is_ascii_alphabetic() {
+            parser.position += 1;
+            next
+        } else {
+            match command {
+                Some(b'M') if parser.at_number() => b'L',
+                Some(b'm') if parser.at_number() => b'l',
+                Some(b'Z' | b'z') | None => return Err(parser.error_at(position)),
+                Some(command) if parser.at_number() => command,
+                Some(_) => return Err(parser.error_at(position)),
+            }
+        };
+        if command.is_none() && !matches!(cmd, b'M' | b'm') {
+            return Err(SvgPathError::MissingMoveTo(position));
+        }
+        if closed && !matches!(cmd, b'M' | b'm' | b'Z' | b'z') {
+            builder.push(PathElement::MoveTo(subpath_start));
+        }
+        closed = false;
+        let relative = cmd.is_ascii_lowercase();
+        let origin = if relative {
+            current.to_vector()
+        } else {
+            CGVector::default()
+        };
+        let offset = |point: CGPoint| point + origin;
+
+        let mut control = None;
+        match cmd.to_ascii_uppercase() {
+            b'M' => {
+                current = offset(parser.point()?);
+                subpath_start = current;
+                builder.push(PathElement::MoveTo(current));
+            }
+            b'L' => {
+                current = offset(parser.point()?);
+                builder.push(PathElement::LineTo(current));
+            }
+            b'H' => {
+                let x = parser.number()?;
+                current.x = if relative {
+                    current.x + x
+                } else {
+                    x
+                };
+                builder.push(PathElement::LineTo(current));
+            }
+            b'V' => {
+                let y = parser.number()?;
+                current.y = if relative {
+                    current.y + y
+                } else {
+                    y
+                };
+                builder.push(PathElement::LineTo(current));
+            }
+            b'C' => {
+                let control1 = offset(parser.point()?);
+                let control2 = offset(parser.point()?);
+                let end = offset(parser.point()?);
+                builder.push(PathElement::CurveTo(control1, control2, end));
+                control = Some((b'C', control2));
+                current = end;
+            }
+            b'S' => {
+                let control1 = reflect(last_control, b'C', current);
+                let control2 = offset(parser.point()?);
+                let end = offset(parser.point()?);
+                builder.push(PathElement::CurveTo(control1, control2, end));
+                control = Some((b'C', control2));
+                current = end;
+            }
+            b'Q' => {
+                let control1 = offset(parser.point()?);
+                let end = offset(parser.point()?);
+                builder.push(PathElement::QuadTo(control1, end));
+                control = Some((b'Q', control1));
+                current = end;
+            }
+            b'T' => {
+                let control1 = reflect(last_control, b'Q', current);
+                let end = offset(parser.point()?);
+                builder.push(PathElement::QuadTo(control1, end));
+                control = Some((b'Q', control1));
+                current = end;
+            }
+            b'A' => {
+                let rx = parser.number()?;
+                let ry = parser.number()?;
+                let x_axis_rotation = parser.number()?;
+                let large_arc = parser.flag()?;
+                let sweep = parser.flag()?;
+                let end = offset(parser.point()?);
+                add_svg_arc(&mut builder, current, end, rx, ry, x_axis_rotation, large_arc, sweep);
+                current = end;
+            }
+            b'Z' => {
+                builder.push(PathElement::Close);
+                current = subpath_start;
+                closed = true;
+            }
+            _ => return Err(SvgPathError::UnexpectedCharacter(position)),
+        }
+        last_control = control;
+        command = Some(cmd);
+    }
+
+    Ok(builder.build())
+}
+
+fn reflect(last_control: Option<(u8, CGPoint)>, kind: u8, current: CGPoint) -> CGPoint {
+    match last_control {
+        Some((last_kind, point)) if last_kind == kind => current + (current - point),
+        _ => current,
+    }
+}
+
+// endpoint to center parameterization, see the SVG 1.1 implementation notes F.6.5
+#[allow(clippy::too_many_arguments)]
+fn add_svg_arc(
+    builder: &mut PathBuilder,
+    start: CGPoint,
+    end: CGPoint,
+    rx: CGFloat,
+    ry: CGFloat,
+    x_axis_rotation: CGFloat,
+    large_arc: bool,
+    sweep: bool,
+) {
+    if start == end {
+        return;
+    }
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0.0 || ry == 0.0 {
+        builder.push(PathElement::LineTo(end));
+        return;
+    }
+    let phi = x_axis_rotation.to_radians();
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let half = (start - end) * 0.5;
+    let x1 = cos_phi * half.dx + sin_phi * half.dy;
+    let y1 = -sin_phi * half.dx + cos_phi * half.dy;
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coefficient = (numerator / denominator).max(0.0).sqrt();
+    if large_arc == sweep {
+        coefficient = -coefficient;
+    }
+    let cx1 = coefficient * rx * y1 / ry;
+    let cy1 = -coefficient * ry * x1 / rx;
+    let mid = start.midpoint(&end);
+    let center = CGPoint::new(cos_phi * cx1 - sin_phi * cy1 + mid.x, sin_phi * cx1 + cos_phi * cy1 + mid.y);
+    let u = CGVector::new((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let v = CGVector::new((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+    let start_angle = u.angle();
+    let mut delta = u.angle_to(&v);
+    if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    } else if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    }
+
+    // build the arc on the unit circle and map it onto the ellipse
+    let transform = CGAffineTransform {
+        a: rx * cos_phi,
+        b: rx * sin_phi,
+        c: -ry * sin_phi,
+        d: ry * cos_phi,
+        tx: center.x,
+        ty: center.y,
+    };
+    let segments = (delta.abs() / (PI / 2.0) - 1e-9).ceil().max(1.0);
+    let step = delta / segments;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let mut angle = start_angle;
+    for segment in 0..segments as usize {
+        let (sin0, cos0) = angle.sin_cos();
+        let (sin1, cos1) = (angle + step).sin_cos();
+        let control1 = CGPoint::new(cos0 - k * sin0, sin0 + k * cos0).apply_transform(&transform);
+        let control2 = CGPoint::new(cos1 + k * sin1, sin1 - k * cos1).apply_transform(&transform);
+        // land exactly on the requested endpoint to avoid accumulating rounding errors
+        let point = if segment + 1 == segments as usize {
+            end
+        } else {
+            CGPoint::new(cos1, sin1).apply_transform(&transform)
+        };
+        builder.push(PathElement::CurveTo(control1, control2, point));
+        angle += step;
+    }
+}
+
+pub fn svg_path_data(path: &Path) -> String {
+    let mut data = String::new();
+    for element in path {
+        if !data.is_empty() {
+            data.push(' ');
+        }
+        let _ = match *element {
+            PathElement::MoveTo(point) => write!(data, "M{},{}", point.x, point.y),
+            PathElement::LineTo(point) => write!(data, "L{},{}", point.x, point.y),
+            PathElement::QuadTo(control, point) => write!(data, "Q{},{} {},{}", control.x, control.y, point.x, point.y),
+            PathElement::CurveTo(control1, control2, point) => {
+                write!(data, "C{},{} {},{} {},{}", control1.x, control1.y, control2.x, control2.y, point.x, point.y)
+            }
+            PathElement::Close => write!(data, "Z"),
+        };
+    }
+    data
+}
+
+impl Path {
+    pub fn from_svg_path_data(data: &str) -> Result<Path, SvgPathError> {
+        parse_svg_path_data(data)
+    }
+
+    pub fn to_svg_path_data(&self) -> String {
+        svg_path_data(self)
+    }
+}
+
+impl FromStr for Path {
+    type Err = SvgPathError;
+
+    fn from_str(data: &str) -> Result<Path, SvgPathError> {
+        parse_svg_path_data(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: CGFloat, y: CGFloat) -> CGPoint {
+        CGPoint::new(x, y)
+    }
+
+    #[test]
+    fn relative_and_implicit_commands() {
+        let path = parse_svg_path_data("m10 10 5,0 h5 v-5 l-10-5z").unwrap();
+        let expected = [
+            PathElement::MoveTo(point(10.0, 10.0)),
+            PathElement::LineTo(point(15.0, 10.0)),
+            PathElement::LineTo(point(20.0, 10.0)),
+            PathElement::LineTo(point(20.0, 5.0)),
+            PathElement::LineTo(point(10.0, 0.0)),
+            PathElement::Close,
+        ];
+        assert_eq!(path.elements(), &expected);
+    }
+
+    #[test]
+    fn smooth_curves_reflect_the_previous_control_point() {
+        let path = parse_svg_path_data("M0,0 C0,10 10,10 10,0 S20,-10 20,0 Q25,5 30,0 T40,0").unwrap();
+        assert_eq!(path.elements()[2], PathElement::CurveTo(point(10.0, -10.0), point(20.0, -10.0), point(20.0, 0.0)));
+        assert_eq!(path.elements()[4], PathElement::QuadTo(point(35.0, -5.0), point(40.0, 0.0)));
+    }
+
+    #[test]
+    fn compact_numbers_and_arc_flags() {
+        let path = parse_svg_path_data("M.5-.5L1e1.25a5 5 0 1010 0").unwrap();
+        assert_eq!(path.elements()[0], PathElement::MoveTo(point(0.5, -0.5)));
+        assert_eq!(path.elements()[1], PathElement::LineTo(point(10.0, 0.25)));
+        // a half circle takes two quarter segments and ends exactly on the endpoint
+        assert_eq!(path.elements().len(), 4);
+        assert_eq!(path.current_point(), Some(point(20.0, 0.25)));
+    }
+
+    #[test]
+    fn drawing_after_close_starts_at_the_subpath_start() {
+        let path = parse_svg_path_data("M1,1 L2,2 Z l1,0").unwrap();
+        assert_eq!(path.elements()[3], PathElement::MoveTo(point(1.0, 1.0)));
+        assert_eq!(path.elements()[4], PathElement::LineTo(point(2.0, 1.0)));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse_svg_path_data("L1,1"), Err(SvgPathError::MissingMoveTo(0)));
+        assert_eq!(parse_svg_path_data("M1,1 L2"), Err(SvgPathError::UnexpectedEnd));
+        assert_eq!(parse_svg_path_data("M1,1 X2,2"), Err(SvgPathError::UnexpectedCharacter(5)));
+        assert_eq!(parse_svg_path_data("M1,1 Z 2,2"), Err(SvgPathError::UnexpectedCharacter(7)));
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        let data = "M0,0 L10.5,0 Q12,3 10,6 C8,9 2,9 0,6 Z";
+        let path: Path = data.parse().unwrap();
+        assert_eq!(path.to_svg_path_data(), data);
+        assert_eq!(Path::from_svg_path_data(&path.to_svg_path_data()), Ok(path));
+    }
+}
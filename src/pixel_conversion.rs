@@ -0,0 +1,777 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    image::{CGBitmapInfo, CGImageAlphaInfo, CGImageByteOrderInfo, CGImagePixelFormatInfo},
+    pixel_format::{alpha_info_from_bits, byte_order_from_bits, pixel_format_from_bits, PixelFormat, PixelFormatError},
+};
+
+const MAX_BYTES_PER_PIXEL: usize = 32;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PixelConversionError {
+    InvalidBitmapInfo(PixelFormatError),
+    UnsupportedLayout,
+    ComponentCountMismatch,
+    BytesPerRowTooSmall,
+    BufferTooSmall,
+}
+
+impl Display for PixelConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PixelConversionError::InvalidBitmapInfo(error) => write!(f, "invalid bitmap info: {}", error),
+            PixelConversionError::UnsupportedLayout => write!(f, "bits per component and bits per pixel do not describe a readable layout"),
+            PixelConversionError::ComponentCountMismatch => write!(f, "source and destination have a different number of color components"),
+            PixelConversionError::BytesPerRowTooSmall => write!(f, "bytes per row is smaller than a row of pixels"),
+            PixelConversionError::BufferTooSmall => write!(f, "buffer is smaller than the rows it should hold"),
+        }
+    }
+}
+
+impl Error for PixelConversionError {}
+
+impl From<PixelFormatError> for PixelConversionError {
+    fn from(error: PixelFormatError) -> PixelConversionError {
+        PixelConversionError::InvalidBitmapInfo(error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum AlphaKind {
+    None,
+    Skip,
+    Straight,
+    Premultiplied,
+}
+
+// the bit offset and width of a channel within a pixel, in big endian order
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Channel {
+    offset: usize,
+    bits: usize,
+}
+
+// any pixel layout a CGImage can describe, unlike PixelFormat this includes straight alpha and
+// layouts bitmap contexts can not draw into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelLayout {
+    color_components: usize,
+    bits_per_component: usize,
+    bits_per_pixel: usize,
+    bitmap_info: CGBitmapInfo,
+    alpha_info: CGImageAlphaInfo,
+    byte_order: CGImageByteOrderInfo,
+    pixel_format: CGImagePixelFormatInfo,
+    float_components: bool,
+    alpha_kind: AlphaKind,
+    color_channels: [Channel; 4],
+    alpha_channel: Option<Channel>,
+}
+
+impl PixelLayout {
+    // color_components is the number of components of the color space, 0 for alpha only layouts
+    pub fn new(
+        color_components: usize,
+        bits_per_component: usize,
+        bits_per_pixel: usize,
+        bitmap_info: CGBitmapInfo,
+    ) -> Result<PixelLayout, PixelConversionError> {
+        let bits = bitmap_info.bits();
+        let alpha_info = alpha_info_from_bits(bits & CGBitmapInfo::AlphaInfoMask.bits())?;
+        let byte_order = byte_order_from_bits(bits & CGBitmapInfo::ByteOrderMask.bits())?;
+        let pixel_format = pixel_format_from_bits(bits & CGBitmapInfo::PixelFormatMask.bits())?;
+        let float_components = bitmap_info.contains(CGBitmapInfo::FloatComponents);
+        let alpha_kind = match alpha_info {
+            CGImageAlphaInfo::AlphaNone => AlphaKind::None,
+            CGImageAlphaInfo::AlphaNoneSkipFirst | CGImageAlphaInfo::AlphaNoneSkipLast => AlphaKind::Skip,
+            CGImageAlphaInfo::AlphaFirst | CGImageAlphaInfo::AlphaLast | CGImageAlphaInfo::AlphaOnly => AlphaKind::Straight,
+            CGImageAlphaInfo::AlphaPremultipliedFirst | CGImageAlphaInfo::AlphaPremultipliedLast => AlphaKind::Premultiplied,
+        };
+        let alpha_first =
+            matches!(alpha_info, CGImageAlphaInfo::AlphaFirst | CGImageAlphaInfo::AlphaPremultipliedFirst | CGImageAlphaInfo::AlphaNoneSkipFirst);
+        let unsupported = Err(PixelConversionError::UnsupportedLayout);
+        if color_components > 4 || (alpha_info == CGImageAlphaInfo::AlphaOnly) != (color_components == 0) {
+            return unsupported;
+        }
+
+        let mut color_channels = [Channel {
+            offset: 0,
+            bits: 0,
+        }; 4];
+        let alpha_channel;
+        match pixel_format {
+            CGImagePixelFormatInfo::PixelFormatPacked => {
+                let channels = color_components + (alpha_kind != AlphaKind::None) as usize;
+                let valid_bits = match bits_per_component {
+                    1 | 2 | 4 => !float_components && channels == 1 && bits_per_pixel == bits_per_component,
+                    8 | 32 => !float_components || bits_per_component == 32,
+                    16 => true,
+                    _ => false,
+                };
+                if !valid_bits || bits_per_pixel < channels * bits_per_component || (bits_per_pixel >= 8 && bits_per_pixel % 8 != 0) {
+                    return unsupported;
+                }
+                let color_start = alpha_first as usize;
+                for (index, channel) in color_channels.iter_mut().take(color_components).enumerate() {
+                    *channel = Channel {
+                        offset: (color_start + index) * bits_per_component,
+                        bits: bits_per_component,
+                    };
+                }
+                alpha_channel = match alpha_kind {
+                    AlphaKind::None => None,
+                    _ => Some(Channel {
+                        offset: if alpha_first {
+                            0
+                        } else {
+                            color_components * bits_per_component
+                        },
+                        bits: bits_per_component,
+                    }),
+                };
+            }
+            CGImagePixelFormatInfo::PixelFormatMask => return unsupported,
+            _ => {
+                // 16 bit formats have 1 bit of alpha and 5 bits for each color, 32 bit formats 2 bits of alpha and 10 bits for each color
+                let (pixel_bits, component_bits, alpha_bits) = match pixel_format {
+                    CGImagePixelFormatInfo::PixelFormatRGB555 => (16, 5, 1),
+                    CGImagePixelFormatInfo::PixelFormatRGB565 => (16, 5, 0),
+                    _ => (32, 10, 2),
+                };
+                if color_components != 3 || float_components || bits_per_pixel != pixel_bits || bits_per_component != component_bits {
+                    return unsupported;
+                }
+                if pixel_format == CGImagePixelFormatInfo::PixelFormatRGB565 {
+                    if alpha_kind != AlphaKind::None {
+                        return unsupported;
+                    }
+                    color_channels[0] = Channel {
+                        offset: 0,
+                        bits: 5,
+                    };
+                    color_channels[1] = Channel {
+                        offset: 5,
+                        bits: 6,
+                    };
+                    color_channels[2] = Channel {
+                        offset: 11,
+                        bits: 5,
+                    };
+                    alpha_channel = None;
+                } else {
+                    // without alpha the unused bits are at the top, as with a skipped first alpha
+                    let color_start = if alpha_first || alpha_kind == AlphaKind::None {
+                        alpha_bits
+                    } else {
+                        0
+                    };
+                    for (index, channel) in color_channels.iter_mut().take(3).enumerate() {
+                        *channel = Channel {
+                            offset: color_start + index * component_bits,
+                            bits: component_bits,
+                        };
+                    }
+                    alpha_channel = match alpha_kind {
+                        AlphaKind::None => None,
+                        _ => Some(Channel {
+                            offset: if alpha_first {
+                                0
+                            } else {
+                                3 * component_bits
+                            },
+                            bits: alpha_bits,
+                        }),
+                    };
+                }
+            }
+        }
+
+        let word_bits = match byte_order {
+            CGImageByteOrderInfo::ByteOrder16Little | CGImageByteOrderInfo::ByteOrder16Big => 16,
+            CGImageByteOrderInfo::ByteOrder32Little | CGImageByteOrderInfo::ByteOrder32Big => 32,
+            _ => 1,
+        };
+        if bits_per_pixel % word_bits != 0 || bits_per_pixel / 8 > MAX_BYTES_PER_PIXEL {
+            return unsupported;
+        }
+
+        Ok(PixelLayout {
+            color_components,
+            bits_per_component,
+            bits_per_pixel,
+            bitmap_info,
+            alpha_info,
+            byte_order,
+            pixel_format,
+            float_components,
+            alpha_kind,
+            color_channels,
+            alpha_channel,
+        })
+    }
+
+    pub fn color_components(&self) -> usize {
+        self.color_components
+    }
+
+    pub fn bits_per_component(&self) -> usize {
+        self.bits_per_component
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.bits_per_pixel
+    }
+
+    pub fn bitmap_info(&self) -> CGBitmapInfo {
+        self.bitmap_info
+    }
+
+    pub fn alpha_info(&self) -> CGImageAlphaInfo {
+        self.alpha_info
+    }
+
+    pub fn byte_order_info(&self) -> CGImageByteOrderInfo {
+        self.byte_order
+    }
+
+    pub fn pixel_format_info(&self) -> CGImagePixelFormatInfo {
+        self.pixel_format
+    }
+
+    pub fn has_float_components(&self) -> bool {
+        self.float_components
+    }
+
+    pub fn min_bytes_per_row(&self, width: usize) -> Option<usize> {
+        Some(width.checked_mul(self.bits_per_pixel)?.checked_add(7)? / 8)
+    }
+
+    fn is_little_endian(&self) -> bool {
+        matches!(self.byte_order, CGImageByteOrderInfo::ByteOrder16Little | CGImageByteOrderInfo::ByteOrder32Little)
+    }
+
+    fn word_size(&self) -> usize {
+        match self.byte_order {
+            CGImageByteOrderInfo::ByteOrder16Little | CGImageByteOrderInfo::ByteOrder16Big => 2,
+            _ => 4,
+        }
+    }
+
+    // the memory index of the red, green, blue and alpha bytes of 8 bit RGB pixels with 4 channels
+    fn rgba8888_indices(&self) -> Option<[usize; 4]> {
+        if self.color_components != 3
+            || self.bits_per_component != 8
+            || self.bits_per_pixel != 32
+            || self.float_components
+            || self.pixel_format != CGImagePixelFormatInfo::PixelFormatPacked
+            || self.alpha_kind == AlphaKind::None
+            || matches!(self.byte_order, CGImageByteOrderInfo::ByteOrder16Little | CGImageByteOrderInfo::ByteOrder16Big)
+        {
+            return None;
+        }
+        let channels = [
+            self.color_channels[0],
+            self.color_channels[1],
+            self.color_channels[2],
+            self.alpha_channel?,
+        ];
+        let little = self.is_little_endian();
+        Some(channels.map(|channel| {
+            let index = channel.offset / 8;
+            if little {
+                3 - index
+            } else {
+                index
+            }
+        }))
+    }
+
+    pub(crate) fn read_pixel(&self, row: &[u8], x: usize) -> ([f32; 4], f32) {
+        let mut buffer = [0u8; MAX_BYTES_PER_PIXEL];
+        let (data, base) = if self.is_little_endian() {
+            let size = self.bits_per_pixel / 8;
+            let start = x * size;
+            buffer[..size].copy_from_slice(&row[start..start + size]);
+            for word in buffer[..size].chunks_exact_mut(self.word_size()) {
+                word.reverse();
+            }
+            (&buffer[..size], 0)
+        } else {
+            (row, x * self.bits_per_pixel)
+        };
+        let mut color = [0.0; 4];
+        for (value, channel) in color.iter_mut().zip(&self.color_channels[..self.color_components]) {
+            *value = self.decode(read_bits(data, base + channel.offset, channel.bits), channel.bits, true);
+        }
+        let alpha = match (self.alpha_kind, self.alpha_channel) {
+            (AlphaKind::Straight, Some(channel)) | (AlphaKind::Premultiplied, Some(channel)) => {
+                self.decode(read_bits(data, base + channel.offset, channel.bits), channel.bits, false)
+            }
+            _ => 1.0,
+        };
+        if self.alpha_kind == AlphaKind::Premultiplied {
+            for value in &mut color {
+                *value = if alpha > 0.0 {
+                    *value / alpha
+                } else {
+                    0.0
+                };
+            }
+        }
+        (color, alpha)
+    }
+
+    // color is straight, layouts without alpha get the color composited over black
+    pub(crate) fn write_pixel(&self, row: &mut [u8], x: usize, mut color: [f32; 4], alpha: f32) {
+        if self.alpha_kind != AlphaKind::Straight {
+            for value in &mut color {
+                *value *= alpha;
+            }
+        }
+        let mut buffer = [0u8; MAX_BYTES_PER_PIXEL];
+        let little = self.is_little_endian();
+        let size = self.bits_per_pixel / 8;
+        let (data, base): (&mut [u8], usize) = if little {
+            (&mut buffer[..size], 0)
+        } else {
+            (&mut *row, x * self.bits_per_pixel)
+        };
+        for (value, channel) in color.iter().zip(&self.color_channels[..self.color_components]) {
+            write_bits(data, base + channel.offset, channel.bits, self.encode(*value, channel.bits, true));
+        }
+        if let Some(channel) = self.alpha_channel {
+            // skipped channels are written as opaque
+            let alpha = match self.alpha_kind {
+                AlphaKind::Skip => 1.0,
+                _ => alpha,
+            };
+            write_bits(data, base + channel.offset, channel.bits, self.encode(alpha, channel.bits, false));
+        }
+        if little {
+            for word in buffer[..size].chunks_exact_mut(self.word_size()) {
+                word.reverse();
+            }
+            row[x * size..(x + 1) * size].copy_from_slice(&buffer[..size]);
+        }
+    }
+
+    fn decode(&self, value: u32, bits: usize, color: bool) -> f32 {
+        if self.float_components {
+            return match bits {
+                16 => f16_to_f32(value as u16),
+                _ => f32::from_bits(value),
+            };
+        }
+        if color && self.pixel_format == CGImagePixelFormatInfo::PixelFormatRGBCIF10 {
+            // the extended range encoding maps 384 to 0 and 894 to 1
+            return (value as f32 - 384.0) / 510.0;
+        }
+        value as f32 / max_value(bits) as f32
+    }
+
+    fn encode(&self, value: f32, bits: usize, color: bool) -> u32 {
+        if self.float_components {
+            return match bits {
+                16 => f32_to_f16(value) as u32,
+                _ => value.to_bits(),
+            };
+        }
+        if color && self.pixel_format == CGImagePixelFormatInfo::PixelFormatRGBCIF10 {
+            return (value * 510.0 + 384.0).round().clamp(0.0, 1023.0) as u32;
+        }
+        let max = max_value(bits) as f32;
+        (value.clamp(0.0, 1.0) * max).round() as u32
+    }
+}
+
+impl From<PixelFormat> for PixelLayout {
+    fn from(format: PixelFormat) -> PixelLayout {
+        PixelLayout::new(format.color_components(), format.bits_per_component(), format.bits_per_pixel(), format.bitmap_info())
+            .expect("every pixel format is a readable layout")
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum AlphaOperation {
+    None,
+    Premultiply,
+    Unpremultiply,
+}
+
+// 8 bit RGB with 4 channels in any order and alpha kind, converted with integer math
+#[derive(Clone, Copy, Debug)]
+struct Rgba8888Conversion {
+    source: [usize; 4],
+    destination: [usize; 4],
+    source_opaque: bool,
+    destination_opaque: bool,
+    operation: AlphaOperation,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PixelConverter {
+    source: PixelLayout,
+    destination: PixelLayout,
+    fast_path: Option<Rgba8888Conversion>,
+}
+
+impl PixelConverter {
+    // alpha only layouts convert to and from any layout, otherwise the color components must match
+    pub fn new(source: PixelLayout, destination: PixelLayout) -> Result<PixelConverter, PixelConversionError> {
+        if source.color_components != destination.color_components && source.color_components != 0 && destination.color_components != 0 {
+            return Err(PixelConversionError::ComponentCountMismatch);
+        }
+        let fast_path = match (source.rgba8888_indices(), destination.rgba8888_indices()) {
+            (Some(source_indices), Some(destination_indices)) => {
+                let source_straight = source.alpha_kind == AlphaKind::Straight;
+                let destination_straight = destination.alpha_kind == AlphaKind::Straight;
+                let operation = if source_straight && !destination_straight {
+                    AlphaOperation::Premultiply
+                } else if source.alpha_kind == AlphaKind::Premultiplied && destination_straight {
+                    AlphaOperation::Unpremultiply
+                } else {
+                    AlphaOperation::None
+                };
+                Some(Rgba8888Conversion {
+                    source: source_indices,
+                    destination: destination_indices,
+                    source_opaque: source.alpha_kind == AlphaKind::Skip,
+                    destination_opaque: destination.alpha_kind == AlphaKind::Skip,
+                    operation,
+                })
+            }
+            _ => None,
+        };
+        Ok(PixelConverter {
+            source,
+            destination,
+            fast_path,
+        })
+    }
+
+    pub fn source(&self) -> &PixelLayout {
+        &self.source
+    }
+
+    pub fn destination(&self) -> &PixelLayout {
+        &self.destination
+    }
+
+    pub fn convert_row(&self, source: &[u8], destination: &mut [u8], width: usize) -> Result<(), PixelConversionError> {
+        let source_length = self.source.min_bytes_per_row(width).ok_or(PixelConversionError::BufferTooSmall)?;
+        let destination_length = self.destination.min_bytes_per_row(width).ok_or(PixelConversionError::BufferTooSmall)?;
+        if source.len() < source_length || destination.len() < destination_length {
+            return Err(PixelConversionError::BufferTooSmall);
+        }
+        self.convert_row_unchecked(&source[..source_length], &mut destination[..destination_length], width);
+        Ok(())
+    }
+
+    pub fn convert(
+        &self,
+        source: &[u8],
+        source_bytes_per_row: usize,
+        destination: &mut [u8],
+        destination_bytes_per_row: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), PixelConversionError> {
+        if height == 0 || width == 0 {
+            return Ok(());
+        }
+        let source_length = self.source.min_bytes_per_row(width).ok_or(PixelConversionError::BufferTooSmall)?;
+        let destination_length = self.destination.min_bytes_per_row(width).ok_or(PixelConversionError::BufferTooSmall)?;
+        if source_bytes_per_row < source_length || destination_bytes_per_row < destination_length {
+            return Err(PixelConversionError::BytesPerRowTooSmall);
+        }
+        let required = |bytes_per_row: usize, length: usize| (height - 1).checked_mul(bytes_per_row)?.checked_add(length);
+        match (required(source_bytes_per_row, source_length), required(destination_bytes_per_row, destination_length)) {
+            (Some(source_required), Some(destination_required)) if source.len() >= source_required && destination.len() >= destination_required => {}
+            _ => return Err(PixelConversionError::BufferTooSmall),
+        }
+        for y in 0..height {
+            let source_row = &source[y * source_bytes_per_row..y * source_bytes_per_row + source_length];
+            let destination_row = &mut destination[y * destination_bytes_per_row..y * destination_bytes_per_row + destination_length];
+            self.convert_row_unchecked(source_row, destination_row, width);
+        }
+        Ok(())
+    }
+
+    fn convert_row_unchecked(&self, source: &[u8], destination: &mut [u8], width: usize) {
+        if let Some(conversion) = &self.fast_path {
+            conversion.convert_row(source, destination);
+            return;
+        }
+        if self.source.bitmap_info == self.destination.bitmap_info
+            && self.source.bits_per_pixel == self.destination.bits_per_pixel
+            && self.source.bits_per_component == self.destination.bits_per_component
+            && self.source.bits_per_pixel % 8 == 0
+        {
+            destination.copy_from_slice(source);
+            return;
+        }
+        for x in 0..width {
+            let (color, alpha) = self.source.read_pixel(source, x);
+            self.destination.write_pixel(destination, x, color, alpha);
+        }
+    }
+}
+
+impl Rgba8888Conversion {
+    fn convert_row(&self, source: &[u8], destination: &mut [u8]) {
+        let [sr, sg, sb, sa] = self.source;
+        let [dr, dg, db, da] = self.destination;
+        for (from, to) in source.chunks_exact(4).zip(destination.chunks_exact_mut(4)) {
+            let alpha = if self.source_opaque {
+                255
+            } else {
+                from[sa]
+            };
+            let mut color = [from[sr], from[sg], from[sb]];
+            match self.operation {
+                AlphaOperation::None => {}
+                AlphaOperation::Premultiply => color = color.map(|value| premultiply(value, alpha)),
+                AlphaOperation::Unpremultiply => color = color.map(|value| unpremultiply(value, alpha)),
+            }
+            to[dr] = color[0];
+            to[dg] = color[1];
+            to[db] = color[2];
+            to[da] = if self.destination_opaque {
+                255
+            } else {
+                alpha
+            };
+        }
+    }
+}
+
+fn premultiply(value: u8, alpha: u8) -> u8 {
+    ((value as u32 * alpha as u32 + 127) / 255) as u8
+}
+
+fn unpremultiply(value: u8, alpha: u8) -> u8 {
+    ((value as u32 * UNPREMULTIPLY_FACTORS[alpha as usize] + 0x8000) >> 16).min(255) as u8
+}
+
+// 16.16 fixed point reciprocals rounded up, which makes the division round exactly for every 8 bit value
+const UNPREMULTIPLY_FACTORS: [u32; 256] = {
+    let mut factors = [0; 256];
+    let mut alpha = 1;
+    while alpha < 256 {
+        factors[alpha] = (255u32 << 16).div_ceil(alpha as u32);
+        alpha += 1;
+    }
+    factors
+};
+
+fn max_value(bits: usize) -> u32 {
+    ((1u64 << bits) - 1) as u32
+}
+
+// reads bits most significant first, starting offset bits into data
+fn read_bits(data: &[u8], offset: usize, count: usize) -> u32 {
+    let (first, last) = (offset / 8, (offset + count).div_ceil(8));
+    let value = data[first..last].iter().fold(0u64, |value, byte| value << 8 | *byte as u64);
+    let trailing = last * 8 - offset - count;
+    ((value >> trailing) & ((1u64 << count) - 1)) as u32
+}
+
+fn write_bits(data: &mut [u8], offset: usize, count: usize, value: u32) {
+    let (first, last) = (offset / 8, (offset + count).div_ceil(8));
+    let trailing = last * 8 - offset - count;
+    let mask = ((1u64 << count) - 1) << trailing;
+    let mut word = data[first..last].iter().fold(0u64, |word, byte| word << 8 | *byte as u64);
+    word = (word & !mask) | ((value as u64) << trailing & mask);
+    for (index, byte) in data[first..last].iter_mut().rev().enumerate() {
+        *byte = (word >> (index * 8)) as u8;
+    }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+    match exponent {
+        0 => {
+            // subnormal halves are exact in single precision
+            let magnitude = mantissa as f32 / 16_777_216.0;
+            if sign != 0 {
+                -magnitude
+            } else {
+                magnitude
+            }
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+    }
+}
+
+// rounds to the nearest half, ties to even
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        return sign
+            | 0x7c00
+            | if mantissa != 0 {
+                0x200
+            } else {
+                0
+            };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    let (half, remainder, halfway) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        (mantissa >> shift, mantissa & ((1 << shift) - 1), 1 << (shift - 1))
+    } else {
+        (((exponent as u32) << 10) | (mantissa >> 13), mantissa & 0x1fff, 0x1000)
+    };
+    // a carry out of the mantissa correctly moves to the next exponent, up to infinity
+    let rounded = if remainder > halfway || (remainder == halfway && half & 1 == 1) {
+        half + 1
+    } else {
+        half
+    };
+    sign | rounded as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(color_components: usize, bits_per_component: usize, bits_per_pixel: usize, alpha_info: CGImageAlphaInfo) -> PixelLayout {
+        PixelLayout::new(color_components, bits_per_component, bits_per_pixel, CGBitmapInfo::from_bits_retain(alpha_info as u32)).unwrap()
+    }
+
+    fn convert(source: PixelLayout, destination: PixelLayout, pixels: &[u8]) -> Vec<u8> {
+        let converter = PixelConverter::new(source, destination).unwrap();
+        let width = pixels.len() * 8 / source.bits_per_pixel();
+        let mut output = vec![0; destination.min_bytes_per_row(width).unwrap()];
+        converter.convert_row(pixels, &mut output, width).unwrap();
+        output
+    }
+
+    #[test]
+    fn premultiplied_round_trips() {
+        for alpha in 1..=255u8 {
+            for value in 0..=alpha {
+                let straight = unpremultiply(value, alpha);
+                assert_eq!(straight as u32, ((value as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255));
+                assert_eq!(premultiply(straight, alpha), value);
+            }
+        }
+        assert_eq!(unpremultiply(0, 0), 0);
+    }
+
+    #[test]
+    fn straight_and_premultiplied_8_bit() {
+        let straight = layout(3, 8, 32, CGImageAlphaInfo::AlphaLast);
+        let premultiplied = PixelLayout::from(PixelFormat::ARGB8888Host);
+        assert!(PixelConverter::new(straight, premultiplied).unwrap().fast_path.is_some());
+        // the host format is stored blue first on little endian machines
+        let expected = if cfg!(target_endian = "little") {
+            [0, 64, 128, 128]
+        } else {
+            [128, 128, 64, 0]
+        };
+        assert_eq!(convert(straight, premultiplied, &[255, 128, 0, 128]), expected);
+        assert_eq!(convert(premultiplied, straight, &expected), [255, 128, 0, 128]);
+
+        // layouts without alpha get the color composited over black and skipped channels read as opaque
+        let skipped = PixelLayout::from(PixelFormat::RGBX8888);
+        assert_eq!(convert(straight, skipped, &[255, 0, 0, 128]), [128, 0, 0, 255]);
+        assert_eq!(convert(skipped, straight, &[10, 20, 30, 0]), [10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn sixteen_and_eight_bit() {
+        let (rgba16, rgba8) = (PixelLayout::from(PixelFormat::RGBA16), PixelLayout::from(PixelFormat::RGBA8888));
+        assert_eq!(convert(rgba16, rgba8, &[0xff, 0xff, 0x80, 0x80, 0x00, 0x00, 0xff, 0xff]), [255, 128, 0, 255]);
+        assert_eq!(convert(rgba8, rgba16, &[255, 128, 0, 255]), [0xff, 0xff, 0x80, 0x80, 0x00, 0x00, 0xff, 0xff]);
+        assert_eq!(convert(rgba16, rgba8, &[0x40, 0x40, 0x00, 0x00, 0x00, 0x00, 0x80, 0x80]), [64, 0, 0, 128]);
+
+        let (host, big) = (PixelLayout::from(PixelFormat::Gray16Host), PixelLayout::from(PixelFormat::Gray16));
+        assert_eq!(convert(host, big, &0x1234u16.to_ne_bytes()), [0x12, 0x34]);
+        assert_eq!(convert(big, PixelLayout::from(PixelFormat::Gray8), &[0x12, 0x34]), [0x12]);
+    }
+
+    #[test]
+    fn packed_formats() {
+        let rgba8 = PixelLayout::from(PixelFormat::RGBA8888);
+        let rgb565 = PixelLayout::from(PixelFormat::RGB565Host);
+        assert_eq!(convert(rgb565, rgba8, &0xf800u16.to_ne_bytes()), [255, 0, 0, 255]);
+        assert_eq!(convert(rgba8, rgb565, &[0, 255, 0, 255]), 0x07e0u16.to_ne_bytes());
+        let xrgb2101010 = PixelLayout::from(PixelFormat::XRGB2101010Host);
+        assert_eq!(convert(rgba8, xrgb2101010, &[0, 0, 255, 255]), (0xc000_03ffu32).to_ne_bytes());
+        assert_eq!(convert(PixelLayout::from(PixelFormat::Alpha8), rgba8, &[128]), [0, 0, 0, 128]);
+    }
+
+    #[test]
+    fn float_components() {
+        let rgba8 = PixelLayout::from(PixelFormat::RGBA8888);
+        let rgba32f = PixelLayout::from(PixelFormat::RGBA32F);
+        let pixel: Vec<u8> = [0.5f32, 0.25, 0.0, 0.5].iter().flat_map(|value| value.to_ne_bytes()).collect();
+        assert_eq!(convert(rgba32f, rgba8, &pixel), [128, 64, 0, 128]);
+        let floats: Vec<f32> = convert(rgba8, rgba32f, &[255, 0, 51, 255])
+            .chunks_exact(4)
+            .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        assert_eq!(floats, [1.0, 0.0, 0.2, 1.0]);
+
+        let rgba16f = PixelLayout::from(PixelFormat::RGBA16F);
+        let halves: Vec<u8> = [0x3c00u16, 0x3800, 0x0000, 0x3c00].iter().flat_map(|value| value.to_ne_bytes()).collect();
+        assert_eq!(convert(rgba16f, rgba8, &halves), [255, 128, 0, 255]);
+        assert_eq!(convert(rgba8, rgba16f, &[255, 128, 0, 255])[..2], 0x3c00u16.to_ne_bytes());
+    }
+
+    #[test]
+    fn half_floats() {
+        for bits in 0..=u16::MAX {
+            let value = f16_to_f32(bits);
+            if !value.is_nan() {
+                assert_eq!(f32_to_f16(value), bits, "{:#x}", bits);
+            }
+        }
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(65519.0), 0x7bff);
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 / 2048.0), 0x3c02);
+        assert_eq!(f32_to_f16(1e-9), 0);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn errors() {
+        let rgba8 = PixelLayout::from(PixelFormat::RGBA8888);
+        assert_eq!(
+            PixelLayout::new(3, 8, 24, CGBitmapInfo::from_bits_retain(CGImageAlphaInfo::AlphaLast as u32)),
+            Err(PixelConversionError::UnsupportedLayout)
+        );
+        assert_eq!(PixelLayout::new(1, 3, 3, CGBitmapInfo::empty()), Err(PixelConversionError::UnsupportedLayout));
+        assert_eq!(
+            PixelLayout::new(3, 8, 32, CGBitmapInfo::from_bits_retain(9)),
+            Err(PixelConversionError::InvalidBitmapInfo(PixelFormatError::InvalidAlphaInfo(9)))
+        );
+        assert_eq!(PixelConverter::new(PixelLayout::from(PixelFormat::Gray8), rgba8).err(), Some(PixelConversionError::ComponentCountMismatch));
+        let converter = PixelConverter::new(rgba8, rgba8).unwrap();
+        let mut destination = [0; 16];
+        assert_eq!(converter.convert(&[0; 16], 4, &mut destination, 8, 2, 2), Err(PixelConversionError::BytesPerRowTooSmall));
+        assert_eq!(converter.convert(&[0; 12], 8, &mut destination, 8, 2, 2), Err(PixelConversionError::BufferTooSmall));
+        assert_eq!(converter.convert_row(&[0; 8], &mut destination[..4], 2), Err(PixelConversionError::BufferTooSmall));
+        assert_eq!(converter.convert(&[7; 16], 8, &mut destination, 8, 2, 2), Ok(()));
+        assert_eq!(destination, [7; 16]);
+    }
+}
@@ -0,0 +1,481 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    color_space::{CGColorSpace, CGColorSpaceModel},
+    image::{
+        kCGImageAlphaFirst, kCGImageAlphaLast, kCGImageAlphaNone, kCGImageAlphaNoneSkipFirst, kCGImageAlphaNoneSkipLast, kCGImageAlphaOnly,
+        kCGImageAlphaPremultipliedFirst, kCGImageAlphaPremultipliedLast, kCGImageByteOrder16Big, kCGImageByteOrder16Little, kCGImageByteOrder32Big,
+        kCGImageByteOrder32Little, kCGImageByteOrderDefault, kCGImageByteOrderMask, kCGImagePixelFormatMask, kCGImagePixelFormatPacked,
+        kCGImagePixelFormatRGB101010, kCGImagePixelFormatRGB555, kCGImagePixelFormatRGB565, kCGImagePixelFormatRGBCIF10, CGBitmapInfo,
+        CGImageAlphaInfo, CGImageByteOrderInfo, CGImagePixelFormatInfo,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PixelFormatError {
+    InvalidAlphaInfo(u32),
+    InvalidByteOrder(u32),
+    InvalidPixelFormat(u32),
+    UnsupportedFormat,
+    UnsupportedByteOrder,
+    ColorSpaceModelMismatch,
+    ComponentCountMismatch { expected: usize, found: usize },
+    BytesPerRowTooSmall { minimum: usize, found: usize },
+    SizeOverflow,
+}
+
+impl Display for PixelFormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PixelFormatError::InvalidAlphaInfo(value) => write!(f, "invalid alpha info {}", value),
+            PixelFormatError::InvalidByteOrder(value) => write!(f, "invalid byte order {:#x}", value),
+            PixelFormatError::InvalidPixelFormat(value) => write!(f, "invalid pixel format {:#x}", value),
+            PixelFormatError::UnsupportedFormat => write!(f, "unsupported combination of color model, bits per component and alpha"),
+            PixelFormatError::UnsupportedByteOrder => write!(f, "byte order does not match the component or pixel size"),
+            PixelFormatError::ColorSpaceModelMismatch => write!(f, "color space model does not match the pixel format"),
+            PixelFormatError::ComponentCountMismatch {
+                expected,
+                found,
+            } => write!(f, "expected a color space with {} components, found {}", expected, found),
+            PixelFormatError::BytesPerRowTooSmall {
+                minimum,
+                found,
+            } => write!(f, "bytes per row must be at least {}, found {}", minimum, found),
+            PixelFormatError::SizeOverflow => write!(f, "bitmap size overflows"),
+        }
+    }
+}
+
+impl Error for PixelFormatError {}
+
+// a pixel layout a bitmap context can be created with, a model of None is an alpha only format
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PixelFormat {
+    model: Option<CGColorSpaceModel>,
+    bits_per_component: usize,
+    alpha_info: CGImageAlphaInfo,
+    float_components: bool,
+    byte_order: CGImageByteOrderInfo,
+    pixel_format: CGImagePixelFormatInfo,
+}
+
+macro_rules! pixel_format {
+    ($model:expr, $bits_per_component:expr, $alpha_info:ident, $float_components:expr, $byte_order:expr, $pixel_format:ident) => {
+        PixelFormat {
+            model: $model,
+            bits_per_component: $bits_per_component,
+            alpha_info: CGImageAlphaInfo::$alpha_info,
+            float_components: $float_components,
+            byte_order: $byte_order,
+            pixel_format: CGImagePixelFormatInfo::$pixel_format,
+        }
+    };
+}
+
+const DEFAULT: CGImageByteOrderInfo = CGImageByteOrderInfo::ByteOrderDefault;
+const GRAY: Option<CGColorSpaceModel> = Some(CGColorSpaceModel::Monochrome);
+const RGB: Option<CGColorSpaceModel> = Some(CGColorSpaceModel::RGB);
+const CMYK: Option<CGColorSpaceModel> = Some(CGColorSpaceModel::CMYK);
+
+cfg_if! {
+    if #[cfg(target_endian = "big")] {
+        const HOST_16: CGImageByteOrderInfo = CGImageByteOrderInfo::ByteOrder16Big;
+        const HOST_32: CGImageByteOrderInfo = CGImageByteOrderInfo::ByteOrder32Big;
+    } else {
+        const HOST_16: CGImageByteOrderInfo = CGImageByteOrderInfo::ByteOrder16Little;
+        const HOST_32: CGImageByteOrderInfo = CGImageByteOrderInfo::ByteOrder32Little;
+    }
+}
+
+// names give the components from the most to the least significant bits of a pixel or component word,
+// X marks a skipped alpha channel and formats without an explicit byte order are big endian
+impl PixelFormat {
+    pub const Alpha8: PixelFormat = pixel_format!(None, 8, AlphaOnly, false, DEFAULT, PixelFormatPacked);
+    pub const Gray8: PixelFormat = pixel_format!(GRAY, 8, AlphaNone, false, DEFAULT, PixelFormatPacked);
+    pub const Gray16: PixelFormat = pixel_format!(GRAY, 16, AlphaNone, false, DEFAULT, PixelFormatPacked);
+    pub const Gray16Host: PixelFormat = pixel_format!(GRAY, 16, AlphaNone, false, HOST_16, PixelFormatPacked);
+    pub const Gray32F: PixelFormat = pixel_format!(GRAY, 32, AlphaNone, true, HOST_32, PixelFormatPacked);
+    pub const XRGB1555Host: PixelFormat = pixel_format!(RGB, 5, AlphaNoneSkipFirst, false, HOST_16, PixelFormatRGB555);
+    pub const RGB565Host: PixelFormat = pixel_format!(RGB, 5, AlphaNone, false, HOST_16, PixelFormatRGB565);
+    pub const XRGB8888: PixelFormat = pixel_format!(RGB, 8, AlphaNoneSkipFirst, false, DEFAULT, PixelFormatPacked);
+    pub const RGBX8888: PixelFormat = pixel_format!(RGB, 8, AlphaNoneSkipLast, false, DEFAULT, PixelFormatPacked);
+    pub const ARGB8888: PixelFormat = pixel_format!(RGB, 8, AlphaPremultipliedFirst, false, DEFAULT, PixelFormatPacked);
+    pub const RGBA8888: PixelFormat = pixel_format!(RGB, 8, AlphaPremultipliedLast, false, DEFAULT, PixelFormatPacked);
+    pub const XRGB8888Host: PixelFormat = pixel_format!(RGB, 8, AlphaNoneSkipFirst, false, HOST_32, PixelFormatPacked);
+    pub const ARGB8888Host: PixelFormat = pixel_format!(RGB, 8, AlphaPremultipliedFirst, false, HOST_32, PixelFormatPacked);
+    pub const XRGB2101010Host: PixelFormat = pixel_format!(RGB, 10, AlphaNoneSkipFirst, false, HOST_32, PixelFormatRGB101010);
+    pub const RGBX16: PixelFormat = pixel_format!(RGB, 16, AlphaNoneSkipLast, false, DEFAULT, PixelFormatPacked);
+    pub const RGBA16: PixelFormat = pixel_format!(RGB, 16, AlphaPremultipliedLast, false, DEFAULT, PixelFormatPacked);
+    pub const RGBX16F: PixelFormat = pixel_format!(RGB, 16, AlphaNoneSkipLast, true, HOST_16, PixelFormatPacked);
+    pub const RGBA16F: PixelFormat = pixel_format!(RGB, 16, AlphaPremultipliedLast, true, HOST_16, PixelFormatPacked);
+    pub const RGBX32F: PixelFormat = pixel_format!(RGB, 32, AlphaNoneSkipLast, true, HOST_32, PixelFormatPacked);
+    pub const RGBA32F: PixelFormat = pixel_format!(RGB, 32, AlphaPremultipliedLast, true, HOST_32, PixelFormatPacked);
+    pub const CMYK8: PixelFormat = pixel_format!(CMYK, 8, AlphaNone, false, DEFAULT, PixelFormatPacked);
+    pub const CMYK16: PixelFormat = pixel_format!(CMYK, 16, AlphaNone, false, DEFAULT, PixelFormatPacked);
+    pub const CMYK32F: PixelFormat = pixel_format!(CMYK, 32, AlphaNone, true, HOST_32, PixelFormatPacked);
+
+    pub fn new(
+        model: Option<CGColorSpaceModel>,
+        bits_per_component: usize,
+        alpha_info: CGImageAlphaInfo,
+        float_components: bool,
+        byte_order: CGImageByteOrderInfo,
+        pixel_format: CGImagePixelFormatInfo,
+    ) -> Result<PixelFormat, PixelFormatError> {
+        let format = PixelFormat {
+            model,
+            bits_per_component,
+            alpha_info,
+            float_components,
+            byte_order,
+            pixel_format,
+        };
+        format.check()?;
+        Ok(format)
+    }
+
+    // the model is not part of the bitmap info, it comes from the color space the bitmap is used with
+    pub fn from_bitmap_info(
+        bitmap_info: CGBitmapInfo,
+        bits_per_component: usize,
+        model: Option<CGColorSpaceModel>,
+    ) -> Result<PixelFormat, PixelFormatError> {
+        let bits = bitmap_info.bits();
+        let alpha_info = alpha_info_from_bits(bits & CGBitmapInfo::AlphaInfoMask.bits())?;
+        let byte_order = byte_order_from_bits(bits & kCGImageByteOrderMask)?;
+        let pixel_format = pixel_format_from_bits(bits & kCGImagePixelFormatMask)?;
+        let float_components = bitmap_info.contains(CGBitmapInfo::FloatComponents);
+        PixelFormat::new(model, bits_per_component, alpha_info, float_components, byte_order, pixel_format)
+    }
+
+    pub fn bitmap_info(&self) -> CGBitmapInfo {
+        let mut bits = self.alpha_info as u32 | self.byte_order as u32 | self.pixel_format as u32;
+        if self.float_components {
+            bits |= CGBitmapInfo::FloatComponents.bits();
+        }
+        CGBitmapInfo::from_bits_retain(bits)
+    }
+
+    pub fn color_space_model(&self) -> Option<CGColorSpaceModel> {
+        self.model
+    }
+
+    pub fn bits_per_component(&self) -> usize {
+        self.bits_per_component
+    }
+
+    pub fn alpha_info(&self) -> CGImageAlphaInfo {
+        self.alpha_info
+    }
+
+    pub fn has_float_components(&self) -> bool {
+        self.float_components
+    }
+
+    pub fn byte_order_info(&self) -> CGImageByteOrderInfo {
+        self.byte_order
+    }
+
+    pub fn pixel_format_info(&self) -> CGImagePixelFormatInfo {
+        self.pixel_format
+    }
+
+    // the number of components the color space must have
+    pub fn color_components(&self) -> usize {
+        match self.model {
+            Some(CGColorSpaceModel::Monochrome) => 1,
+            Some(CGColorSpaceModel::RGB) => 3,
+            Some(CGColorSpaceModel::CMYK) => 4,
+            _ => 0,
+        }
+    }
+
+    // the number of components stored for each pixel, including alpha or the skipped alpha channel
+    pub fn components_per_pixel(&self) -> usize {
+        match self.alpha_info {
+            CGImageAlphaInfo::AlphaNone => self.color_components(),
+            CGImageAlphaInfo::AlphaOnly => 1,
+            _ => self.color_components() + 1,
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        matches!(
+            self.alpha_info,
+            CGImageAlphaInfo::AlphaPremultipliedLast
+                | CGImageAlphaInfo::AlphaPremultipliedFirst
+                | CGImageAlphaInfo::AlphaLast
+                | CGImageAlphaInfo::AlphaFirst
+                | CGImageAlphaInfo::AlphaOnly
+        )
+    }
+
+    pub fn is_premultiplied(&self) -> bool {
+        matches!(self.alpha_info, CGImageAlphaInfo::AlphaPremultipliedLast | CGImageAlphaInfo::AlphaPremultipliedFirst)
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        match self.pixel_format {
+            CGImagePixelFormatInfo::PixelFormatRGB555 | CGImagePixelFormatInfo::PixelFormatRGB565 => 16,
+            CGImagePixelFormatInfo::PixelFormatRGB101010 | CGImagePixelFormatInfo::PixelFormatRGBCIF10 => 32,
+            _ => self.components_per_pixel() * self.bits_per_component,
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bits_per_pixel() / 8
+    }
+
+    pub fn min_bytes_per_row(&self, width: usize) -> Option<usize> {
+        Some(width.checked_mul(self.bits_per_pixel())?.checked_add(7)? / 8)
+    }
+
+    // the row length rounded up to the 16 byte alignment CoreGraphics prefers
+    pub fn aligned_bytes_per_row(&self, width: usize) -> Option<usize> {
+        Some(self.min_bytes_per_row(width)?.checked_add(15)? & !15)
+    }
+
+    pub fn validate_component_count(&self, count: usize) -> Result<(), PixelFormatError> {
+        if count != self.color_components() {
+            return Err(PixelFormatError::ComponentCountMismatch {
+                expected: self.color_components(),
+                found: count,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn validate_bytes_per_row(&self, width: usize, bytes_per_row: usize) -> Result<(), PixelFormatError> {
+        let minimum = self.min_bytes_per_row(width).ok_or(PixelFormatError::SizeOverflow)?;
+        if bytes_per_row < minimum {
+            return Err(PixelFormatError::BytesPerRowTooSmall {
+                minimum,
+                found: bytes_per_row,
+            });
+        }
+        Ok(())
+    }
+
+    // alpha only formats take no color space
+    pub fn validate_color_space(&self, space: Option<&CGColorSpace>) -> Result<(), PixelFormatError> {
+        match space {
+            Some(space) => {
+                if Some(space.model()) != self.model {
+                    return Err(PixelFormatError::ColorSpaceModelMismatch);
+                }
+                self.validate_component_count(space.number_of_components())
+            }
+            None if self.model.is_none() => Ok(()),
+            None => Err(PixelFormatError::ColorSpaceModelMismatch),
+        }
+    }
+
+    fn check(&self) -> Result<(), PixelFormatError> {
+        use CGImageAlphaInfo::*;
+        use CGImagePixelFormatInfo::*;
+
+        let alpha = self.alpha_info;
+        let supported = match (self.model, self.pixel_format, self.bits_per_component, self.float_components) {
+            (None, PixelFormatPacked, 8, false) => alpha == AlphaOnly,
+            (Some(CGColorSpaceModel::Monochrome), PixelFormatPacked, 8 | 16, false)
+            | (Some(CGColorSpaceModel::Monochrome), PixelFormatPacked, 32, true) => alpha == AlphaNone,
+            (Some(CGColorSpaceModel::RGB), PixelFormatRGB555, 5, false) => alpha == AlphaNoneSkipFirst,
+            (Some(CGColorSpaceModel::RGB), PixelFormatRGB565, 5, false) => alpha == AlphaNone,
+            (Some(CGColorSpaceModel::RGB), PixelFormatRGB101010, 10, false) => matches!(alpha, AlphaNoneSkipFirst | AlphaNoneSkipLast),
+            (Some(CGColorSpaceModel::RGB), PixelFormatPacked, 8, false) => {
+                matches!(alpha, AlphaNoneSkipFirst | AlphaNoneSkipLast | AlphaPremultipliedFirst | AlphaPremultipliedLast)
+            }
+            (Some(CGColorSpaceModel::RGB), PixelFormatPacked, 16, _) | (Some(CGColorSpaceModel::RGB), PixelFormatPacked, 32, true) => {
+                matches!(alpha, AlphaNoneSkipLast | AlphaPremultipliedLast)
+            }
+            (Some(CGColorSpaceModel::CMYK), PixelFormatPacked, 8 | 16, false) | (Some(CGColorSpaceModel::CMYK), PixelFormatPacked, 32, true) => {
+                alpha == AlphaNone
+            }
+            _ => false,
+        };
+        if !supported {
+            return Err(PixelFormatError::UnsupportedFormat);
+        }
+        // the byte order swaps words of the component or packed pixel size
+        let word_bits = match self.pixel_format {
+            PixelFormatPacked => self.bits_per_component,
+            _ => self.bits_per_pixel(),
+        };
+        let byte_order_supported = match self.byte_order {
+            CGImageByteOrderInfo::ByteOrderDefault => true,
+            CGImageByteOrderInfo::ByteOrder16Little | CGImageByteOrderInfo::ByteOrder16Big => word_bits == 16,
+            CGImageByteOrderInfo::ByteOrder32Little | CGImageByteOrderInfo::ByteOrder32Big => {
+                word_bits == 32 || (self.bits_per_component == 8 && self.bits_per_pixel() == 32)
+            }
+            CGImageByteOrderInfo::ByteOrderMask => false,
+        };
+        if !byte_order_supported {
+            return Err(PixelFormatError::UnsupportedByteOrder);
+        }
+        Ok(())
+    }
+}
+
+impl From<PixelFormat> for CGBitmapInfo {
+    fn from(format: PixelFormat) -> CGBitmapInfo {
+        format.bitmap_info()
+    }
+}
+
+pub(crate) fn alpha_info_from_bits(bits: u32) -> Result<CGImageAlphaInfo, PixelFormatError> {
+    Ok(match bits {
+        kCGImageAlphaNone => CGImageAlphaInfo::AlphaNone,
+        kCGImageAlphaPremultipliedLast => CGImageAlphaInfo::AlphaPremultipliedLast,
+        kCGImageAlphaPremultipliedFirst => CGImageAlphaInfo::AlphaPremultipliedFirst,
+        kCGImageAlphaLast => CGImageAlphaInfo::AlphaLast,
+        kCGImageAlphaFirst => CGImageAlphaInfo::AlphaFirst,
+        kCGImageAlphaNoneSkipLast => CGImageAlphaInfo::AlphaNoneSkipLast,
+        kCGImageAlphaNoneSkipFirst => CGImageAlphaInfo::AlphaNoneSkipFirst,
+        kCGImageAlphaOnly => CGImageAlphaInfo::AlphaOnly,
+        _ => return Err(PixelFormatError::InvalidAlphaInfo(bits)),
+    })
+}
+
+pub(crate) fn byte_order_from_bits(bits: u32) -> Result<CGImageByteOrderInfo, PixelFormatError> {
+    Ok(match bits {
+        kCGImageByteOrderDefault => CGImageByteOrderInfo::ByteOrderDefault,
+        kCGImageByteOrder16Little => CGImageByteOrderInfo::ByteOrder16Little,
+        kCGImageByteOrder32Little => CGImageByteOrderInfo::ByteOrder32Little,
+        kCGImageByteOrder16Big => CGImageByteOrderInfo::ByteOrder16Big,
+        kCGImageByteOrder32Big => CGImageByteOrderInfo::ByteOrder32Big,
+        _ => return Err(PixelFormatError::InvalidByteOrder(bits)),
+    })
+}
+
+pub(crate) fn pixel_format_from_bits(bits: u32) -> Result<CGImagePixelFormatInfo, PixelFormatError> {
+    Ok(match bits {
+        kCGImagePixelFormatPacked => CGImagePixelFormatInfo::PixelFormatPacked,
+        kCGImagePixelFormatRGB555 => CGImagePixelFormatInfo::PixelFormatRGB555,
+        kCGImagePixelFormatRGB565 => CGImagePixelFormatInfo::PixelFormatRGB565,
+        kCGImagePixelFormatRGB101010 => CGImagePixelFormatInfo::PixelFormatRGB101010,
+        kCGImagePixelFormatRGBCIF10 => CGImagePixelFormatInfo::PixelFormatRGBCIF10,
+        _ => return Err(PixelFormatError::InvalidPixelFormat(bits)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [PixelFormat; 23] = [
+        PixelFormat::Alpha8,
+        PixelFormat::Gray8,
+        PixelFormat::Gray16,
+        PixelFormat::Gray16Host,
+        PixelFormat::Gray32F,
+        PixelFormat::XRGB1555Host,
+        PixelFormat::RGB565Host,
+        PixelFormat::XRGB8888,
+        PixelFormat::RGBX8888,
+        PixelFormat::ARGB8888,
+        PixelFormat::RGBA8888,
+        PixelFormat::XRGB8888Host,
+        PixelFormat::ARGB8888Host,
+        PixelFormat::XRGB2101010Host,
+        PixelFormat::RGBX16,
+        PixelFormat::RGBA16,
+        PixelFormat::RGBX16F,
+        PixelFormat::RGBA16F,
+        PixelFormat::RGBX32F,
+        PixelFormat::RGBA32F,
+        PixelFormat::CMYK8,
+        PixelFormat::CMYK16,
+        PixelFormat::CMYK32F,
+    ];
+
+    #[test]
+    fn constants_round_trip_through_bitmap_info() {
+        for format in FORMATS {
+            assert_eq!(format.check(), Ok(()), "{:?}", format);
+            let bitmap_info: CGBitmapInfo = format.into();
+            assert_eq!(PixelFormat::from_bitmap_info(bitmap_info, format.bits_per_component(), format.color_space_model()), Ok(format));
+        }
+    }
+
+    #[test]
+    fn pixel_sizes() {
+        let sizes = FORMATS.map(|format| (format.components_per_pixel(), format.bits_per_pixel()));
+        assert_eq!(&sizes[..5], &[(1, 8), (1, 8), (1, 16), (1, 16), (1, 32)]);
+        assert_eq!(&sizes[5..7], &[(4, 16), (3, 16)]);
+        assert_eq!(sizes[9], (4, 32));
+        assert_eq!(sizes[13], (4, 32));
+        assert_eq!(sizes[15], (4, 64));
+        assert_eq!(sizes[19], (4, 128));
+        assert_eq!(sizes[21], (4, 64));
+        assert_eq!(PixelFormat::RGBA8888.bytes_per_pixel(), 4);
+        assert_eq!(PixelFormat::RGB565Host.min_bytes_per_row(3), Some(6));
+        assert_eq!(PixelFormat::RGB565Host.aligned_bytes_per_row(3), Some(16));
+        assert_eq!(PixelFormat::RGBA32F.aligned_bytes_per_row(2), Some(32));
+        assert_eq!(PixelFormat::RGBA8888.aligned_bytes_per_row(usize::MAX), None);
+        assert_eq!(PixelFormat::CMYK8.aligned_bytes_per_row(0), Some(0));
+    }
+
+    #[test]
+    fn alpha() {
+        assert!(PixelFormat::Alpha8.has_alpha());
+        assert_eq!(PixelFormat::Alpha8.color_components(), 0);
+        assert!(!PixelFormat::Alpha8.is_premultiplied());
+        assert!(PixelFormat::ARGB8888.has_alpha() && PixelFormat::ARGB8888.is_premultiplied());
+        assert!(!PixelFormat::XRGB8888.has_alpha());
+        assert_eq!(PixelFormat::XRGB8888.components_per_pixel(), 4);
+        assert!(!PixelFormat::CMYK16.has_alpha());
+        assert!(PixelFormat::RGBA16F.has_float_components());
+        assert!(!PixelFormat::RGBA16.has_float_components());
+    }
+
+    #[test]
+    fn unsupported_formats() {
+        use CGImageAlphaInfo::*;
+        use CGImageByteOrderInfo::*;
+        use CGImagePixelFormatInfo::*;
+
+        let new = |model, bits, alpha, float, byte_order, pixel_format| PixelFormat::new(model, bits, alpha, float, byte_order, pixel_format);
+        assert_eq!(new(RGB, 8, AlphaLast, false, ByteOrderDefault, PixelFormatPacked), Err(PixelFormatError::UnsupportedFormat));
+        assert_eq!(new(RGB, 8, AlphaNone, false, ByteOrderDefault, PixelFormatPacked), Err(PixelFormatError::UnsupportedFormat));
+        assert_eq!(new(GRAY, 8, AlphaNone, true, ByteOrderDefault, PixelFormatPacked), Err(PixelFormatError::UnsupportedFormat));
+        assert_eq!(new(None, 8, AlphaNone, false, ByteOrderDefault, PixelFormatPacked), Err(PixelFormatError::UnsupportedFormat));
+        assert_eq!(new(CMYK, 8, AlphaPremultipliedLast, false, ByteOrderDefault, PixelFormatPacked), Err(PixelFormatError::UnsupportedFormat));
+        assert_eq!(new(GRAY, 8, AlphaNone, false, ByteOrder16Little, PixelFormatPacked), Err(PixelFormatError::UnsupportedByteOrder));
+        assert_eq!(new(RGB, 16, AlphaPremultipliedLast, false, ByteOrder32Big, PixelFormatPacked), Err(PixelFormatError::UnsupportedByteOrder));
+        assert_eq!(new(RGB, 5, AlphaNone, false, ByteOrder32Little, PixelFormatRGB565), Err(PixelFormatError::UnsupportedByteOrder));
+        assert_eq!(new(RGB, 8, AlphaPremultipliedFirst, false, ByteOrder32Little, PixelFormatPacked), Ok(PixelFormat::ARGB8888Host));
+    }
+
+    #[test]
+    fn invalid_bitmap_info() {
+        let bitmap_info = |bits| CGBitmapInfo::from_bits_retain(bits);
+        assert_eq!(PixelFormat::from_bitmap_info(bitmap_info(9), 8, RGB), Err(PixelFormatError::InvalidAlphaInfo(9)));
+        assert_eq!(PixelFormat::from_bitmap_info(bitmap_info(5 << 12), 8, RGB), Err(PixelFormatError::InvalidByteOrder(5 << 12)));
+        assert_eq!(PixelFormat::from_bitmap_info(bitmap_info(5 << 16), 8, RGB), Err(PixelFormatError::InvalidPixelFormat(5 << 16)));
+    }
+
+    #[test]
+    fn validation() {
+        let format = PixelFormat::RGBX8888;
+        assert_eq!(format.validate_component_count(3), Ok(()));
+        assert_eq!(
+            format.validate_component_count(1),
+            Err(PixelFormatError::ComponentCountMismatch {
+                expected: 3,
+                found: 1,
+            })
+        );
+        assert_eq!(format.validate_bytes_per_row(4, 16), Ok(()));
+        assert_eq!(
+            format.validate_bytes_per_row(4, 15),
+            Err(PixelFormatError::BytesPerRowTooSmall {
+                minimum: 16,
+                found: 15,
+            })
+        );
+        assert_eq!(format.validate_bytes_per_row(usize::MAX, 0), Err(PixelFormatError::SizeOverflow));
+    }
+}
@@ -0,0 +1,755 @@
+#[cfg(target_os = "macos")]
+use std::sync::Arc;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+#[cfg(target_os = "macos")]
+use core_foundation::data::CFData;
+
+use crate::{
+    bitmap::{Bitmap, BitmapView},
+    color_space::CGColorSpaceModel,
+    deflate,
+    image::{CGBitmapInfo, CGImageAlphaInfo},
+    pixel_conversion::{PixelConverter, PixelLayout},
+};
+#[cfg(target_os = "macos")]
+use crate::{
+    color_space::{CGColorRenderingIntent, CGColorSpace, CGColorSpaceNames},
+    data_provider::CGDataProvider,
+    image::CGImage,
+    pixel_format::PixelFormat,
+};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const MAX_DIMENSION: usize = i32::MAX as usize;
+const MAX_ICC_PROFILE_SIZE: usize = 1 << 26;
+const IDAT_SIZE: usize = 1 << 20;
+
+const COLOR_TYPE_GRAY: u8 = 0;
+const COLOR_TYPE_RGB: u8 = 2;
+const COLOR_TYPE_INDEXED: u8 = 3;
+const COLOR_TYPE_GRAY_ALPHA: u8 = 4;
+const COLOR_TYPE_RGB_ALPHA: u8 = 6;
+
+// x, y, dx and dy of the adam7 passes
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PngError {
+    InvalidSignature,
+    InvalidChunk,
+    ChecksumMismatch,
+    InvalidHeader,
+    InvalidSize,
+    InvalidData,
+    UnsupportedFormat,
+    UnexpectedEnd,
+}
+
+impl Display for PngError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PngError::InvalidSignature => write!(f, "not a PNG file"),
+            PngError::InvalidChunk => write!(f, "invalid or misplaced chunk"),
+            PngError::ChecksumMismatch => write!(f, "chunk checksum mismatch"),
+            PngError::InvalidHeader => write!(f, "invalid image header"),
+            PngError::InvalidSize => write!(f, "image size is zero or too large"),
+            PngError::InvalidData => write!(f, "invalid compressed image data"),
+            PngError::UnsupportedFormat => write!(f, "unsupported pixel format"),
+            PngError::UnexpectedEnd => write!(f, "unexpected end of data"),
+        }
+    }
+}
+
+impl Error for PngError {}
+
+// a decoded image keeps the depth of the file, 16 bit samples are big endian and alpha is not premultiplied,
+// palettes and transparency keys are expanded and gray below 8 bits is scaled to 8 bits
+#[derive(Clone, Debug)]
+pub struct PngImage {
+    bitmap: Bitmap,
+    icc_profile: Option<Vec<u8>>,
+}
+
+impl PngImage {
+    pub fn new(bitmap: Bitmap, icc_profile: Option<Vec<u8>>) -> PngImage {
+        PngImage {
+            bitmap,
+            icc_profile,
+        }
+    }
+
+    pub fn decode(data: &[u8]) -> Result<PngImage, PngError> {
+        decode(data)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, PngError> {
+        encode(&self.bitmap.as_view(), self.icc_profile.as_deref())
+    }
+
+    // 8 or 16 bit depending on the image, with alpha when the image has it and the ICC profile of its color space
+    #[cfg(target_os = "macos")]
+    pub fn from_image(image: &CGImage) -> Option<PngImage> {
+        let space = image.color_space()?;
+        let deep = image.bits_per_component() > 8;
+        let alpha =
+            !matches!(image.alpha_info(), CGImageAlphaInfo::AlphaNone | CGImageAlphaInfo::AlphaNoneSkipFirst | CGImageAlphaInfo::AlphaNoneSkipLast);
+        let format = match (space.model(), deep, alpha) {
+            (CGColorSpaceModel::Monochrome, false, false) => PixelFormat::Gray8,
+            (CGColorSpaceModel::Monochrome, true, false) => PixelFormat::Gray16,
+            (_, false, false) => PixelFormat::RGBX8888,
+            (_, true, false) => PixelFormat::RGBX16,
+            (_, false, true) => PixelFormat::RGBA8888,
+            (_, true, true) => PixelFormat::RGBA16,
+        };
+        let bitmap = image.to_bitmap(format)?;
+        // the bitmap is only in the image's color space when the models agree
+        let icc_profile = match bitmap.color_model() == Some(space.model()) {
+            true => space.copy_icc_profile().map(|profile| profile.bytes().to_vec()),
+            false => None,
+        };
+        Some(PngImage::new(bitmap, icc_profile))
+    }
+
+    // uses the embedded ICC profile, or sRGB and gamma 2.2 gray when there is none
+    #[cfg(target_os = "macos")]
+    pub fn to_image(&self) -> Option<CGImage> {
+        let bitmap = &self.bitmap;
+        let model = bitmap.color_model()?;
+        let space = self
+            .icc_profile
+            .as_ref()
+            .and_then(|profile| CGColorSpace::from_icc_profile(&CFData::from_buffer(profile)))
+            .filter(|space| space.model() == model)
+            .or_else(|| match model {
+                CGColorSpaceModel::Monochrome => CGColorSpace::from_name(&CGColorSpaceNames::GenericGrayGamma2_2.into()),
+                _ => CGColorSpace::from_name(&CGColorSpaceNames::SRGB.into()),
+            })?;
+        let provider = CGDataProvider::from_buffer(Arc::new(bitmap.to_vec()))?;
+        CGImage::new(
+            bitmap.width(),
+            bitmap.height(),
+            bitmap.bits_per_component(),
+            bitmap.bits_per_pixel(),
+            bitmap.bytes_per_row(),
+            Some(&space),
+            bitmap.bitmap_info().bits(),
+            Some(&provider),
+            None,
+            true,
+            CGColorRenderingIntent::Default,
+        )
+    }
+
+    pub fn bitmap(&self) -> &Bitmap {
+        &self.bitmap
+    }
+
+    pub fn into_bitmap(self) -> Bitmap {
+        self.bitmap
+    }
+
+    pub fn icc_profile(&self) -> Option<&[u8]> {
+        self.icc_profile.as_deref()
+    }
+}
+
+// gray and RGB bitmaps are written as 8 bit samples, or 16 bit when they have more than 8 bits per component,
+// premultiplied alpha is converted to straight alpha and skipped alpha is dropped
+pub fn encode(bitmap: &BitmapView<'_>, icc_profile: Option<&[u8]>) -> Result<Vec<u8>, PngError> {
+    let (width, height) = (bitmap.width(), bitmap.height());
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(PngError::InvalidSize);
+    }
+    let layout = bitmap.layout();
+    let color_components = match bitmap.color_model() {
+        Some(CGColorSpaceModel::Monochrome) => 1,
+        Some(CGColorSpaceModel::RGB) => 3,
+        _ => return Err(PngError::UnsupportedFormat),
+    };
+    let alpha =
+        !matches!(layout.alpha_info(), CGImageAlphaInfo::AlphaNone | CGImageAlphaInfo::AlphaNoneSkipFirst | CGImageAlphaInfo::AlphaNoneSkipLast);
+    let bit_depth = if layout.bits_per_component() > 8 {
+        16
+    } else {
+        8
+    };
+    let channels = color_components + alpha as usize;
+    let color_type = match (color_components, alpha) {
+        (1, false) => COLOR_TYPE_GRAY,
+        (1, true) => COLOR_TYPE_GRAY_ALPHA,
+        (_, false) => COLOR_TYPE_RGB,
+        (_, true) => COLOR_TYPE_RGB_ALPHA,
+    };
+    let alpha_info = if alpha {
+        CGImageAlphaInfo::AlphaLast
+    } else {
+        CGImageAlphaInfo::AlphaNone
+    };
+    let png_layout = PixelLayout::new(color_components, bit_depth, bit_depth * channels, CGBitmapInfo::from_bits_retain(alpha_info as u32))
+        .map_err(|_| PngError::UnsupportedFormat)?;
+    let converter = PixelConverter::new(*layout, png_layout).map_err(|_| PngError::UnsupportedFormat)?;
+    let row_len = png_layout.min_bytes_per_row(width).ok_or(PngError::InvalidSize)?;
+    let raw_len = (row_len + 1).checked_mul(height).ok_or(PngError::InvalidSize)?;
+
+    let bytes_per_pixel = channels * bit_depth / 8;
+    let mut raw = Vec::with_capacity(raw_len);
+    let mut previous = vec![0; row_len];
+    let mut current = vec![0; row_len];
+    let mut filtered = vec![vec![0; row_len]; 5];
+    for row in bitmap.rows() {
+        converter.convert_row(row, &mut current, width).map_err(|_| PngError::UnsupportedFormat)?;
+        // the filter with the smallest sum of absolute differences usually compresses best
+        let mut best = (0, usize::MAX);
+        for (filter, output) in filtered.iter_mut().enumerate() {
+            filter_row(filter as u8, &current, &previous, bytes_per_pixel, output);
+            let cost = output.iter().map(|byte| (*byte as i8).unsigned_abs() as usize).sum::<usize>();
+            if cost < best.1 {
+                best = (filter, cost);
+            }
+        }
+        raw.push(best.0 as u8);
+        raw.extend_from_slice(&filtered[best.0]);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let mut output = SIGNATURE.to_vec();
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[bit_depth as u8, color_type, 0, 0, 0]);
+    write_chunk(&mut output, b"IHDR", &header);
+    if let Some(icc_profile) = icc_profile {
+        let mut chunk = b"ICC Profile\0\0".to_vec();
+        chunk.extend_from_slice(&deflate::compress(icc_profile));
+        write_chunk(&mut output, b"iCCP", &chunk);
+    }
+    for data in deflate::compress(&raw).chunks(IDAT_SIZE) {
+        write_chunk(&mut output, b"IDAT", data);
+    }
+    write_chunk(&mut output, b"IEND", &[]);
+    Ok(output)
+}
+
+pub fn decode(data: &[u8]) -> Result<PngImage, PngError> {
+    let mut data = data.strip_prefix(&SIGNATURE[..]).ok_or(PngError::InvalidSignature)?;
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut transparency = None;
+    let mut icc_profile = None;
+    let mut compressed = Vec::new();
+    loop {
+        let (kind, chunk) = read_chunk(&mut data)?;
+        if header.is_none() != (&kind == b"IHDR") {
+            return Err(PngError::InvalidChunk);
+        }
+        match &kind {
+            b"IHDR" => header = Some(Header::parse(chunk)?),
+            b"PLTE" => {
+                if chunk.is_empty() || chunk.len() % 3 != 0 || chunk.len() > 256 * 3 {
+                    return Err(PngError::InvalidChunk);
+                }
+                palette = chunk.to_vec();
+            }
+            b"tRNS" => transparency = Some(chunk.to_vec()),
+            b"iCCP" => {
+                let name_len = chunk.iter().position(|byte| *byte == 0).filter(|len| (1..80).contains(len)).ok_or(PngError::InvalidChunk)?;
+                if chunk.get(name_len + 1) != Some(&0) {
+                    return Err(PngError::UnsupportedFormat);
+                }
+                icc_profile = Some(deflate::decompress(&chunk[name_len + 2..], MAX_ICC_PROFILE_SIZE).ok_or(PngError::InvalidData)?);
+            }
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            // unknown critical chunks change how the image is read
+            _ if kind[0] & 0x20 == 0 => return Err(PngError::UnsupportedFormat),
+            _ => {}
+        }
+    }
+    let header = header.ok_or(PngError::InvalidChunk)?;
+    if header.color_type == COLOR_TYPE_INDEXED && palette.is_empty() {
+        return Err(PngError::InvalidChunk);
+    }
+    let transparency = match (header.color_type, transparency) {
+        (_, None) => None,
+        (COLOR_TYPE_GRAY, Some(key)) if key.len() == 2 => Some(key),
+        (COLOR_TYPE_RGB, Some(key)) if key.len() == 6 => Some(key),
+        (COLOR_TYPE_INDEXED, Some(alpha)) if alpha.len() <= palette.len() / 3 => Some(alpha),
+        _ => return Err(PngError::InvalidChunk),
+    };
+
+    let passes = header.passes();
+    let raw_len = passes.iter().try_fold(0usize, |len, pass| {
+        let row_len = header.row_len(pass.4)?;
+        len.checked_add(if pass.4 == 0 {
+            0
+        } else {
+            (row_len + 1).checked_mul(pass.5)?
+        })
+    });
+    let raw_len = raw_len.ok_or(PngError::InvalidSize)?;
+    let mut raw = deflate::decompress(&compressed, raw_len).ok_or(PngError::InvalidData)?;
+    if raw.len() != raw_len {
+        return Err(PngError::InvalidData);
+    }
+
+    let alpha = transparency.is_some() || matches!(header.color_type, COLOR_TYPE_GRAY_ALPHA | COLOR_TYPE_RGB_ALPHA);
+    let (color_components, model) = match header.color_type {
+        COLOR_TYPE_GRAY | COLOR_TYPE_GRAY_ALPHA => (1, CGColorSpaceModel::Monochrome),
+        _ => (3, CGColorSpaceModel::RGB),
+    };
+    let bit_depth = if header.bit_depth == 16 {
+        16
+    } else {
+        8
+    };
+    let alpha_info = if alpha {
+        CGImageAlphaInfo::AlphaLast
+    } else {
+        CGImageAlphaInfo::AlphaNone
+    };
+    let channels = color_components + alpha as usize;
+    let layout = PixelLayout::new(color_components, bit_depth, bit_depth * channels, CGBitmapInfo::from_bits_retain(alpha_info as u32))
+        .map_err(|_| PngError::UnsupportedFormat)?;
+    let bytes_per_pixel = channels * bit_depth / 8;
+    let bytes_per_row =
+        header.width.checked_mul(bytes_per_pixel).and_then(|len| len.checked_add(15)).map(|len| len / 16 * 16).ok_or(PngError::InvalidSize)?;
+    let mut bitmap = Bitmap::with_layout(header.width, header.height, bytes_per_row, Some(model), layout).map_err(|_| PngError::InvalidSize)?;
+
+    let expand = Expand {
+        header: &header,
+        palette: &palette,
+        transparency: transparency.as_deref(),
+        bit_depth,
+    };
+    let filter_bytes_per_pixel = (header.channels() * header.bit_depth as usize).div_ceil(8);
+    let mut pixels = vec![0; header.width * bytes_per_pixel];
+    let mut start = 0;
+    for (x, y, dx, dy, width, height) in passes {
+        if width == 0 {
+            continue;
+        }
+        let row_len = header.row_len(width).ok_or(PngError::InvalidSize)?;
+        let mut previous = vec![0; row_len];
+        for row in 0..height {
+            let (filter, current) = raw[start..start + row_len + 1].split_first_mut().unwrap();
+            unfilter_row(*filter, current, &previous, filter_bytes_per_pixel)?;
+            previous.copy_from_slice(current);
+            start += row_len + 1;
+            expand.row(current, width, &mut pixels)?;
+            let output = bitmap.row_mut(y + row * dy).unwrap();
+            if dx == 1 {
+                output.copy_from_slice(&pixels[..width * bytes_per_pixel]);
+            } else {
+                for (index, pixel) in pixels[..width * bytes_per_pixel].chunks_exact(bytes_per_pixel).enumerate() {
+                    let offset = (x + index * dx) * bytes_per_pixel;
+                    output[offset..offset + bytes_per_pixel].copy_from_slice(pixel);
+                }
+            }
+        }
+    }
+    Ok(PngImage {
+        bitmap,
+        icc_profile,
+    })
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(chunk: &[u8]) -> Result<Header, PngError> {
+        if chunk.len() != 13 {
+            return Err(PngError::InvalidHeader);
+        }
+        let width = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as usize;
+        let height = u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+        let (bit_depth, color_type) = (chunk[8], chunk[9]);
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(PngError::InvalidSize);
+        }
+        let valid_depth = match color_type {
+            COLOR_TYPE_GRAY => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+            COLOR_TYPE_INDEXED => matches!(bit_depth, 1 | 2 | 4 | 8),
+            COLOR_TYPE_RGB | COLOR_TYPE_GRAY_ALPHA | COLOR_TYPE_RGB_ALPHA => matches!(bit_depth, 8 | 16),
+            _ => false,
+        };
+        if !valid_depth || chunk[10] != 0 || chunk[11] != 0 || chunk[12] > 1 {
+            return Err(PngError::InvalidHeader);
+        }
+        Ok(Header {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced: chunk[12] == 1,
+        })
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            COLOR_TYPE_GRAY | COLOR_TYPE_INDEXED => 1,
+            COLOR_TYPE_GRAY_ALPHA => 2,
+            COLOR_TYPE_RGB => 3,
+            _ => 4,
+        }
+    }
+
+    fn row_len(&self, width: usize) -> Option<usize> {
+        Some(width.checked_mul(self.channels() * self.bit_depth as usize)?.div_ceil(8))
+    }
+
+    // x, y, dx, dy, width and height of the passes, a single pass when not interlaced
+    fn passes(&self) -> Vec<(usize, usize, usize, usize, usize, usize)> {
+        if !self.interlaced {
+            return vec![(0, 0, 1, 1, self.width, self.height)];
+        }
+        ADAM7_PASSES
+            .iter()
+            .map(|(x, y, dx, dy)| {
+                // passes without pixels in either direction are empty
+                let width = self.width.saturating_sub(*x).div_ceil(*dx);
+                let height = self.height.saturating_sub(*y).div_ceil(*dy);
+                match width == 0 || height == 0 {
+                    true => (*x, *y, *dx, *dy, 0, 0),
+                    false => (*x, *y, *dx, *dy, width, height),
+                }
+            })
+            .collect()
+    }
+}
+
+// converts unfiltered scanlines to the pixels of the decoded bitmap
+struct Expand<'a> {
+    header: &'a Header,
+    palette: &'a [u8],
+    transparency: Option<&'a [u8]>,
+    bit_depth: usize,
+}
+
+impl Expand<'_> {
+    fn row(&self, scanline: &[u8], width: usize, output: &mut [u8]) -> Result<(), PngError> {
+        let depth = self.header.bit_depth as usize;
+        let max = (1u32 << depth) - 1;
+        let sample = |index: usize| -> u16 {
+            match depth {
+                16 => u16::from_be_bytes([scanline[index * 2], scanline[index * 2 + 1]]),
+                8 => scanline[index] as u16,
+                _ => {
+                    let bit = index * depth;
+                    ((scanline[bit / 8] >> (8 - depth - bit % 8)) as u32 & max) as u16
+                }
+            }
+        };
+        let mut index = 0;
+        let mut write = |value: u32| {
+            if self.bit_depth == 16 {
+                output[index..index + 2].copy_from_slice(&(value as u16).to_be_bytes());
+                index += 2;
+            } else {
+                output[index] = value as u8;
+                index += 1;
+            }
+        };
+        let opaque = if self.bit_depth == 16 {
+            0xffff
+        } else {
+            0xff
+        };
+        let channels = self.header.channels();
+        for x in 0..width {
+            match self.header.color_type {
+                COLOR_TYPE_INDEXED => {
+                    let entry = sample(x) as usize;
+                    let color = self.palette.get(entry * 3..entry * 3 + 3).ok_or(PngError::InvalidData)?;
+                    color.iter().for_each(|component| write(*component as u32));
+                    if let Some(transparency) = self.transparency {
+                        write(transparency.get(entry).map_or(opaque, |alpha| *alpha as u32));
+                    }
+                }
+                _ => {
+                    let mut transparent = self.transparency.is_some();
+                    for channel in 0..channels {
+                        let value = sample(x * channels + channel);
+                        if let Some(key) = self.transparency {
+                            transparent &= u16::from_be_bytes([key[channel * 2], key[channel * 2 + 1]]) == value;
+                        }
+                        // gray below 8 bits is scaled to the full range
+                        write(if depth < 8 {
+                            value as u32 * 255 / max
+                        } else {
+                            value as u32
+                        });
+                    }
+                    if self.transparency.is_some() {
+                        write(if transparent {
+                            0
+                        } else {
+                            opaque
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn predict(filter: u8, left: u8, up: u8, up_left: u8) -> u8 {
+    match filter {
+        1 => left,
+        2 => up,
+        3 => ((left as u16 + up as u16) / 2) as u8,
+        4 => {
+            let (a, b, c) = (left as i16, up as i16, up_left as i16);
+            let p = a + b - c;
+            let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+            if pa <= pb && pa <= pc {
+                left
+            } else if pb <= pc {
+                up
+            } else {
+                up_left
+            }
+        }
+        _ => 0,
+    }
+}
+
+fn filter_row(filter: u8, current: &[u8], previous: &[u8], bytes_per_pixel: usize, output: &mut [u8]) {
+    for index in 0..current.len() {
+        let (left, up_left) = match index.checked_sub(bytes_per_pixel) {
+            Some(left) => (current[left], previous[left]),
+            None => (0, 0),
+        };
+        output[index] = current[index].wrapping_sub(predict(filter, left, previous[index], up_left));
+    }
+}
+
+fn unfilter_row(filter: u8, current: &mut [u8], previous: &[u8], bytes_per_pixel: usize) -> Result<(), PngError> {
+    if filter > 4 {
+        return Err(PngError::InvalidData);
+    }
+    for index in 0..current.len() {
+        let (left, up_left) = match index.checked_sub(bytes_per_pixel) {
+            Some(left) => (current[left], previous[left]),
+            None => (0, 0),
+        };
+        current[index] = current[index].wrapping_add(predict(filter, left, previous[index], up_left));
+    }
+    Ok(())
+}
+
+fn read_chunk<'a>(data: &mut &'a [u8]) -> Result<([u8; 4], &'a [u8]), PngError> {
+    if data.len() < 12 {
+        return Err(PngError::UnexpectedEnd);
+    }
+    let len = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    if len > i32::MAX as usize {
+        return Err(PngError::InvalidChunk);
+    }
+    if data.len() - 12 < len {
+        return Err(PngError::UnexpectedEnd);
+    }
+    let kind = [data[4], data[5], data[6], data[7]];
+    let chunk = &data[8..8 + len];
+    let crc = &data[8 + len..12 + len];
+    if u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]) != crc32(&data[4..8 + len]) {
+        return Err(PngError::ChecksumMismatch);
+    }
+    *data = &data[12 + len..];
+    Ok((kind, chunk))
+}
+
+fn write_chunk(output: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = output.len();
+    output.extend_from_slice(kind);
+    output.extend_from_slice(data);
+    let crc = crc32(&output[start..]);
+    output.extend_from_slice(&crc.to_be_bytes());
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel_format::PixelFormat;
+
+    fn bitmap(width: usize, height: usize, format: PixelFormat, pixel: impl Fn(usize, usize) -> Vec<u8>) -> Bitmap {
+        let mut bitmap = Bitmap::new(width, height, format).unwrap();
+        for y in 0..height {
+            let row: Vec<u8> = (0..width).flat_map(|x| pixel(x, y)).collect();
+            bitmap.row_mut(y).unwrap().copy_from_slice(&row);
+        }
+        bitmap
+    }
+
+    // a file with the given header fields and chunks between the header and the image data
+    fn file(width: u32, height: u32, bit_depth: u8, color_type: u8, interlaced: bool, chunks: &[(&[u8; 4], &[u8])], raw: &[u8]) -> Vec<u8> {
+        let mut output = SIGNATURE.to_vec();
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[bit_depth, color_type, 0, 0, interlaced as u8]);
+        write_chunk(&mut output, b"IHDR", &header);
+        for (kind, data) in chunks {
+            write_chunk(&mut output, kind, data);
+        }
+        write_chunk(&mut output, b"IDAT", &deflate::compress(raw));
+        write_chunk(&mut output, b"IEND", &[]);
+        output
+    }
+
+    fn rows(bitmap: &Bitmap) -> Vec<&[u8]> {
+        bitmap.rows().collect()
+    }
+
+    #[test]
+    fn rgba8_round_trip() {
+        let original = bitmap(5, 3, PixelFormat::RGBA8888, |x, y| {
+            let alpha = (x * 60 + y * 5) as u8;
+            vec![alpha / 2, alpha / 3, (y * 40).min(alpha as usize) as u8, alpha]
+        });
+        let data = encode(&original.as_view(), None).unwrap();
+        assert_eq!(&data[24..26], &[8, COLOR_TYPE_RGB_ALPHA]);
+        let decoded = decode(&data).unwrap();
+        assert_eq!(decoded.icc_profile(), None);
+        let bitmap = decoded.bitmap();
+        assert_eq!((bitmap.width(), bitmap.height(), bitmap.bits_per_component()), (5, 3, 8));
+        assert_eq!(bitmap.alpha_info(), CGImageAlphaInfo::AlphaLast);
+        // 122, 81 and 40 premultiplied by 245 are stored straight
+        assert_eq!(bitmap.color(4, 1), Some(([127.0 / 255.0, 84.0 / 255.0, 42.0 / 255.0, 0.0], 245.0 / 255.0)));
+        assert_eq!(rows(&bitmap.as_view().to_bitmap(PixelFormat::RGBA8888).unwrap()), rows(&original));
+    }
+
+    #[test]
+    fn gray16_round_trip() {
+        let original = bitmap(7, 4, PixelFormat::Gray16, |x, y| ((x * 9001 + y * 1234) as u16).to_be_bytes().to_vec());
+        let data = encode(&original.as_view(), None).unwrap();
+        assert_eq!(&data[24..26], &[16, COLOR_TYPE_GRAY]);
+        let decoded = decode(&data).unwrap().into_bitmap();
+        assert_eq!(decoded.color_model(), Some(CGColorSpaceModel::Monochrome));
+        assert_eq!(decoded.alpha_info(), CGImageAlphaInfo::AlphaNone);
+        assert_eq!(rows(&decoded), rows(&original));
+
+        // skipped alpha is dropped and 8 bit RGB stays 8 bit
+        let rgbx = bitmap(2, 2, PixelFormat::RGBX8888, |x, y| vec![x as u8, y as u8, 7, 0]);
+        let decoded = decode(&encode(&rgbx.as_view(), None).unwrap()).unwrap().into_bitmap();
+        assert_eq!(rows(&decoded), [&[0, 0, 7, 1, 0, 7][..], &[0, 1, 7, 1, 1, 7][..]]);
+    }
+
+    #[test]
+    fn transparency_keys_and_palettes() {
+        let raw = [0, 10, 20, 30, 40, 50, 60];
+        let data = file(2, 1, 8, COLOR_TYPE_RGB, false, &[(b"tRNS", &[0, 10, 0, 20, 0, 30])], &raw);
+        let decoded = decode(&data).unwrap().into_bitmap();
+        assert_eq!(decoded.alpha_info(), CGImageAlphaInfo::AlphaLast);
+        assert_eq!(rows(&decoded), [&[10, 20, 30, 0, 40, 50, 60, 255][..]]);
+
+        // 2 bit indices, entries past the end of tRNS are opaque
+        let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let raw = [0, 0b00_01_10_00];
+        let data = file(3, 1, 2, COLOR_TYPE_INDEXED, false, &[(b"PLTE", &palette), (b"tRNS", &[0, 128])], &raw);
+        let decoded = decode(&data).unwrap().into_bitmap();
+        assert_eq!(rows(&decoded), [&[255, 0, 0, 0, 0, 255, 0, 128, 0, 0, 255, 255][..]]);
+
+        // gray below 8 bits is scaled
+        let data = file(4, 1, 2, COLOR_TYPE_GRAY, false, &[(b"tRNS", &[0, 2])], &[0, 0b00_01_10_11]);
+        assert_eq!(rows(&decode(&data).unwrap().into_bitmap()), [&[0, 255, 85, 255, 170, 0, 255, 255][..]]);
+
+        let data = file(2, 1, 8, COLOR_TYPE_RGB, false, &[(b"tRNS", &[0, 10])], &raw);
+        assert_eq!(decode(&data).err(), Some(PngError::InvalidChunk));
+        let data = file(2, 1, 8, COLOR_TYPE_INDEXED, false, &[], &raw);
+        assert_eq!(decode(&data).err(), Some(PngError::InvalidChunk));
+    }
+
+    #[test]
+    fn adam7() {
+        for (width, height) in [(9, 9), (3, 2), (1, 1), (17, 5)] {
+            let value = |x: usize, y: usize| (x * 13 + y * 29) as u8;
+            let mut raw = Vec::new();
+            for (x0, y0, dx, dy) in ADAM7_PASSES {
+                if x0 >= width || y0 >= height {
+                    continue;
+                }
+                for y in (y0..height).step_by(dy) {
+                    raw.push(0);
+                    raw.extend((x0..width).step_by(dx).map(|x| value(x, y)));
+                }
+            }
+            let data = file(width as u32, height as u32, 8, COLOR_TYPE_GRAY, true, &[], &raw);
+            let expected = bitmap(width, height, PixelFormat::Gray8, |x, y| vec![value(x, y)]);
+            assert_eq!(rows(&decode(&data).unwrap().into_bitmap()), rows(&expected), "{}x{}", width, height);
+        }
+    }
+
+    #[test]
+    fn icc_profile_round_trip() {
+        let profile: Vec<u8> = (0..3000u32).map(|index| (index * index % 251) as u8).collect();
+        let image = PngImage::new(bitmap(2, 2, PixelFormat::Gray8, |x, y| vec![(x + y * 2) as u8]), Some(profile.clone()));
+        let decoded = PngImage::decode(&image.encode().unwrap()).unwrap();
+        assert_eq!(decoded.icc_profile(), Some(&profile[..]));
+        assert_eq!(rows(decoded.bitmap()), rows(image.bitmap()));
+    }
+
+    #[test]
+    fn rejects_corrupt_files() {
+        let data = encode(&bitmap(2, 2, PixelFormat::RGBA8888, |_, _| vec![1, 2, 3, 255]).as_view(), None).unwrap();
+        // a flipped bit in the header and in the checksum of the last chunk
+        let mut corrupt = data.clone();
+        corrupt[17] ^= 1;
+        assert_eq!(decode(&corrupt).err(), Some(PngError::ChecksumMismatch));
+        let mut corrupt = data.clone();
+        *corrupt.last_mut().unwrap() ^= 0x80;
+        assert_eq!(decode(&corrupt).err(), Some(PngError::ChecksumMismatch));
+
+        assert_eq!(decode(&data[..data.len() - 1]).err(), Some(PngError::UnexpectedEnd));
+        assert_eq!(decode(&data[1..]).err(), Some(PngError::InvalidSignature));
+        assert_eq!(decode(&file(2, 1, 8, COLOR_TYPE_GRAY, false, &[], &[0, 1])).err(), Some(PngError::InvalidData));
+        assert_eq!(decode(&file(1, 1, 8, COLOR_TYPE_GRAY, false, &[], &[5, 1])).err(), Some(PngError::InvalidData));
+        assert_eq!(decode(&file(1, 1, 8, COLOR_TYPE_RGB_ALPHA, false, &[(b"ZzZz", &[])], &[0; 5])).err(), Some(PngError::UnsupportedFormat));
+        assert_eq!(decode(&file(1, 1, 4, COLOR_TYPE_RGB, false, &[], &[0; 3])).err(), Some(PngError::InvalidHeader));
+        assert_eq!(decode(&file(0, 1, 8, COLOR_TYPE_GRAY, false, &[], &[0])).err(), Some(PngError::InvalidSize));
+    }
+}
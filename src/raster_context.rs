@@ -0,0 +1,1685 @@
+use std::rc::Rc;
+
+use crate::{
+    affine_transform::{CGAffineTransform, CGAffineTransformIdentity},
+    base::CGFloat,
+    bitmap::{Bitmap, BitmapView},
+    color_space::{CGColorRenderingIntent, CGColorSpaceModel},
+    context::{CGBlendMode, CGInterpolationQuality, CGPathDrawingMode, CGTextDrawingMode},
+    font::CGGlyph,
+    geometry::{CGPoint, CGRect, CGSize},
+    graphics_context::GraphicsContext,
+    image::{CGBitmapInfo, CGImageAlphaInfo},
+    path::{CGLineCap, CGLineJoin},
+    path_boolean::{is_inside, polygon_edges, Edge},
+    path_builder::{Path, PathBuilder},
+    path_stroke::StrokeStyle,
+    resample,
+};
+#[cfg(target_os = "macos")]
+use crate::{
+    color::CGColor,
+    color_space::CGColorSpace,
+    font::CGFont,
+    gradient::{CGGradient, CGGradientDrawingOptions},
+    image::CGImage,
+    pattern::CGPattern,
+    pixel_format::PixelFormat,
+    shading::CGShading,
+};
+
+const BYTES_PER_PIXEL: usize = 4;
+const SUBSAMPLES: usize = 16;
+// device space flattening error for the default flatness of 1
+const FLATTENING_TOLERANCE: CGFloat = 0.025;
+// the radius of the widest resampling filter, lanczos
+const EDGE_EXTENSION: usize = 3;
+
+type Color = [CGFloat; 4];
+
+#[derive(Clone, Debug)]
+struct GState {
+    ctm: CGAffineTransform,
+    clip: Option<Rc<Vec<f32>>>,
+    fill_color: Color,
+    stroke_color: Color,
+    stroke_style: StrokeStyle,
+    flatness: CGFloat,
+    alpha: CGFloat,
+    blend_mode: CGBlendMode,
+    should_antialias: bool,
+    interpolation_quality: CGInterpolationQuality,
+}
+
+impl Default for GState {
+    fn default() -> GState {
+        GState {
+            ctm: CGAffineTransformIdentity,
+            clip: None,
+            fill_color: [0.0, 0.0, 0.0, 1.0],
+            stroke_color: [0.0, 0.0, 0.0, 1.0],
+            stroke_style: StrokeStyle::default(),
+            flatness: 1.0,
+            alpha: 1.0,
+            blend_mode: CGBlendMode::Normal,
+            should_antialias: true,
+            interpolation_quality: CGInterpolationQuality::Default,
+        }
+    }
+}
+
+// the backdrop a transparency layer is drawn over, along with the state it is composited with
+#[derive(Clone, Debug)]
+struct Layer {
+    data: Vec<u8>,
+    state: GState,
+    clip: Option<Rc<Vec<f32>>>,
+    depth: usize,
+}
+
+// the drawing operations the software rasterizer cannot render, they draw nothing and are reported
+// by RasterContext::unsupported_operations instead
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum UnsupportedOperation {
+    // fill and stroke patterns, the fill or stroke color is left unchanged
+    Pattern,
+    // linear and radial gradients
+    Gradient,
+    Shading,
+    // shadows are not drawn under later operations
+    Shadow,
+    // glyphs need the outlines of the font
+    Glyphs,
+    // images whose pixels cannot be read into a bitmap
+    Image,
+}
+
+// an 8 bits per component RGBA context with premultiplied alpha, rows are stored top to bottom and
+// the origin of user space is at the bottom left, as with CGBitmapContext
+#[derive(Clone, Debug)]
+pub struct RasterContext {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+    state: GState,
+    state_stack: Vec<GState>,
+    path: PathBuilder,
+    allows_antialiasing: bool,
+    layers: Vec<Layer>,
+    unsupported_operations: Vec<UnsupportedOperation>,
+}
+
+impl RasterContext {
+    pub fn new(width: usize, height: usize) -> Option<RasterContext> {
+        let size = width.checked_mul(height)?.checked_mul(BYTES_PER_PIXEL)?;
+        Some(RasterContext {
+            width,
+            height,
+            data: vec![0; size],
+            state: GState::default(),
+            state_stack: Vec::new(),
+            path: PathBuilder::new(),
+            allows_antialiasing: true,
+            layers: Vec::new(),
+            unsupported_operations: Vec::new(),
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn bits_per_component(&self) -> usize {
+        8
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        BYTES_PER_PIXEL * 8
+    }
+
+    pub fn bytes_per_row(&self) -> usize {
+        self.width * BYTES_PER_PIXEL
+    }
+
+    pub fn alpha_info(&self) -> CGImageAlphaInfo {
+        CGImageAlphaInfo::AlphaPremultipliedLast
+    }
+
+    pub fn bitmap_info(&self) -> CGBitmapInfo {
+        CGBitmapInfo::AlphaPremultipliedLast
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    // each kind of operation that was skipped so far, in the order they were first encountered
+    pub fn unsupported_operations(&self) -> &[UnsupportedOperation] {
+        &self.unsupported_operations
+    }
+
+    pub fn save_state(&mut self) {
+        self.state_stack.push(self.state.clone());
+    }
+
+    pub fn restore_state(&mut self) {
+        if let Some(state) = self.state_stack.pop() {
+            self.state = state;
+        }
+    }
+
+    pub fn scale(&mut self, sx: CGFloat, sy: CGFloat) {
+        self.concat_ctm(CGAffineTransform::new_scale(sx, sy));
+    }
+
+    pub fn translate(&mut self, tx: CGFloat, ty: CGFloat) {
+        self.concat_ctm(CGAffineTransform::new_translate(tx, ty));
+    }
+
+    pub fn rotate(&mut self, angle: CGFloat) {
+        self.concat_ctm(CGAffineTransform::new_rotate(angle));
+    }
+
+    pub fn concat_ctm(&mut self, transform: CGAffineTransform) {
+        self.state.ctm = transform.concat(&self.state.ctm);
+    }
+
+    pub fn get_ctm(&self) -> CGAffineTransform {
+        self.state.ctm
+    }
+
+    pub fn set_line_width(&mut self, width: CGFloat) {
+        self.state.stroke_style.line_width = width;
+    }
+
+    pub fn set_line_cap(&mut self, cap: CGLineCap) {
+        self.state.stroke_style.line_cap = cap;
+    }
+
+    pub fn set_line_join(&mut self, join: CGLineJoin) {
+        self.state.stroke_style.line_join = join;
+    }
+
+    pub fn set_line_dash(&mut self, phase: CGFloat, lengths: &[CGFloat]) {
+        self.state.stroke_style.dash_phase = phase;
+        self.state.stroke_style.dash_lengths = lengths.to_vec();
+    }
+
+    pub fn set_miter_limit(&mut self, limit: CGFloat) {
+        self.state.stroke_style.miter_limit = limit;
+    }
+
+    pub fn set_flatness(&mut self, flatness: CGFloat) {
+        self.state.flatness = flatness.max(0.0);
+    }
+
+    pub fn set_alpha(&mut self, alpha: CGFloat) {
+        self.state.alpha = alpha.clamp(0.0, 1.0);
+    }
+
+    pub fn set_blend_mode(&mut self, mode: CGBlendMode) {
+        self.state.blend_mode = mode;
+    }
+
+    pub fn set_allows_antialiasing(&mut self, allows_antialiasing: bool) {
+        self.allows_antialiasing = allows_antialiasing;
+    }
+
+    pub fn set_should_antialias(&mut self, should_antialias: bool) {
+        self.state.should_antialias = should_antialias;
+    }
+
+    pub fn begin_path(&mut self) {
+        self.path = PathBuilder::new();
+    }
+
+    pub fn close_path(&mut self) {
+        self.path.close_subpath();
+    }
+
+    pub fn move_to_point(&mut self, x: CGFloat, y: CGFloat) {
+        self.path.move_to_point(Some(&self.state.ctm), x, y);
+    }
+
+    pub fn add_line_to_point(&mut self, x: CGFloat, y: CGFloat) {
+        self.path.add_line_to_point(Some(&self.state.ctm), x, y);
+    }
+
+    pub fn add_curve_to_point(&mut self, cp1x: CGFloat, cp1y: CGFloat, cp2x: CGFloat, cp2y: CGFloat, x: CGFloat, y: CGFloat) {
+        self.path.add_curve_to_point(Some(&self.state.ctm), cp1x, cp1y, cp2x, cp2y, x, y);
+    }
+
+    pub fn add_quad_curve_to_point(&mut self, cpx: CGFloat, cpy: CGFloat, x: CGFloat, y: CGFloat) {
+        self.path.add_quad_curve_to_point(Some(&self.state.ctm), cpx, cpy, x, y);
+    }
+
+    pub fn add_rect(&mut self, rect: CGRect) {
+        self.path.add_rect(Some(&self.state.ctm), rect);
+    }
+
+    pub fn add_rects(&mut self, rects: &[CGRect]) {
+        self.path.add_rects(Some(&self.state.ctm), rects);
+    }
+
+    pub fn add_lines(&mut self, points: &[CGPoint]) {
+        self.path.add_lines(Some(&self.state.ctm), points);
+    }
+
+    pub fn add_ellipse_in_rect(&mut self, rect: CGRect) {
+        self.path.add_ellipse_in_rect(Some(&self.state.ctm), rect);
+    }
+
+    pub fn add_arc(&mut self, x: CGFloat, y: CGFloat, radius: CGFloat, start_angle: CGFloat, end_angle: CGFloat, clockwise: bool) {
+        self.path.add_arc(Some(&self.state.ctm), x, y, radius, start_angle, end_angle, clockwise);
+    }
+
+    pub fn add_arc_to_point(&mut self, x1: CGFloat, y1: CGFloat, x2: CGFloat, y2: CGFloat, radius: CGFloat) {
+        self.path.add_arc_to_point(Some(&self.state.ctm), x1, y1, x2, y2, radius);
+    }
+
+    pub fn add_path(&mut self, path: &Path) {
+        self.path.add_path(Some(&self.state.ctm), path);
+    }
+
+    pub fn replace_path_with_stroked_path(&mut self) {
+        let path = self.take_path();
+        let outline = self.stroke_outline(&path);
+        self.path.add_path(None, &outline);
+    }
+
+    pub fn is_path_empty(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    pub fn get_path_current_point(&self) -> CGPoint {
+        match self.path.current_point() {
+            Some(point) => point.apply_transform(&self.state.ctm.invert()),
+            None => CGPoint::new(0.0, 0.0),
+        }
+    }
+
+    pub fn get_path_bounding_box(&self) -> CGRect {
+        self.path.path().apply_transform(&self.state.ctm.invert()).bounding_box()
+    }
+
+    pub fn copy_path(&self) -> Option<Path> {
+        if self.path.is_empty() {
+            None
+        } else {
+            Some(self.path.path().apply_transform(&self.state.ctm.invert()))
+        }
+    }
+
+    pub fn path_contains_point(&self, point: CGPoint, mode: CGPathDrawingMode) -> bool {
+        let point = point.apply_transform(&self.state.ctm);
+        match mode {
+            CGPathDrawingMode::Fill | CGPathDrawingMode::EOFill => self.path.path().contains_point(None, point, mode == CGPathDrawingMode::EOFill),
+            CGPathDrawingMode::Stroke => self.stroke_outline(self.path.path()).contains_point(None, point, false),
+            CGPathDrawingMode::FillStroke | CGPathDrawingMode::EOFillStroke => {
+                self.path.path().contains_point(None, point, mode == CGPathDrawingMode::EOFillStroke)
+                    || self.stroke_outline(self.path.path()).contains_point(None, point, false)
+            }
+        }
+    }
+
+    pub fn draw_path(&mut self, mode: CGPathDrawingMode) {
+        let path = self.take_path();
+        if matches!(mode, CGPathDrawingMode::Fill | CGPathDrawingMode::FillStroke) {
+            self.paint_path(&path, false, self.state.fill_color);
+        }
+        if matches!(mode, CGPathDrawingMode::EOFill | CGPathDrawingMode::EOFillStroke) {
+            self.paint_path(&path, true, self.state.fill_color);
+        }
+        if matches!(mode, CGPathDrawingMode::Stroke | CGPathDrawingMode::FillStroke | CGPathDrawingMode::EOFillStroke) {
+            let outline = self.stroke_outline(&path);
+            self.paint_path(&outline, false, self.state.stroke_color);
+        }
+    }
+
+    pub fn fill_path(&mut self) {
+        self.draw_path(CGPathDrawingMode::Fill);
+    }
+
+    pub fn eo_fill_path(&mut self) {
+        self.draw_path(CGPathDrawingMode::EOFill);
+    }
+
+    pub fn stroke_path(&mut self) {
+        self.draw_path(CGPathDrawingMode::Stroke);
+    }
+
+    pub fn fill_rect(&mut self, rect: CGRect) {
+        self.fill_rects(&[rect]);
+    }
+
+    pub fn fill_rects(&mut self, rects: &[CGRect]) {
+        let mut path = PathBuilder::new();
+        path.add_rects(Some(&self.state.ctm), rects);
+        self.paint_path(path.path(), false, self.state.fill_color);
+    }
+
+    pub fn stroke_rect(&mut self, rect: CGRect) {
+        let mut path = PathBuilder::new();
+        path.add_rect(Some(&self.state.ctm), rect);
+        let outline = self.stroke_outline(path.path());
+        self.paint_path(&outline, false, self.state.stroke_color);
+    }
+
+    pub fn stroke_rect_with_width(&mut self, rect: CGRect, width: CGFloat) {
+        let line_width = self.state.stroke_style.line_width;
+        self.state.stroke_style.line_width = width;
+        self.stroke_rect(rect);
+        self.state.stroke_style.line_width = line_width;
+    }
+
+    pub fn clear_rect(&mut self, rect: CGRect) {
+        let blend_mode = self.state.blend_mode;
+        self.state.blend_mode = CGBlendMode::Clear;
+        self.fill_rect(rect);
+        self.state.blend_mode = blend_mode;
+    }
+
+    pub fn fill_ellipse_in_rect(&mut self, rect: CGRect) {
+        let mut path = PathBuilder::new();
+        path.add_ellipse_in_rect(Some(&self.state.ctm), rect);
+        self.paint_path(path.path(), false, self.state.fill_color);
+    }
+
+    pub fn stroke_ellipse_in_rect(&mut self, rect: CGRect) {
+        let mut path = PathBuilder::new();
+        path.add_ellipse_in_rect(Some(&self.state.ctm), rect);
+        let outline = self.stroke_outline(path.path());
+        self.paint_path(&outline, false, self.state.stroke_color);
+    }
+
+    pub fn stroke_line_segments(&mut self, points: &[CGPoint]) {
+        let mut path = PathBuilder::new();
+        for pair in points.chunks_exact(2) {
+            path.move_to_point(Some(&self.state.ctm), pair[0].x, pair[0].y);
+            path.add_line_to_point(Some(&self.state.ctm), pair[1].x, pair[1].y);
+        }
+        let outline = self.stroke_outline(path.path());
+        self.paint_path(&outline, false, self.state.stroke_color);
+    }
+
+    pub fn clip(&mut self) {
+        let path = self.take_path();
+        self.clip_to_path(&path, false);
+    }
+
+    pub fn eo_clip(&mut self) {
+        let path = self.take_path();
+        self.clip_to_path(&path, true);
+    }
+
+    pub fn reset_clip(&mut self) {
+        self.state.clip = None;
+    }
+
+    pub fn clip_bounding_box(&self) -> CGRect {
+        let bounds = match &self.state.clip {
+            Some(clip) => {
+                let (mut min_x, mut min_y, mut max_x, mut max_y) = (self.width, self.height, 0, 0);
+                for (index, _) in clip.iter().enumerate().filter(|(_, coverage)| **coverage > 0.0) {
+                    let (x, y) = (index % self.width, index / self.width);
+                    min_x = min_x.min(x);
+                    max_x = max_x.max(x + 1);
+                    min_y = min_y.min(y);
+                    max_y = max_y.max(y + 1);
+                }
+                if min_x >= max_x {
+                    return CGRect::new(0.0, 0.0, 0.0, 0.0);
+                }
+                // rows are stored top to bottom
+                CGRect::new(min_x as CGFloat, (self.height - max_y) as CGFloat, (max_x - min_x) as CGFloat, (max_y - min_y) as CGFloat)
+            }
+            None => CGRect::new(0.0, 0.0, self.width as CGFloat, self.height as CGFloat),
+        };
+        bounds.apply_transform(&self.state.ctm.invert())
+    }
+
+    pub fn clip_to_rect(&mut self, rect: CGRect) {
+        self.clip_to_rects(&[rect]);
+    }
+
+    pub fn clip_to_rects(&mut self, rects: &[CGRect]) {
+        let mut path = PathBuilder::new();
+        path.add_rects(Some(&self.state.ctm), rects);
+        self.clip_to_path(path.path(), false);
+    }
+
+    pub fn set_gray_fill_color(&mut self, gray: CGFloat, alpha: CGFloat) {
+        self.state.fill_color = clamp_color([gray, gray, gray, alpha]);
+    }
+
+    pub fn set_gray_stroke_color(&mut self, gray: CGFloat, alpha: CGFloat) {
+        self.state.stroke_color = clamp_color([gray, gray, gray, alpha]);
+    }
+
+    pub fn set_rgb_fill_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
+        self.state.fill_color = clamp_color([red, green, blue, alpha]);
+    }
+
+    pub fn set_rgb_stroke_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
+        self.state.stroke_color = clamp_color([red, green, blue, alpha]);
+    }
+
+    pub fn set_cmyk_fill_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) {
+        self.state.fill_color = cmyk_to_rgb(cyan, magenta, yellow, black, alpha);
+    }
+
+    pub fn set_cmyk_stroke_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) {
+        self.state.stroke_color = cmyk_to_rgb(cyan, magenta, yellow, black, alpha);
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn set_fill_color(&mut self, color: &CGColor) {
+        if let Some(color) = color_from_components(color.components()) {
+            self.state.fill_color = color;
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn set_stroke_color(&mut self, color: &CGColor) {
+        if let Some(color) = color_from_components(color.components()) {
+            self.state.stroke_color = color;
+        }
+    }
+
+    // drawing goes to a cleared buffer that is composited with the current alpha, blend mode and clip when the layer ends
+    pub fn begin_transparency_layer(&mut self) {
+        let clip = self.state.clip.clone();
+        self.push_layer(clip);
+    }
+
+    pub fn begin_transparency_layer_with_rect(&mut self, rect: CGRect) {
+        let clip = self.state.clip.clone();
+        self.clip_to_rect(rect);
+        let layer_clip = std::mem::replace(&mut self.state.clip, clip);
+        self.push_layer(layer_clip);
+    }
+
+    pub fn end_transparency_layer(&mut self) {
+        let layer = match self.layers.pop() {
+            Some(layer) => layer,
+            None => return,
+        };
+        self.state_stack.truncate(layer.depth);
+        self.state = layer.state;
+        let source = std::mem::replace(&mut self.data, layer.data);
+        let (alpha, blend_mode) = (self.state.alpha, self.state.blend_mode);
+        let pixels = source.chunks_exact(BYTES_PER_PIXEL).zip(self.data.chunks_exact_mut(BYTES_PER_PIXEL));
+        for (index, (source, pixel)) in pixels.enumerate() {
+            let coverage = match &layer.clip {
+                Some(clip) => clip[index] as CGFloat,
+                None => 1.0,
+            };
+            if coverage == 0.0 || (source[3] == 0 && blend_mode == CGBlendMode::Normal) {
+                continue;
+            }
+            let source = [source[0], source[1], source[2], source[3]].map(|component| component as CGFloat / 255.0 * alpha);
+            composite(pixel, source, coverage, blend_mode);
+        }
+    }
+
+    pub fn set_interpolation_quality(&mut self, quality: CGInterpolationQuality) {
+        self.state.interpolation_quality = quality;
+    }
+
+    pub fn get_interpolation_quality(&self) -> CGInterpolationQuality {
+        self.state.interpolation_quality
+    }
+
+    // draws the bitmap upright into rect, resampled with the filter of the interpolation quality, alpha only
+    // bitmaps paint the fill color like image masks
+    pub fn draw_bitmap(&mut self, rect: CGRect, bitmap: &BitmapView<'_>) {
+        if rect.is_empty() || bitmap.width() == 0 || bitmap.height() == 0 {
+            return;
+        }
+        let rect = rect.standardize();
+        let resampled = match self.resample_bitmap(rect, bitmap) {
+            Some(resampled) => resampled,
+            None => return,
+        };
+        let area = self.pixel_edges(&Path::from_rect(rect, Some(&self.state.ctm)));
+        let (fill_color, alpha) = (self.state.fill_color, self.state.alpha);
+        self.composite_pixels(&area, |x, y| {
+            let (color, sample_alpha) = resampled.color(x, y).unwrap_or_default();
+            bitmap_color(resampled.color_model(), color, sample_alpha, fill_color, alpha)
+        });
+    }
+
+    // repeats the bitmap drawn into rect across the whole clip, tiles are sampled at the nearest pixel
+    pub fn draw_tiled_bitmap(&mut self, rect: CGRect, bitmap: &BitmapView<'_>) {
+        let (width, height) = (bitmap.width(), bitmap.height());
+        if rect.is_empty() || width == 0 || height == 0 {
+            return;
+        }
+        let rect = rect.standardize();
+        let to_tile = self.bitmap_to_pixels(rect, bitmap).invert();
+        let (fill_color, alpha, model) = (self.state.fill_color, self.state.alpha, bitmap.color_model());
+        let mut tile = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (color, sample_alpha) = bitmap.color(x, y).unwrap_or_default();
+                tile.push(bitmap_color(model, color, sample_alpha, fill_color, alpha));
+            }
+        }
+        let everything = self.pixel_edges(&Path::from_rect(CGRect::new(0.0, 0.0, self.width as CGFloat, self.height as CGFloat), None));
+        self.composite_pixels(&everything, |x, y| {
+            let point = CGPoint::new(x as CGFloat + 0.5, y as CGFloat + 0.5).apply_transform(&to_tile);
+            let column = (point.x.rem_euclid(width as CGFloat) as usize).min(width - 1);
+            let row = (point.y.rem_euclid(height as CGFloat) as usize).min(height - 1);
+            tile[row * width + column]
+        });
+    }
+
+    // intersects the clip with the mask drawn into rect, gray masks clip where they are black and alpha only masks
+    // where they are transparent, everything outside rect is clipped
+    pub fn clip_to_bitmap(&mut self, rect: CGRect, mask: &BitmapView<'_>) {
+        let rect = rect.standardize();
+        let resampled = if rect.is_empty() || mask.width() == 0 || mask.height() == 0 {
+            None
+        } else {
+            self.resample_bitmap(rect, mask)
+        };
+        let (width, height, antialias) = (self.width, self.height, self.antialias());
+        let mut clip = vec![0.0f32; width * height];
+        if let Some(resampled) = resampled {
+            let area = self.pixel_edges(&Path::from_rect(rect, Some(&self.state.ctm)));
+            let white = [1.0; 4];
+            rasterize(&area, false, width, height, antialias, |x, y, coverage| {
+                let (color, alpha) = resampled.color(x, y).unwrap_or_default();
+                let [red, green, blue, alpha] = bitmap_color(resampled.color_model(), color, alpha, white, 1.0);
+                let value = match resampled.color_model() {
+                    None => alpha,
+                    Some(_) => lum([red, green, blue]),
+                };
+                clip[y * width + x] = (coverage * value) as f32;
+            });
+        }
+        if let Some(current) = &self.state.clip {
+            for (coverage, current) in clip.iter_mut().zip(current.iter()) {
+                *coverage *= *current;
+            }
+        }
+        self.state.clip = Some(Rc::new(clip));
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn draw_image(&mut self, rect: CGRect, image: &CGImage) {
+        match image_bitmap(image, PixelFormat::RGBA8888) {
+            Some(bitmap) => self.draw_bitmap(rect, &bitmap.as_view()),
+            None => self.report_unsupported(UnsupportedOperation::Image),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn draw_tiled_image(&mut self, rect: CGRect, image: &CGImage) {
+        match image_bitmap(image, PixelFormat::RGBA8888) {
+            Some(bitmap) => self.draw_tiled_bitmap(rect, &bitmap.as_view()),
+            None => self.report_unsupported(UnsupportedOperation::Image),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn clip_to_mask(&mut self, rect: CGRect, mask: &CGImage) {
+        match image_bitmap(mask, PixelFormat::Gray8) {
+            Some(bitmap) => self.clip_to_bitmap(rect, &bitmap.as_view()),
+            None => self.report_unsupported(UnsupportedOperation::Image),
+        }
+    }
+
+    pub fn get_user_space_to_device_space_transform(&self) -> CGAffineTransform {
+        self.state.ctm
+    }
+
+    pub fn convert_point_to_device_space(&self, point: CGPoint) -> CGPoint {
+        point.apply_transform(&self.state.ctm)
+    }
+
+    pub fn convert_point_to_user_space(&self, point: CGPoint) -> CGPoint {
+        point.apply_transform(&self.state.ctm.invert())
+    }
+
+    pub fn convert_size_to_device_space(&self, size: CGSize) -> CGSize {
+        size.apply_transform(&self.state.ctm)
+    }
+
+    pub fn convert_size_to_user_space(&self, size: CGSize) -> CGSize {
+        size.apply_transform(&self.state.ctm.invert())
+    }
+
+    pub fn convert_rect_to_device_space(&self, rect: CGRect) -> CGRect {
+        rect.apply_transform(&self.state.ctm)
+    }
+
+    pub fn convert_rect_to_user_space(&self, rect: CGRect) -> CGRect {
+        rect.apply_transform(&self.state.ctm.invert())
+    }
+
+    fn push_layer(&mut self, clip: Option<Rc<Vec<f32>>>) {
+        let data = std::mem::replace(&mut self.data, vec![0; self.width * self.height * BYTES_PER_PIXEL]);
+        self.layers.push(Layer {
+            data,
+            state: self.state.clone(),
+            clip,
+            depth: self.state_stack.len(),
+        });
+        self.state.clip = None;
+        self.state.alpha = 1.0;
+        self.state.blend_mode = CGBlendMode::Normal;
+    }
+
+    fn report_unsupported(&mut self, operation: UnsupportedOperation) {
+        if !self.unsupported_operations.contains(&operation) {
+            self.unsupported_operations.push(operation);
+        }
+    }
+
+    // the bitmap drawn into rect at the size of the context, its edge pixels are repeated outward first so the
+    // filter does not fade the edges of rect into transparent
+    fn resample_bitmap(&self, rect: CGRect, bitmap: &BitmapView<'_>) -> Option<Bitmap> {
+        let filter = self.state.interpolation_quality.into();
+        let transform = self.bitmap_to_pixels(rect, bitmap);
+        match extend_edges(bitmap, EDGE_EXTENSION) {
+            Some(extended) => {
+                let offset = EDGE_EXTENSION as CGFloat;
+                let transform = CGAffineTransform::new_translate(-offset, -offset).concat(&transform);
+                resample::transform(&extended.as_view(), &transform, self.width, self.height, filter).ok()
+            }
+            None => resample::transform(bitmap, &transform, self.width, self.height, filter).ok(),
+        }
+    }
+
+    // from the pixels of a bitmap drawn into rect, first row at the top, to the pixels of the context
+    fn bitmap_to_pixels(&self, rect: CGRect, bitmap: &BitmapView<'_>) -> CGAffineTransform {
+        let (min_x, _) = rect.min();
+        let (_, max_y) = rect.max();
+        let to_user = CGAffineTransform::new_scale(rect.width() / bitmap.width() as CGFloat, -rect.height() / bitmap.height() as CGFloat)
+            .concat(&CGAffineTransform::new_translate(min_x, max_y));
+        let to_pixels = CGAffineTransform::new_scale(1.0, -1.0).concat(&CGAffineTransform::new_translate(0.0, self.height as CGFloat));
+        to_user.concat(&self.state.ctm).concat(&to_pixels)
+    }
+
+    fn take_path(&mut self) -> Path {
+        std::mem::take(&mut self.path).build()
+    }
+
+    fn tolerance(&self) -> CGFloat {
+        (FLATTENING_TOLERANCE * self.state.flatness).max(0.001)
+    }
+
+    fn antialias(&self) -> bool {
+        self.allows_antialiasing && self.state.should_antialias
+    }
+
+    // strokes in user space so that the line width follows the CTM, the outline is returned in device space
+    fn stroke_outline(&self, path: &Path) -> Path {
+        let style = &self.state.stroke_style;
+        let ctm = &self.state.ctm;
+        if style.line_width == 0.0 {
+            // a zero width is drawn as the thinnest visible line
+            let hairline = StrokeStyle {
+                line_width: 1.0,
+                ..style.clone()
+            };
+            return hairline.stroke_with_tolerance(path, self.tolerance());
+        }
+        let scale = (ctm.a * ctm.a + ctm.b * ctm.b).max(ctm.c * ctm.c + ctm.d * ctm.d).sqrt();
+        if scale == 0.0 || ctm.a * ctm.d - ctm.b * ctm.c == 0.0 {
+            return Path::new();
+        }
+        let user_path = path.apply_transform(&ctm.invert());
+        style.stroke_with_tolerance(&user_path, self.tolerance() / scale).apply_transform(ctm)
+    }
+
+    fn pixel_edges(&self, path: &Path) -> Vec<Edge> {
+        let height = self.height as CGFloat;
+        polygon_edges(path, self.tolerance())
+            .into_iter()
+            .map(|edge| Edge {
+                from: CGPoint::new(edge.from.x, height - edge.from.y),
+                to: CGPoint::new(edge.to.x, height - edge.to.y),
+            })
+            .collect()
+    }
+
+    fn clip_to_path(&mut self, path: &Path, even_odd_fill_rule: bool) {
+        let mut mask = vec![0.0f32; self.width * self.height];
+        let edges = self.pixel_edges(path);
+        let width = self.width;
+        rasterize(&edges, even_odd_fill_rule, self.width, self.height, self.antialias(), |x, y, coverage| {
+            mask[y * width + x] = coverage as f32;
+        });
+        if let Some(clip) = &self.state.clip {
+            for (coverage, clip) in mask.iter_mut().zip(clip.iter()) {
+                *coverage *= *clip;
+            }
+        }
+        self.state.clip = Some(Rc::new(mask));
+    }
+
+    // composites the premultiplied colors of the pixels covered by the edges
+    fn composite_pixels<F>(&mut self, edges: &[Edge], mut color: F)
+    where
+        F: FnMut(usize, usize) -> Color,
+    {
+        let (width, height, antialias, blend_mode) = (self.width, self.height, self.antialias(), self.state.blend_mode);
+        let clip = self.state.clip.clone();
+        let data = &mut self.data;
+        rasterize(edges, false, width, height, antialias, |x, y, coverage| {
+            let coverage = match &clip {
+                Some(clip) => coverage * clip[y * width + x] as CGFloat,
+                None => coverage,
+            };
+            let source = color(x, y);
+            if coverage > 0.0 && (source[3] > 0.0 || blend_mode != CGBlendMode::Normal) {
+                let offset = (y * width + x) * BYTES_PER_PIXEL;
+                composite(&mut data[offset..offset + BYTES_PER_PIXEL], source, coverage, blend_mode);
+            }
+        });
+    }
+
+    fn paint_path(&mut self, path: &Path, even_odd_fill_rule: bool, color: Color) {
+        let alpha = color[3] * self.state.alpha;
+        let source = [color[0] * alpha, color[1] * alpha, color[2] * alpha, alpha];
+        let edges = self.pixel_edges(path);
+        let (width, height, antialias, blend_mode) = (self.width, self.height, self.antialias(), self.state.blend_mode);
+        let clip = self.state.clip.clone();
+        let data = &mut self.data;
+        rasterize(&edges, even_odd_fill_rule, width, height, antialias, |x, y, coverage| {
+            let coverage = match &clip {
+                Some(clip) => coverage * clip[y * width + x] as CGFloat,
+                None => coverage,
+            };
+            if coverage > 0.0 {
+                let offset = (y * width + x) * BYTES_PER_PIXEL;
+                composite(&mut data[offset..offset + BYTES_PER_PIXEL], source, coverage, blend_mode);
+            }
+        });
+    }
+}
+
+fn clamp_color(color: Color) -> Color {
+    color.map(|component| component.clamp(0.0, 1.0))
+}
+
+fn cmyk_to_rgb(cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) -> Color {
+    let [cyan, magenta, yellow, black, alpha] = [cyan, magenta, yellow, black, alpha].map(|component| component.clamp(0.0, 1.0));
+    [
+        (1.0 - cyan) * (1.0 - black),
+        (1.0 - magenta) * (1.0 - black),
+        (1.0 - yellow) * (1.0 - black),
+        alpha,
+    ]
+}
+
+// a copy of the bitmap with its edge pixels repeated count times on every side, None for pixels that are not
+// whole bytes
+fn extend_edges(bitmap: &BitmapView<'_>, count: usize) -> Option<Bitmap> {
+    let bits_per_pixel = bitmap.layout().bits_per_pixel();
+    if bits_per_pixel % 8 != 0 {
+        return None;
+    }
+    let bytes_per_pixel = bits_per_pixel / 8;
+    let (width, height) = (bitmap.width(), bitmap.height());
+    let bytes_per_row = (width + count * 2) * bytes_per_pixel;
+    let mut extended = Bitmap::with_layout(width + count * 2, height + count * 2, bytes_per_row, bitmap.color_model(), *bitmap.layout()).ok()?;
+    for (y, row) in extended.data_mut().chunks_exact_mut(bytes_per_row).enumerate() {
+        let source = &bitmap.row(y.saturating_sub(count).min(height - 1))?[..width * bytes_per_pixel];
+        let (first, last) = (&source[..bytes_per_pixel], &source[source.len() - bytes_per_pixel..]);
+        for (x, pixel) in row.chunks_exact_mut(bytes_per_pixel).enumerate() {
+            pixel.copy_from_slice(match x {
+                x if x < count => first,
+                x if x >= width + count => last,
+                x => &source[(x - count) * bytes_per_pixel..(x - count + 1) * bytes_per_pixel],
+            });
+        }
+    }
+    Some(extended)
+}
+
+// the premultiplied color of a straight bitmap pixel, pixels without color components take the fill color
+fn bitmap_color(model: Option<CGColorSpaceModel>, color: [f32; 4], alpha: f32, fill_color: Color, global_alpha: CGFloat) -> Color {
+    let [c0, c1, c2, c3] = color.map(|component| component as CGFloat);
+    let [red, green, blue, alpha] = match model {
+        Some(CGColorSpaceModel::Monochrome) => [c0, c0, c0, alpha as CGFloat],
+        Some(CGColorSpaceModel::CMYK) => cmyk_to_rgb(c0, c1, c2, c3, alpha as CGFloat),
+        Some(_) => [c0, c1, c2, alpha as CGFloat],
+        None => [fill_color[0], fill_color[1], fill_color[2], fill_color[3] * alpha as CGFloat],
+    };
+    let alpha = alpha.clamp(0.0, 1.0) * global_alpha;
+    [red * alpha, green * alpha, blue * alpha, alpha]
+}
+
+// image masks are read as the coverage they paint with, other images in the given format
+#[cfg(target_os = "macos")]
+fn image_bitmap(image: &CGImage, format: PixelFormat) -> Option<crate::bitmap::Bitmap> {
+    if image.is_mask() {
+        image.to_bitmap(PixelFormat::Alpha8)
+    } else {
+        image.to_bitmap(format)
+    }
+}
+
+// gray, rgb and cmyk components followed by alpha
+#[cfg(target_os = "macos")]
+fn color_from_components(components: &[CGFloat]) -> Option<Color> {
+    match *components {
+        [gray, alpha] => Some(clamp_color([gray, gray, gray, alpha])),
+        [red, green, blue, alpha] => Some(clamp_color([red, green, blue, alpha])),
+        [cyan, magenta, yellow, black, alpha] => Some(cmyk_to_rgb(cyan, magenta, yellow, black, alpha)),
+        _ => None,
+    }
+}
+
+// edges are in pixel space with y pointing down, coverage is reported once per touched pixel
+fn rasterize<F>(edges: &[Edge], even_odd_fill_rule: bool, width: usize, height: usize, antialias: bool, mut f: F)
+where
+    F: FnMut(usize, usize, CGFloat),
+{
+    let mut edges: Vec<&Edge> = edges.iter().filter(|edge| edge.from.y != edge.to.y).collect();
+    if edges.is_empty() || width == 0 || height == 0 {
+        return;
+    }
+    let top = |edge: &Edge| edge.from.y.min(edge.to.y);
+    let bottom = |edge: &Edge| edge.from.y.max(edge.to.y);
+    edges.sort_by(|a, b| top(a).total_cmp(&top(b)));
+    let first_row = top(edges[0]).floor().max(0.0) as usize;
+    let last_row = edges.iter().map(|edge| bottom(edge)).fold(CGFloat::MIN, CGFloat::max).ceil().min(height as CGFloat) as usize;
+
+    let samples = if antialias {
+        SUBSAMPLES
+    } else {
+        1
+    };
+    let weight = 1.0 / samples as CGFloat;
+    // partial coverage at the span ends, and the running coverage of the fully covered pixels in between
+    let mut coverage = vec![0.0; width + 1];
+    let mut cover = vec![0.0; width + 2];
+    let mut crossings: Vec<(CGFloat, i32)> = Vec::new();
+    let mut active: Vec<&Edge> = Vec::new();
+    let mut next = 0;
+    for row in first_row..last_row {
+        let row_bottom = (row + 1) as CGFloat;
+        while next < edges.len() && top(edges[next]) < row_bottom {
+            active.push(edges[next]);
+            next += 1;
+        }
+        active.retain(|edge| bottom(edge) > row as CGFloat);
+        if active.is_empty() {
+            continue;
+        }
+        let (mut min_x, mut max_x) = (width, 0);
+        for sample in 0..samples {
+            let y = row as CGFloat + (sample as CGFloat + 0.5) * weight;
+            crossings.clear();
+            for edge in &active {
+                let (from, to, direction) = if edge.from.y < edge.to.y {
+                    (edge.from, edge.to, 1)
+                } else {
+                    (edge.to, edge.from, -1)
+                };
+                if from.y <= y && y < to.y {
+                    crossings.push((from.x + (y - from.y) * (to.x - from.x) / (to.y - from.y), direction));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                if !is_inside(winding, even_odd_fill_rule) {
+                    continue;
+                }
+                let (mut x0, mut x1) = (pair[0].0.max(0.0), pair[1].0.min(width as CGFloat));
+                if !antialias {
+                    // pixels whose centers are inside the span are fully covered
+                    x0 = (x0 - 0.5).ceil();
+                    x1 = (x1 - 0.5).ceil();
+                }
+                if x0 >= x1 {
+                    continue;
+                }
+                add_span(&mut coverage, &mut cover, x0, x1, weight);
+                min_x = min_x.min(x0.floor() as usize);
+                max_x = max_x.max((x1.ceil() as usize).min(width));
+            }
+        }
+        let mut running = 0.0;
+        for x in min_x..max_x {
+            running += cover[x];
+            let value = coverage[x] + running;
+            if value > 1e-9 {
+                f(x, row, value.min(1.0));
+            }
+            coverage[x] = 0.0;
+            cover[x] = 0.0;
+        }
+        cover[max_x] = 0.0;
+    }
+}
+
+fn add_span(coverage: &mut [CGFloat], cover: &mut [CGFloat], x0: CGFloat, x1: CGFloat, weight: CGFloat) {
+    let (first, last) = (x0.floor() as usize, x1.floor() as usize);
+    if first == last {
+        coverage[first] += (x1 - x0) * weight;
+        return;
+    }
+    coverage[first] += (first as CGFloat + 1.0 - x0) * weight;
+    cover[first + 1] += weight;
+    cover[last] -= weight;
+    coverage[last] += (x1 - last as CGFloat) * weight;
+}
+
+// blends a premultiplied source into a premultiplied destination pixel, partial coverage
+// interpolates between the destination and the fully covered result
+fn composite(pixel: &mut [u8], source: Color, coverage: CGFloat, mode: CGBlendMode) {
+    let destination = [pixel[0], pixel[1], pixel[2], pixel[3]].map(|component| component as CGFloat / 255.0);
+    let result = blend(source, destination, mode);
+    for (index, component) in pixel.iter_mut().enumerate() {
+        let value = destination[index] + (result[index] - destination[index]) * coverage;
+        *component = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+}
+
+fn blend(s: Color, d: Color, mode: CGBlendMode) -> Color {
+    let (sa, da) = (s[3], d[3]);
+    let porter_duff = |fs: CGFloat, fd: CGFloat| [0, 1, 2, 3].map(|i| s[i] * fs + d[i] * fd);
+    match mode {
+        CGBlendMode::Normal => porter_duff(1.0, 1.0 - sa),
+        CGBlendMode::Clear => [0.0; 4],
+        CGBlendMode::Copy => s,
+        CGBlendMode::SourceIn => porter_duff(da, 0.0),
+        CGBlendMode::SourceOut => porter_duff(1.0 - da, 0.0),
+        CGBlendMode::SourceAtop => porter_duff(da, 1.0 - sa),
+        CGBlendMode::DestinationOver => porter_duff(1.0 - da, 1.0),
+        CGBlendMode::DestinationIn => porter_duff(0.0, sa),
+        CGBlendMode::DestinationOut => porter_duff(0.0, 1.0 - sa),
+        CGBlendMode::DestinationAtop => porter_duff(1.0 - da, sa),
+        CGBlendMode::XOR => porter_duff(1.0 - da, 1.0 - sa),
+        CGBlendMode::PlusLighter => [0, 1, 2, 3].map(|i| (s[i] + d[i]).min(1.0)),
+        CGBlendMode::PlusDarker => {
+            let alpha = (sa + da).min(1.0);
+            let color = [0, 1, 2].map(|i| (alpha - ((da - d[i]) + (sa - s[i]))).max(0.0));
+            [color[0], color[1], color[2], alpha]
+        }
+        CGBlendMode::Hue | CGBlendMode::Saturation | CGBlendMode::Color | CGBlendMode::Luminosity => {
+            let backdrop = unpremultiply(d);
+            let source = unpremultiply(s);
+            let mixed = match mode {
+                CGBlendMode::Hue => set_lum(set_sat(source, sat(backdrop)), lum(backdrop)),
+                CGBlendMode::Saturation => set_lum(set_sat(backdrop, sat(source)), lum(backdrop)),
+                CGBlendMode::Color => set_lum(source, lum(backdrop)),
+                _ => set_lum(backdrop, lum(source)),
+            };
+            let color = [0, 1, 2].map(|i| s[i] * (1.0 - da) + d[i] * (1.0 - sa) + sa * da * mixed[i]);
+            [color[0], color[1], color[2], sa + da - sa * da]
+        }
+        _ => {
+            let backdrop = unpremultiply(d);
+            let source = unpremultiply(s);
+            let color = [0, 1, 2].map(|i| s[i] * (1.0 - da) + d[i] * (1.0 - sa) + sa * da * blend_component(backdrop[i], source[i], mode));
+            [color[0], color[1], color[2], sa + da - sa * da]
+        }
+    }
+}
+
+fn unpremultiply(color: Color) -> [CGFloat; 3] {
+    if color[3] > 0.0 {
+        [0, 1, 2].map(|i| (color[i] / color[3]).min(1.0))
+    } else {
+        [0.0; 3]
+    }
+}
+
+// the separable blend functions of the W3C compositing specification, b is the backdrop and s the source
+fn blend_component(b: CGFloat, s: CGFloat, mode: CGBlendMode) -> CGFloat {
+    match mode {
+        CGBlendMode::Multiply => b * s,
+        CGBlendMode::Screen => b + s - b * s,
+        CGBlendMode::Overlay => blend_component(s, b, CGBlendMode::HardLight),
+        CGBlendMode::Darken => b.min(s),
+        CGBlendMode::Lighten => b.max(s),
+        CGBlendMode::ColorDodge => {
+            if b == 0.0 {
+                0.0
+            } else if s >= 1.0 {
+                1.0
+            } else {
+                (b / (1.0 - s)).min(1.0)
+            }
+        }
+        CGBlendMode::ColorBurn => {
+            if b >= 1.0 {
+                1.0
+            } else if s <= 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - b) / s).min(1.0)
+            }
+        }
+        CGBlendMode::HardLight => {
+            if s <= 0.5 {
+                b * 2.0 * s
+            } else {
+                blend_component(b, 2.0 * s - 1.0, CGBlendMode::Screen)
+            }
+        }
+        CGBlendMode::SoftLight => {
+            if s <= 0.5 {
+                b - (1.0 - 2.0 * s) * b * (1.0 - b)
+            } else {
+                let d = if b <= 0.25 {
+                    ((16.0 * b - 12.0) * b + 4.0) * b
+                } else {
+                    b.sqrt()
+                };
+                b + (2.0 * s - 1.0) * (d - b)
+            }
+        }
+        CGBlendMode::Difference => (b - s).abs(),
+        CGBlendMode::Exclusion => b + s - 2.0 * b * s,
+        _ => s,
+    }
+}
+
+fn lum(color: [CGFloat; 3]) -> CGFloat {
+    0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]
+}
+
+fn sat(color: [CGFloat; 3]) -> CGFloat {
+    color[0].max(color[1]).max(color[2]) - color[0].min(color[1]).min(color[2])
+}
+
+fn set_lum(color: [CGFloat; 3], l: CGFloat) -> [CGFloat; 3] {
+    let delta = l - lum(color);
+    let color = color.map(|component| component + delta);
+    let l = lum(color);
+    let (min, max) = (color[0].min(color[1]).min(color[2]), color[0].max(color[1]).max(color[2]));
+    color.map(|component| {
+        if min < 0.0 {
+            l + (component - l) * l / (l - min)
+        } else if max > 1.0 {
+            l + (component - l) * (1.0 - l) / (max - l)
+        } else {
+            component
+        }
+    })
+}
+
+fn set_sat(color: [CGFloat; 3], s: CGFloat) -> [CGFloat; 3] {
+    let (min, max) = (color[0].min(color[1]).min(color[2]), color[0].max(color[1]).max(color[2]));
+    if max > min {
+        color.map(|component| (component - min) * s / (max - min))
+    } else {
+        [0.0; 3]
+    }
+}
+
+impl GraphicsContext for RasterContext {
+    fn save_state(&mut self) {
+        RasterContext::save_state(self)
+    }
+
+    fn restore_state(&mut self) {
+        RasterContext::restore_state(self)
+    }
+
+    fn scale(&mut self, sx: CGFloat, sy: CGFloat) {
+        RasterContext::scale(self, sx, sy)
+    }
+
+    fn translate(&mut self, tx: CGFloat, ty: CGFloat) {
+        RasterContext::translate(self, tx, ty)
+    }
+
+    fn rotate(&mut self, angle: CGFloat) {
+        RasterContext::rotate(self, angle)
+    }
+
+    fn concat_ctm(&mut self, transform: CGAffineTransform) {
+        RasterContext::concat_ctm(self, transform)
+    }
+
+    fn set_line_width(&mut self, width: CGFloat) {
+        RasterContext::set_line_width(self, width)
+    }
+
+    fn set_line_cap(&mut self, cap: CGLineCap) {
+        RasterContext::set_line_cap(self, cap)
+    }
+
+    fn set_line_join(&mut self, join: CGLineJoin) {
+        RasterContext::set_line_join(self, join)
+    }
+
+    fn set_line_dash(&mut self, phase: CGFloat, lengths: &[CGFloat]) {
+        RasterContext::set_line_dash(self, phase, lengths)
+    }
+
+    fn set_miter_limit(&mut self, limit: CGFloat) {
+        RasterContext::set_miter_limit(self, limit)
+    }
+
+    fn set_flatness(&mut self, flatness: CGFloat) {
+        RasterContext::set_flatness(self, flatness)
+    }
+
+    fn set_alpha(&mut self, alpha: CGFloat) {
+        RasterContext::set_alpha(self, alpha)
+    }
+
+    fn set_blend_mode(&mut self, mode: CGBlendMode) {
+        RasterContext::set_blend_mode(self, mode)
+    }
+
+    fn begin_path(&mut self) {
+        RasterContext::begin_path(self)
+    }
+
+    fn close_path(&mut self) {
+        RasterContext::close_path(self)
+    }
+
+    fn move_to_point(&mut self, x: CGFloat, y: CGFloat) {
+        RasterContext::move_to_point(self, x, y)
+    }
+
+    fn add_line_to_point(&mut self, x: CGFloat, y: CGFloat) {
+        RasterContext::add_line_to_point(self, x, y)
+    }
+
+    fn add_curve_to_point(&mut self, cp1x: CGFloat, cp1y: CGFloat, cp2x: CGFloat, cp2y: CGFloat, x: CGFloat, y: CGFloat) {
+        RasterContext::add_curve_to_point(self, cp1x, cp1y, cp2x, cp2y, x, y)
+    }
+
+    fn add_quad_curve_to_point(&mut self, cpx: CGFloat, cpy: CGFloat, x: CGFloat, y: CGFloat) {
+        RasterContext::add_quad_curve_to_point(self, cpx, cpy, x, y)
+    }
+
+    fn add_rect(&mut self, rect: CGRect) {
+        RasterContext::add_rect(self, rect)
+    }
+
+    fn add_rects(&mut self, rects: &[CGRect]) {
+        RasterContext::add_rects(self, rects)
+    }
+
+    fn add_lines(&mut self, points: &[CGPoint]) {
+        RasterContext::add_lines(self, points)
+    }
+
+    fn add_ellipse_in_rect(&mut self, rect: CGRect) {
+        RasterContext::add_ellipse_in_rect(self, rect)
+    }
+
+    fn add_arc(&mut self, x: CGFloat, y: CGFloat, radius: CGFloat, start_angle: CGFloat, end_angle: CGFloat, clockwise: bool) {
+        RasterContext::add_arc(self, x, y, radius, start_angle, end_angle, clockwise)
+    }
+
+    fn add_arc_to_point(&mut self, x1: CGFloat, y1: CGFloat, x2: CGFloat, y2: CGFloat, radius: CGFloat) {
+        RasterContext::add_arc_to_point(self, x1, y1, x2, y2, radius)
+    }
+
+    fn add_path(&mut self, path: &Path) {
+        RasterContext::add_path(self, path)
+    }
+
+    fn replace_path_with_stroked_path(&mut self) {
+        RasterContext::replace_path_with_stroked_path(self)
+    }
+
+    fn draw_path(&mut self, mode: CGPathDrawingMode) {
+        RasterContext::draw_path(self, mode)
+    }
+
+    fn fill_path(&mut self) {
+        RasterContext::fill_path(self)
+    }
+
+    fn eo_fill_path(&mut self) {
+        RasterContext::eo_fill_path(self)
+    }
+
+    fn stroke_path(&mut self) {
+        RasterContext::stroke_path(self)
+    }
+
+    fn fill_rect(&mut self, rect: CGRect) {
+        RasterContext::fill_rect(self, rect)
+    }
+
+    fn fill_rects(&mut self, rects: &[CGRect]) {
+        RasterContext::fill_rects(self, rects)
+    }
+
+    fn stroke_rect(&mut self, rect: CGRect) {
+        RasterContext::stroke_rect(self, rect)
+    }
+
+    fn stroke_rect_with_width(&mut self, rect: CGRect, width: CGFloat) {
+        RasterContext::stroke_rect_with_width(self, rect, width)
+    }
+
+    fn clear_rect(&mut self, rect: CGRect) {
+        RasterContext::clear_rect(self, rect)
+    }
+
+    fn fill_ellipse_in_rect(&mut self, rect: CGRect) {
+        RasterContext::fill_ellipse_in_rect(self, rect)
+    }
+
+    fn stroke_ellipse_in_rect(&mut self, rect: CGRect) {
+        RasterContext::stroke_ellipse_in_rect(self, rect)
+    }
+
+    fn stroke_line_segments(&mut self, points: &[CGPoint]) {
+        RasterContext::stroke_line_segments(self, points)
+    }
+
+    fn clip(&mut self) {
+        RasterContext::clip(self)
+    }
+
+    fn eo_clip(&mut self) {
+        RasterContext::eo_clip(self)
+    }
+
+    fn reset_clip(&mut self) {
+        RasterContext::reset_clip(self)
+    }
+
+    fn clip_to_rect(&mut self, rect: CGRect) {
+        RasterContext::clip_to_rect(self, rect)
+    }
+
+    fn clip_to_rects(&mut self, rects: &[CGRect]) {
+        RasterContext::clip_to_rects(self, rects)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_fill_color(&mut self, color: &CGColor) {
+        RasterContext::set_fill_color(self, color)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_stroke_color(&mut self, color: &CGColor) {
+        RasterContext::set_stroke_color(self, color)
+    }
+
+    fn set_gray_fill_color(&mut self, gray: CGFloat, alpha: CGFloat) {
+        RasterContext::set_gray_fill_color(self, gray, alpha)
+    }
+
+    fn set_gray_stroke_color(&mut self, gray: CGFloat, alpha: CGFloat) {
+        RasterContext::set_gray_stroke_color(self, gray, alpha)
+    }
+
+    fn set_rgb_fill_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
+        RasterContext::set_rgb_fill_color(self, red, green, blue, alpha)
+    }
+
+    fn set_rgb_stroke_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
+        RasterContext::set_rgb_stroke_color(self, red, green, blue, alpha)
+    }
+
+    fn set_cmyk_fill_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) {
+        RasterContext::set_cmyk_fill_color(self, cyan, magenta, yellow, black, alpha)
+    }
+
+    fn set_cmyk_stroke_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) {
+        RasterContext::set_cmyk_stroke_color(self, cyan, magenta, yellow, black, alpha)
+    }
+
+    fn set_allows_antialiasing(&mut self, allows_antialiasing: bool) {
+        RasterContext::set_allows_antialiasing(self, allows_antialiasing)
+    }
+
+    fn set_should_antialias(&mut self, should_antialias: bool) {
+        RasterContext::set_should_antialias(self, should_antialias)
+    }
+
+    fn begin_transparency_layer(&mut self) {
+        RasterContext::begin_transparency_layer(self)
+    }
+
+    fn begin_transparency_layer_with_rect(&mut self, rect: CGRect) {
+        RasterContext::begin_transparency_layer_with_rect(self, rect)
+    }
+
+    fn end_transparency_layer(&mut self) {
+        RasterContext::end_transparency_layer(self)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn clip_to_mask(&mut self, rect: CGRect, image: &CGImage) {
+        RasterContext::clip_to_mask(self, rect, image)
+    }
+
+    // setting a color space resets the color to its initial black
+    #[cfg(target_os = "macos")]
+    fn set_fill_color_space(&mut self, _space: &CGColorSpace) {
+        self.state.fill_color = [0.0, 0.0, 0.0, 1.0];
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_stroke_color_space(&mut self, _space: &CGColorSpace) {
+        self.state.stroke_color = [0.0, 0.0, 0.0, 1.0];
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn set_fill_pattern(&mut self, _pattern: &CGPattern, _components: &[CGFloat]) {
+        self.report_unsupported(UnsupportedOperation::Pattern);
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn set_stroke_pattern(&mut self, _pattern: &CGPattern, _components: &[CGFloat]) {
+        self.report_unsupported(UnsupportedOperation::Pattern);
+    }
+
+    // only used by patterns
+    fn set_pattern_phase(&mut self, _phase: CGSize) {}
+
+    // the buffer has no color space, so there is no color matching to apply an intent to
+    fn set_rendering_intent(&mut self, _intent: CGColorRenderingIntent) {}
+
+    #[cfg(target_os = "macos")]
+    fn draw_image(&mut self, rect: CGRect, image: &CGImage) {
+        RasterContext::draw_image(self, rect, image)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_tiled_image(&mut self, rect: CGRect, image: &CGImage) {
+        RasterContext::draw_tiled_image(self, rect, image)
+    }
+
+    fn set_interpolation_quality(&mut self, quality: CGInterpolationQuality) {
+        RasterContext::set_interpolation_quality(self, quality)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_linear_gradient(&mut self, _gradient: &CGGradient, _start_point: CGPoint, _end_point: CGPoint, _options: CGGradientDrawingOptions) {
+        self.report_unsupported(UnsupportedOperation::Gradient);
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_radial_gradient(
+        &mut self,
+        _gradient: &CGGradient,
+        _start_center: CGPoint,
+        _start_radius: CGFloat,
+        _end_center: CGPoint,
+        _end_radius: CGFloat,
+        _options: CGGradientDrawingOptions,
+    ) {
+        self.report_unsupported(UnsupportedOperation::Gradient);
+    }
+
+    fn set_shadow(&mut self, _offset: CGSize, _blur: CGFloat) {
+        self.report_unsupported(UnsupportedOperation::Shadow);
+    }
+
+    // a transparent color turns the shadow off
+    #[cfg(target_os = "macos")]
+    fn set_shadow_with_color(&mut self, _offset: CGSize, _blur: CGFloat, color: &CGColor) {
+        if color.alpha() > 0.0 {
+            self.report_unsupported(UnsupportedOperation::Shadow);
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_shading(&mut self, _shading: &CGShading) {
+        self.report_unsupported(UnsupportedOperation::Shading);
+    }
+
+    // the text state only matters for glyphs, which are not drawn
+    #[cfg(target_os = "macos")]
+    fn set_font(&mut self, _font: &CGFont) {}
+
+    fn set_font_size(&mut self, _size: CGFloat) {}
+
+    fn set_text_matrix(&mut self, _t: &CGAffineTransform) {}
+
+    fn set_text_drawing_mode(&mut self, _mode: CGTextDrawingMode) {}
+
+    fn set_text_position(&mut self, _x: CGFloat, _y: CGFloat) {}
+
+    fn set_allows_font_smoothing(&mut self, _allows_font_smoothing: bool) {}
+
+    fn set_should_smooth_fonts(&mut self, _should_smooth_fonts: bool) {}
+
+    fn set_allows_font_subpixel_quantization(&mut self, _allows_font_subpixel_quantization: bool) {}
+
+    fn set_should_subpixel_quantize_fonts(&mut self, _should_subpixel_quantize_fonts: bool) {}
+
+    fn set_allows_font_subpixel_positioning(&mut self, _allows_font_subpixel_positioning: bool) {}
+
+    fn set_should_subpixel_position_fonts(&mut self, _should_subpixel_position_fonts: bool) {}
+
+    fn set_font_smoothing_style(&mut self, _style: i32) {}
+
+    fn show_glyphs_at_positions(&mut self, glyphs: &[CGGlyph], _positions: &[CGPoint]) {
+        if !glyphs.is_empty() {
+            self.report_unsupported(UnsupportedOperation::Glyphs);
+        }
+    }
+
+    fn flush(&mut self) {}
+
+    fn synchronize(&mut self) {}
+
+    fn begin_page(&mut self, _media_box: &CGRect) {}
+
+    fn end_page(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pixel_format::PixelFormat, recording_context::RecordingContext};
+
+    // the pixel at x, y counted from the bottom left like user space
+    fn pixel(context: &RasterContext, x: usize, y: usize) -> [u8; 4] {
+        let offset = (context.height() - 1 - y) * context.bytes_per_row() + x * BYTES_PER_PIXEL;
+        let data = &context.data()[offset..offset + BYTES_PER_PIXEL];
+        [data[0], data[1], data[2], data[3]]
+    }
+
+    #[test]
+    fn fill_rect_covers_whole_pixels() {
+        let mut context = RasterContext::new(8, 8).unwrap();
+        context.set_rgb_fill_color(1.0, 0.0, 0.0, 1.0);
+        context.fill_rect(CGRect::new(2.0, 1.0, 3.0, 2.0));
+        assert_eq!(pixel(&context, 2, 1), [255, 0, 0, 255]);
+        assert_eq!(pixel(&context, 4, 2), [255, 0, 0, 255]);
+        assert_eq!(pixel(&context, 5, 2), [0; 4]);
+        assert_eq!(pixel(&context, 2, 3), [0; 4]);
+        assert_eq!(context.data().iter().filter(|component| **component == 255).count(), 12);
+    }
+
+    #[test]
+    fn antialiasing_and_alpha_are_premultiplied() {
+        let mut context = RasterContext::new(4, 4).unwrap();
+        context.set_gray_fill_color(1.0, 1.0);
+        context.fill_rect(CGRect::new(0.0, 0.0, 1.5, 1.0));
+        assert_eq!(pixel(&context, 1, 0), [128, 128, 128, 128]);
+        context.set_should_antialias(false);
+        context.set_alpha(0.5);
+        context.fill_rect(CGRect::new(2.0, 2.0, 1.5, 1.0));
+        assert_eq!(pixel(&context, 2, 2), [128, 128, 128, 128]);
+        assert_eq!(pixel(&context, 3, 2), [0; 4]);
+    }
+
+    #[test]
+    fn ctm_and_clipping() {
+        let mut context = RasterContext::new(8, 8).unwrap();
+        context.scale(2.0, 2.0);
+        context.clip_to_rect(CGRect::new(0.0, 0.0, 2.0, 4.0));
+        assert_eq!(context.clip_bounding_box(), CGRect::new(0.0, 0.0, 2.0, 4.0));
+        context.set_rgb_fill_color(0.0, 0.0, 1.0, 1.0);
+        context.fill_rect(CGRect::new(1.0, 1.0, 2.0, 2.0));
+        assert_eq!(pixel(&context, 3, 3), [0, 0, 255, 255]);
+        assert_eq!(pixel(&context, 4, 3), [0; 4]);
+        assert_eq!(pixel(&context, 1, 1), [0; 4]);
+        context.reset_clip();
+        context.clear_rect(CGRect::new(0.0, 0.0, 4.0, 4.0));
+        assert!(context.data().iter().all(|component| *component == 0));
+    }
+
+    #[test]
+    fn blend_modes() {
+        let mut context = RasterContext::new(1, 1).unwrap();
+        context.set_rgb_fill_color(1.0, 0.5, 0.0, 1.0);
+        context.fill_rect(CGRect::new(0.0, 0.0, 1.0, 1.0));
+        context.set_blend_mode(CGBlendMode::Multiply);
+        context.set_rgb_fill_color(0.5, 1.0, 1.0, 1.0);
+        context.fill_rect(CGRect::new(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(pixel(&context, 0, 0), [128, 128, 0, 255]);
+        context.set_blend_mode(CGBlendMode::DestinationOut);
+        context.fill_rect(CGRect::new(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(pixel(&context, 0, 0), [0; 4]);
+    }
+
+    #[test]
+    fn stroke() {
+        let mut context = RasterContext::new(10, 10).unwrap();
+        context.set_line_width(2.0);
+        context.set_gray_stroke_color(1.0, 1.0);
+        context.stroke_line_segments(&[CGPoint::new(0.0, 5.0), CGPoint::new(10.0, 5.0)]);
+        assert_eq!(pixel(&context, 5, 4), [255; 4]);
+        assert_eq!(pixel(&context, 5, 5), [255; 4]);
+        assert_eq!(pixel(&context, 5, 6), [0; 4]);
+    }
+
+    #[test]
+    fn transparency_layer() {
+        // the layer is composited as a whole with the alpha of the state it was begun in
+        let mut context = RasterContext::new(4, 4).unwrap();
+        context.set_alpha(0.5);
+        context.begin_transparency_layer();
+        context.set_gray_fill_color(0.0, 1.0);
+        context.fill_rect(CGRect::new(0.0, 0.0, 4.0, 2.0));
+        context.fill_rect(CGRect::new(0.0, 0.0, 4.0, 2.0));
+        context.end_transparency_layer();
+        assert_eq!(pixel(&context, 0, 0), [0, 0, 0, 128]);
+        assert_eq!(pixel(&context, 0, 2), [0; 4]);
+    }
+
+    #[test]
+    fn replay_into_dyn_graphics_context() {
+        let mut recording = RecordingContext::new();
+        let context: &mut dyn GraphicsContext = &mut recording;
+        context.translate(1.0, 1.0);
+        context.set_rgb_fill_color(0.0, 1.0, 0.0, 1.0);
+        context.fill_rect(CGRect::new(0.0, 0.0, 2.0, 2.0));
+
+        let mut expected = RasterContext::new(4, 4).unwrap();
+        expected.translate(1.0, 1.0);
+        expected.set_rgb_fill_color(0.0, 1.0, 0.0, 1.0);
+        expected.fill_rect(CGRect::new(0.0, 0.0, 2.0, 2.0));
+
+        let mut raster = RasterContext::new(4, 4).unwrap();
+        let context: &mut dyn GraphicsContext = &mut raster;
+        recording.replay(context);
+        assert_eq!(raster.data(), expected.data());
+        assert_eq!(pixel(&raster, 1, 1), [0, 255, 0, 255]);
+        assert_eq!(pixel(&raster, 0, 0), [0; 4]);
+    }
+
+    fn bitmap(width: usize, height: usize, format: PixelFormat, pixels: &[&[u8]]) -> Bitmap {
+        let mut bitmap = Bitmap::new(width, height, format).unwrap();
+        let bytes_per_row = bitmap.bytes_per_row();
+        for (row, pixels) in bitmap.data_mut().chunks_mut(bytes_per_row).zip(pixels) {
+            row[..pixels.len()].copy_from_slice(pixels);
+        }
+        bitmap
+    }
+
+    #[test]
+    fn draw_bitmap_upright() {
+        let image = bitmap(2, 2, PixelFormat::RGBA8888, &[&[255, 0, 0, 255, 0, 255, 0, 255], &[0, 0, 255, 255, 255, 255, 255, 255]]);
+        let mut context = RasterContext::new(4, 4).unwrap();
+        context.set_interpolation_quality(CGInterpolationQuality::None);
+        assert_eq!(context.get_interpolation_quality(), CGInterpolationQuality::None);
+        context.draw_bitmap(CGRect::new(0.0, 0.0, 4.0, 4.0), &image.as_view());
+        assert_eq!(pixel(&context, 0, 3), [255, 0, 0, 255]);
+        assert_eq!(pixel(&context, 3, 3), [0, 255, 0, 255]);
+        assert_eq!(pixel(&context, 1, 1), [0, 0, 255, 255]);
+        assert_eq!(pixel(&context, 2, 0), [255; 4]);
+
+        // the ctm scales the rect, gray bitmaps are opaque and alpha only bitmaps paint the fill color
+        let gray = bitmap(1, 1, PixelFormat::Gray8, &[&[128]]);
+        let mask = bitmap(1, 1, PixelFormat::Alpha8, &[&[255]]);
+        let mut context = RasterContext::new(4, 4).unwrap();
+        context.scale(2.0, 2.0);
+        context.draw_bitmap(CGRect::new(0.0, 0.0, 1.0, 1.0), &gray.as_view());
+        context.set_rgb_fill_color(1.0, 0.0, 0.0, 1.0);
+        context.draw_bitmap(CGRect::new(1.0, 1.0, 1.0, 1.0), &mask.as_view());
+        assert_eq!(pixel(&context, 0, 0), [128, 128, 128, 255]);
+        assert_eq!(pixel(&context, 1, 1), [128, 128, 128, 255]);
+        assert_eq!(pixel(&context, 2, 1), [0; 4]);
+        assert_eq!(pixel(&context, 2, 2), [255, 0, 0, 255]);
+        assert_eq!(pixel(&context, 3, 3), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draw_tiled_bitmap_repeats() {
+        let image = bitmap(2, 1, PixelFormat::RGBA8888, &[&[255, 0, 0, 255, 0, 0, 255, 255]]);
+        let mut context = RasterContext::new(5, 2).unwrap();
+        context.draw_tiled_bitmap(CGRect::new(0.0, 0.0, 2.0, 1.0), &image.as_view());
+        for y in 0..2 {
+            assert_eq!(pixel(&context, 0, y), [255, 0, 0, 255]);
+            assert_eq!(pixel(&context, 1, y), [0, 0, 255, 255]);
+            assert_eq!(pixel(&context, 2, y), [255, 0, 0, 255]);
+            assert_eq!(pixel(&context, 3, y), [0, 0, 255, 255]);
+            assert_eq!(pixel(&context, 4, y), [255, 0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn clip_to_bitmap_masks() {
+        // gray masks clip where they are black
+        let gray = bitmap(2, 1, PixelFormat::Gray8, &[&[0, 255]]);
+        let mut context = RasterContext::new(3, 1).unwrap();
+        context.clip_to_bitmap(CGRect::new(0.0, 0.0, 2.0, 1.0), &gray.as_view());
+        context.set_gray_fill_color(1.0, 1.0);
+        context.fill_rect(CGRect::new(0.0, 0.0, 3.0, 1.0));
+        assert_eq!(pixel(&context, 0, 0), [0; 4]);
+        assert_eq!(pixel(&context, 1, 0), [255; 4]);
+        assert_eq!(pixel(&context, 2, 0), [0; 4]);
+
+        // alpha only masks clip where they are transparent and intersect the current clip
+        let alpha = bitmap(3, 1, PixelFormat::Alpha8, &[&[255, 255, 0]]);
+        let mut context = RasterContext::new(3, 1).unwrap();
+        context.clip_to_rect(CGRect::new(1.0, 0.0, 2.0, 1.0));
+        context.clip_to_bitmap(CGRect::new(0.0, 0.0, 3.0, 1.0), &alpha.as_view());
+        context.fill_rect(CGRect::new(0.0, 0.0, 3.0, 1.0));
+        assert_eq!(pixel(&context, 0, 0), [0; 4]);
+        assert_eq!(pixel(&context, 1, 0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&context, 2, 0), [0; 4]);
+    }
+
+    #[test]
+    fn unsupported_operations_are_reported() {
+        let mut context = RasterContext::new(1, 1).unwrap();
+        GraphicsContext::show_glyphs_at_positions(&mut context, &[], &[]);
+        assert!(context.unsupported_operations().is_empty());
+        GraphicsContext::set_shadow(&mut context, CGSize::new(1.0, -1.0), 2.0);
+        GraphicsContext::show_glyphs_at_positions(&mut context, &[1], &[CGPoint::new(0.0, 0.0)]);
+        GraphicsContext::set_shadow(&mut context, CGSize::new(1.0, -1.0), 2.0);
+        assert_eq!(context.unsupported_operations(), &[UnsupportedOperation::Shadow, UnsupportedOperation::Glyphs]);
+    }
+}
@@ -0,0 +1,908 @@
+use std::{
+    fmt::{Display, Formatter},
+    ops::Range,
+};
+
+use crate::{
+    affine_transform::CGAffineTransform,
+    base::CGFloat,
+    color_space::CGColorRenderingIntent,
+    context::{CGBlendMode, CGInterpolationQuality, CGPathDrawingMode, CGTextDrawingMode},
+    font::CGGlyph,
+    geometry::{CGPoint, CGRect, CGSize},
+    graphics_context::GraphicsContext,
+    path::{CGLineCap, CGLineJoin},
+    path_builder::Path,
+};
+#[cfg(target_os = "macos")]
+use crate::{
+    color::CGColor,
+    color_space::CGColorSpace,
+    font::CGFont,
+    gradient::{CGGradient, CGGradientDrawingOptions},
+    image::CGImage,
+    pattern::CGPattern,
+    shading::CGShading,
+};
+
+// commands holding framework objects are only available where the framework is
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawingCommand {
+    SaveState,
+    RestoreState,
+    Scale(CGFloat, CGFloat),
+    Translate(CGFloat, CGFloat),
+    Rotate(CGFloat),
+    ConcatCTM(CGAffineTransform),
+    SetLineWidth(CGFloat),
+    SetLineCap(CGLineCap),
+    SetLineJoin(CGLineJoin),
+    SetLineDash(CGFloat, Vec<CGFloat>),
+    SetMiterLimit(CGFloat),
+    SetFlatness(CGFloat),
+    SetAlpha(CGFloat),
+    SetBlendMode(CGBlendMode),
+    BeginPath,
+    ClosePath,
+    MoveToPoint(CGPoint),
+    AddLineToPoint(CGPoint),
+    AddCurveToPoint(CGPoint, CGPoint, CGPoint),
+    AddQuadCurveToPoint(CGPoint, CGPoint),
+    AddRect(CGRect),
+    AddRects(Vec<CGRect>),
+    AddLines(Vec<CGPoint>),
+    AddEllipseInRect(CGRect),
+    AddArc(CGPoint, CGFloat, CGFloat, CGFloat, bool),
+    AddArcToPoint(CGPoint, CGPoint, CGFloat),
+    AddPath(Path),
+    ReplacePathWithStrokedPath,
+    DrawPath(CGPathDrawingMode),
+    FillPath,
+    EOFillPath,
+    StrokePath,
+    FillRect(CGRect),
+    FillRects(Vec<CGRect>),
+    StrokeRect(CGRect),
+    StrokeRectWithWidth(CGRect, CGFloat),
+    ClearRect(CGRect),
+    FillEllipseInRect(CGRect),
+    StrokeEllipseInRect(CGRect),
+    StrokeLineSegments(Vec<CGPoint>),
+    Clip,
+    EOClip,
+    ResetClip,
+    #[cfg(target_os = "macos")]
+    ClipToMask(CGRect, CGImage),
+    ClipToRect(CGRect),
+    ClipToRects(Vec<CGRect>),
+    #[cfg(target_os = "macos")]
+    SetFillColor(CGColor),
+    #[cfg(target_os = "macos")]
+    SetStrokeColor(CGColor),
+    #[cfg(target_os = "macos")]
+    SetFillColorSpace(CGColorSpace),
+    #[cfg(target_os = "macos")]
+    SetStrokeColorSpace(CGColorSpace),
+    #[cfg(target_os = "macos")]
+    SetFillPattern(CGPattern, Vec<CGFloat>),
+    #[cfg(target_os = "macos")]
+    SetStrokePattern(CGPattern, Vec<CGFloat>),
+    SetPatternPhase(CGSize),
+    SetGrayFillColor(CGFloat, CGFloat),
+    SetGrayStrokeColor(CGFloat, CGFloat),
+    SetRGBFillColor(CGFloat, CGFloat, CGFloat, CGFloat),
+    SetRGBStrokeColor(CGFloat, CGFloat, CGFloat, CGFloat),
+    SetCMYKFillColor(CGFloat, CGFloat, CGFloat, CGFloat, CGFloat),
+    SetCMYKStrokeColor(CGFloat, CGFloat, CGFloat, CGFloat, CGFloat),
+    SetRenderingIntent(CGColorRenderingIntent),
+    #[cfg(target_os = "macos")]
+    DrawImage(CGRect, CGImage),
+    #[cfg(target_os = "macos")]
+    DrawTiledImage(CGRect, CGImage),
+    SetInterpolationQuality(CGInterpolationQuality),
+    #[cfg(target_os = "macos")]
+    DrawLinearGradient(CGGradient, CGPoint, CGPoint, CGGradientDrawingOptions),
+    #[cfg(target_os = "macos")]
+    DrawRadialGradient(CGGradient, CGPoint, CGFloat, CGPoint, CGFloat, CGGradientDrawingOptions),
+    SetShadow(CGSize, CGFloat),
+    #[cfg(target_os = "macos")]
+    SetShadowWithColor(CGSize, CGFloat, CGColor),
+    #[cfg(target_os = "macos")]
+    DrawShading(CGShading),
+    #[cfg(target_os = "macos")]
+    SetFont(CGFont),
+    SetFontSize(CGFloat),
+    SetTextMatrix(CGAffineTransform),
+    SetTextDrawingMode(CGTextDrawingMode),
+    SetTextPosition(CGPoint),
+    SetAllowsFontSmoothing(bool),
+    SetShouldSmoothFonts(bool),
+    SetAllowsAntialiasing(bool),
+    SetShouldAntialias(bool),
+    SetAllowsFontSubpixelQuantization(bool),
+    SetShouldSubpixelQuantizeFonts(bool),
+    SetAllowsFontSubpixelPositioning(bool),
+    SetShouldSubpixelPositionFonts(bool),
+    SetFontSmoothingStyle(i32),
+    ShowGlyphsAtPositions(Vec<CGGlyph>, Vec<CGPoint>),
+    BeginTransparencyLayer,
+    BeginTransparencyLayerWithRect(CGRect),
+    EndTransparencyLayer,
+    Flush,
+    Synchronize,
+    BeginPage(CGRect),
+    EndPage,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandDifference {
+    // index into the expected log
+    Removed(usize, DrawingCommand),
+    // index into the actual log
+    Added(usize, DrawingCommand),
+}
+
+impl Display for CommandDifference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandDifference::Removed(index, command) => write!(f, "- [{}] {:?}", index, command),
+            CommandDifference::Added(index, command) => write!(f, "+ [{}] {:?}", index, command),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecordingContext {
+    commands: Vec<DrawingCommand>,
+}
+
+impl RecordingContext {
+    pub fn new() -> RecordingContext {
+        RecordingContext::default()
+    }
+
+    pub fn from_commands(commands: Vec<DrawingCommand>) -> RecordingContext {
+        RecordingContext {
+            commands,
+        }
+    }
+
+    pub fn commands(&self) -> &[DrawingCommand] {
+        &self.commands
+    }
+
+    pub fn into_commands(self) -> Vec<DrawingCommand> {
+        self.commands
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn record(&mut self, command: DrawingCommand) {
+        self.commands.push(command);
+    }
+
+    // the smallest set of removals from self and additions from other that turns one log into the other
+    pub fn diff(&self, other: &RecordingContext) -> Vec<CommandDifference> {
+        let mut differ = Differ::new(&self.commands, &other.commands);
+        differ.diff(0..self.commands.len(), 0..other.commands.len());
+        differ.differences
+    }
+}
+
+// Myers' O((n + m) d) difference algorithm in linear space, the ranges are split at a middle snake until one side is
+// empty, see "An O(ND) Difference Algorithm and Its Variations"
+struct Differ<'a> {
+    expected: &'a [DrawingCommand],
+    actual: &'a [DrawingCommand],
+    // furthest reaching x of the forward and backward paths on each diagonal, offset so that -d..=d can be indexed
+    forward: Vec<usize>,
+    backward: Vec<usize>,
+    differences: Vec<CommandDifference>,
+}
+
+impl<'a> Differ<'a> {
+    fn new(expected: &'a [DrawingCommand], actual: &'a [DrawingCommand]) -> Differ<'a> {
+        let size = expected.len() + actual.len() + 3;
+        Differ {
+            expected,
+            actual,
+            forward: vec![0; 2 * size],
+            backward: vec![0; 2 * size],
+            differences: Vec::new(),
+        }
+    }
+
+    fn diff(&mut self, mut expected: Range<usize>, mut actual: Range<usize>) {
+        let prefix = self.common_prefix(expected.clone(), actual.clone());
+        expected.start += prefix;
+        actual.start += prefix;
+        let suffix = self.common_suffix(expected.clone(), actual.clone());
+        expected.end -= suffix;
+        actual.end -= suffix;
+
+        if expected.is_empty() || actual.is_empty() {
+            let (expected_log, actual_log) = (self.expected, self.actual);
+            self.differences.extend(expected.map(|i| CommandDifference::Removed(i, expected_log[i].clone())));
+            self.differences.extend(actual.map(|j| CommandDifference::Added(j, actual_log[j].clone())));
+            return;
+        }
+        let (x, y) = self.middle_snake(expected.clone(), actual.clone());
+        self.diff(expected.start..x, actual.start..y);
+        self.diff(x..expected.end, y..actual.end);
+    }
+
+    fn common_prefix(&self, expected: Range<usize>, actual: Range<usize>) -> usize {
+        self.expected[expected].iter().zip(&self.actual[actual]).take_while(|(a, b)| a == b).count()
+    }
+
+    fn common_suffix(&self, expected: Range<usize>, actual: Range<usize>) -> usize {
+        self.expected[expected].iter().rev().zip(self.actual[actual].iter().rev()).take_while(|(a, b)| a == b).count()
+    }
+
+    // a point on an optimal edit path, strictly between the corners of ranges that differ at both ends
+    fn middle_snake(&mut self, expected: Range<usize>, actual: Range<usize>) -> (usize, usize) {
+        let (n, m) = (expected.len() as isize, actual.len() as isize);
+        let delta = n - m;
+        let odd = delta & 1 == 1;
+        let offset = (n + m + 1) as usize;
+        let index = |k: isize| (k + offset as isize) as usize;
+        self.forward[index(1)] = 0;
+        self.backward[index(1)] = 0;
+        for d in 0..=(n + m + 1) / 2 {
+            for k in (-d..=d).step_by(2) {
+                let mut x = if k == -d || (k != d && self.forward[index(k - 1)] < self.forward[index(k + 1)]) {
+                    self.forward[index(k + 1)]
+                } else {
+                    self.forward[index(k - 1)] + 1
+                };
+                let start = (x, (x as isize - k) as usize);
+                let y = start.1;
+                if (x as isize) < n && (y as isize) < m {
+                    x += self.common_prefix(expected.start + x..expected.end, actual.start + y..actual.end);
+                }
+                self.forward[index(k)] = x;
+                if odd && (k - delta).abs() < d && x + self.backward[index(delta - k)] >= n as usize {
+                    return (expected.start + start.0, actual.start + start.1);
+                }
+            }
+            for k in (-d..=d).step_by(2) {
+                let mut x = if k == -d || (k != d && self.backward[index(k - 1)] < self.backward[index(k + 1)]) {
+                    self.backward[index(k + 1)]
+                } else {
+                    self.backward[index(k - 1)] + 1
+                };
+                let mut y = (x as isize - k) as usize;
+                if (x as isize) < n && (y as isize) < m {
+                    let suffix = self.common_suffix(expected.start..expected.end - x, actual.start..actual.end - y);
+                    x += suffix;
+                    y += suffix;
+                }
+                self.backward[index(k)] = x;
+                if !odd && (k - delta).abs() <= d && x + self.forward[index(delta - k)] >= n as usize {
+                    return (expected.end - x, actual.end - y);
+                }
+            }
+        }
+        unreachable!("the forward and backward paths always meet")
+    }
+}
+
+impl GraphicsContext for RecordingContext {
+    fn save_state(&mut self) {
+        self.record(DrawingCommand::SaveState);
+    }
+
+    fn restore_state(&mut self) {
+        self.record(DrawingCommand::RestoreState);
+    }
+
+    fn scale(&mut self, sx: CGFloat, sy: CGFloat) {
+        self.record(DrawingCommand::Scale(sx, sy));
+    }
+
+    fn translate(&mut self, tx: CGFloat, ty: CGFloat) {
+        self.record(DrawingCommand::Translate(tx, ty));
+    }
+
+    fn rotate(&mut self, angle: CGFloat) {
+        self.record(DrawingCommand::Rotate(angle));
+    }
+
+    fn concat_ctm(&mut self, transform: CGAffineTransform) {
+        self.record(DrawingCommand::ConcatCTM(transform));
+    }
+
+    fn set_line_width(&mut self, width: CGFloat) {
+        self.record(DrawingCommand::SetLineWidth(width));
+    }
+
+    fn set_line_cap(&mut self, cap: CGLineCap) {
+        self.record(DrawingCommand::SetLineCap(cap));
+    }
+
+    fn set_line_join(&mut self, join: CGLineJoin) {
+        self.record(DrawingCommand::SetLineJoin(join));
+    }
+
+    fn set_line_dash(&mut self, phase: CGFloat, lengths: &[CGFloat]) {
+        self.record(DrawingCommand::SetLineDash(phase, lengths.to_vec()));
+    }
+
+    fn set_miter_limit(&mut self, limit: CGFloat) {
+        self.record(DrawingCommand::SetMiterLimit(limit));
+    }
+
+    fn set_flatness(&mut self, flatness: CGFloat) {
+        self.record(DrawingCommand::SetFlatness(flatness));
+    }
+
+    fn set_alpha(&mut self, alpha: CGFloat) {
+        self.record(DrawingCommand::SetAlpha(alpha));
+    }
+
+    fn set_blend_mode(&mut self, mode: CGBlendMode) {
+        self.record(DrawingCommand::SetBlendMode(mode));
+    }
+
+    fn begin_path(&mut self) {
+        self.record(DrawingCommand::BeginPath);
+    }
+
+    fn close_path(&mut self) {
+        self.record(DrawingCommand::ClosePath);
+    }
+
+    fn move_to_point(&mut self, x: CGFloat, y: CGFloat) {
+        self.record(DrawingCommand::MoveToPoint(CGPoint::new(x, y)));
+    }
+
+    fn add_line_to_point(&mut self, x: CGFloat, y: CGFloat) {
+        self.record(DrawingCommand::AddLineToPoint(CGPoint::new(x, y)));
+    }
+
+    fn add_curve_to_point(&mut self, cp1x: CGFloat, cp1y: CGFloat, cp2x: CGFloat, cp2y: CGFloat, x: CGFloat, y: CGFloat) {
+        self.record(DrawingCommand::AddCurveToPoint(CGPoint::new(cp1x, cp1y), CGPoint::new(cp2x, cp2y), CGPoint::new(x, y)));
+    }
+
+    fn add_quad_curve_to_point(&mut self, cpx: CGFloat, cpy: CGFloat, x: CGFloat, y: CGFloat) {
+        self.record(DrawingCommand::AddQuadCurveToPoint(CGPoint::new(cpx, cpy), CGPoint::new(x, y)));
+    }
+
+    fn add_rect(&mut self, rect: CGRect) {
+        self.record(DrawingCommand::AddRect(rect));
+    }
+
+    fn add_rects(&mut self, rects: &[CGRect]) {
+        self.record(DrawingCommand::AddRects(rects.to_vec()));
+    }
+
+    fn add_lines(&mut self, points: &[CGPoint]) {
+        self.record(DrawingCommand::AddLines(points.to_vec()));
+    }
+
+    fn add_ellipse_in_rect(&mut self, rect: CGRect) {
+        self.record(DrawingCommand::AddEllipseInRect(rect));
+    }
+
+    fn add_arc(&mut self, x: CGFloat, y: CGFloat, radius: CGFloat, start_angle: CGFloat, end_angle: CGFloat, clockwise: bool) {
+        self.record(DrawingCommand::AddArc(CGPoint::new(x, y), radius, start_angle, end_angle, clockwise));
+    }
+
+    fn add_arc_to_point(&mut self, x1: CGFloat, y1: CGFloat, x2: CGFloat, y2: CGFloat, radius: CGFloat) {
+        self.record(DrawingCommand::AddArcToPoint(CGPoint::new(x1, y1), CGPoint::new(x2, y2), radius));
+    }
+
+    fn add_path(&mut self, path: &Path) {
+        self.record(DrawingCommand::AddPath(path.clone()));
+    }
+
+    fn replace_path_with_stroked_path(&mut self) {
+        self.record(DrawingCommand::ReplacePathWithStrokedPath);
+    }
+
+    fn draw_path(&mut self, mode: CGPathDrawingMode) {
+        self.record(DrawingCommand::DrawPath(mode));
+    }
+
+    fn fill_path(&mut self) {
+        self.record(DrawingCommand::FillPath);
+    }
+
+    fn eo_fill_path(&mut self) {
+        self.record(DrawingCommand::EOFillPath);
+    }
+
+    fn stroke_path(&mut self) {
+        self.record(DrawingCommand::StrokePath);
+    }
+
+    fn fill_rect(&mut self, rect: CGRect) {
+        self.record(DrawingCommand::FillRect(rect));
+    }
+
+    fn fill_rects(&mut self, rects: &[CGRect]) {
+        self.record(DrawingCommand::FillRects(rects.to_vec()));
+    }
+
+    fn stroke_rect(&mut self, rect: CGRect) {
+        self.record(DrawingCommand::StrokeRect(rect));
+    }
+
+    fn stroke_rect_with_width(&mut self, rect: CGRect, width: CGFloat) {
+        self.record(DrawingCommand::StrokeRectWithWidth(rect, width));
+    }
+
+    fn clear_rect(&mut self, rect: CGRect) {
+        self.record(DrawingCommand::ClearRect(rect));
+    }
+
+    fn fill_ellipse_in_rect(&mut self, rect: CGRect) {
+        self.record(DrawingCommand::FillEllipseInRect(rect));
+    }
+
+    fn stroke_ellipse_in_rect(&mut self, rect: CGRect) {
+        self.record(DrawingCommand::StrokeEllipseInRect(rect));
+    }
+
+    fn stroke_line_segments(&mut self, points: &[CGPoint]) {
+        self.record(DrawingCommand::StrokeLineSegments(points.to_vec()));
+    }
+
+    fn clip(&mut self) {
+        self.record(DrawingCommand::Clip);
+    }
+
+    fn eo_clip(&mut self) {
+        self.record(DrawingCommand::EOClip);
+    }
+
+    fn reset_clip(&mut self) {
+        self.record(DrawingCommand::ResetClip);
+    }
+
+    #[cfg(target_os = "macos")]
+    fn clip_to_mask(&mut self, rect: CGRect, image: &CGImage) {
+        self.record(DrawingCommand::ClipToMask(rect, image.clone()));
+    }
+
+    fn clip_to_rect(&mut self, rect: CGRect) {
+        self.record(DrawingCommand::ClipToRect(rect));
+    }
+
+    fn clip_to_rects(&mut self, rects: &[CGRect]) {
+        self.record(DrawingCommand::ClipToRects(rects.to_vec()));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_fill_color(&mut self, color: &CGColor) {
+        self.record(DrawingCommand::SetFillColor(color.clone()));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_stroke_color(&mut self, color: &CGColor) {
+        self.record(DrawingCommand::SetStrokeColor(color.clone()));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_fill_color_space(&mut self, space: &CGColorSpace) {
+        self.record(DrawingCommand::SetFillColorSpace(space.clone()));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_stroke_color_space(&mut self, space: &CGColorSpace) {
+        self.record(DrawingCommand::SetStrokeColorSpace(space.clone()));
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn set_fill_pattern(&mut self, pattern: &CGPattern, components: &[CGFloat]) {
+        self.record(DrawingCommand::SetFillPattern(pattern.clone(), components.to_vec()));
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn set_stroke_pattern(&mut self, pattern: &CGPattern, components: &[CGFloat]) {
+        self.record(DrawingCommand::SetStrokePattern(pattern.clone(), components.to_vec()));
+    }
+
+    fn set_pattern_phase(&mut self, phase: CGSize) {
+        self.record(DrawingCommand::SetPatternPhase(phase));
+    }
+
+    fn set_gray_fill_color(&mut self, gray: CGFloat, alpha: CGFloat) {
+        self.record(DrawingCommand::SetGrayFillColor(gray, alpha));
+    }
+
+    fn set_gray_stroke_color(&mut self, gray: CGFloat, alpha: CGFloat) {
+        self.record(DrawingCommand::SetGrayStrokeColor(gray, alpha));
+    }
+
+    fn set_rgb_fill_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
+        self.record(DrawingCommand::SetRGBFillColor(red, green, blue, alpha));
+    }
+
+    fn set_rgb_stroke_color(&mut self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
+        self.record(DrawingCommand::SetRGBStrokeColor(red, green, blue, alpha));
+    }
+
+    fn set_cmyk_fill_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) {
+        self.record(DrawingCommand::SetCMYKFillColor(cyan, magenta, yellow, black, alpha));
+    }
+
+    fn set_cmyk_stroke_color(&mut self, cyan: CGFloat, magenta: CGFloat, yellow: CGFloat, black: CGFloat, alpha: CGFloat) {
+        self.record(DrawingCommand::SetCMYKStrokeColor(cyan, magenta, yellow, black, alpha));
+    }
+
+    fn set_rendering_intent(&mut self, intent: CGColorRenderingIntent) {
+        self.record(DrawingCommand::SetRenderingIntent(intent));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_image(&mut self, rect: CGRect, image: &CGImage) {
+        self.record(DrawingCommand::DrawImage(rect, image.clone()));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_tiled_image(&mut self, rect: CGRect, image: &CGImage) {
+        self.record(DrawingCommand::DrawTiledImage(rect, image.clone()));
+    }
+
+    fn set_interpolation_quality(&mut self, quality: CGInterpolationQuality) {
+        self.record(DrawingCommand::SetInterpolationQuality(quality));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_linear_gradient(&mut self, gradient: &CGGradient, start_point: CGPoint, end_point: CGPoint, options: CGGradientDrawingOptions) {
+        self.record(DrawingCommand::DrawLinearGradient(gradient.clone(), start_point, end_point, options));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_radial_gradient(
+        &mut self,
+        gradient: &CGGradient,
+        start_center: CGPoint,
+        start_radius: CGFloat,
+        end_center: CGPoint,
+        end_radius: CGFloat,
+        options: CGGradientDrawingOptions,
+    ) {
+        self.record(DrawingCommand::DrawRadialGradient(gradient.clone(), start_center, start_radius, end_center, end_radius, options));
+    }
+
+    fn set_shadow(&mut self, offset: CGSize, blur: CGFloat) {
+        self.record(DrawingCommand::SetShadow(offset, blur));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_shadow_with_color(&mut self, offset: CGSize, blur: CGFloat, color: &CGColor) {
+        self.record(DrawingCommand::SetShadowWithColor(offset, blur, color.clone()));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn draw_shading(&mut self, shading: &CGShading) {
+        self.record(DrawingCommand::DrawShading(shading.clone()));
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_font(&mut self, font: &CGFont) {
+        self.record(DrawingCommand::SetFont(font.clone()));
+    }
+
+    fn set_font_size(&mut self, size: CGFloat) {
+        self.record(DrawingCommand::SetFontSize(size));
+    }
+
+    fn set_text_matrix(&mut self, t: &CGAffineTransform) {
+        self.record(DrawingCommand::SetTextMatrix(*t));
+    }
+
+    fn set_text_drawing_mode(&mut self, mode: CGTextDrawingMode) {
+        self.record(DrawingCommand::SetTextDrawingMode(mode));
+    }
+
+    fn set_text_position(&mut self, x: CGFloat, y: CGFloat) {
+        self.record(DrawingCommand::SetTextPosition(CGPoint::new(x, y)));
+    }
+
+    fn set_allows_font_smoothing(&mut self, allows_font_smoothing: bool) {
+        self.record(DrawingCommand::SetAllowsFontSmoothing(allows_font_smoothing));
+    }
+
+    fn set_should_smooth_fonts(&mut self, should_smooth_fonts: bool) {
+        self.record(DrawingCommand::SetShouldSmoothFonts(should_smooth_fonts));
+    }
+
+    fn set_allows_antialiasing(&mut self, allows_antialiasing: bool) {
+        self.record(DrawingCommand::SetAllowsAntialiasing(allows_antialiasing));
+    }
+
+    fn set_should_antialias(&mut self, should_antialias: bool) {
+        self.record(DrawingCommand::SetShouldAntialias(should_antialias));
+    }
+
+    fn set_allows_font_subpixel_quantization(&mut self, allows_font_subpixel_quantization: bool) {
+        self.record(DrawingCommand::SetAllowsFontSubpixelQuantization(allows_font_subpixel_quantization));
+    }
+
+    fn set_should_subpixel_quantize_fonts(&mut self, should_subpixel_quantize_fonts: bool) {
+        self.record(DrawingCommand::SetShouldSubpixelQuantizeFonts(should_subpixel_quantize_fonts));
+    }
+
+    fn set_allows_font_subpixel_positioning(&mut self, allows_font_subpixel_positioning: bool) {
+        self.record(DrawingCommand::SetAllowsFontSubpixelPositioning(allows_font_subpixel_positioning));
+    }
+
+    fn set_should_subpixel_position_fonts(&mut self, should_subpixel_position_fonts: bool) {
+        self.record(DrawingCommand::SetShouldSubpixelPositionFonts(should_subpixel_position_fonts));
+    }
+
+    fn set_font_smoothing_style(&mut self, style: i32) {
+        self.record(DrawingCommand::SetFontSmoothingStyle(style));
+    }
+
+    fn show_glyphs_at_positions(&mut self, glyphs: &[CGGlyph], positions: &[CGPoint]) {
+        let count = std::cmp::min(glyphs.len(), positions.len());
+        self.record(DrawingCommand::ShowGlyphsAtPositions(glyphs[..count].to_vec(), positions[..count].to_vec()));
+    }
+
+    // auxiliary info dictionaries are not recorded
+    fn begin_transparency_layer(&mut self) {
+        self.record(DrawingCommand::BeginTransparencyLayer);
+    }
+
+    fn begin_transparency_layer_with_rect(&mut self, rect: CGRect) {
+        self.record(DrawingCommand::BeginTransparencyLayerWithRect(rect));
+    }
+
+    fn end_transparency_layer(&mut self) {
+        self.record(DrawingCommand::EndTransparencyLayer);
+    }
+
+    fn flush(&mut self) {
+        self.record(DrawingCommand::Flush);
+    }
+
+    fn synchronize(&mut self) {
+        self.record(DrawingCommand::Synchronize);
+    }
+
+    fn begin_page(&mut self, media_box: &CGRect) {
+        self.record(DrawingCommand::BeginPage(*media_box));
+    }
+
+    fn end_page(&mut self) {
+        self.record(DrawingCommand::EndPage);
+    }
+}
+
+impl RecordingContext {
+    pub fn replay<C: GraphicsContext + ?Sized>(&self, context: &mut C) {
+        for command in &self.commands {
+            command.replay(context);
+        }
+    }
+}
+
+impl DrawingCommand {
+    pub fn replay<C: GraphicsContext + ?Sized>(&self, context: &mut C) {
+        match self {
+            DrawingCommand::SaveState => context.save_state(),
+            DrawingCommand::RestoreState => context.restore_state(),
+            DrawingCommand::Scale(sx, sy) => context.scale(*sx, *sy),
+            DrawingCommand::Translate(tx, ty) => context.translate(*tx, *ty),
+            DrawingCommand::Rotate(angle) => context.rotate(*angle),
+            DrawingCommand::ConcatCTM(transform) => context.concat_ctm(*transform),
+            DrawingCommand::SetLineWidth(width) => context.set_line_width(*width),
+            DrawingCommand::SetLineCap(cap) => context.set_line_cap(*cap),
+            DrawingCommand::SetLineJoin(join) => context.set_line_join(*join),
+            DrawingCommand::SetLineDash(phase, lengths) => context.set_line_dash(*phase, lengths),
+            DrawingCommand::SetMiterLimit(limit) => context.set_miter_limit(*limit),
+            DrawingCommand::SetFlatness(flatness) => context.set_flatness(*flatness),
+            DrawingCommand::SetAlpha(alpha) => context.set_alpha(*alpha),
+            DrawingCommand::SetBlendMode(mode) => context.set_blend_mode(*mode),
+            DrawingCommand::BeginPath => context.begin_path(),
+            DrawingCommand::ClosePath => context.close_path(),
+            DrawingCommand::MoveToPoint(point) => context.move_to_point(point.x, point.y),
+            DrawingCommand::AddLineToPoint(point) => context.add_line_to_point(point.x, point.y),
+            DrawingCommand::AddCurveToPoint(cp1, cp2, point) => context.add_curve_to_point(cp1.x, cp1.y, cp2.x, cp2.y, point.x, point.y),
+            DrawingCommand::AddQuadCurveToPoint(cp, point) => context.add_quad_curve_to_point(cp.x, cp.y, point.x, point.y),
+            DrawingCommand::AddRect(rect) => context.add_rect(*rect),
+            DrawingCommand::AddRects(rects) => context.add_rects(rects),
+            DrawingCommand::AddLines(points) => context.add_lines(points),
+            DrawingCommand::AddEllipseInRect(rect) => context.add_ellipse_in_rect(*rect),
+            DrawingCommand::AddArc(center, radius, start_angle, end_angle, clockwise) => {
+                context.add_arc(center.x, center.y, *radius, *start_angle, *end_angle, *clockwise)
+            }
+            DrawingCommand::AddArcToPoint(p1, p2, radius) => context.add_arc_to_point(p1.x, p1.y, p2.x, p2.y, *radius),
+            DrawingCommand::AddPath(path) => context.add_path(path),
+            DrawingCommand::ReplacePathWithStrokedPath => context.replace_path_with_stroked_path(),
+            DrawingCommand::DrawPath(mode) => context.draw_path(*mode),
+            DrawingCommand::FillPath => context.fill_path(),
+            DrawingCommand::EOFillPath => context.eo_fill_path(),
+            DrawingCommand::StrokePath => context.stroke_path(),
+            DrawingCommand::FillRect(rect) => context.fill_rect(*rect),
+            DrawingCommand::FillRects(rects) => context.fill_rects(rects),
+            DrawingCommand::StrokeRect(rect) => context.stroke_rect(*rect),
+            DrawingCommand::StrokeRectWithWidth(rect, width) => context.stroke_rect_with_width(*rect, *width),
+            DrawingCommand::ClearRect(rect) => context.clear_rect(*rect),
+            DrawingCommand::FillEllipseInRect(rect) => context.fill_ellipse_in_rect(*rect),
+            DrawingCommand::StrokeEllipseInRect(rect) => context.stroke_ellipse_in_rect(*rect),
+            DrawingCommand::StrokeLineSegments(points) => context.stroke_line_segments(points),
+            DrawingCommand::Clip => context.clip(),
+            DrawingCommand::EOClip => context.eo_clip(),
+            DrawingCommand::ResetClip => context.reset_clip(),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::ClipToMask(rect, image) => context.clip_to_mask(*rect, image),
+            DrawingCommand::ClipToRect(rect) => context.clip_to_rect(*rect),
+            DrawingCommand::ClipToRects(rects) => context.clip_to_rects(rects),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::SetFillColor(color) => context.set_fill_color(color),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::SetStrokeColor(color) => context.set_stroke_color(color),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::SetFillColorSpace(space) => context.set_fill_color_space(space),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::SetStrokeColorSpace(space) => context.set_stroke_color_space(space),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::SetFillPattern(pattern, components) => unsafe { context.set_fill_pattern(pattern, components) },
+            #[cfg(target_os = "macos")]
+            DrawingCommand::SetStrokePattern(pattern, components) => unsafe { context.set_stroke_pattern(pattern, components) },
+            DrawingCommand::SetPatternPhase(phase) => context.set_pattern_phase(*phase),
+            DrawingCommand::SetGrayFillColor(gray, alpha) => context.set_gray_fill_color(*gray, *alpha),
+            DrawingCommand::SetGrayStrokeColor(gray, alpha) => context.set_gray_stroke_color(*gray, *alpha),
+            DrawingCommand::SetRGBFillColor(red, green, blue, alpha) => context.set_rgb_fill_color(*red, *green, *blue, *alpha),
+            DrawingCommand::SetRGBStrokeColor(red, green, blue, alpha) => context.set_rgb_stroke_color(*red, *green, *blue, *alpha),
+            DrawingCommand::SetCMYKFillColor(cyan, magenta, yellow, black, alpha) => {
+                context.set_cmyk_fill_color(*cyan, *magenta, *yellow, *black, *alpha)
+            }
+            DrawingCommand::SetCMYKStrokeColor(cyan, magenta, yellow, black, alpha) => {
+                context.set_cmyk_stroke_color(*cyan, *magenta, *yellow, *black, *alpha)
+            }
+            DrawingCommand::SetRenderingIntent(intent) => context.set_rendering_intent(*intent),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::DrawImage(rect, image) => context.draw_image(*rect, image),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::DrawTiledImage(rect, image) => context.draw_tiled_image(*rect, image),
+            DrawingCommand::SetInterpolationQuality(quality) => context.set_interpolation_quality(*quality),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::DrawLinearGradient(gradient, start_point, end_point, options) => {
+                context.draw_linear_gradient(gradient, *start_point, *end_point, *options)
+            }
+            #[cfg(target_os = "macos")]
+            DrawingCommand::DrawRadialGradient(gradient, start_center, start_radius, end_center, end_radius, options) => {
+                context.draw_radial_gradient(gradient, *start_center, *start_radius, *end_center, *end_radius, *options)
+            }
+            DrawingCommand::SetShadow(offset, blur) => context.set_shadow(*offset, *blur),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::SetShadowWithColor(offset, blur, color) => context.set_shadow_with_color(*offset, *blur, color),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::DrawShading(shading) => context.draw_shading(shading),
+            #[cfg(target_os = "macos")]
+            DrawingCommand::SetFont(font) => context.set_font(font),
+            DrawingCommand::SetFontSize(size) => context.set_font_size(*size),
+            DrawingCommand::SetTextMatrix(t) => context.set_text_matrix(t),
+            DrawingCommand::SetTextDrawingMode(mode) => context.set_text_drawing_mode(*mode),
+            DrawingCommand::SetTextPosition(point) => context.set_text_position(point.x, point.y),
+            DrawingCommand::SetAllowsFontSmoothing(value) => context.set_allows_font_smoothing(*value),
+            DrawingCommand::SetShouldSmoothFonts(value) => context.set_should_smooth_fonts(*value),
+            DrawingCommand::SetAllowsAntialiasing(value) => context.set_allows_antialiasing(*value),
+            DrawingCommand::SetShouldAntialias(value) => context.set_should_antialias(*value),
+            DrawingCommand::SetAllowsFontSubpixelQuantization(value) => context.set_allows_font_subpixel_quantization(*value),
+            DrawingCommand::SetShouldSubpixelQuantizeFonts(value) => context.set_should_subpixel_quantize_fonts(*value),
+            DrawingCommand::SetAllowsFontSubpixelPositioning(value) => context.set_allows_font_subpixel_positioning(*value),
+            DrawingCommand::SetShouldSubpixelPositionFonts(value) => context.set_should_subpixel_position_fonts(*value),
+            DrawingCommand::SetFontSmoothingStyle(style) => context.set_font_smoothing_style(*style),
+            DrawingCommand::ShowGlyphsAtPositions(glyphs, positions) => context.show_glyphs_at_positions(glyphs, positions),
+            DrawingCommand::BeginTransparencyLayer => context.begin_transparency_layer(),
+            DrawingCommand::BeginTransparencyLayerWithRect(rect) => context.begin_transparency_layer_with_rect(*rect),
+            DrawingCommand::EndTransparencyLayer => context.end_transparency_layer(),
+            DrawingCommand::Flush => context.flush(),
+            DrawingCommand::Synchronize => context.synchronize(),
+            DrawingCommand::BeginPage(media_box) => context.begin_page(media_box),
+            DrawingCommand::EndPage => context.end_page(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(values: &[u8]) -> RecordingContext {
+        RecordingContext::from_commands(values.iter().map(|value| DrawingCommand::SetAlpha(*value as CGFloat)).collect())
+    }
+
+    fn lcs_length(a: &[DrawingCommand], b: &[DrawingCommand]) -> usize {
+        let mut lengths = vec![0; b.len() + 1];
+        for x in a {
+            let mut diagonal = 0;
+            for (j, y) in b.iter().enumerate() {
+                let above = lengths[j + 1];
+                lengths[j + 1] = if x == y {
+                    diagonal + 1
+                } else {
+                    above.max(lengths[j])
+                };
+                diagonal = above;
+            }
+        }
+        lengths[b.len()]
+    }
+
+    // applies the differences to the expected log and checks that they are minimal and in order
+    fn check(expected: &RecordingContext, actual: &RecordingContext) {
+        let differences = expected.diff(actual);
+        let (expected, actual) = (expected.commands(), actual.commands());
+        assert_eq!(differences.len(), expected.len() + actual.len() - 2 * lcs_length(expected, actual));
+        let mut result = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        for difference in &differences {
+            match difference {
+                CommandDifference::Removed(index, command) => {
+                    assert!(*index >= i && expected[*index] == *command);
+                    result.extend_from_slice(&expected[i..*index]);
+                    j += index - i;
+                    i = index + 1;
+                }
+                CommandDifference::Added(index, command) => {
+                    assert!(*index >= j && actual[*index] == *command);
+                    result.extend_from_slice(&expected[i..i + (index - j)]);
+                    i += index - j;
+                    result.push(command.clone());
+                    j = index + 1;
+                }
+            }
+        }
+        result.extend_from_slice(&expected[i..]);
+        assert_eq!(result, actual);
+    }
+
+    #[test]
+    fn differences_in_order() {
+        let differences = log(&[1, 2, 3, 4]).diff(&log(&[1, 3, 4, 5]));
+        assert_eq!(
+            differences,
+            vec![
+                CommandDifference::Removed(1, DrawingCommand::SetAlpha(2.0)),
+                CommandDifference::Added(3, DrawingCommand::SetAlpha(5.0)),
+            ]
+        );
+        assert!(log(&[1, 2]).diff(&log(&[1, 2])).is_empty());
+        check(&log(&[]), &log(&[1, 2]));
+        check(&log(&[1, 2]), &log(&[]));
+    }
+
+    #[test]
+    fn minimal_differences() {
+        // a small linear congruential generator over a few distinct commands, so that there are many common commands
+        let mut seed = 12345u32;
+        let mut next = move |limit: u32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((seed >> 16) % limit) as u8
+        };
+        for _ in 0..500 {
+            let expected: Vec<u8> = (0..next(20)).map(|_| next(4)).collect();
+            let actual: Vec<u8> = (0..next(20)).map(|_| next(4)).collect();
+            check(&log(&expected), &log(&actual));
+        }
+    }
+
+    #[test]
+    fn large_logs() {
+        let expected: Vec<u8> = (0..200_000).map(|index| (index % 251) as u8).collect();
+        let mut actual = expected.clone();
+        actual.remove(1000);
+        actual.insert(150_000, 255);
+        actual[100_000] = 254;
+        let differences = log(&expected).diff(&log(&actual));
+        assert_eq!(differences.len(), 4);
+    }
+}
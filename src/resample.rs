@@ -0,0 +1,422 @@
+use std::{
+    error::Error,
+    f32::consts::PI,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    affine_transform::CGAffineTransform,
+    bitmap::{Bitmap, BitmapError, BitmapView},
+    context::CGInterpolationQuality,
+    image::{kCGImageByteOrder32Host, CGBitmapInfo, CGImageAlphaInfo},
+    pixel_conversion::{PixelConversionError, PixelConverter, PixelLayout},
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ResampleError {
+    Bitmap(BitmapError),
+    InvalidSize,
+    SingularTransform,
+}
+
+impl Display for ResampleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ResampleError::Bitmap(error) => write!(f, "{}", error),
+            ResampleError::InvalidSize => write!(f, "source or destination size is zero"),
+            ResampleError::SingularTransform => write!(f, "transform is not invertible"),
+        }
+    }
+}
+
+impl Error for ResampleError {}
+
+impl From<BitmapError> for ResampleError {
+    fn from(error: BitmapError) -> ResampleError {
+        ResampleError::Bitmap(error)
+    }
+}
+
+impl From<PixelConversionError> for ResampleError {
+    fn from(error: PixelConversionError) -> ResampleError {
+        ResampleError::Bitmap(BitmapError::InvalidLayout(error))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ResampleFilter {
+    Nearest,
+    Bilinear,
+    Bicubic,
+    Lanczos,
+}
+
+// the default quality is left to the context in Core Graphics, here it is bilinear
+impl From<CGInterpolationQuality> for ResampleFilter {
+    fn from(quality: CGInterpolationQuality) -> ResampleFilter {
+        match quality {
+            CGInterpolationQuality::None => ResampleFilter::Nearest,
+            CGInterpolationQuality::Default | CGInterpolationQuality::Low => ResampleFilter::Bilinear,
+            CGInterpolationQuality::Medium => ResampleFilter::Bicubic,
+            CGInterpolationQuality::High => ResampleFilter::Lanczos,
+        }
+    }
+}
+
+impl ResampleFilter {
+    fn radius(&self) -> f32 {
+        match self {
+            ResampleFilter::Nearest => 0.5,
+            ResampleFilter::Bilinear => 1.0,
+            ResampleFilter::Bicubic => 2.0,
+            ResampleFilter::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            ResampleFilter::Nearest => (x < 0.5) as u32 as f32,
+            ResampleFilter::Bilinear => (1.0 - x).max(0.0),
+            // Catmull-Rom
+            ResampleFilter::Bicubic if x < 1.0 => (1.5 * x - 2.5) * x * x + 1.0,
+            ResampleFilter::Bicubic if x < 2.0 => ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0,
+            ResampleFilter::Bicubic => 0.0,
+            ResampleFilter::Lanczos if x < 1e-6 => 1.0,
+            ResampleFilter::Lanczos if x < 3.0 => {
+                let x = PI * x;
+                3.0 * x.sin() * (x / 3.0).sin() / (x * x)
+            }
+            ResampleFilter::Lanczos => 0.0,
+        }
+    }
+}
+
+// pixels are filtered as premultiplied f32 so transparent pixels do not bleed their color into their neighbors
+struct Working {
+    channels: usize,
+    alpha: bool,
+    clamp: bool,
+    to_working: PixelConverter,
+    from_working: PixelConverter,
+}
+
+impl Working {
+    fn new(layout: &PixelLayout) -> Result<Working, ResampleError> {
+        let alpha_info = match layout.alpha_info() {
+            CGImageAlphaInfo::AlphaNone | CGImageAlphaInfo::AlphaNoneSkipFirst | CGImageAlphaInfo::AlphaNoneSkipLast => CGImageAlphaInfo::AlphaNone,
+            CGImageAlphaInfo::AlphaOnly => CGImageAlphaInfo::AlphaOnly,
+            _ => CGImageAlphaInfo::AlphaPremultipliedLast,
+        };
+        let alpha = alpha_info != CGImageAlphaInfo::AlphaNone;
+        let channels = layout.color_components() + alpha as usize;
+        let bitmap_info = CGBitmapInfo::from_bits_retain(alpha_info as u32 | kCGImageByteOrder32Host) | CGBitmapInfo::FloatComponents;
+        let working = PixelLayout::new(layout.color_components(), 32, 32 * channels, bitmap_info)?;
+        Ok(Working {
+            channels,
+            alpha,
+            // extended range float components are kept, integer ones can not hold the overshoot of sharp filters
+            clamp: !layout.has_float_components(),
+            to_working: PixelConverter::new(*layout, working)?,
+            from_working: PixelConverter::new(working, *layout)?,
+        })
+    }
+
+    fn read(&self, source: &BitmapView<'_>) -> Result<Vec<f32>, ResampleError> {
+        let width = source.width();
+        let mut row = vec![0u8; width * self.channels * 4];
+        let mut samples = Vec::with_capacity(width * source.height() * self.channels);
+        for y in 0..source.height() {
+            self.to_working.convert_row(source.row(y).unwrap(), &mut row, width)?;
+            samples.extend(row.chunks_exact(4).map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])));
+        }
+        Ok(samples)
+    }
+
+    fn write(&self, samples: &mut [f32], destination: &mut Bitmap) -> Result<(), ResampleError> {
+        let width = destination.width();
+        let mut row = vec![0u8; width * self.channels * 4];
+        for (y, pixels) in samples.chunks_exact_mut(width * self.channels).enumerate() {
+            for (pixel, bytes) in pixels.chunks_exact_mut(self.channels).zip(row.chunks_exact_mut(self.channels * 4)) {
+                self.clamp_pixel(pixel);
+                for (sample, bytes) in pixel.iter().zip(bytes.chunks_exact_mut(4)) {
+                    bytes.copy_from_slice(&sample.to_ne_bytes());
+                }
+            }
+            self.from_working.convert_row(&row, destination.row_mut(y).unwrap(), width)?;
+        }
+        Ok(())
+    }
+
+    fn clamp_pixel(&self, pixel: &mut [f32]) {
+        let (color, alpha) = match self.alpha {
+            true => {
+                let (color, alpha) = pixel.split_at_mut(self.channels - 1);
+                alpha[0] = alpha[0].clamp(0.0, 1.0);
+                (color, alpha[0])
+            }
+            false => (pixel, 1.0),
+        };
+        if self.clamp {
+            for component in color {
+                *component = component.clamp(0.0, alpha);
+            }
+        }
+    }
+}
+
+fn destination_bitmap(source: &BitmapView<'_>, width: usize, height: usize) -> Result<Bitmap, ResampleError> {
+    let layout = *source.layout();
+    let bytes_per_row =
+        layout.min_bytes_per_row(width).and_then(|len| len.checked_add(15)).map(|len| len / 16 * 16).ok_or(BitmapError::SizeOverflow)?;
+    Ok(Bitmap::with_layout(width, height, bytes_per_row, source.color_model(), layout)?)
+}
+
+// the first source index and weights of each destination sample along one axis, taps past the edges are clamped to them
+fn axis_weights(source: usize, destination: usize, filter: ResampleFilter) -> Vec<(usize, Vec<f32>)> {
+    let scale = source as f32 / destination as f32;
+    (0..destination)
+        .map(|index| {
+            let center = (index as f32 + 0.5) * scale;
+            if filter == ResampleFilter::Nearest {
+                return ((center as usize).min(source - 1), vec![1.0]);
+            }
+            // downscaling widens the filter to cover every source pixel
+            let filter_scale = scale.max(1.0);
+            let support = filter.radius() * filter_scale;
+            let center = center - 0.5;
+            let first = (center - support).ceil() as isize;
+            let last = (center + support).floor() as isize;
+            let start = first.clamp(0, source as isize - 1) as usize;
+            let end = last.clamp(0, source as isize - 1) as usize;
+            let mut weights = vec![0f32; end - start + 1];
+            for tap in first..=last {
+                let position = tap.clamp(start as isize, end as isize) as usize - start;
+                weights[position] += filter.weight((tap as f32 - center) / filter_scale);
+            }
+            let total = weights.iter().sum::<f32>();
+            if total != 0.0 {
+                weights.iter_mut().for_each(|weight| *weight /= total);
+            }
+            (start, weights)
+        })
+        .collect()
+}
+
+// scales the bitmap to the given size with a separable filter, the result has the layout of the source
+pub fn resize(source: &BitmapView<'_>, width: usize, height: usize, filter: ResampleFilter) -> Result<Bitmap, ResampleError> {
+    if source.width() == 0 || source.height() == 0 || width == 0 || height == 0 {
+        return Err(ResampleError::InvalidSize);
+    }
+    let working = Working::new(source.layout())?;
+    let channels = working.channels;
+    let mut destination = destination_bitmap(source, width, height)?;
+    let samples = working.read(source)?;
+
+    let columns = axis_weights(source.width(), width, filter);
+    let mut horizontal = vec![0f32; width * source.height() * channels];
+    for (input, output) in samples.chunks_exact(source.width() * channels).zip(horizontal.chunks_exact_mut(width * channels)) {
+        for ((start, weights), pixel) in columns.iter().zip(output.chunks_exact_mut(channels)) {
+            for (offset, weight) in weights.iter().enumerate() {
+                let tap = &input[(start + offset) * channels..(start + offset + 1) * channels];
+                pixel.iter_mut().zip(tap).for_each(|(sample, value)| *sample += value * weight);
+            }
+        }
+    }
+
+    let rows = axis_weights(source.height(), height, filter);
+    let stride = width * channels;
+    let mut vertical = vec![0f32; stride * height];
+    for ((start, weights), output) in rows.iter().zip(vertical.chunks_exact_mut(stride)) {
+        for (offset, weight) in weights.iter().enumerate() {
+            let input = &horizontal[(start + offset) * stride..(start + offset + 1) * stride];
+            output.iter_mut().zip(input).for_each(|(sample, value)| *sample += value * weight);
+        }
+    }
+    working.write(&mut vertical, &mut destination)?;
+    Ok(destination)
+}
+
+// draws the source through the transform into a bitmap of the given size, coordinates are in pixels with the
+// origin at the top left corner of the first row, destination pixels the source does not cover are transparent
+// or black for layouts without alpha
+pub fn transform(
+    source: &BitmapView<'_>,
+    transform: &CGAffineTransform,
+    width: usize,
+    height: usize,
+    filter: ResampleFilter,
+) -> Result<Bitmap, ResampleError> {
+    if source.width() == 0 || source.height() == 0 || width == 0 || height == 0 {
+        return Err(ResampleError::InvalidSize);
+    }
+    let determinant = transform.a * transform.d - transform.b * transform.c;
+    if determinant == 0.0 || !determinant.is_finite() {
+        return Err(ResampleError::SingularTransform);
+    }
+    let inverse = CGAffineTransform {
+        a: transform.d / determinant,
+        b: -transform.b / determinant,
+        c: -transform.c / determinant,
+        d: transform.a / determinant,
+        tx: (transform.c * transform.ty - transform.d * transform.tx) / determinant,
+        ty: (transform.b * transform.tx - transform.a * transform.ty) / determinant,
+    };
+    let working = Working::new(source.layout())?;
+    let channels = working.channels;
+    let mut destination = destination_bitmap(source, width, height)?;
+    let samples = working.read(source)?;
+    let (source_width, source_height) = (source.width() as isize, source.height() as isize);
+
+    // the footprint of a destination pixel in the source, widened when the transform shrinks the image
+    let scale_x = (inverse.a.hypot(inverse.c) as f32).max(1.0);
+    let scale_y = (inverse.b.hypot(inverse.d) as f32).max(1.0);
+    let (support_x, support_y) = (filter.radius() * scale_x, filter.radius() * scale_y);
+    let mut output = vec![0f32; width * height * channels];
+    let mut row_weights = Vec::new();
+    for (y, row) in output.chunks_exact_mut(width * channels).enumerate() {
+        for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+            let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+            let source_x = (inverse.a * x + inverse.c * y + inverse.tx) as f32;
+            let source_y = (inverse.b * x + inverse.d * y + inverse.ty) as f32;
+            if filter == ResampleFilter::Nearest {
+                let (column, line) = (source_x.floor() as isize, source_y.floor() as isize);
+                if (0..source_width).contains(&column) && (0..source_height).contains(&line) {
+                    let start = (line * source_width + column) as usize * channels;
+                    pixel.copy_from_slice(&samples[start..start + channels]);
+                }
+                continue;
+            }
+            let (center_x, center_y) = (source_x - 0.5, source_y - 0.5);
+            let (first_x, last_x) = ((center_x - support_x).ceil() as isize, (center_x + support_x).floor() as isize);
+            let (first_y, last_y) = ((center_y - support_y).ceil() as isize, (center_y + support_y).floor() as isize);
+            if last_x < 0 || last_y < 0 || first_x >= source_width || first_y >= source_height {
+                continue;
+            }
+            row_weights.clear();
+            row_weights.extend((first_x..=last_x).map(|tap| filter.weight((tap as f32 - center_x) / scale_x)));
+            let row_total = row_weights.iter().sum::<f32>();
+            let mut total = 0.0;
+            for line in first_y..=last_y {
+                let line_weight = filter.weight((line as f32 - center_y) / scale_y);
+                total += line_weight * row_total;
+                if line_weight == 0.0 || !(0..source_height).contains(&line) {
+                    continue;
+                }
+                for (column, weight) in (first_x..=last_x).zip(&row_weights) {
+                    if !(0..source_width).contains(&column) {
+                        continue;
+                    }
+                    let start = (line * source_width + column) as usize * channels;
+                    let weight = weight * line_weight;
+                    pixel.iter_mut().zip(&samples[start..start + channels]).for_each(|(sample, value)| *sample += value * weight);
+                }
+            }
+            if total != 0.0 {
+                pixel.iter_mut().for_each(|sample| *sample /= total);
+            }
+        }
+    }
+    working.write(&mut output, &mut destination)?;
+    Ok(destination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel_format::PixelFormat;
+
+    const FILTERS: [ResampleFilter; 4] = [
+        ResampleFilter::Nearest,
+        ResampleFilter::Bilinear,
+        ResampleFilter::Bicubic,
+        ResampleFilter::Lanczos,
+    ];
+
+    fn pattern(width: usize, height: usize) -> Bitmap {
+        let mut bitmap = Bitmap::new(width, height, PixelFormat::RGBA8888).unwrap();
+        for y in 0..height {
+            for x in 0..width {
+                let alpha = (x * 61 + y * 37) as u8 | 0x80;
+                let pixel = [
+                    (x * 53 + y * 11) as u8 % alpha,
+                    (x * 7 + y * 97) as u8 % alpha,
+                    (x * 29) as u8 % alpha,
+                    alpha,
+                ];
+                bitmap.set_pixel(x, y, pixel).unwrap();
+            }
+        }
+        bitmap
+    }
+
+    fn pixels(bitmap: &Bitmap) -> Vec<[u8; 4]> {
+        bitmap.rows_as::<[u8; 4]>().unwrap().flatten().copied().collect()
+    }
+
+    #[test]
+    fn filter_from_quality() {
+        assert_eq!(ResampleFilter::from(CGInterpolationQuality::None), ResampleFilter::Nearest);
+        assert_eq!(ResampleFilter::from(CGInterpolationQuality::Default), ResampleFilter::Bilinear);
+        assert_eq!(ResampleFilter::from(CGInterpolationQuality::Low), ResampleFilter::Bilinear);
+        assert_eq!(ResampleFilter::from(CGInterpolationQuality::Medium), ResampleFilter::Bicubic);
+        assert_eq!(ResampleFilter::from(CGInterpolationQuality::High), ResampleFilter::Lanczos);
+    }
+
+    #[test]
+    fn identity_is_exact() {
+        let source = pattern(7, 5);
+        for filter in FILTERS {
+            let resized = source.resize(7, 5, filter).unwrap();
+            assert_eq!(pixels(&resized), pixels(&source), "{:?}", filter);
+            let transformed = source.transform(&CGAffineTransform::new_translate(0.0, 0.0), 7, 5, filter).unwrap();
+            assert_eq!(pixels(&transformed), pixels(&source), "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn transparent_neighbors_do_not_darken() {
+        let mut source = Bitmap::new(2, 2, PixelFormat::RGBA8888).unwrap();
+        source.set_pixel(0, 0, [255u8, 0, 0, 255]).unwrap();
+        source.set_pixel(1, 1, [0u8, 0, 255, 255]).unwrap();
+        for filter in FILTERS {
+            let resized = source.resize(5, 5, filter).unwrap();
+            for [red, green, blue, alpha] in pixels(&resized) {
+                // the premultiplied components stay within alpha and no gray appears between the colors
+                assert!(red <= alpha && blue <= alpha && green == 0, "{:?}", filter);
+                if alpha > 0 {
+                    assert!(red as usize + blue as usize + 1 >= alpha as usize, "{:?}", filter);
+                }
+            }
+        }
+        let mut edge = Bitmap::new(2, 1, PixelFormat::RGBA8888).unwrap();
+        edge.set_pixel(0, 0, [255u8, 0, 0, 255]).unwrap();
+        for [red, green, blue, alpha] in pixels(&edge.resize(4, 1, ResampleFilter::Bilinear).unwrap()) {
+            assert_eq!((red, green, blue), (alpha, 0, 0));
+        }
+    }
+
+    #[test]
+    fn translate_by_one_pixel() {
+        let source = pattern(4, 3);
+        let expected = pixels(&source);
+        for filter in FILTERS {
+            let moved = source.transform(&CGAffineTransform::new_translate(1.0, 0.0), 4, 3, filter).unwrap();
+            let moved = pixels(&moved);
+            for y in 0..3 {
+                assert_eq!(moved[y * 4], [0; 4], "{:?}", filter);
+                assert_eq!(moved[y * 4 + 1..y * 4 + 4], expected[y * 4..y * 4 + 3], "{:?}", filter);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let source = pattern(2, 2);
+        assert_eq!(source.resize(0, 2, ResampleFilter::Bilinear).err(), Some(ResampleError::InvalidSize));
+        assert_eq!(
+            source.transform(&CGAffineTransform::new_scale(0.0, 1.0), 2, 2, ResampleFilter::Bilinear).err(),
+            Some(ResampleError::SingularTransform)
+        );
+    }
+}